@@ -0,0 +1,338 @@
+use anyhow::{bail, ensure, Result};
+use std::collections::BTreeSet;
+
+use crate::ast::FunctionCall;
+use crate::collation::Collation;
+use crate::json::{self, Json, Subtype};
+use crate::value::{parse_number, SortKey, Value};
+
+/// Checks whether a function call is a call to an aggregate function.
+/// `min` and `max` are only aggregates when given a single argument. Calls
+/// with `OVER` are calls to window functions instead.
+pub fn is_aggregate(call: &FunctionCall) -> bool {
+    if call.over.is_some() {
+        return false;
+    }
+    is_aggregate_function(call)
+}
+
+/// Checks whether a function call would call an aggregate function without
+/// its `OVER` clause.
+pub fn is_aggregate_function(call: &FunctionCall) -> bool {
+    match call.name.to_ascii_lowercase().as_str() {
+        "count" | "sum" | "total" | "avg" | "group_concat" | "string_agg" | "json_group_array"
+        | "json_group_object" => true,
+        "min" | "max" => call.args.len() == 1,
+        _ => false,
+    }
+}
+
+/// The running state of an aggregate function over the rows of a group.
+pub struct Aggregate {
+    state: State,
+    /// The values seen so far, for aggregates over `DISTINCT` values.
+    seen: Option<BTreeSet<SortKey>>,
+    /// How `min`, `max` and `DISTINCT` compare the values of the argument.
+    collation: Collation,
+}
+
+enum State {
+    Count(i64),
+    /// The state shared by `sum`, `total` and `avg`. The sum is kept as an
+    /// integer for as long as all values are integers.
+    Sum {
+        kind: SumKind,
+        integer: i64,
+        real: f64,
+        all_integers: bool,
+        count: i64,
+    },
+    /// The smallest or largest value, depending on the ordering that is wanted.
+    Extreme {
+        wanted: std::cmp::Ordering,
+        value: Value,
+    },
+    GroupConcat(Option<String>),
+    /// The values of `json_group_array`, and whether they are JSON.
+    JsonArray(Vec<Json>, Subtype),
+    /// The members of `json_group_object`, and whether the values are JSON.
+    JsonObject(Vec<(String, Json)>, Subtype),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum SumKind {
+    Sum,
+    Total,
+    Avg,
+}
+
+impl Aggregate {
+    /// Creates the state for evaluating an aggregate function call, whose
+    /// argument compares by the given collation.
+    pub fn new(call: &FunctionCall, collation: Collation) -> Result<Self> {
+        let name = call.name.to_ascii_lowercase();
+        let arity_ok = match name.as_str() {
+            "count" => call.args.len() <= 1,
+            "group_concat" => (1..=2).contains(&call.args.len()),
+            "string_agg" | "json_group_object" => call.args.len() == 2,
+            _ => call.args.len() == 1,
+        };
+        ensure!(
+            arity_ok && !(call.star && name != "count"),
+            "wrong number of arguments to function {}()",
+            call.name
+        );
+        ensure!(
+            !call.distinct || call.args.len() == 1,
+            "DISTINCT aggregates must have exactly one argument"
+        );
+
+        let sum = |kind| State::Sum {
+            kind,
+            integer: 0,
+            real: 0.0,
+            all_integers: true,
+            count: 0,
+        };
+        let state = match name.as_str() {
+            "count" => State::Count(0),
+            "sum" => sum(SumKind::Sum),
+            "total" => sum(SumKind::Total),
+            "avg" => sum(SumKind::Avg),
+            "min" => State::Extreme {
+                wanted: std::cmp::Ordering::Less,
+                value: Value::Null,
+            },
+            "max" => State::Extreme {
+                wanted: std::cmp::Ordering::Greater,
+                value: Value::Null,
+            },
+            "group_concat" | "string_agg" => State::GroupConcat(None),
+            "json_group_array" => State::JsonArray(Vec::new(), Subtype::of(&call.args[0])),
+            "json_group_object" => State::JsonObject(Vec::new(), Subtype::of(&call.args[1])),
+            _ => bail!("no such function: {}", call.name),
+        };
+
+        Ok(Self {
+            state,
+            seen: call.distinct.then(BTreeSet::new),
+            collation,
+        })
+    }
+
+    /// Adds the arguments of a row to the aggregate. Returns whether the row
+    /// holds the current result, which is only ever the case for `min` and
+    /// `max`. Bare columns in a query with those take their values from it.
+    pub fn step(&mut self, args: &[Value]) -> Result<bool> {
+        // Aggregates other than count(*) and json_group_array skip NULLs.
+        let value = match args.first() {
+            Some(Value::Null) if !matches!(self.state, State::JsonArray(..)) => return Ok(false),
+            Some(value) => value,
+            None => {
+                if let State::Count(count) = &mut self.state {
+                    *count += 1;
+                }
+                return Ok(false);
+            }
+        };
+        if let Some(seen) = &mut self.seen {
+            let key = match value {
+                Value::Text(text) => Value::Text(self.collation.key(text).into_owned()),
+                value => value.clone(),
+            };
+            if !seen.insert(SortKey(vec![key])) {
+                return Ok(false);
+            }
+        }
+
+        match &mut self.state {
+            State::Count(count) => *count += 1,
+            State::Sum {
+                kind,
+                integer,
+                real,
+                all_integers,
+                count,
+            } => {
+                *count += 1;
+                let number = match value {
+                    Value::Text(t) => parse_number(t),
+                    value => Some(value.clone()),
+                };
+                match number {
+                    Some(Value::Integer(i)) if *all_integers => match integer.checked_add(i) {
+                        Some(sum) => *integer = sum,
+                        None if *kind == SumKind::Sum => bail!("integer overflow"),
+                        None => {
+                            *all_integers = false;
+                            *real = *integer as f64 + i as f64;
+                        }
+                    },
+                    _ => {
+                        if *all_integers {
+                            *all_integers = false;
+                            *real = *integer as f64;
+                        }
+                        *real += value.to_real();
+                    }
+                }
+            }
+            State::Extreme {
+                wanted,
+                value: current,
+            } => {
+                if current.is_null() || value.compare_with(current, self.collation) == *wanted {
+                    *current = value.clone();
+                    return Ok(true);
+                }
+            }
+            State::GroupConcat(text) => match text {
+                Some(text) => {
+                    match args.get(1) {
+                        Some(separator) => text.push_str(&separator.to_string()),
+                        None => text.push(','),
+                    }
+                    text.push_str(&value.to_string());
+                }
+                None => *text = Some(value.to_string()),
+            },
+            State::JsonArray(items, subtype) => items.push(subtype.to_json(value)?),
+            State::JsonObject(members, subtype) => {
+                members.push((json::escape(&value.to_string()), subtype.to_json(&args[1])?));
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// The result of the aggregate over the rows added so far.
+    pub fn finish(&self) -> Value {
+        match &self.state {
+            State::Count(count) => Value::Integer(*count),
+            State::Sum {
+                kind,
+                integer,
+                real,
+                all_integers,
+                count,
+            } => {
+                let sum = if *all_integers {
+                    *integer as f64
+                } else {
+                    *real
+                };
+                match kind {
+                    SumKind::Sum if *count == 0 => Value::Null,
+                    SumKind::Sum if *all_integers => Value::Integer(*integer),
+                    SumKind::Sum | SumKind::Total => Value::Real(sum),
+                    SumKind::Avg if *count == 0 => Value::Null,
+                    SumKind::Avg => Value::Real(sum / *count as f64),
+                }
+            }
+            State::Extreme { value, .. } => value.clone(),
+            State::GroupConcat(text) => text.clone().map_or(Value::Null, Value::Text),
+            State::JsonArray(items, _) => Value::Text(Json::Array(items.clone()).to_string()),
+            State::JsonObject(members, _) => Value::Text(Json::Object(members.clone()).to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aggregate::*;
+    use crate::ast::{Expr, Statement};
+    use crate::parser::parse;
+
+    /// Runs the aggregate function call `sql` over the given values.
+    fn aggregate(sql: &str, values: &[Value]) -> Result<Value> {
+        let Some(Statement::Select(select)) = parse(&format!("SELECT {sql}")).unwrap().pop() else {
+            panic!("Expected SELECT");
+        };
+        let crate::ast::ResultColumn::Expr {
+            expr: Expr::Function(call),
+            ..
+        } = &select.columns[0]
+        else {
+            panic!("Expected a function call");
+        };
+
+        let mut aggregate = Aggregate::new(call, Collation::Binary)?;
+        for value in values {
+            let mut args = vec![value.clone()];
+            args.extend(call.args.iter().skip(1).map(|arg| match arg {
+                Expr::Literal(value) => value.clone(),
+                _ => panic!("Expected a literal"),
+            }));
+            if call.star {
+                args.clear();
+            }
+            aggregate.step(&args)?;
+        }
+        Ok(aggregate.finish())
+    }
+
+    #[test]
+    fn aggregates_like_sqlite() {
+        let text = |t: &str| Value::Text(t.to_string());
+        let values = vec![text("3"), Value::Integer(4), Value::Null, text("abc")];
+        let cases = vec![
+            ("count(*)", Value::Integer(4)),
+            ("count(x)", Value::Integer(3)),
+            ("sum(x)", Value::Real(7.0)),
+            ("total(x)", Value::Real(7.0)),
+            ("avg(x)", Value::Real(7.0 / 3.0)),
+            ("min(x)", Value::Integer(4)),
+            ("max(x)", text("abc")),
+            ("group_concat(x)", text("3,4,abc")),
+            ("group_concat(x, '; ')", text("3; 4; abc")),
+            ("string_agg(x, '')", text("34abc")),
+            ("json_group_array(x)", text(r#"["3",4,null,"abc"]"#)),
+            ("json_group_object(x, 1)", text(r#"{"3":1,"4":1,"abc":1}"#)),
+        ];
+
+        for (sql, expected) in cases {
+            assert_eq!(aggregate(sql, &values).unwrap(), expected, "{sql}");
+        }
+    }
+
+    #[test]
+    fn handles_empty_groups_and_distinct_values() {
+        let cases = vec![
+            ("count(x)", Value::Integer(0)),
+            ("sum(x)", Value::Null),
+            ("total(x)", Value::Real(0.0)),
+            ("avg(x)", Value::Null),
+            ("max(x)", Value::Null),
+            ("group_concat(x)", Value::Null),
+            ("json_group_array(x)", Value::Text("[]".to_string())),
+        ];
+        for (sql, expected) in cases {
+            assert_eq!(aggregate(sql, &[]).unwrap(), expected, "{sql}");
+        }
+
+        let values = [1, 2, 1].map(Value::Integer);
+        assert_eq!(aggregate("sum(x)", &values).unwrap(), Value::Integer(4));
+        let values = [Value::Integer(1), Value::Real(1.0), Value::Integer(2)];
+        assert_eq!(
+            aggregate("count(DISTINCT x)", &values).unwrap(),
+            Value::Integer(2)
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        let values = [i64::MAX, 1].map(Value::Integer);
+        assert_eq!(
+            aggregate("sum(x)", &values).unwrap_err().to_string(),
+            "integer overflow"
+        );
+        assert_eq!(
+            aggregate("total(x)", &values).unwrap(),
+            Value::Real(i64::MAX as f64 + 1.0)
+        );
+        assert_eq!(
+            aggregate("avg(x, 1)", &[]).unwrap_err().to_string(),
+            "wrong number of arguments to function avg()"
+        );
+    }
+}
@@ -0,0 +1,1086 @@
+use std::fmt;
+
+use crate::value::{Affinity, Value};
+
+/// A parsed SQL statement.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Statement {
+    Select(Box<Select>),
+    CreateTable(Box<CreateTable>),
+    CreateIndex(Box<CreateIndex>),
+    CreateView(Box<CreateView>),
+    Pragma(Pragma),
+}
+
+/// A `SELECT` statement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Select {
+    /// The common table expressions the statement defines, before any
+    /// compound operators.
+    pub with: Option<With>,
+    pub distinct: bool,
+    pub columns: Vec<ResultColumn>,
+    pub from: Option<TableOrSubquery>,
+    /// The tables joined to the one in `from`, from left to right.
+    pub joins: Vec<Join>,
+    pub where_clause: Option<Expr>,
+    pub group_by: Vec<Expr>,
+    pub having: Option<Expr>,
+    /// The windows defined by the `WINDOW` clause, by name.
+    pub windows: Vec<(String, Window)>,
+    /// The `SELECT`s combined with this one by compound operators, in order.
+    /// The `ORDER BY`, `LIMIT` and `OFFSET` of this `SELECT` then apply to
+    /// the compound result, and the combined `SELECT`s have none of their own.
+    pub compound: Vec<CompoundSelect>,
+    pub order_by: Vec<OrderingTerm>,
+    pub limit: Option<Expr>,
+    pub offset: Option<Expr>,
+}
+
+/// A `WITH [RECURSIVE]` clause. SQLite treats the CTEs of a clause without
+/// `RECURSIVE` as recursive as well.
+#[derive(Clone, Debug, PartialEq)]
+pub struct With {
+    pub recursive: bool,
+    pub ctes: Vec<CommonTableExpr>,
+}
+
+/// `name [(columns)] AS (select)`, which queries may refer to like a table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommonTableExpr {
+    pub name: String,
+    /// The names of the columns, if given instead of those of the result.
+    pub columns: Vec<String>,
+    pub select: Box<Select>,
+}
+
+/// A `SELECT` combined with the ones before it by a compound operator.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompoundSelect {
+    pub operator: CompoundOperator,
+    pub select: Select,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CompoundOperator {
+    Union,
+    UnionAll,
+    Intersect,
+    Except,
+}
+
+/// An entry in the list of columns a `SELECT` returns.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ResultColumn {
+    /// `*`
+    Star,
+    /// `table.*`
+    TableStar(String),
+    Expr {
+        expr: Expr,
+        alias: Option<String>,
+    },
+}
+
+/// A table in a `FROM` clause, which is either named, the result of a
+/// subquery or the result of a table-valued function.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TableOrSubquery {
+    Table(TableName),
+    /// `(select) [AS alias]`
+    Subquery {
+        select: Box<Select>,
+        alias: Option<String>,
+    },
+    /// `name(args) [AS alias]`, like `json_each(column)`.
+    Function {
+        name: String,
+        args: Vec<Expr>,
+        alias: Option<String>,
+    },
+}
+
+/// A table named in a `FROM` clause.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TableName {
+    /// The name of the attached database, like `main`.
+    pub schema: Option<String>,
+    pub name: String,
+    pub alias: Option<String>,
+}
+
+/// A table joined to the tables before it in a `FROM` clause. A comma join
+/// is an inner join without a constraint.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Join {
+    pub operator: JoinOperator,
+    /// Whether the tables are joined on all the columns they have in common.
+    pub natural: bool,
+    pub table: TableOrSubquery,
+    pub constraint: Option<JoinConstraint>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum JoinOperator {
+    Inner,
+    /// `LEFT [OUTER] JOIN`, which keeps the rows on the left that have no
+    /// matching rows on the right, with NULLs for the columns on the right.
+    Left,
+    Cross,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JoinConstraint {
+    On(Expr),
+    /// `USING (columns)`, which joins on equal values of columns with the
+    /// same names on both sides.
+    Using(Vec<String>),
+}
+
+/// An expression in an `ORDER BY` clause.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrderingTerm {
+    pub expr: Expr,
+    pub descending: bool,
+    /// Whether NULLs are sorted first, if given explicitly.
+    pub nulls_first: Option<bool>,
+}
+
+/// A `CREATE TABLE` statement, as stored in the `sql` column of `sqlite_schema`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CreateTable {
+    pub temporary: bool,
+    pub if_not_exists: bool,
+    pub schema: Option<String>,
+    pub name: String,
+    pub columns: Vec<ColumnDef>,
+    pub constraints: Vec<TableConstraint>,
+    pub without_rowid: bool,
+    pub strict: bool,
+}
+
+/// A `PRAGMA [schema.]name [= value | (value)]` statement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pragma {
+    pub schema: Option<String>,
+    pub name: String,
+    /// The value the pragma is set to, which is a name, a string or a number,
+    /// as written.
+    pub value: Option<String>,
+}
+
+/// A `CREATE INDEX` statement, as stored in the `sql` column of `sqlite_schema`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CreateIndex {
+    pub unique: bool,
+    pub if_not_exists: bool,
+    pub schema: Option<String>,
+    pub name: String,
+    pub table: String,
+    /// The indexed columns or expressions, in the order of the key, with the
+    /// order they are sorted in.
+    pub columns: Vec<OrderingTerm>,
+    /// The condition of a partial index, which only has entries for the rows
+    /// that satisfy it.
+    pub where_clause: Option<Expr>,
+}
+
+/// A `CREATE VIEW` statement, as stored in the `sql` column of `sqlite_schema`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CreateView {
+    pub temporary: bool,
+    pub if_not_exists: bool,
+    pub schema: Option<String>,
+    pub name: String,
+    /// The names of the columns, if given instead of those of the result.
+    pub columns: Vec<String>,
+    pub select: Box<Select>,
+}
+
+impl CreateTable {
+    /// Finds the position of a column by its name, ignoring case.
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns
+            .iter()
+            .position(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The columns of the primary key, given either as a column constraint or
+    /// as a table constraint, in the order they appear in the key.
+    pub fn primary_key(&self) -> Vec<IndexedColumn> {
+        for constraint in &self.constraints {
+            if let TableConstraintKind::PrimaryKey(columns) = &constraint.kind {
+                return columns.clone();
+            }
+        }
+        self.columns
+            .iter()
+            .filter_map(|column| match column.primary_key()?.kind {
+                ColumnConstraintKind::PrimaryKey { descending, .. } => Some(IndexedColumn {
+                    name: column.name.clone(),
+                    collation: column.collation().map(str::to_string),
+                    descending,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The position of the column that is an alias for the rowid, if there is
+    /// one. That is the case for a column declared `INTEGER PRIMARY KEY` in a
+    /// table with a rowid, see <https://www.sqlite.org/lang_createtable.html#rowid>.
+    pub fn rowid_alias(&self) -> Option<usize> {
+        if self.without_rowid {
+            return None;
+        }
+        let [key] = &self.primary_key()[..] else {
+            return None;
+        };
+        let index = self.column_index(&key.name)?;
+        let column = &self.columns[index];
+        let is_integer = column
+            .declared_type
+            .as_ref()
+            .is_some_and(|t| t.eq_ignore_ascii_case("INTEGER"));
+        // For backwards compatibility, SQLite doesn't treat a column declared
+        // `INTEGER PRIMARY KEY DESC` as an alias, unlike a descending table constraint.
+        let descending_column = matches!(
+            column.primary_key().map(|c| &c.kind),
+            Some(ColumnConstraintKind::PrimaryKey {
+                descending: true,
+                ..
+            })
+        );
+
+        (is_integer && !descending_column).then_some(index)
+    }
+}
+
+/// The definition of a column in a `CREATE TABLE` statement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColumnDef {
+    pub name: String,
+    /// The type name as written, like `VARCHAR(255)`, if there is one.
+    pub declared_type: Option<String>,
+    /// The affinity resolved from the declared type.
+    pub affinity: Affinity,
+    pub constraints: Vec<ColumnConstraint>,
+}
+
+impl ColumnDef {
+    /// The column's `PRIMARY KEY` constraint, if it has one.
+    pub fn primary_key(&self) -> Option<&ColumnConstraint> {
+        self.constraints
+            .iter()
+            .find(|c| matches!(c.kind, ColumnConstraintKind::PrimaryKey { .. }))
+    }
+
+    /// The collation given by the column's `COLLATE` constraint, if it has one.
+    pub fn collation(&self) -> Option<&str> {
+        self.constraints.iter().find_map(|c| match &c.kind {
+            ColumnConstraintKind::Collate(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// The expression given by the column's `DEFAULT` constraint, if it has
+    /// one.
+    pub fn default_expr(&self) -> Option<&Expr> {
+        self.constraints.iter().find_map(|c| match &c.kind {
+            ColumnConstraintKind::Default(expr) => Some(expr),
+            _ => None,
+        })
+    }
+
+    /// The expression of a `VIRTUAL` generated column, whose values are not
+    /// stored but computed from the other columns of the row.
+    pub fn virtual_expr(&self) -> Option<&Expr> {
+        self.constraints.iter().find_map(|c| match &c.kind {
+            ColumnConstraintKind::Generated {
+                expr,
+                stored: false,
+            } => Some(expr),
+            _ => None,
+        })
+    }
+}
+
+/// A column constraint, optionally named with `CONSTRAINT name`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColumnConstraint {
+    pub name: Option<String>,
+    pub kind: ColumnConstraintKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColumnConstraintKind {
+    PrimaryKey {
+        descending: bool,
+        autoincrement: bool,
+    },
+    NotNull,
+    /// `NULL`, which SQLite accepts but which has no effect.
+    Null,
+    Unique,
+    Default(Expr),
+    Collate(String),
+    Check(Expr),
+    References(ForeignKey),
+    /// `[GENERATED ALWAYS] AS (expr) [STORED | VIRTUAL]`
+    Generated {
+        expr: Expr,
+        stored: bool,
+    },
+}
+
+/// A table constraint, optionally named with `CONSTRAINT name`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TableConstraint {
+    pub name: Option<String>,
+    pub kind: TableConstraintKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TableConstraintKind {
+    PrimaryKey(Vec<IndexedColumn>),
+    Unique(Vec<IndexedColumn>),
+    Check(Expr),
+    ForeignKey {
+        columns: Vec<String>,
+        references: ForeignKey,
+    },
+}
+
+/// A column of a key, like in `PRIMARY KEY (a COLLATE NOCASE, b DESC)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexedColumn {
+    pub name: String,
+    pub collation: Option<String>,
+    pub descending: bool,
+}
+
+/// The `REFERENCES` clause of a foreign key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForeignKey {
+    pub table: String,
+    /// The referenced columns, or empty to reference the primary key.
+    pub columns: Vec<String>,
+    pub on_delete: Option<ForeignKeyAction>,
+    pub on_update: Option<ForeignKeyAction>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ForeignKeyAction {
+    SetNull,
+    SetDefault,
+    Cascade,
+    Restrict,
+    NoAction,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    /// A parameter, whose value is bound when the statement runs. Parameters
+    /// are numbered from 1, and those with the same name share a number.
+    Parameter {
+        index: usize,
+        /// How the parameter is written, like `?3` or `:name`.
+        name: String,
+    },
+    Column {
+        table: Option<String>,
+        name: String,
+    },
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    /// `expr ISNULL` or `expr NOTNULL`, and their spelled out variants.
+    IsNull {
+        expr: Box<Expr>,
+        negated: bool,
+    },
+    /// `expr [NOT] IN (list)`
+    InList {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+        negated: bool,
+    },
+    /// `expr [NOT] IN (select)`
+    InSelect {
+        expr: Box<Expr>,
+        select: Box<Select>,
+        negated: bool,
+    },
+    /// `(select)`, whose value is the first column of the first row of the
+    /// subquery, or NULL if it has no rows.
+    Subquery(Box<Select>),
+    /// `EXISTS (select)`
+    Exists(Box<Select>),
+    /// `expr [NOT] BETWEEN low AND high`
+    Between {
+        expr: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+        negated: bool,
+    },
+    /// `expr [NOT] {LIKE | GLOB | REGEXP} pattern [ESCAPE escape]`
+    Like {
+        op: PatternOp,
+        expr: Box<Expr>,
+        pattern: Box<Expr>,
+        escape: Option<Box<Expr>>,
+        negated: bool,
+    },
+    Function(FunctionCall),
+    /// `CAST(expr AS type)`, where the type may be left out.
+    Cast {
+        expr: Box<Expr>,
+        type_name: Option<String>,
+    },
+    /// `expr COLLATE name`
+    Collate {
+        expr: Box<Expr>,
+        collation: String,
+    },
+    /// `CASE [operand] WHEN ... THEN ... [ELSE ...] END`
+    Case {
+        operand: Option<Box<Expr>>,
+        when_then: Vec<(Expr, Expr)>,
+        else_expr: Option<Box<Expr>>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FunctionCall {
+    pub name: String,
+    pub args: Vec<Expr>,
+    pub distinct: bool,
+    /// Whether the function was called as `name(*)`, like `count(*)`.
+    pub star: bool,
+    /// The window of a call to a window function, given by `OVER`.
+    pub over: Option<Window>,
+}
+
+/// The rows a window function is evaluated over:
+/// `([base] [PARTITION BY ...] [ORDER BY ...] [frame])`. `OVER name` refers
+/// to a window of the `WINDOW` clause as the base of an empty window.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Window {
+    /// The name of the window in the `WINDOW` clause this one extends.
+    pub base: Option<String>,
+    pub partition_by: Vec<Expr>,
+    pub order_by: Vec<OrderingTerm>,
+    pub frame: Option<Frame>,
+}
+
+/// `{ROWS | RANGE | GROUPS} BETWEEN start AND end [EXCLUDE ...]`, the rows of
+/// its partition that an aggregate or value function sees for a row.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub units: FrameUnits,
+    pub start: FrameBound,
+    pub end: FrameBound,
+    pub exclude: FrameExclude,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrameUnits {
+    Rows,
+    /// Rows whose `ORDER BY` value is within a distance of the current one.
+    Range,
+    /// Groups of peers, which are rows with equal `ORDER BY` values.
+    Groups,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FrameBound {
+    UnboundedPreceding,
+    Preceding(Box<Expr>),
+    CurrentRow,
+    Following(Box<Expr>),
+    UnboundedFollowing,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrameExclude {
+    NoOthers,
+    CurrentRow,
+    /// The current row and its peers.
+    Group,
+    /// The peers of the current row, but not the row itself.
+    Ties,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PatternOp {
+    Like,
+    Glob,
+    Regexp,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    /// `-expr`
+    Negate,
+    /// `+expr`
+    Plus,
+    /// `NOT expr`
+    Not,
+    /// `~expr`
+    BitNot,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Concat,
+    /// `->`, which extracts a part of JSON as JSON.
+    Extract,
+    /// `->>`, which extracts a part of JSON as an SQL value.
+    ExtractValue,
+    Multiply,
+    Divide,
+    Modulo,
+    Add,
+    Subtract,
+    ShiftLeft,
+    ShiftRight,
+    BitAnd,
+    BitOr,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    Eq,
+    NotEq,
+    Is,
+    IsNot,
+    And,
+    Or,
+}
+
+impl Expr {
+    /// Calls `f` for the expression and all expressions nested in it, parents
+    /// before their children.
+    pub fn walk<'a>(&'a self, f: &mut impl FnMut(&'a Expr)) {
+        f(self);
+        for child in self.children() {
+            child.walk(f);
+        }
+    }
+
+    /// The number of levels of the expression tree, which is 1 for an
+    /// expression without nested ones.
+    pub fn height(&self) -> usize {
+        let children = self.children().into_iter().map(Expr::height);
+        1 + children.max().unwrap_or(0)
+    }
+
+    /// The expressions directly nested in this one.
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            // Subqueries are queries of their own, whose expressions are not
+            // part of this one.
+            Expr::Literal(_)
+            | Expr::Parameter { .. }
+            | Expr::Column { .. }
+            | Expr::Subquery(_)
+            | Expr::Exists(_) => vec![],
+            Expr::Unary { expr, .. }
+            | Expr::IsNull { expr, .. }
+            | Expr::Collate { expr, .. }
+            | Expr::Cast { expr, .. }
+            | Expr::InSelect { expr, .. } => vec![expr],
+            Expr::Binary { left, right, .. } => vec![left, right],
+            Expr::InList { expr, list, .. } => std::iter::once(&**expr).chain(list).collect(),
+            Expr::Between {
+                expr, low, high, ..
+            } => vec![expr, low, high],
+            Expr::Like {
+                expr,
+                pattern,
+                escape,
+                ..
+            } => [&**expr, pattern]
+                .into_iter()
+                .chain(escape.as_deref())
+                .collect(),
+            Expr::Function(call) => {
+                let mut children = call.args.iter().collect::<Vec<_>>();
+                if let Some(window) = &call.over {
+                    children.extend(&window.partition_by);
+                    children.extend(window.order_by.iter().map(|term| &term.expr));
+                    if let Some(frame) = &window.frame {
+                        children.extend(frame.start.offset());
+                        children.extend(frame.end.offset());
+                    }
+                }
+                children
+            }
+            Expr::Case {
+                operand,
+                when_then,
+                else_expr,
+            } => operand
+                .iter()
+                .map(|e| &**e)
+                .chain(when_then.iter().flat_map(|(when, then)| [when, then]))
+                .chain(else_expr.iter().map(|e| &**e))
+                .collect(),
+        }
+    }
+}
+
+impl BinaryOp {
+    /// Whether the operator compares its operands.
+    pub fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Lt
+                | BinaryOp::LtEq
+                | BinaryOp::Gt
+                | BinaryOp::GtEq
+                | BinaryOp::Eq
+                | BinaryOp::NotEq
+                | BinaryOp::Is
+                | BinaryOp::IsNot
+        )
+    }
+
+    /// How tightly the operator binds, higher binding tighter.
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Eq | BinaryOp::NotEq | BinaryOp::Is | BinaryOp::IsNot => 3,
+            BinaryOp::Lt | BinaryOp::LtEq | BinaryOp::Gt | BinaryOp::GtEq => 4,
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight | BinaryOp::BitAnd | BinaryOp::BitOr => 5,
+            BinaryOp::Add | BinaryOp::Subtract => 6,
+            BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo => 7,
+            BinaryOp::Concat | BinaryOp::Extract | BinaryOp::ExtractValue => 8,
+        }
+    }
+}
+
+impl fmt::Display for CompoundOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            CompoundOperator::Union => "UNION",
+            CompoundOperator::UnionAll => "UNION ALL",
+            CompoundOperator::Intersect => "INTERSECT",
+            CompoundOperator::Except => "EXCEPT",
+        };
+        f.write_str(op)
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            BinaryOp::Concat => "||",
+            BinaryOp::Extract => "->",
+            BinaryOp::ExtractValue => "->>",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Modulo => "%",
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::ShiftLeft => "<<",
+            BinaryOp::ShiftRight => ">>",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::BitOr => "|",
+            BinaryOp::Lt => "<",
+            BinaryOp::LtEq => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::GtEq => ">=",
+            BinaryOp::Eq => "=",
+            BinaryOp::NotEq => "!=",
+            BinaryOp::Is => "IS",
+            BinaryOp::IsNot => "IS NOT",
+            BinaryOp::And => "AND",
+            BinaryOp::Or => "OR",
+        };
+        write!(f, "{op}")
+    }
+}
+
+impl PatternOp {
+    /// The name of the function that implements the operator.
+    pub fn function_name(self) -> &'static str {
+        match self {
+            PatternOp::Like => "like",
+            PatternOp::Glob => "glob",
+            PatternOp::Regexp => "regexp",
+        }
+    }
+}
+
+impl fmt::Display for PatternOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.function_name().to_ascii_uppercase())
+    }
+}
+
+impl FrameBound {
+    /// The expression giving the distance from the current row, if any.
+    pub fn offset(&self) -> Option<&Expr> {
+        match self {
+            FrameBound::Preceding(offset) | FrameBound::Following(offset) => Some(offset),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for OrderingTerm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expr)?;
+        if self.descending {
+            write!(f, " DESC")?;
+        }
+        match self.nulls_first {
+            Some(true) => write!(f, " NULLS FIRST"),
+            Some(false) => write!(f, " NULLS LAST"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(base) = &self.base {
+            parts.push(base.clone());
+        }
+        if !self.partition_by.is_empty() {
+            let exprs = self.partition_by.iter().map(|expr| expr.to_string());
+            parts.push(format!(
+                "PARTITION BY {}",
+                exprs.collect::<Vec<_>>().join(", ")
+            ));
+        }
+        if !self.order_by.is_empty() {
+            let terms = self.order_by.iter().map(|term| term.to_string());
+            parts.push(format!("ORDER BY {}", terms.collect::<Vec<_>>().join(", ")));
+        }
+        if let Some(frame) = &self.frame {
+            parts.push(frame.to_string());
+        }
+        write!(f, "({})", parts.join(" "))
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let units = match self.units {
+            FrameUnits::Rows => "ROWS",
+            FrameUnits::Range => "RANGE",
+            FrameUnits::Groups => "GROUPS",
+        };
+        write!(f, "{units} BETWEEN {} AND {}", self.start, self.end)?;
+        match self.exclude {
+            FrameExclude::NoOthers => Ok(()),
+            FrameExclude::CurrentRow => write!(f, " EXCLUDE CURRENT ROW"),
+            FrameExclude::Group => write!(f, " EXCLUDE GROUP"),
+            FrameExclude::Ties => write!(f, " EXCLUDE TIES"),
+        }
+    }
+}
+
+impl fmt::Display for FrameBound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameBound::UnboundedPreceding => write!(f, "UNBOUNDED PRECEDING"),
+            FrameBound::Preceding(offset) => write!(f, "{offset} PRECEDING"),
+            FrameBound::CurrentRow => write!(f, "CURRENT ROW"),
+            FrameBound::Following(offset) => write!(f, "{offset} FOLLOWING"),
+            FrameBound::UnboundedFollowing => write!(f, "UNBOUNDED FOLLOWING"),
+        }
+    }
+}
+
+impl fmt::Display for Select {
+    /// Formats the statement as SQL, for naming the result columns of
+    /// expressions with subqueries.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(with) = &self.with {
+            write!(f, "WITH ")?;
+            if with.recursive {
+                write!(f, "RECURSIVE ")?;
+            }
+            for (i, cte) in with.ctes.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", cte.name)?;
+                if !cte.columns.is_empty() {
+                    write!(f, "({})", cte.columns.join(", "))?;
+                }
+                write!(f, " AS ({})", cte.select)?;
+            }
+            write!(f, " ")?;
+        }
+        write_core(f, self)?;
+        for compound in &self.compound {
+            write!(f, " {} ", compound.operator)?;
+            write_core(f, &compound.select)?;
+        }
+        for (i, term) in self.order_by.iter().enumerate() {
+            write!(f, "{}{term}", if i == 0 { " ORDER BY " } else { ", " })?;
+        }
+        if let Some(limit) = &self.limit {
+            write!(f, " LIMIT {limit}")?;
+        }
+        if let Some(offset) = &self.offset {
+            write!(f, " OFFSET {offset}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats a `SELECT` without its compound operators, `ORDER BY` and `LIMIT`.
+fn write_core(f: &mut fmt::Formatter<'_>, select: &Select) -> fmt::Result {
+    write!(f, "SELECT ")?;
+    if select.distinct {
+        write!(f, "DISTINCT ")?;
+    }
+    for (i, column) in select.columns.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        match column {
+            ResultColumn::Star => write!(f, "*")?,
+            ResultColumn::TableStar(table) => write!(f, "{table}.*")?,
+            ResultColumn::Expr { expr, alias } => {
+                write!(f, "{expr}")?;
+                if let Some(alias) = alias {
+                    write!(f, " AS {alias}")?;
+                }
+            }
+        }
+    }
+    if let Some(from) = &select.from {
+        write!(f, " FROM {from}")?;
+    }
+    for join in &select.joins {
+        let natural = if join.natural { "NATURAL " } else { "" };
+        let operator = match join.operator {
+            JoinOperator::Inner => "",
+            JoinOperator::Left => "LEFT ",
+            JoinOperator::Cross => "CROSS ",
+        };
+        write!(f, " {natural}{operator}JOIN {}", join.table)?;
+        match &join.constraint {
+            Some(JoinConstraint::On(expr)) => write!(f, " ON {expr}")?,
+            Some(JoinConstraint::Using(columns)) => write!(f, " USING ({})", columns.join(", "))?,
+            None => {}
+        }
+    }
+    if let Some(condition) = &select.where_clause {
+        write!(f, " WHERE {condition}")?;
+    }
+    if !select.group_by.is_empty() {
+        write!(f, " GROUP BY ")?;
+        write_list(f, &select.group_by)?;
+    }
+    if let Some(condition) = &select.having {
+        write!(f, " HAVING {condition}")?;
+    }
+    for (i, (name, window)) in select.windows.iter().enumerate() {
+        write!(
+            f,
+            "{}{name} AS {window}",
+            if i == 0 { " WINDOW " } else { ", " }
+        )?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for TableOrSubquery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let alias = match self {
+            TableOrSubquery::Table(table) => {
+                if let Some(schema) = &table.schema {
+                    write!(f, "{schema}.")?;
+                }
+                write!(f, "{}", table.name)?;
+                &table.alias
+            }
+            TableOrSubquery::Subquery { select, alias } => {
+                write!(f, "({select})")?;
+                alias
+            }
+            TableOrSubquery::Function { name, args, alias } => {
+                write!(f, "{name}(")?;
+                write_list(f, args)?;
+                write!(f, ")")?;
+                alias
+            }
+        };
+        match alias {
+            Some(alias) => write!(f, " AS {alias}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Formats a list of expressions separated by commas.
+fn write_list(f: &mut fmt::Formatter<'_>, exprs: &[Expr]) -> fmt::Result {
+    for (i, expr) in exprs.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{expr}")?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Expr {
+    /// Formats the expression as SQL, which is what SQLite names result
+    /// columns without an alias after.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Literal(Value::Null) => write!(f, "NULL"),
+            Expr::Literal(Value::Text(t)) => write!(f, "'{}'", t.replace('\'', "''")),
+            Expr::Literal(Value::Blob(b)) => {
+                write!(f, "X'")?;
+                for byte in b {
+                    write!(f, "{byte:02X}")?;
+                }
+                write!(f, "'")
+            }
+            Expr::Literal(value) => write!(f, "{value}"),
+            Expr::Parameter { name, .. } => write!(f, "{name}"),
+            Expr::Column {
+                table: Some(table),
+                name,
+            } => write!(f, "{table}.{name}"),
+            Expr::Column { table: None, name } => write!(f, "{name}"),
+            Expr::Unary { op, expr } => match op {
+                UnaryOp::Negate => write!(f, "-{expr}"),
+                UnaryOp::Plus => write!(f, "+{expr}"),
+                UnaryOp::Not => write!(f, "NOT {expr}"),
+                UnaryOp::BitNot => write!(f, "~{expr}"),
+            },
+            Expr::Binary { op, left, right } => {
+                // Parenthesize operands that would otherwise bind differently.
+                let needs_parens = |operand: &Expr, right_side: bool| match operand {
+                    Expr::Binary { op: inner, .. } => {
+                        inner.precedence() < op.precedence()
+                            || (right_side && inner.precedence() == op.precedence())
+                    }
+                    _ => false,
+                };
+                if needs_parens(left, false) {
+                    write!(f, "({left})")?;
+                } else {
+                    write!(f, "{left}")?;
+                }
+                write!(f, " {op} ")?;
+                if needs_parens(right, true) {
+                    write!(f, "({right})")
+                } else {
+                    write!(f, "{right}")
+                }
+            }
+            Expr::IsNull { expr, negated } => {
+                let not = if *negated { "NOT " } else { "" };
+                write!(f, "{expr} IS {not}NULL")
+            }
+            Expr::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let not = if *negated { "NOT " } else { "" };
+                write!(f, "{expr} {not}IN (")?;
+                write_list(f, list)?;
+                write!(f, ")")
+            }
+            Expr::InSelect {
+                expr,
+                select,
+                negated,
+            } => {
+                let not = if *negated { "NOT " } else { "" };
+                write!(f, "{expr} {not}IN ({select})")
+            }
+            Expr::Subquery(select) => write!(f, "({select})"),
+            Expr::Exists(select) => write!(f, "EXISTS ({select})"),
+            Expr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => {
+                let not = if *negated { "NOT " } else { "" };
+                write!(f, "{expr} {not}BETWEEN {low} AND {high}")
+            }
+            Expr::Like {
+                op,
+                expr,
+                pattern,
+                escape,
+                negated,
+            } => {
+                let not = if *negated { "NOT " } else { "" };
+                write!(f, "{expr} {not}{op} {pattern}")?;
+                match escape {
+                    Some(escape) => write!(f, " ESCAPE {escape}"),
+                    None => Ok(()),
+                }
+            }
+            Expr::Function(call) => {
+                write!(f, "{}(", call.name)?;
+                if call.star {
+                    write!(f, "*")?;
+                } else {
+                    if call.distinct {
+                        write!(f, "DISTINCT ")?;
+                    }
+                    write_list(f, &call.args)?;
+                }
+                write!(f, ")")?;
+                match &call.over {
+                    Some(Window {
+                        base: Some(base),
+                        partition_by,
+                        order_by,
+                        frame: None,
+                    }) if partition_by.is_empty() && order_by.is_empty() => {
+                        write!(f, " OVER {base}")
+                    }
+                    Some(window) => write!(f, " OVER {window}"),
+                    None => Ok(()),
+                }
+            }
+            Expr::Cast { expr, type_name } => match type_name {
+                Some(type_name) => write!(f, "CAST({expr} AS {type_name})"),
+                None => write!(f, "CAST({expr} AS)"),
+            },
+            Expr::Collate { expr, collation } => write!(f, "{expr} COLLATE {collation}"),
+            Expr::Case {
+                operand,
+                when_then,
+                else_expr,
+            } => {
+                write!(f, "CASE")?;
+                if let Some(operand) = operand {
+                    write!(f, " {operand}")?;
+                }
+                for (when, then) in when_then {
+                    write!(f, " WHEN {when} THEN {then}")?;
+                }
+                if let Some(else_expr) = else_expr {
+                    write!(f, " ELSE {else_expr}")?;
+                }
+                write!(f, " END")
+            }
+        }
+    }
+}
@@ -1,8 +1,9 @@
-use anyhow::{anyhow, ensure, Result};
+use anyhow::{anyhow, bail, ensure, Result};
 
+use crate::value::Value;
 use crate::varint::*;
 
-#[expect(dead_code)]
+#[cfg_attr(not(test), expect(dead_code))]
 pub struct BTreePage {
     /// The header for this database page.
     pub header: BTreePageHeader,
@@ -40,6 +41,124 @@ impl BTreePage {
     }
 }
 
+/// Assembles the image of a B-Tree page from its cells.
+/// This is the inverse of [`BTreePage::parse`].
+pub struct BTreePageBuilder {
+    page_type: BTreePageType,
+    page_size: usize,
+    /// Whether this is page 1, whose first 100 bytes hold the database header.
+    first_page: bool,
+    right_most: Option<u32>,
+    /// The encoded cells, in the order they should appear in the cell pointer array.
+    cells: Vec<Vec<u8>>,
+    /// The number of bytes used by the encoded cells.
+    cells_len: usize,
+}
+
+#[cfg_attr(not(test), expect(dead_code))]
+impl BTreePageBuilder {
+    pub fn new(page_type: BTreePageType, page_size: u32, first_page: bool) -> Self {
+        Self {
+            page_type,
+            page_size: page_size as usize,
+            first_page,
+            right_most: None,
+            cells: Vec::new(),
+            cells_len: 0,
+        }
+    }
+
+    /// Sets the right-most pointer of an interior page.
+    pub fn right_most(mut self, page_num: u32) -> Self {
+        self.right_most = Some(page_num);
+        self
+    }
+
+    /// Adds a cell after the cells added so far.
+    /// Returns `false` and leaves the page unchanged if the cell does not fit.
+    pub fn add_cell(&mut self, cell: &Cell) -> Result<bool> {
+        let usable_size = self.page_size;
+        let max_local = match self.page_type {
+            BTreePageType::LeafTable => usable_size - 35,
+            _ => (usable_size - 12) * 64 / 255 - 23,
+        };
+        let mut bytes = cell.serialize(self.page_type, max_local)?;
+        // SQLite never stores cells smaller than 4 bytes, so that they can
+        // be turned into freeblocks when they're deleted.
+        if bytes.len() < 4 {
+            bytes.resize(4, 0);
+        }
+
+        if self.free_space() < bytes.len() + 2 {
+            return Ok(false);
+        }
+        self.cells_len += bytes.len();
+        self.cells.push(bytes);
+
+        Ok(true)
+    }
+
+    /// The number of bytes still available for cells and their pointers.
+    pub fn free_space(&self) -> usize {
+        let used = self.header_offset() + self.header_len() + self.cells.len() * 2 + self.cells_len;
+        self.page_size - used
+    }
+
+    /// Produces the page image. On page 1 the first 100 bytes are left
+    /// zeroed, for the database header to be written into.
+    pub fn build(self) -> Result<Vec<u8>> {
+        let is_interior = matches!(
+            self.page_type,
+            BTreePageType::InteriorIndex | BTreePageType::InteriorTable
+        );
+        ensure!(
+            is_interior == self.right_most.is_some(),
+            "Only interior pages have a right-most pointer, and they must have one"
+        );
+
+        let mut page = vec![0; self.page_size];
+        let header_offset = self.header_offset();
+        let mut pointer_offset = header_offset + self.header_len();
+        let mut content_offset = self.page_size;
+        for cell in self.cells.iter() {
+            content_offset -= cell.len();
+            page[content_offset..content_offset + cell.len()].copy_from_slice(cell);
+            page[pointer_offset..pointer_offset + 2]
+                .copy_from_slice(&(content_offset as u16).to_be_bytes());
+            pointer_offset += 2;
+        }
+
+        let header = &mut page[header_offset..];
+        header[0] = self.page_type as u8;
+        // There are no freeblocks and no fragmented bytes on a fresh page.
+        header[1..3].copy_from_slice(&0u16.to_be_bytes());
+        header[3..5].copy_from_slice(&(self.cells.len() as u16).to_be_bytes());
+        // A cell content area starting at 65536 is stored as 0.
+        header[5..7].copy_from_slice(&(content_offset as u16).to_be_bytes());
+        header[7] = 0;
+        if let Some(right_most) = self.right_most {
+            header[8..12].copy_from_slice(&right_most.to_be_bytes());
+        }
+
+        Ok(page)
+    }
+
+    fn header_offset(&self) -> usize {
+        if self.first_page {
+            100
+        } else {
+            0
+        }
+    }
+
+    fn header_len(&self) -> usize {
+        match self.page_type {
+            BTreePageType::InteriorIndex | BTreePageType::InteriorTable => 12,
+            BTreePageType::LeafIndex | BTreePageType::LeafTable => 8,
+        }
+    }
+}
+
 /// "SQLite format 3" plus the null terminator character at the end.
 const MAGIC: &[u8] = &[
     0x53, 0x51, 0x4c, 0x69, 0x74, 0x65, 0x20, 0x66, 0x6f, 0x72, 0x6d, 0x61, 0x74, 0x20, 0x33, 0x00,
@@ -48,7 +167,6 @@ const MAGIC: &[u8] = &[
 /// The database file header.
 /// The first 100 bytes of the database file. All multi-byte fields are stored as big-endian
 /// in the on-disk format.
-#[expect(dead_code)]
 pub struct DbHeader<'m> {
     /// Every SQLite database begins with a 16 byte sequence corresponding to
     /// the UTF-8 string "SQLite format 3" including the nul terminator character at the end.
@@ -110,7 +228,75 @@ pub struct DbHeader<'m> {
     pub sqlite_version: u32,
 }
 
+#[cfg_attr(not(test), expect(dead_code))]
+impl DbHeader<'static> {
+    /// Creates the header for a new database with the given page size and
+    /// number of pages, using UTF-8 and the current schema format.
+    pub fn new(page_size: u32, page_count: u32) -> Self {
+        Self {
+            magic: MAGIC,
+            page_size,
+            format_write_version: 1,
+            format_read_version: 1,
+            reserved_space: 0,
+            max_embedded_payload: 64,
+            min_embedded_payload: 32,
+            leaf_payload: 32,
+            file_change_count: 1,
+            page_count,
+            freelist_trunk_head: 0,
+            freelist_page_count: 0,
+            schema_cookie: 1,
+            schema_format: 4,
+            default_page_cache_size: 0,
+            vacuum_root_page: None,
+            db_text_encoding: 1,
+            user_version: 0,
+            incremental_vacuum: false,
+            application_id: 0,
+            version_valid_for: 1,
+            sqlite_version: 3045000,
+        }
+    }
+}
+
 impl<'m> DbHeader<'m> {
+    /// Encodes the header into the first 100 bytes of a database file.
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn serialize(&self) -> [u8; 100] {
+        let mut bytes = [0; 100];
+        bytes[0..16].copy_from_slice(self.magic);
+        let page_size = if self.page_size == 65536 {
+            1
+        } else {
+            self.page_size as u16
+        };
+        bytes[16..18].copy_from_slice(&page_size.to_be_bytes());
+        bytes[18] = self.format_write_version;
+        bytes[19] = self.format_read_version;
+        bytes[20] = self.reserved_space;
+        bytes[21] = self.max_embedded_payload;
+        bytes[22] = self.min_embedded_payload;
+        bytes[23] = self.leaf_payload;
+        bytes[24..28].copy_from_slice(&self.file_change_count.to_be_bytes());
+        bytes[28..32].copy_from_slice(&self.page_count.to_be_bytes());
+        bytes[32..36].copy_from_slice(&self.freelist_trunk_head.to_be_bytes());
+        bytes[36..40].copy_from_slice(&self.freelist_page_count.to_be_bytes());
+        bytes[40..44].copy_from_slice(&self.schema_cookie.to_be_bytes());
+        bytes[44..48].copy_from_slice(&self.schema_format.to_be_bytes());
+        bytes[48..52].copy_from_slice(&self.default_page_cache_size.to_be_bytes());
+        bytes[52..56].copy_from_slice(&self.vacuum_root_page.unwrap_or(0).to_be_bytes());
+        bytes[56..60].copy_from_slice(&self.db_text_encoding.to_be_bytes());
+        bytes[60..64].copy_from_slice(&self.user_version.to_be_bytes());
+        bytes[64..68].copy_from_slice(&u32::from(self.incremental_vacuum).to_be_bytes());
+        bytes[68..72].copy_from_slice(&self.application_id.to_be_bytes());
+        // Bytes 72 to 91 are reserved for expansion and must be zero.
+        bytes[92..96].copy_from_slice(&self.version_valid_for.to_be_bytes());
+        bytes[96..100].copy_from_slice(&self.sqlite_version.to_be_bytes());
+
+        bytes
+    }
+
     /// Parse a database header from the first 100 bytes of the database file.
     pub fn parse(bytes: &'m [u8]) -> Result<Self> {
         let magic = &bytes[0..16];
@@ -232,7 +418,7 @@ impl<'m> DbHeader<'m> {
 
 /// The header of a B-Tree page.
 /// 8 bytes for leaf pages and 12 bytes for interior pages.
-#[expect(dead_code)]
+#[cfg_attr(not(test), expect(dead_code))]
 pub struct BTreePageHeader {
     /// A one byte flag indicating the page type.
     pub page_type: BTreePageType,
@@ -324,42 +510,56 @@ pub enum Cell {
         left_child: u32,
         key: Varint,
     },
-    IndexLeaf,
-    IndexInterior,
+    IndexLeaf {
+        payload_len: Varint,
+        payload: Record,
+        overflow: Option<u32>,
+    },
+    IndexInterior {
+        left_child: u32,
+        payload_len: Varint,
+        payload: Record,
+        overflow: Option<u32>,
+    },
 }
 
 impl Cell {
     fn parse(kind: BTreePageType, bytes: &[u8]) -> Result<Self> {
         match kind {
-            BTreePageType::InteriorIndex => todo!(),
+            BTreePageType::InteriorIndex => {
+                let left_child = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                let (payload_len, read) = Varint::parse(&bytes[4..]);
+                let (payload, overflow) = Self::parse_payload(payload_len, &bytes[4 + read..]);
+
+                Ok(Self::IndexInterior {
+                    left_child,
+                    payload_len,
+                    payload,
+                    overflow,
+                })
+            }
             BTreePageType::InteriorTable => {
                 let left_child = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
                 let (key, _) = Varint::parse(&bytes[4..]);
                 Ok(Self::TableInterior { left_child, key })
             }
-            BTreePageType::LeafIndex => todo!(),
+            BTreePageType::LeafIndex => {
+                let (payload_len, read) = Varint::parse(&bytes[0..]);
+                let (payload, overflow) = Self::parse_payload(payload_len, &bytes[read..]);
+
+                Ok(Self::IndexLeaf {
+                    payload_len,
+                    payload,
+                    overflow,
+                })
+            }
             BTreePageType::LeafTable => {
                 let mut bytes_read: usize = 0;
                 let (payload_len, read) = Varint::parse(&bytes[0..]);
                 bytes_read += read;
                 let (key, read) = Varint::parse(&bytes[bytes_read..]);
                 bytes_read += read;
-                let (payload, read) = Record::parse(&bytes[bytes_read..], payload_len.0 as usize);
-                bytes_read += read;
-                let overflow = if read == payload_len.0 as usize {
-                    // No overflow.
-                    None
-                } else {
-                    // TODO: Implement overflow pages.
-                    eprintln!("WARNING: Overflow pages are currently unimplemented");
-                    // Overflow.
-                    Some(u32::from_be_bytes([
-                        bytes[bytes_read],
-                        bytes[bytes_read + 1],
-                        bytes[bytes_read + 2],
-                        bytes[bytes_read + 3],
-                    ]))
-                };
+                let (payload, overflow) = Self::parse_payload(payload_len, &bytes[bytes_read..]);
 
                 Ok(Self::TableLeaf {
                     payload_len,
@@ -370,9 +570,76 @@ impl Cell {
             }
         }
     }
+
+    /// Encodes the cell for a page of the given type. Payloads larger than
+    /// `max_local` would need overflow pages, which are not supported.
+    fn serialize(&self, kind: BTreePageType, max_local: usize) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        let payload = match (kind, self) {
+            (BTreePageType::LeafTable, Cell::TableLeaf { key, payload, .. }) => {
+                let payload = payload.serialize();
+                bytes.extend(Varint(payload.len() as i64).encode());
+                bytes.extend(key.encode());
+                payload
+            }
+            (BTreePageType::InteriorTable, Cell::TableInterior { left_child, key }) => {
+                bytes.extend(left_child.to_be_bytes());
+                bytes.extend(key.encode());
+                return Ok(bytes);
+            }
+            (BTreePageType::LeafIndex, Cell::IndexLeaf { payload, .. }) => {
+                let payload = payload.serialize();
+                bytes.extend(Varint(payload.len() as i64).encode());
+                payload
+            }
+            (
+                BTreePageType::InteriorIndex,
+                Cell::IndexInterior {
+                    left_child,
+                    payload,
+                    ..
+                },
+            ) => {
+                let payload = payload.serialize();
+                bytes.extend(left_child.to_be_bytes());
+                bytes.extend(Varint(payload.len() as i64).encode());
+                payload
+            }
+            _ => bail!("Cell type does not match page type"),
+        };
+        ensure!(
+            payload.len() <= max_local,
+            "Payload of {} bytes does not fit on the page without overflow pages",
+            payload.len()
+        );
+        bytes.extend(payload);
+
+        Ok(bytes)
+    }
+
+    /// Parses the record stored in a cell's payload, starting right after the
+    /// cell's payload length (and key, for table cells).
+    fn parse_payload(payload_len: Varint, bytes: &[u8]) -> (Record, Option<u32>) {
+        let (payload, read) = Record::parse(bytes, payload_len.0 as usize);
+        let overflow = if read == payload_len.0 as usize {
+            // No overflow.
+            None
+        } else {
+            // TODO: Implement overflow pages.
+            eprintln!("WARNING: Overflow pages are currently unimplemented");
+            // Overflow.
+            Some(u32::from_be_bytes([
+                bytes[read],
+                bytes[read + 1],
+                bytes[read + 2],
+                bytes[read + 3],
+            ]))
+        };
+
+        (payload, overflow)
+    }
 }
 
-#[expect(dead_code)]
 pub struct Record {
     pub header_len: Varint,
     pub serial_types: Vec<SerialType>,
@@ -380,7 +647,49 @@ pub struct Record {
 }
 
 impl Record {
-    fn parse(bytes: &[u8], _payload_len: usize) -> (Self, usize) {
+    /// Builds a record from logical values, storing each one with the smallest
+    /// serial type that can hold it. The integers 0 and 1 are only stored
+    /// without content if the database uses schema format 4.
+    pub fn from_values(values: Vec<Value>, schema_format: u32) -> Self {
+        let values: Vec<RecordValue> = values
+            .into_iter()
+            .map(|v| RecordValue::from_value(v, schema_format))
+            .collect();
+        let serial_types: Vec<SerialType> = values.iter().map(RecordValue::serial_type).collect();
+
+        // The header length includes the varint holding it.
+        let types_len: usize = serial_types
+            .iter()
+            .map(|st| Varint::from(*st).encoded_len())
+            .sum();
+        let mut header_len = types_len + 1;
+        while Varint(header_len as i64).encoded_len() + types_len != header_len {
+            header_len = Varint(header_len as i64).encoded_len() + types_len;
+        }
+
+        Self {
+            header_len: Varint(header_len as i64),
+            serial_types,
+            values,
+        }
+    }
+
+    /// Encodes the record into the on-disk record format.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = self.header_len.encode();
+        for st in self.serial_types.iter() {
+            bytes.extend(Varint::from(*st).encode());
+        }
+        for value in self.values.iter() {
+            value.write(&mut bytes);
+        }
+
+        bytes
+    }
+
+    /// Parses a record from the start of `bytes`, returning it along with the
+    /// number of bytes it took up.
+    pub fn parse(bytes: &[u8], _payload_len: usize) -> (Self, usize) {
         let mut bytes_read = 0;
         let (header_len, read) = Varint::parse(&bytes[0..]);
         bytes_read += read;
@@ -460,6 +769,24 @@ impl SerialType {
     }
 }
 
+impl From<SerialType> for Varint {
+    fn from(value: SerialType) -> Self {
+        match value {
+            SerialType::Null => Varint(0),
+            SerialType::I8 => Varint(1),
+            SerialType::I16 => Varint(2),
+            SerialType::I24 => Varint(3),
+            SerialType::I32 => Varint(4),
+            SerialType::I48 => Varint(5),
+            SerialType::I64 => Varint(6),
+            SerialType::F64 => Varint(7),
+            SerialType::Zero => Varint(8),
+            SerialType::One => Varint(9),
+            SerialType::N12AndEven(n) | SerialType::N13AndOdd(n) => n,
+        }
+    }
+}
+
 impl From<Varint> for SerialType {
     fn from(value: Varint) -> Self {
         match value.0 {
@@ -480,7 +807,7 @@ impl From<Varint> for SerialType {
     }
 }
 
-#[expect(dead_code)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum RecordValue {
     /// Value is a NULL. Content size 0.
     Null,
@@ -510,6 +837,81 @@ pub enum RecordValue {
 }
 
 impl RecordValue {
+    /// Picks the smallest representation for a logical value.
+    fn from_value(value: Value, schema_format: u32) -> Self {
+        match value {
+            Value::Null => RecordValue::Null,
+            // Schema format 4 introduced the serial types for 0 and 1.
+            Value::Integer(0) if schema_format >= 4 => RecordValue::Zero,
+            Value::Integer(1) if schema_format >= 4 => RecordValue::One,
+            Value::Integer(i) => {
+                if let Ok(i) = i8::try_from(i) {
+                    RecordValue::I8(i)
+                } else if let Ok(i) = i16::try_from(i) {
+                    RecordValue::I16(i)
+                } else if (-0x80_0000..0x80_0000).contains(&i) {
+                    RecordValue::I24(i as i32)
+                } else if let Ok(i) = i32::try_from(i) {
+                    RecordValue::I32(i)
+                } else if (-0x8000_0000_0000..0x8000_0000_0000).contains(&i) {
+                    RecordValue::I48(i)
+                } else {
+                    RecordValue::I64(i)
+                }
+            }
+            Value::Real(r) => RecordValue::F64(r),
+            Value::Text(t) => RecordValue::N13AndOdd(t),
+            Value::Blob(b) => RecordValue::N12AndEven(b),
+        }
+    }
+
+    /// The serial type describing how the value is stored.
+    fn serial_type(&self) -> SerialType {
+        match self {
+            RecordValue::Null => SerialType::Null,
+            RecordValue::I8(_) => SerialType::I8,
+            RecordValue::I16(_) => SerialType::I16,
+            RecordValue::I24(_) => SerialType::I24,
+            RecordValue::I32(_) => SerialType::I32,
+            RecordValue::I48(_) => SerialType::I48,
+            RecordValue::I64(_) => SerialType::I64,
+            RecordValue::F64(_) => SerialType::F64,
+            RecordValue::Zero => SerialType::Zero,
+            RecordValue::One => SerialType::One,
+            RecordValue::N12AndEven(b) => SerialType::N12AndEven(Varint(b.len() as i64 * 2 + 12)),
+            RecordValue::N13AndOdd(t) => SerialType::N13AndOdd(Varint(t.len() as i64 * 2 + 13)),
+        }
+    }
+
+    /// Appends the big-endian content of the value to `bytes`.
+    fn write(&self, bytes: &mut Vec<u8>) {
+        match self {
+            RecordValue::Null | RecordValue::Zero | RecordValue::One => {}
+            RecordValue::I8(v) => bytes.extend(v.to_be_bytes()),
+            RecordValue::I16(v) => bytes.extend(v.to_be_bytes()),
+            RecordValue::I24(v) => bytes.extend(&v.to_be_bytes()[1..]),
+            RecordValue::I32(v) => bytes.extend(v.to_be_bytes()),
+            RecordValue::I48(v) => bytes.extend(&v.to_be_bytes()[2..]),
+            RecordValue::I64(v) => bytes.extend(v.to_be_bytes()),
+            RecordValue::F64(v) => bytes.extend(v.to_be_bytes()),
+            RecordValue::N12AndEven(b) => bytes.extend(b),
+            RecordValue::N13AndOdd(t) => bytes.extend(t.as_bytes()),
+        }
+    }
+
+    /// Returns the value as an integer, if it is stored as one.
+    pub fn as_integer(&self) -> Option<i64> {
+        match *self {
+            RecordValue::Zero => Some(0),
+            RecordValue::One => Some(1),
+            RecordValue::I8(v) => Some(v.into()),
+            RecordValue::I16(v) => Some(v.into()),
+            RecordValue::I24(v) | RecordValue::I32(v) => Some(v.into()),
+            RecordValue::I48(v) | RecordValue::I64(v) => Some(v),
+            _ => None,
+        }
+    }
+
     fn parse(st: &SerialType, bytes: &[u8]) -> (Self, usize) {
         match st {
             SerialType::Null => (RecordValue::Null, 0),
@@ -577,3 +979,212 @@ impl RecordValue {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::btree_page::*;
+
+    #[test]
+    fn record_round_trips_through_serialization() {
+        let values = vec![
+            Value::Null,
+            Value::Integer(0),
+            Value::Integer(1),
+            Value::Integer(-128),
+            Value::Integer(300),
+            Value::Integer(-8_388_608),
+            Value::Integer(2_000_000_000),
+            Value::Integer(-140_737_488_355_328),
+            Value::Integer(i64::MAX),
+            Value::Real(-2.5),
+            Value::Text("Granny Smith".to_string()),
+            Value::Blob(vec![0xde, 0xad, 0xbe, 0xef]),
+        ];
+
+        let record = Record::from_values(values.clone(), 4);
+        let bytes = record.serialize();
+        let (parsed, read) = Record::parse(&bytes, bytes.len());
+
+        assert_eq!(read, bytes.len());
+        assert_eq!(parsed.header_len.0, record.header_len.0);
+        assert_eq!(parsed.values, record.values);
+        assert_eq!(
+            parsed
+                .values
+                .into_iter()
+                .map(Value::from)
+                .collect::<Vec<_>>(),
+            values
+        );
+    }
+
+    #[test]
+    fn record_picks_smallest_serial_types() {
+        let record = Record::from_values(
+            vec![
+                Value::Integer(0),
+                Value::Integer(1),
+                Value::Integer(127),
+                Value::Integer(128),
+                Value::Integer(40_000),
+                Value::Integer(3_000_000_000),
+                Value::Integer(1 << 50),
+            ],
+            4,
+        );
+        let serial_types = record
+            .serial_types
+            .into_iter()
+            .map(|st| Varint::from(st).0)
+            .collect::<Vec<_>>();
+        assert_eq!(serial_types, vec![8, 9, 1, 2, 3, 5, 6]);
+
+        // Older schema formats have no serial types for 0 and 1.
+        let record = Record::from_values(vec![Value::Integer(0), Value::Integer(1)], 1);
+        assert_eq!(record.values, vec![RecordValue::I8(0), RecordValue::I8(1)]);
+    }
+
+    #[test]
+    fn record_header_length_accounts_for_its_own_varint() {
+        // 127 serial types of one byte each need a two byte header length.
+        let record = Record::from_values(vec![Value::Null; 127], 4);
+        let bytes = record.serialize();
+
+        assert_eq!(record.header_len.0, 129);
+        assert_eq!(bytes.len(), 129);
+        assert_eq!(Record::parse(&bytes, bytes.len()).0.values.len(), 127);
+    }
+
+    fn table_leaf(key: i64, values: Vec<Value>) -> Cell {
+        let payload = Record::from_values(values, 4);
+        Cell::TableLeaf {
+            payload_len: Varint(payload.serialize().len() as i64),
+            key: Varint(key),
+            payload,
+            overflow: None,
+        }
+    }
+
+    #[test]
+    fn page_builder_round_trips_through_parse() {
+        let mut builder = BTreePageBuilder::new(BTreePageType::LeafTable, 4096, false);
+        for key in 1..=3 {
+            let cell = table_leaf(key, vec![Value::Integer(key), Value::Text("x".repeat(10))]);
+            assert!(builder.add_cell(&cell).unwrap());
+        }
+        let page = builder.build().unwrap();
+        let parsed = BTreePage::parse(&page, false).unwrap();
+
+        assert_eq!(page.len(), 4096);
+        assert_eq!(parsed.header.num_cells, 3);
+        assert_eq!(parsed.header.first_freeblock, 0);
+        assert_eq!(parsed.header.fragmented, 0);
+        assert_eq!(
+            parsed.header.cell_content_area,
+            *parsed.cell_pointer_array.iter().min().unwrap() as u32
+        );
+        for (cell, key) in parsed.cells.into_iter().zip(1..) {
+            let Cell::TableLeaf {
+                key: k, payload, ..
+            } = cell
+            else {
+                panic!("Unexpected cell type");
+            };
+            assert_eq!(k.0, key);
+            assert_eq!(Value::from(payload.values[0].clone()), Value::Integer(key));
+        }
+    }
+
+    #[test]
+    fn page_builder_writes_interior_pages() {
+        let mut builder =
+            BTreePageBuilder::new(BTreePageType::InteriorIndex, 512, false).right_most(7);
+        let payload = Record::from_values(vec![Value::Text("m".to_string()), Value::Integer(3)], 4);
+        let cell = Cell::IndexInterior {
+            left_child: 6,
+            payload_len: Varint(payload.serialize().len() as i64),
+            payload,
+            overflow: None,
+        };
+        assert!(builder.add_cell(&cell).unwrap());
+        let page = builder.build().unwrap();
+        let parsed = BTreePage::parse(&page, false).unwrap();
+
+        assert_eq!(parsed.header.right_most, Some(7));
+        let Cell::IndexInterior {
+            left_child,
+            payload,
+            ..
+        } = &parsed.cells[0]
+        else {
+            panic!("Unexpected cell type");
+        };
+        assert_eq!(*left_child, 6);
+        assert_eq!(payload.values[0], RecordValue::N13AndOdd("m".to_string()));
+
+        // A leaf page can't have a right-most pointer.
+        let builder = BTreePageBuilder::new(BTreePageType::LeafIndex, 512, false).right_most(7);
+        assert!(builder.build().is_err());
+    }
+
+    #[test]
+    fn page_builder_reports_when_cells_no_longer_fit() {
+        let mut builder = BTreePageBuilder::new(BTreePageType::LeafTable, 512, false);
+        let cell = table_leaf(1, vec![Value::Blob(vec![0; 100])]);
+        let mut added = 0;
+        while builder.add_cell(&cell).unwrap() {
+            added += 1;
+        }
+
+        // Each cell takes 105 bytes plus 2 for its pointer, after the 8 byte header.
+        assert_eq!(added, 4);
+        assert_eq!(builder.free_space(), 512 - 8 - 4 * 107);
+        assert_eq!(
+            BTreePage::parse(&builder.build().unwrap(), false)
+                .unwrap()
+                .cells
+                .len(),
+            4
+        );
+
+        // Payloads that would need overflow pages are rejected outright.
+        let mut builder = BTreePageBuilder::new(BTreePageType::LeafTable, 512, false);
+        assert!(builder
+            .add_cell(&table_leaf(1, vec![Value::Blob(vec![0; 500])]))
+            .is_err());
+    }
+
+    #[test]
+    fn builds_a_database_that_can_be_opened() {
+        let page_size = 1024;
+        let sql = "CREATE TABLE t (id integer primary key, name text)";
+        let schema_row = vec![
+            Value::Text("table".to_string()),
+            Value::Text("t".to_string()),
+            Value::Text("t".to_string()),
+            Value::Integer(2),
+            Value::Text(sql.to_string()),
+        ];
+        let mut schema_page = BTreePageBuilder::new(BTreePageType::LeafTable, page_size, true);
+        assert!(schema_page.add_cell(&table_leaf(1, schema_row)).unwrap());
+        let mut schema_page = schema_page.build().unwrap();
+        schema_page[..100].copy_from_slice(&DbHeader::new(page_size, 2).serialize());
+
+        let mut table_page = BTreePageBuilder::new(BTreePageType::LeafTable, page_size, false);
+        for key in 1..=20 {
+            let cell = table_leaf(key, vec![Value::Null, Value::Text(format!("row {key}"))]);
+            assert!(table_page.add_cell(&cell).unwrap());
+        }
+        let table_page = table_page.build().unwrap();
+
+        let path = std::env::temp_dir().join(format!("page-builder-{}.db", std::process::id()));
+        std::fs::write(&path, [schema_page, table_page].concat()).unwrap();
+        let db = crate::database::Database::open(&path).unwrap();
+        let header = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(DbHeader::parse(&header[..100]).unwrap().page_count, 2);
+        assert_eq!(db.schema.table("t").unwrap().sql.as_deref(), Some(sql));
+        assert_eq!(db.count_entries(2).unwrap(), 20);
+    }
+}
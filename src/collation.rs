@@ -0,0 +1,74 @@
+use anyhow::{bail, Result};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+/// A collating sequence, which defines how text values are compared.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Collation {
+    /// Compares text byte by byte.
+    #[default]
+    Binary,
+    /// Like `Binary`, but ignores the case of ASCII letters.
+    NoCase,
+    /// Like `Binary`, but ignores trailing spaces.
+    RTrim,
+}
+
+impl Collation {
+    /// Looks up one of the built-in collating sequences by name, ignoring case.
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "BINARY" => Ok(Self::Binary),
+            "NOCASE" => Ok(Self::NoCase),
+            "RTRIM" => Ok(Self::RTrim),
+            _ => bail!("no such collation sequence: {name}"),
+        }
+    }
+
+    pub fn compare(self, a: &str, b: &str) -> Ordering {
+        match self {
+            Self::Binary => a.as_bytes().cmp(b.as_bytes()),
+            Self::NoCase => a
+                .bytes()
+                .map(|c| c.to_ascii_lowercase())
+                .cmp(b.bytes().map(|c| c.to_ascii_lowercase())),
+            Self::RTrim => a.trim_end_matches(' ').cmp(b.trim_end_matches(' ')),
+        }
+    }
+
+    /// Converts text to a key that compares by the binary collation the way
+    /// the text compares by this collation.
+    pub fn key(self, text: &str) -> Cow<'_, str> {
+        match self {
+            Self::Binary => Cow::Borrowed(text),
+            Self::NoCase => Cow::Owned(text.to_ascii_lowercase()),
+            Self::RTrim => Cow::Borrowed(text.trim_end_matches(' ')),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::collation::*;
+
+    #[test]
+    fn compares_text_by_collation() {
+        assert_eq!(Collation::Binary.compare("a", "B"), Ordering::Greater);
+        assert_eq!(Collation::NoCase.compare("a", "B"), Ordering::Less);
+        assert_eq!(Collation::NoCase.compare("ABC", "abc"), Ordering::Equal);
+        assert_eq!(Collation::RTrim.compare("abc  ", "abc"), Ordering::Equal);
+        assert_eq!(Collation::RTrim.compare(" abc", "abc"), Ordering::Less);
+        assert_eq!(Collation::from_name("nocase").unwrap(), Collation::NoCase);
+        assert_eq!(
+            Collation::from_name("french").unwrap_err().to_string(),
+            "no such collation sequence: french"
+        );
+    }
+
+    #[test]
+    fn converts_text_to_keys() {
+        assert_eq!(Collation::Binary.key("Abc "), "Abc ");
+        assert_eq!(Collation::NoCase.key("Abc "), "abc ");
+        assert_eq!(Collation::RTrim.key("Abc  "), "Abc");
+    }
+}
@@ -0,0 +1,418 @@
+use anyhow::{bail, ensure, Result};
+use std::collections::BTreeSet;
+
+use crate::ast::*;
+use crate::collation::Collation;
+use crate::query::{
+    aggregates_in, distinct_key, execute_core, resolve_compound_term, run, Context, Limits,
+    ResultSet,
+};
+use crate::sort::{PriorityQueue, SortOrder};
+use crate::value::{Affinity, Value};
+
+/// The common table expressions of a `WITH` clause, which the statement it
+/// belongs to and the CTEs themselves may refer to like tables.
+pub struct CteScope<'a> {
+    with: &'a With,
+    /// The scope of the `WITH` clause of an enclosing statement.
+    parent: Option<&'a CteScope<'a>>,
+}
+
+impl<'a> CteScope<'a> {
+    /// Enters the scope of the `WITH` clause of a statement, if it has one.
+    pub fn enter(
+        select: &'a Select,
+        context: Context<'a>,
+        scope: &'a mut Option<Self>,
+    ) -> Result<Context<'a>> {
+        let Some(with) = &select.with else {
+            return Ok(context);
+        };
+        for (i, cte) in with.ctes.iter().enumerate() {
+            let earlier = &with.ctes[..i];
+            if earlier
+                .iter()
+                .any(|c| c.name.eq_ignore_ascii_case(&cte.name))
+            {
+                bail!("duplicate WITH table name: {}", cte.name);
+            }
+        }
+        let scope = scope.insert(Self {
+            with,
+            parent: context.ctes,
+        });
+        Ok(Context {
+            ctes: Some(scope),
+            ..context
+        })
+    }
+
+    /// Finds the CTE a table name refers to, along with the scope of the
+    /// `WITH` clause that defines it.
+    pub fn find(&'a self, name: &str) -> Option<(&'a Self, &'a CommonTableExpr)> {
+        let ctes = &self.with.ctes;
+        match ctes.iter().find(|cte| cte.name.eq_ignore_ascii_case(name)) {
+            Some(cte) => Some((self, cte)),
+            None => self.parent?.find(name),
+        }
+    }
+}
+
+/// The row of a recursive CTE that its recursive `SELECT`s run for.
+pub struct RecursiveRow<'a> {
+    cte: &'a CommonTableExpr,
+    columns: &'a [String],
+    collations: &'a [Option<Collation>],
+    affinities: &'a [Option<Affinity>],
+    values: &'a [Value],
+}
+
+/// Runs the common table expression a table name refers to, if there is one
+/// by that name. A query that only needs the first `needed` rows of the CTE
+/// says so, so that a recursive CTE stops there rather than running until it
+/// has no more rows.
+pub fn lookup(name: &str, needed: Option<usize>, context: Context) -> Result<Option<ResultSet>> {
+    if let Some(recursive) = context.recursive {
+        if recursive.cte.name.eq_ignore_ascii_case(name) {
+            return Ok(Some(ResultSet {
+                columns: recursive.columns.to_vec(),
+                rows: vec![recursive.values.to_vec()],
+                collations: recursive.collations.to_vec(),
+                affinities: recursive.affinities.to_vec(),
+            }));
+        }
+    }
+    let Some((scope, cte)) = context.ctes.and_then(|ctes| ctes.find(name)) else {
+        return Ok(None);
+    };
+
+    let key = cte as *const CommonTableExpr;
+    if context.state.running.borrow().contains(&key) {
+        bail!("circular reference: {}", cte.name);
+    }
+    context.state.running.borrow_mut().push(key);
+    // A CTE sees the CTEs of the WITH clause that defines it, wherever it is
+    // referred to.
+    let context = Context {
+        ctes: Some(scope),
+        recursive: None,
+        ..context
+    };
+    let result = run_cte(cte, needed, context);
+    context.state.running.borrow_mut().pop();
+
+    result.map(Some)
+}
+
+/// Checks that the `FROM` clause of a recursive `SELECT` refers to the rows
+/// of its CTE no more than once.
+pub fn check_recursive_references(select: &Select, context: Context) -> Result<()> {
+    if let Some(recursive) = context.recursive {
+        let name = &recursive.cte.name;
+        if references(select, name) > 1 {
+            bail!("multiple references to recursive table: {name}");
+        }
+    }
+    Ok(())
+}
+
+/// Checks that a subquery of a recursive `SELECT` doesn't refer to the rows
+/// of its CTE.
+pub fn check_nested_references(select: &Select, context: Context) -> Result<()> {
+    if let Some(recursive) = context.recursive {
+        let name = &recursive.cte.name;
+        let mut selects = std::iter::once(select).chain(select.compound.iter().map(|c| &c.select));
+        if selects.any(|select| references(select, name) > 0) {
+            bail!("multiple recursive references: {name}");
+        }
+    }
+    Ok(())
+}
+
+fn run_cte(cte: &CommonTableExpr, needed: Option<usize>, context: Context) -> Result<ResultSet> {
+    let mut result = match recursive_start(cte) {
+        Some(start) => run_recursive(cte, start, needed, context)?,
+        None => run(&cte.select, false, context)?,
+    };
+    result.columns = column_names(cte, result.columns)?;
+    Ok(result)
+}
+
+/// Runs a recursive CTE. The rows of the `SELECT`s before `start` go into a
+/// queue, and each row taken from the queue is added to the result, and the
+/// recursive `SELECT`s run for it add their rows to the queue, until the
+/// queue is empty. With `ORDER BY`, the queue returns the first row by that
+/// order rather than the row added first. With `UNION`, rows that were added
+/// to the queue before are left out. The queue is also left alone once the
+/// result has the `needed` rows.
+fn run_recursive(
+    cte: &CommonTableExpr,
+    start: usize,
+    needed: Option<usize>,
+    context: Context,
+) -> Result<ResultSet> {
+    let mut scope = None;
+    let context = CteScope::enter(&cte.select, context, &mut scope)?;
+    let select = &*cte.select;
+    let recursive = &select.compound[start..];
+    for CompoundSelect { select, .. } in recursive {
+        let aggregate = select.columns.iter().any(|column| match column {
+            ResultColumn::Expr { expr, .. } => !aggregates_in(expr).is_empty(),
+            _ => false,
+        });
+        if aggregate || !select.group_by.is_empty() {
+            bail!("recursive aggregate queries not supported");
+        }
+    }
+
+    let initial = Select {
+        with: None,
+        compound: select.compound[..start].to_vec(),
+        order_by: Vec::new(),
+        limit: None,
+        offset: None,
+        ..select.clone()
+    };
+    let first = run(&initial, false, context)?;
+    let columns = column_names(cte, first.columns)?;
+    // The columns have the collations and affinities of the SELECTs before the
+    // recursive ones.
+    let collations = first.collations;
+    let affinities = first.affinities;
+
+    let mut sort_keys = Vec::new();
+    let mut sort_order = Vec::new();
+    for (i, term) in select.order_by.iter().enumerate() {
+        let (expr, collation) = match &term.expr {
+            Expr::Collate { expr, collation } => (&**expr, Some(Collation::from_name(collation)?)),
+            expr => (expr, None),
+        };
+        let column = resolve_compound_term(expr, i, &[(select, columns.clone())])?;
+        let collation = collation.or(collations[column]).unwrap_or_default();
+        sort_keys.push(column);
+        sort_order.push(SortOrder::new(term.descending, term.nulls_first, collation));
+    }
+    let Limits { mut offset, limit } = Limits::new(select, context)?;
+    let limit = limit.into_iter().chain(needed).min();
+
+    let union = recursive[0].operator == CompoundOperator::Union;
+    let key_collations = collations
+        .iter()
+        .map(|collation| collation.unwrap_or_default())
+        .collect::<Vec<_>>();
+    let mut seen = BTreeSet::new();
+    let mut queue = PriorityQueue::new(sort_order);
+    let mut pending = first.rows;
+    let mut rows = Vec::new();
+    loop {
+        for row in pending.drain(..) {
+            if union && !seen.insert(distinct_key(&row, &key_collations)) {
+                continue;
+            }
+            let keys = sort_keys.iter().map(|&i| row[i].clone()).collect();
+            queue.push(keys, row);
+        }
+        if limit.is_some_and(|limit| rows.len() >= limit) {
+            break;
+        }
+        let Some(row) = queue.pop() else {
+            break;
+        };
+
+        let current = RecursiveRow {
+            cte,
+            columns: &columns,
+            collations: &collations,
+            affinities: &affinities,
+            values: &row,
+        };
+        let context = Context {
+            recursive: Some(&current),
+            ..context
+        };
+        for CompoundSelect { operator, select } in recursive {
+            let result = execute_core(select, &[], Limits::default(), context)?;
+            ensure!(
+                result.columns.len() == columns.len(),
+                "SELECTs to the left and right of {operator} do not have the same number of result columns"
+            );
+            pending.extend(result.rows);
+        }
+
+        if offset > 0 {
+            offset -= 1;
+        } else {
+            rows.push(row);
+        }
+    }
+
+    Ok(ResultSet {
+        columns,
+        rows,
+        collations,
+        affinities,
+    })
+}
+
+/// The names of the columns of a CTE, which are those of its result unless
+/// given explicitly.
+fn column_names(cte: &CommonTableExpr, columns: Vec<String>) -> Result<Vec<String>> {
+    if cte.columns.is_empty() {
+        return Ok(columns);
+    }
+    ensure!(
+        cte.columns.len() == columns.len(),
+        "table {} has {} values for {} columns",
+        cte.name,
+        columns.len(),
+        cte.columns.len()
+    );
+    Ok(cte.columns.clone())
+}
+
+/// Finds the first of the recursive `SELECT`s of a CTE, if it has any. These
+/// are the `SELECT`s at the end that refer to the CTE in their `FROM` clause
+/// and are combined with the same operator, `UNION` or `UNION ALL`. The
+/// `SELECT`s before them give the initial rows.
+fn recursive_start(cte: &CommonTableExpr) -> Option<usize> {
+    let compound = &cte.select.compound;
+    let operator = compound.last()?.operator;
+    if !matches!(
+        operator,
+        CompoundOperator::Union | CompoundOperator::UnionAll
+    ) {
+        return None;
+    }
+    let start = compound
+        .iter()
+        .rposition(|c| c.operator != operator || references(&c.select, &cte.name) == 0)
+        .map_or(0, |i| i + 1);
+    (start < compound.len()).then_some(start)
+}
+
+/// Counts the tables in the `FROM` clause of a `SELECT` with the given name.
+fn references(select: &Select, name: &str) -> usize {
+    let tables = select
+        .from
+        .iter()
+        .chain(select.joins.iter().map(|j| &j.table));
+    tables
+        .filter(|table| {
+            matches!(table, TableOrSubquery::Table(table)
+                if table.schema.is_none() && table.name.eq_ignore_ascii_case(name))
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::query::tests::{rows, run};
+
+    #[test]
+    fn runs_ctes_referring_to_each_other() {
+        let sql = "WITH big AS (SELECT * FROM per_city WHERE n > 1), \
+                   per_city AS (SELECT city, count(*) n FROM customers GROUP BY city) \
+                   SELECT city, n FROM big";
+
+        assert_eq!(rows("joins.db", sql), ["Paris|2"]);
+        assert_eq!(run("joins.db", sql).unwrap().columns, ["city", "n"]);
+    }
+
+    #[test]
+    fn runs_recursive_ctes() {
+        assert_eq!(
+            rows(
+                "joins.db",
+                "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c WHERE x < 5) \
+                 SELECT sum(x) FROM c"
+            ),
+            ["15"]
+        );
+        // Each recursive SELECT runs for each row in turn.
+        assert_eq!(
+            rows(
+                "joins.db",
+                "WITH c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c WHERE x < 3 \
+                 UNION ALL SELECT x + 10 FROM c WHERE x < 3) SELECT x FROM c"
+            ),
+            ["1", "2", "11", "3", "12"]
+        );
+        // The rows are taken from the queue by the ORDER BY, and the
+        // recursion goes on for rows left out by the OFFSET.
+        assert_eq!(
+            rows(
+                "joins.db",
+                "WITH chain(id, depth) AS (SELECT 1, 0 \
+                 UNION ALL SELECT id * 2, depth + 1 FROM chain WHERE id < 40 \
+                 UNION ALL SELECT id * 3, depth + 1 FROM chain WHERE id < 40 \
+                 ORDER BY 2 DESC, 1 LIMIT 6 OFFSET 2) SELECT id FROM chain"
+            ),
+            ["4", "8", "16", "32", "64", "96"]
+        );
+        // UNION leaves out rows seen before, which ends the recursion.
+        assert_eq!(
+            rows(
+                "joins.db",
+                "WITH c(x) AS (SELECT 1 UNION SELECT 3 - x FROM c) SELECT x FROM c"
+            ),
+            ["1", "2"]
+        );
+    }
+
+    #[test]
+    fn stops_recursive_ctes_at_the_limit_of_the_query() {
+        assert_eq!(
+            rows(
+                "joins.db",
+                "WITH RECURSIVE r(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM r) \
+                 SELECT x FROM r LIMIT 3"
+            ),
+            ["1", "2", "3"]
+        );
+        assert_eq!(
+            rows(
+                "joins.db",
+                "WITH RECURSIVE r(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM r LIMIT 10) \
+                 SELECT x * 2 FROM r LIMIT 2 OFFSET 3"
+            ),
+            ["8", "10"]
+        );
+        assert_eq!(
+            rows(
+                "joins.db",
+                "WITH RECURSIVE r(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM r LIMIT 4) \
+                 SELECT x FROM r LIMIT 10"
+            ),
+            ["1", "2", "3", "4"]
+        );
+    }
+
+    #[test]
+    fn reports_invalid_ctes() {
+        let error = |sql| run("joins.db", sql).err().unwrap().to_string();
+
+        assert_eq!(
+            error("WITH a AS (SELECT 1), A AS (SELECT 2) SELECT * FROM a"),
+            "duplicate WITH table name: A"
+        );
+        assert_eq!(
+            error("WITH a(x, y) AS (SELECT 1) SELECT * FROM a"),
+            "table a has 1 values for 2 columns"
+        );
+        assert_eq!(
+            error("WITH a AS (SELECT * FROM a) SELECT * FROM a"),
+            "circular reference: a"
+        );
+        assert_eq!(
+            error("WITH a(x) AS (SELECT 1 UNION ALL SELECT count(*) FROM a) SELECT * FROM a"),
+            "recursive aggregate queries not supported"
+        );
+        assert_eq!(
+            error(
+                "WITH a(x) AS (SELECT 1 UNION ALL SELECT a.x + 1 FROM a, a b WHERE a.x < 3) \
+                 SELECT * FROM a"
+            ),
+            "multiple references to recursive table: a"
+        );
+    }
+}
@@ -0,0 +1,270 @@
+use anyhow::{bail, ensure, Result};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::prelude::*;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+use crate::btree_page::*;
+use crate::schema::Schema;
+
+/// An open database file.
+pub struct Database {
+    file: File,
+    /// Database page size in bytes.
+    pub page_size: u32,
+    /// The contents of the `sqlite_schema` table.
+    pub schema: Schema,
+    /// Whether `LIKE` tells upper and lower case ASCII letters apart, as set
+    /// by `PRAGMA case_sensitive_like`.
+    pub case_sensitive_like: bool,
+}
+
+impl Database {
+    /// Opens the database file at the given path and loads its schema.
+    pub fn open(db_file: impl AsRef<Path>) -> Result<Self> {
+        let mut file = File::open(db_file)?;
+        let mut header = [0; 100];
+        file.read_exact(&mut header)?;
+
+        let header = DbHeader::parse(&header)?;
+        let page_size = header.page_size;
+
+        let mut db = Self {
+            file,
+            page_size,
+            schema: Schema::default(),
+            case_sensitive_like: false,
+        };
+        db.schema = Schema::load(&db)?;
+
+        Ok(db)
+    }
+
+    /// Reads and parses the B-tree page with the given page number.
+    /// Page numbers start at 1, which is the page holding the database header
+    /// and the root of the `sqlite_schema` table.
+    pub fn read_page(&self, page_num: u32) -> Result<BTreePage> {
+        ensure!(page_num > 0, "Invalid page number: {page_num}");
+        let mut page = vec![0; self.page_size as usize];
+        self.file
+            .read_exact_at(&mut page, (page_num - 1) as u64 * self.page_size as u64)?;
+
+        if page_num == 1 {
+            // The first page includes the database header of 100 bytes.
+            BTreePage::parse(&page[100..], true)
+        } else {
+            BTreePage::parse(&page[0..], false)
+        }
+    }
+
+    /// Returns a cursor over the entries of the B-tree rooted at `rootpage`.
+    pub fn cursor(&self, rootpage: u32) -> Cursor<'_> {
+        Cursor {
+            db: self,
+            rootpage: Some(rootpage),
+            stack: Vec::new(),
+        }
+    }
+
+    /// Looks up the entry with the given rowid in the table B-tree rooted at
+    /// `rootpage`, descending from the root instead of scanning the table.
+    pub fn seek_rowid(&self, rootpage: u32, rowid: i64) -> Result<Option<Cell>> {
+        let mut page_num = rootpage;
+        loop {
+            let btree = self.read_page(page_num)?;
+            match btree.header.page_type {
+                BTreePageType::InteriorTable => {
+                    // The key of an interior cell is the largest rowid in its
+                    // left subtree.
+                    let child = btree.cells.iter().find_map(|cell| match cell {
+                        Cell::TableInterior { left_child, key } if rowid <= key.0 => {
+                            Some(*left_child)
+                        }
+                        _ => None,
+                    });
+                    let Some(child) = child.or(btree.header.right_most) else {
+                        bail!("Missing right-most pointer in interior page {page_num}");
+                    };
+                    page_num = child;
+                }
+                BTreePageType::LeafTable => {
+                    let cell = btree
+                        .cells
+                        .into_iter()
+                        .find(|cell| matches!(cell, Cell::TableLeaf { key, .. } if key.0 == rowid));
+                    return Ok(cell);
+                }
+                _ => bail!("Expected a table B-tree page at page {page_num}"),
+            }
+        }
+    }
+
+    /// Returns a cursor over the entries of the index B-tree rooted at
+    /// `rootpage`, starting at the first entry that is not less than some
+    /// key. `compare` compares the record of an entry with that key.
+    pub fn seek(&self, rootpage: u32, compare: impl Fn(&Record) -> Ordering) -> Result<Cursor<'_>> {
+        let mut stack = Vec::new();
+        let mut page_num = rootpage;
+        loop {
+            let btree = self.read_page(page_num)?;
+            let mut cells = btree.cells;
+            let position = cells.iter().position(|cell| match cell {
+                Cell::IndexInterior { payload, .. } | Cell::IndexLeaf { payload, .. } => {
+                    compare(payload).is_ge()
+                }
+                _ => false,
+            });
+
+            match btree.header.page_type {
+                BTreePageType::InteriorIndex => {
+                    // Entries equal to the key may also be in the left subtree
+                    // of the first entry that is not less than it, so continue
+                    // there and return to that entry afterwards.
+                    let (child, pending, right_most) = match position {
+                        Some(position) => {
+                            let rest = cells.split_off(position + 1);
+                            let cell = cells.pop();
+                            cells = rest;
+                            let Some(Cell::IndexInterior { left_child, .. }) = &cell else {
+                                bail!("Unexpected cell type in interior index page");
+                            };
+                            (*left_child, cell, btree.header.right_most)
+                        }
+                        None => {
+                            cells.clear();
+                            let Some(right_most) = btree.header.right_most else {
+                                bail!("Missing right-most pointer in interior page {page_num}");
+                            };
+                            (right_most, None, None)
+                        }
+                    };
+                    stack.push(CursorPage {
+                        cells: cells.into_iter(),
+                        right_most,
+                        pending,
+                    });
+                    page_num = child;
+                }
+                BTreePageType::LeafIndex => {
+                    cells.drain(..position.unwrap_or(cells.len()));
+                    stack.push(CursorPage {
+                        cells: cells.into_iter(),
+                        right_most: None,
+                        pending: None,
+                    });
+                    return Ok(Cursor {
+                        db: self,
+                        rootpage: None,
+                        stack,
+                    });
+                }
+                _ => bail!("Expected an index B-tree page at page {page_num}"),
+            }
+        }
+    }
+
+    /// Counts the entries in the B-tree rooted at `rootpage` by adding up the
+    /// number of cells on its pages, without parsing any records.
+    pub fn count_entries(&self, rootpage: u32) -> Result<usize> {
+        let mut entries = 0;
+        let mut remaining_pages = vec![rootpage];
+        while let Some(page_num) = remaining_pages.pop() {
+            let btree = self.read_page(page_num)?;
+            match btree.header.page_type {
+                BTreePageType::InteriorIndex | BTreePageType::InteriorTable => {
+                    if let BTreePageType::InteriorIndex = btree.header.page_type {
+                        // Interior cells of index B-trees hold entries of their own.
+                        entries += btree.header.num_cells as usize;
+                    }
+                    for cell in btree.cells {
+                        let (Cell::TableInterior { left_child, .. }
+                        | Cell::IndexInterior { left_child, .. }) = cell
+                        else {
+                            unreachable!("Interior pages only contain interior cells");
+                        };
+                        remaining_pages.push(left_child);
+                    }
+                    let rightmost = btree
+                        .header
+                        .right_most
+                        .expect("Right-most pointer should exist in interior page");
+                    remaining_pages.push(rightmost);
+                }
+                BTreePageType::LeafIndex | BTreePageType::LeafTable => {
+                    entries += btree.header.num_cells as usize;
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+}
+
+/// Iterates over the cells of a B-tree that hold entries in key order,
+/// i.e. the cells of leaf pages and the cells of interior index pages.
+pub struct Cursor<'db> {
+    db: &'db Database,
+    /// The root page, until it has been loaded.
+    rootpage: Option<u32>,
+    /// The pages from the root down to the current page.
+    stack: Vec<CursorPage>,
+}
+
+struct CursorPage {
+    cells: std::vec::IntoIter<Cell>,
+    right_most: Option<u32>,
+    /// An interior index cell whose left subtree is being visited. It is
+    /// returned once that subtree is done.
+    pending: Option<Cell>,
+}
+
+impl Cursor<'_> {
+    fn push(&mut self, page_num: u32) -> Result<()> {
+        let btree = self.db.read_page(page_num)?;
+        self.stack.push(CursorPage {
+            cells: btree.cells.into_iter(),
+            right_most: btree.header.right_most,
+            pending: None,
+        });
+        Ok(())
+    }
+
+    fn advance(&mut self) -> Result<Option<Cell>> {
+        if let Some(rootpage) = self.rootpage.take() {
+            self.push(rootpage)?;
+        }
+
+        loop {
+            let Some(page) = self.stack.last_mut() else {
+                return Ok(None);
+            };
+            match page.cells.next() {
+                Some(Cell::TableInterior { left_child, .. }) => self.push(left_child)?,
+                Some(cell @ Cell::IndexInterior { left_child, .. }) => {
+                    page.pending = Some(cell);
+                    self.push(left_child)?;
+                }
+                Some(cell) => return Ok(Some(cell)),
+                None => {
+                    if let Some(right_most) = page.right_most.take() {
+                        self.push(right_most)?;
+                        continue;
+                    }
+                    self.stack.pop();
+                    if let Some(cell) = self.stack.last_mut().and_then(|p| p.pending.take()) {
+                        return Ok(Some(cell));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for Cursor<'_> {
+    type Item = Result<Cell>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.advance().transpose()
+    }
+}
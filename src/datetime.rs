@@ -0,0 +1,1077 @@
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::printf;
+use crate::value::{parse_number, Value};
+
+/// Milliseconds in a day.
+const DAY: i64 = 86_400_000;
+
+/// The Julian day of the unix epoch, 1970-01-01 00:00:00, in milliseconds.
+const UNIX_EPOCH_JD: i64 = 210_866_760_000_000;
+
+/// The largest Julian day in milliseconds that is a valid time, at the end of
+/// the year 9999.
+const MAX_JD: i64 = 464_269_060_799_999;
+
+/// The units of modifiers like `'+N days'`: their names, the magnitude that
+/// is out of range for them, and how many seconds each of them takes.
+const UNITS: [(&str, f64, f64); 6] = [
+    ("second", 4.6427e14, 1.0),
+    ("minute", 7.7379e12, 60.0),
+    ("hour", 1.2897e11, 3600.0),
+    ("day", 5373485.0, 86400.0),
+    ("month", 176546.0, 2592000.0),
+    ("year", 14713.0, 31536000.0),
+];
+
+/// A point in time, as SQLite's date and time functions keep track of it: as
+/// a Julian day in milliseconds, or as the year, month and day and the time
+/// of the day, whichever are valid. Computing one from the other is done
+/// lazily, as some functions print fields that are out of range, like
+/// `'24:00'`, as they were given.
+#[derive(Clone, Default)]
+struct DateTime {
+    jd: i64,
+    year: i32,
+    month: i32,
+    day: i32,
+    hour: i32,
+    minute: i32,
+    second: f64,
+    /// The time zone the time was given in, in minutes east of UTC.
+    tz: i32,
+    valid_jd: bool,
+    valid_ymd: bool,
+    valid_hms: bool,
+    /// The time value was a number, which modifiers like `'unixepoch'` may
+    /// still reinterpret.
+    raw: Option<f64>,
+    tz_set: bool,
+    utc: bool,
+    local: bool,
+    subsec: bool,
+    /// How many days a month or year added overflowed the end of the month
+    /// by, which the `'floor'` modifier takes back.
+    floor: i32,
+    error: bool,
+}
+
+impl DateTime {
+    /// Parses a time value and applies the modifiers to it, returning `None`
+    /// if either is invalid. No time value stands for now.
+    fn new(args: &[Value]) -> Option<Self> {
+        let mut dt = Self::default();
+        match args.first() {
+            None => dt.set_now(),
+            Some(Value::Null) => return None,
+            Some(Value::Integer(i)) => dt.set_raw(*i as f64),
+            Some(Value::Real(r)) => dt.set_raw(*r),
+            Some(value) => dt.parse(&value.to_string())?,
+        }
+        for (i, modifier) in args.iter().enumerate().skip(1) {
+            if modifier.is_null() {
+                return None;
+            }
+            dt.modify(&modifier.to_string(), i)?;
+        }
+        dt.compute_jd();
+        if dt.error || !valid_jd(dt.jd) {
+            return None;
+        }
+        // Make sure a lone YYYY-MM-DD is normalized, like 2023-02-31 to
+        // 2023-03-03.
+        if args.len() == 1 && dt.valid_ymd && dt.day > 28 {
+            dt.valid_ymd = false;
+        }
+        Some(dt)
+    }
+
+    fn set_now(&mut self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_millis() as i64);
+        self.jd = now + UNIX_EPOCH_JD;
+        self.valid_jd = true;
+        self.utc = true;
+        self.local = false;
+        self.clear_ymd_hms_tz();
+    }
+
+    /// Takes a number as a Julian day, if it is in range.
+    fn set_raw(&mut self, r: f64) {
+        self.raw = Some(r);
+        if (0.0..5373484.5).contains(&r) {
+            self.jd = (r * DAY as f64 + 0.5) as i64;
+            self.valid_jd = true;
+        }
+    }
+
+    fn set_error(&mut self) {
+        *self = Self {
+            error: true,
+            ..Self::default()
+        };
+    }
+
+    fn clear_ymd_hms_tz(&mut self) {
+        self.valid_ymd = false;
+        self.valid_hms = false;
+        self.tz = 0;
+    }
+
+    fn compute_jd(&mut self) {
+        if self.valid_jd {
+            return;
+        }
+        let (mut y, mut m, d) = if self.valid_ymd {
+            (self.year, self.month, self.day)
+        } else {
+            (2000, 1, 1)
+        };
+        if !(-4713..=9999).contains(&y) || self.raw.is_some() {
+            self.set_error();
+            return;
+        }
+        if m <= 2 {
+            y -= 1;
+            m += 12;
+        }
+        let a = (y + 4800) / 100;
+        let b = 38 - a + a / 4;
+        let x1 = 36525 * (y + 4716) / 100;
+        let x2 = 306001 * (m + 1) / 10000;
+        self.jd = (((x1 + x2 + d + b) as f64 - 1524.5) * DAY as f64) as i64;
+        self.valid_jd = true;
+        if self.valid_hms {
+            self.jd += self.hour as i64 * 3_600_000
+                + self.minute as i64 * 60_000
+                + (self.second * 1000.0 + 0.5) as i64;
+            if self.tz != 0 {
+                self.jd -= self.tz as i64 * 60_000;
+                self.valid_ymd = false;
+                self.valid_hms = false;
+                self.tz = 0;
+                self.utc = true;
+                self.local = false;
+            }
+        }
+    }
+
+    fn compute_ymd(&mut self) {
+        if self.valid_ymd {
+            return;
+        }
+        if !self.valid_jd {
+            (self.year, self.month, self.day) = (2000, 1, 1);
+        } else if !valid_jd(self.jd) {
+            self.set_error();
+            return;
+        } else {
+            let z = ((self.jd + DAY / 2) / DAY) as i32;
+            let alpha = ((z as f64 + 32044.75) / 36524.25) as i32 - 52;
+            let a = z + 1 + alpha - (alpha + 100) / 4 + 25;
+            let b = a + 1524;
+            let c = ((b as f64 - 122.1) / 365.25) as i32;
+            let d = (36525 * (c & 32767)) / 100;
+            let e = ((b - d) as f64 / 30.6001) as i32;
+            let x1 = (30.6001 * e as f64) as i32;
+            self.day = b - d - x1;
+            self.month = if e < 14 { e - 1 } else { e - 13 };
+            self.year = if self.month > 2 { c - 4716 } else { c - 4715 };
+        }
+        self.valid_ymd = true;
+    }
+
+    fn compute_hms(&mut self) {
+        if self.valid_hms {
+            return;
+        }
+        self.compute_jd();
+        let day_ms = ((self.jd + DAY / 2) % DAY) as i32;
+        self.second = (day_ms % 60_000) as f64 / 1000.0;
+        let day_minutes = day_ms / 60_000;
+        self.minute = day_minutes % 60;
+        self.hour = day_minutes / 60;
+        self.raw = None;
+        self.valid_hms = true;
+    }
+
+    fn compute_ymd_hms(&mut self) {
+        self.compute_ymd();
+        self.compute_hms();
+    }
+
+    /// Notes how many days past the end of its month the day is.
+    fn compute_floor(&mut self) {
+        self.floor = if self.day <= 28 || (1 << self.month) & 0x15aa != 0 {
+            0
+        } else if self.month != 2 {
+            i32::from(self.day == 31)
+        } else if self.year % 4 != 0 || (self.year % 100 == 0 && self.year % 400 != 0) {
+            self.day - 28
+        } else {
+            self.day - 29
+        };
+    }
+
+    /// Parses a time value given as text.
+    fn parse(&mut self, text: &str) -> Option<()> {
+        let bytes = text.as_bytes();
+        if self.parse_date(bytes).is_some() || self.parse_time(bytes).is_some() {
+            return Some(());
+        }
+        if text.eq_ignore_ascii_case("now") {
+            self.set_now();
+        } else if let Some(number) = parse_number(text) {
+            self.set_raw(number.to_real());
+        } else if text.eq_ignore_ascii_case("subsec") || text.eq_ignore_ascii_case("subsecond") {
+            self.subsec = true;
+            self.set_now();
+        } else {
+            return None;
+        }
+        Some(())
+    }
+
+    /// Parses `[-]YYYY-MM-DD`, optionally followed by a time.
+    fn parse_date(&mut self, mut bytes: &[u8]) -> Option<()> {
+        let negative = bytes.first() == Some(&b'-');
+        if negative {
+            bytes = &bytes[1..];
+        }
+        let [year, month, day] = digits(bytes, "40f-21a-21d")[..] else {
+            return None;
+        };
+        let mut rest = &bytes[10..];
+        while let [b' ' | b'\t' | b'\n' | b'\r' | b'T', tail @ ..] = rest {
+            rest = tail;
+        }
+        if self.parse_time(rest).is_none() {
+            if !rest.is_empty() {
+                return None;
+            }
+            self.valid_hms = false;
+        }
+        self.valid_jd = false;
+        self.valid_ymd = true;
+        self.year = if negative { -year } else { year };
+        self.month = month;
+        self.day = day;
+        self.compute_floor();
+        if self.tz_set {
+            self.compute_jd();
+        }
+        Some(())
+    }
+
+    /// Parses `HH:MM[:SS[.SSS]]`, optionally followed by a time zone.
+    fn parse_time(&mut self, bytes: &[u8]) -> Option<()> {
+        let [hour, minute] = digits(bytes, "20c:20e")[..] else {
+            return None;
+        };
+        let mut rest = &bytes[5..];
+        let mut second = 0.0;
+        if let [b':', tail @ ..] = rest {
+            let [s] = digits(tail, "20e")[..] else {
+                return None;
+            };
+            second = s as f64;
+            rest = &tail[2..];
+            if let [b'.', digit, ..] = rest {
+                if digit.is_ascii_digit() {
+                    rest = &rest[1..];
+                    let (mut fraction, mut scale) = (0.0, 1.0);
+                    while let [digit @ b'0'..=b'9', tail @ ..] = rest {
+                        fraction = fraction * 10.0 + (digit - b'0') as f64;
+                        scale *= 10.0;
+                        rest = tail;
+                    }
+                    // Truncate to avoid rounding up to the next second.
+                    second += (fraction / scale).min(0.999);
+                }
+            }
+        }
+        self.valid_jd = false;
+        self.raw = None;
+        self.valid_hms = true;
+        self.hour = hour;
+        self.minute = minute;
+        self.second = second;
+        self.parse_timezone(rest)
+    }
+
+    /// Parses an optional `[+-]HH:MM` or `Z` time zone, which may be preceded
+    /// and followed by whitespace.
+    fn parse_timezone(&mut self, bytes: &[u8]) -> Option<()> {
+        let mut rest = bytes.trim_ascii_start();
+        self.tz = 0;
+        let sign = match rest.first() {
+            Some(b'-') => -1,
+            Some(b'+') => 1,
+            Some(b'Z' | b'z') => 0,
+            Some(_) => return None,
+            None => return Some(()),
+        };
+        rest = &rest[1..];
+        if sign == 0 {
+            self.local = false;
+            self.utc = true;
+        } else {
+            let [hours, minutes] = digits(rest, "20b:20e")[..] else {
+                return None;
+            };
+            rest = &rest[5..];
+            self.tz = sign * (minutes + hours * 60);
+        }
+        self.tz_set = true;
+        rest.trim_ascii_start().is_empty().then_some(())
+    }
+
+    /// Applies a modifier, the `index`-th argument of the function.
+    fn modify(&mut self, modifier: &str, index: usize) -> Option<()> {
+        let lower = modifier.to_ascii_lowercase();
+        match lower.as_str() {
+            "auto" => {
+                if index > 1 {
+                    return None;
+                }
+                match self.raw {
+                    Some(r) if !self.valid_jd => {
+                        if (-210_866_760_000.0..=253_402_300_799.0).contains(&r) {
+                            self.clear_ymd_hms_tz();
+                            self.jd = (r * 1000.0 + UNIX_EPOCH_JD as f64 + 0.5) as i64;
+                            self.valid_jd = true;
+                            self.raw = None;
+                        }
+                    }
+                    _ => self.raw = None,
+                }
+            }
+            "ceiling" => {
+                self.compute_jd();
+                self.clear_ymd_hms_tz();
+                self.floor = 0;
+            }
+            "floor" => {
+                self.compute_jd();
+                self.jd -= self.floor as i64 * DAY;
+                self.clear_ymd_hms_tz();
+            }
+            "julianday" => {
+                if index > 1 || !self.valid_jd || self.raw.is_none() {
+                    return None;
+                }
+                self.raw = None;
+            }
+            "localtime" => {
+                if !self.local {
+                    self.shift_to_localtime();
+                }
+                self.utc = false;
+                self.local = true;
+            }
+            "unixepoch" => {
+                let r = self.raw.filter(|_| index == 1)?;
+                let jd = r * 1000.0 + UNIX_EPOCH_JD as f64;
+                if !(0.0..(MAX_JD + 1) as f64).contains(&jd) {
+                    return None;
+                }
+                self.clear_ymd_hms_tz();
+                self.jd = (jd + 0.5) as i64;
+                self.valid_jd = true;
+                self.raw = None;
+            }
+            "utc" => {
+                if !self.utc {
+                    self.compute_jd();
+                    let original = self.jd;
+                    let mut guess = original;
+                    let mut error = 0;
+                    for _ in 0..4 {
+                        guess -= error;
+                        let mut local = Self {
+                            jd: guess,
+                            valid_jd: true,
+                            ..Self::default()
+                        };
+                        local.shift_to_localtime();
+                        local.compute_jd();
+                        error = local.jd - original;
+                        if error == 0 {
+                            break;
+                        }
+                    }
+                    *self = Self {
+                        jd: guess,
+                        valid_jd: true,
+                        ..Self::default()
+                    };
+                }
+                self.utc = true;
+                self.local = false;
+            }
+            "subsec" | "subsecond" => self.subsec = true,
+            _ => {
+                if let Some(weekday) = lower.strip_prefix("weekday ") {
+                    self.weekday(weekday)?;
+                } else if let Some(unit) = lower.strip_prefix("start of ") {
+                    self.start_of(unit)?;
+                } else if lower.starts_with(|c: char| c == '+' || c == '-' || c.is_ascii_digit()) {
+                    self.add(lower.as_bytes())?;
+                } else {
+                    return None;
+                }
+            }
+        }
+        Some(())
+    }
+
+    /// `'weekday N'`: advances to the next day that is the `N`-th day of the
+    /// week, counting from Sunday, unless it already is.
+    fn weekday(&mut self, n: &str) -> Option<()> {
+        let r = parse_number(n)?.to_real();
+        if !(0.0..7.0).contains(&r) || r.fract() != 0.0 {
+            return None;
+        }
+        self.compute_ymd_hms();
+        self.tz = 0;
+        self.valid_jd = false;
+        self.compute_jd();
+        let mut z = ((self.jd + 129_600_000) / DAY) % 7;
+        if z > r as i64 {
+            z -= 7;
+        }
+        self.jd += (r as i64 - z) * DAY;
+        self.clear_ymd_hms_tz();
+        Some(())
+    }
+
+    /// `'start of month|year|day'`.
+    fn start_of(&mut self, unit: &str) -> Option<()> {
+        if !self.valid_jd && !self.valid_ymd && !self.valid_hms {
+            return None;
+        }
+        self.compute_ymd();
+        self.valid_hms = true;
+        self.hour = 0;
+        self.minute = 0;
+        self.second = 0.0;
+        self.raw = None;
+        self.tz = 0;
+        self.valid_jd = false;
+        match unit {
+            "month" => self.day = 1,
+            "year" => (self.month, self.day) = (1, 1),
+            "day" => {}
+            _ => return None,
+        }
+        Some(())
+    }
+
+    /// Adds or subtracts a number of units (`'+N days'`), a time
+    /// (`'+HH:MM:SS.SSS'`), or years, months and days optionally followed by
+    /// a time (`'+YYYY-MM-DD HH:MM:SS.SSS'`).
+    fn add(&mut self, bytes: &[u8]) -> Option<()> {
+        let sign = bytes[0];
+        let mut n = 1;
+        while n < bytes.len() && bytes[n] != b':' && !bytes[n].is_ascii_whitespace() {
+            if bytes[n] == b'-' && n == 5 && digits(&bytes[1..], "40f").len() == 1 {
+                break;
+            }
+            n += 1;
+        }
+        let r = parse_number(std::str::from_utf8(&bytes[..n]).ok()?)?.to_real();
+        let mut time = bytes;
+
+        if bytes.get(n) == Some(&b'-') {
+            if sign != b'+' && sign != b'-' {
+                return None;
+            }
+            let [years, months, days] = digits(&bytes[1..], "40f-20a-20d")[..] else {
+                return None;
+            };
+            if months >= 12 || days >= 31 {
+                return None;
+            }
+            self.compute_ymd_hms();
+            self.valid_jd = false;
+            let days = if sign == b'-' {
+                self.year -= years;
+                self.month -= months;
+                -days
+            } else {
+                self.year += years;
+                self.month += months;
+                days
+            };
+            self.normalize_month();
+            self.compute_floor();
+            self.compute_jd();
+            self.valid_hms = false;
+            self.valid_ymd = false;
+            self.jd += days as i64 * DAY;
+            match bytes.get(11) {
+                None => return Some(()),
+                Some(c)
+                    if c.is_ascii_whitespace() && digits(&bytes[12..], "20c:20e").len() == 2 =>
+                {
+                    time = &bytes[12..];
+                    n = 2;
+                }
+                Some(_) => return None,
+            }
+        }
+        if time.get(n) == Some(&b':') {
+            if !time[0].is_ascii_digit() {
+                time = &time[1..];
+            }
+            let mut offset = Self::default();
+            offset.parse_time(time)?;
+            offset.compute_jd();
+            offset.jd -= DAY / 2;
+            offset.jd -= offset.jd / DAY * DAY;
+            if sign == b'-' {
+                offset.jd = -offset.jd;
+            }
+            self.compute_jd();
+            self.clear_ymd_hms_tz();
+            self.jd += offset.jd;
+            return Some(());
+        }
+
+        let unit = std::str::from_utf8(&bytes[n..]).ok()?.trim_start();
+        if !(3..=10).contains(&unit.len()) {
+            return None;
+        }
+        let unit = unit.strip_suffix('s').unwrap_or(unit);
+        self.compute_jd();
+        self.floor = 0;
+        let (i, &(_, _, seconds)) = UNITS
+            .iter()
+            .enumerate()
+            .find(|(_, (name, limit, _))| *name == unit && r > -limit && r < *limit)?;
+        let mut r = r;
+        match UNITS[i].0 {
+            "month" => {
+                self.compute_ymd_hms();
+                self.month += r as i32;
+                self.normalize_month();
+                self.compute_floor();
+                self.valid_jd = false;
+                r -= r.trunc();
+            }
+            "year" => {
+                self.compute_ymd_hms();
+                self.year += r as i32;
+                self.compute_floor();
+                self.valid_jd = false;
+                r -= r.trunc();
+            }
+            _ => {}
+        }
+        self.compute_jd();
+        let rounder = if r < 0.0 { -0.5 } else { 0.5 };
+        self.jd += (r * 1000.0 * seconds + rounder) as i64;
+        self.clear_ymd_hms_tz();
+        Some(())
+    }
+
+    /// Carries months out of the range 1 to 12 over into the year.
+    fn normalize_month(&mut self) {
+        let years = if self.month > 0 {
+            (self.month - 1) / 12
+        } else {
+            (self.month - 12) / 12
+        };
+        self.year += years;
+        self.month -= years * 12;
+    }
+
+    /// Converts the time from UTC to local time. Years the time zone rules
+    /// may not cover are mapped to an equivalent year between 2000 and 2003.
+    fn shift_to_localtime(&mut self) {
+        self.compute_jd();
+        let (year_diff, unix) = if (UNIX_EPOCH_JD..=213_014_145_600_000).contains(&self.jd) {
+            (0, self.jd / 1000 - UNIX_EPOCH_JD / 1000)
+        } else {
+            let mut x = self.clone();
+            x.compute_ymd_hms();
+            let year_diff = 2000 + x.year % 4 - x.year;
+            x.year += year_diff;
+            x.valid_jd = false;
+            x.compute_jd();
+            (year_diff, x.jd / 1000 - UNIX_EPOCH_JD / 1000)
+        };
+        let mut local = Self {
+            jd: (unix + local_offset(unix)) * 1000 + UNIX_EPOCH_JD,
+            valid_jd: true,
+            ..Self::default()
+        };
+        local.compute_ymd_hms();
+        self.year = local.year - year_diff;
+        self.month = local.month;
+        self.day = local.day;
+        self.hour = local.hour;
+        self.minute = local.minute;
+        self.second = local.second.trunc() + (self.jd % 1000) as f64 * 0.001;
+        self.valid_ymd = true;
+        self.valid_hms = true;
+        self.valid_jd = false;
+        self.raw = None;
+        self.tz = 0;
+        self.error = false;
+    }
+
+    /// The number of days since January 1 of the year.
+    fn days_after_jan01(&self) -> i64 {
+        let mut jan01 = self.clone();
+        jan01.valid_jd = false;
+        jan01.month = 1;
+        jan01.day = 1;
+        jan01.compute_jd();
+        (self.jd - jan01.jd + DAY / 2) / DAY
+    }
+
+    fn days_after_monday(&self) -> i64 {
+        ((self.jd + DAY / 2) / DAY) % 7
+    }
+
+    fn days_after_sunday(&self) -> i64 {
+        ((self.jd + 129_600_000) / DAY) % 7
+    }
+
+    /// The Thursday of the same week, which decides which ISO 8601 year and
+    /// week number the week has.
+    fn iso_thursday(&self) -> Self {
+        let mut thursday = self.clone();
+        thursday.jd += (3 - self.days_after_monday()) * DAY;
+        thursday.valid_ymd = false;
+        thursday.compute_ymd();
+        thursday
+    }
+
+    fn unix_seconds(&self) -> i64 {
+        self.jd / 1000 - UNIX_EPOCH_JD / 1000
+    }
+
+    fn unix_subsec(&self) -> f64 {
+        (self.jd - UNIX_EPOCH_JD) as f64 / 1000.0
+    }
+
+    fn date_string(&self) -> String {
+        let sign = if self.year < 0 { "-" } else { "" };
+        format!(
+            "{sign}{:04}-{:02}-{:02}",
+            self.year.abs() % 10000,
+            self.month,
+            self.day
+        )
+    }
+
+    fn time_string(&self) -> String {
+        let seconds = if self.subsec {
+            let ms = (1000.0 * self.second + 0.5) as i32;
+            format!("{:02}.{:03}", ms / 1000 % 100, ms % 1000)
+        } else {
+            format!("{:02}", self.second as i32 % 100)
+        };
+        format!("{:02}:{:02}:{seconds}", self.hour % 100, self.minute % 100)
+    }
+}
+
+/// Whether a Julian day in milliseconds is between the start of 4713 BC and
+/// the end of 9999.
+fn valid_jd(jd: i64) -> bool {
+    (0..=MAX_JD).contains(&jd)
+}
+
+/// Reads fixed-width fields of digits, like SQLite's `getDigits()`. Each
+/// field is described by four characters: the number of digits, the minimum
+/// value, a letter for the maximum value, and the separator that must follow
+/// it, if any. Returns the fields read up to the first invalid one.
+fn digits(bytes: &[u8], format: &str) -> Vec<i32> {
+    const MAX: [i32; 6] = [12, 14, 24, 31, 59, 14712];
+    let mut values = Vec::new();
+    let mut i = 0;
+    for field in format.as_bytes().chunks(4) {
+        let width = (field[0] - b'0') as usize;
+        let min = (field[1] - b'0') as i32;
+        let max = MAX[(field[2] - b'a') as usize];
+        let mut value = 0;
+        for _ in 0..width {
+            match bytes.get(i) {
+                Some(digit) if digit.is_ascii_digit() => value = value * 10 + (digit - b'0') as i32,
+                _ => return values,
+            }
+            i += 1;
+        }
+        let separator_ok = match field.get(3) {
+            Some(separator) => bytes.get(i) == Some(separator),
+            None => true,
+        };
+        if value < min || value > max || !separator_ok {
+            return values;
+        }
+        values.push(value);
+        i += 1;
+    }
+    values
+}
+
+/// The offset of local time from UTC at a unix time, in seconds, from the
+/// time zone rules in the file named by `TZ` or `/etc/localtime`. Times past
+/// the last transition of the rules keep its offset, and without rules the
+/// local time is UTC.
+fn local_offset(unix: i64) -> i64 {
+    static ZONE: OnceLock<Vec<(i64, i64)>> = OnceLock::new();
+    let transitions = ZONE.get_or_init(|| {
+        let path = match std::env::var("TZ") {
+            Ok(tz) if tz.starts_with('/') => tz,
+            Ok(tz) if !tz.is_empty() => {
+                format!("/usr/share/zoneinfo/{}", tz.trim_start_matches(':'))
+            }
+            _ => "/etc/localtime".to_string(),
+        };
+        std::fs::read(path)
+            .ok()
+            .and_then(|data| parse_tzif(&data))
+            .unwrap_or_default()
+    });
+    transitions
+        .iter()
+        .take_while(|(time, _)| *time <= unix)
+        .last()
+        .or(transitions.first())
+        .map_or(0, |(_, offset)| *offset)
+}
+
+/// Parses the transitions of a TZif file into their times and the offsets
+/// from UTC they change to. The first entry is the offset before the first
+/// transition.
+fn parse_tzif(data: &[u8]) -> Option<Vec<(i64, i64)>> {
+    let header = |data: &[u8]| -> Option<[usize; 6]> {
+        if data.get(..4)? != b"TZif" {
+            return None;
+        }
+        let mut counts = [0; 6];
+        for (i, count) in counts.iter_mut().enumerate() {
+            let bytes = data.get(20 + 4 * i..24 + 4 * i)?;
+            *count = u32::from_be_bytes(bytes.try_into().ok()?) as usize;
+        }
+        Some(counts)
+    };
+    let [isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt] = header(data)?;
+    // Version 2 and later files repeat the data with 64-bit times.
+    let (data, time_size, counts) = if data.get(4).is_some_and(|&version| version >= b'2') {
+        let v1_len = timecnt * 5 + typecnt * 6 + charcnt + leapcnt * 8 + isstdcnt + isutcnt;
+        let data = data.get(44 + v1_len..)?;
+        (data, 8, header(data)?)
+    } else {
+        (
+            data,
+            4,
+            [isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt],
+        )
+    };
+    let [_, _, _, timecnt, typecnt, _] = counts;
+
+    let body = data.get(44..)?;
+    let times = body.get(..timecnt * time_size)?;
+    let indices = body.get(timecnt * time_size..timecnt * (time_size + 1))?;
+    let types = body.get(timecnt * (time_size + 1)..timecnt * (time_size + 1) + typecnt * 6)?;
+    let offset = |index: usize| -> Option<i64> {
+        let ttinfo = types.get(index * 6..index * 6 + 4)?;
+        Some(i32::from_be_bytes(ttinfo.try_into().ok()?) as i64)
+    };
+
+    let mut transitions = vec![(i64::MIN, offset(0)?)];
+    for (time, &index) in times.chunks(time_size).zip(indices) {
+        let time = match time_size {
+            8 => i64::from_be_bytes(time.try_into().ok()?),
+            _ => i32::from_be_bytes(time.try_into().ok()?) as i64,
+        };
+        transitions.push((time, offset(index as usize)?));
+    }
+    Some(transitions)
+}
+
+/// Evaluates a call of one of the date and time functions, which return NULL
+/// for invalid time values and modifiers.
+pub fn call(name: &str, args: &[Value]) -> Value {
+    let result = match name {
+        "date" => DateTime::new(args).map(|mut dt| {
+            dt.compute_ymd();
+            Value::Text(dt.date_string())
+        }),
+        "time" => DateTime::new(args).map(|mut dt| {
+            dt.compute_hms();
+            Value::Text(dt.time_string())
+        }),
+        "datetime" => DateTime::new(args).map(|mut dt| {
+            dt.compute_ymd_hms();
+            Value::Text(format!("{} {}", dt.date_string(), dt.time_string()))
+        }),
+        "julianday" => DateTime::new(args).map(|dt| Value::Real(dt.jd as f64 / DAY as f64)),
+        "unixepoch" => DateTime::new(args).map(|dt| {
+            if dt.subsec {
+                Value::Real(dt.unix_subsec())
+            } else {
+                Value::Integer(dt.unix_seconds())
+            }
+        }),
+        "strftime" => match args.first() {
+            None | Some(Value::Null) => None,
+            Some(format) => DateTime::new(&args[1..])
+                .and_then(|dt| strftime(&format.to_string(), dt).map(Value::Text)),
+        },
+        "timediff" => timediff(&args[0], &args[1]).map(Value::Text),
+        _ => unreachable!("{name}() is a date and time function"),
+    };
+    result.unwrap_or(Value::Null)
+}
+
+/// Formats a time with `%` substitutions like the C function of the same
+/// name. Unknown substitutions make the result NULL.
+fn strftime(format: &str, mut dt: DateTime) -> Option<String> {
+    dt.compute_jd();
+    dt.compute_ymd_hms();
+    let mut out = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let hour12 = match dt.hour {
+            0 => 12,
+            h if h > 12 => h - 12,
+            h => h,
+        };
+        let text = match chars.next()? {
+            'd' => format!("{:02}", dt.day),
+            'e' => format!("{:2}", dt.day),
+            'f' => printf::real("%06.3f", dt.second.min(59.999)),
+            'F' => format!("{:04}-{:02}-{:02}", dt.year, dt.month, dt.day),
+            'G' => format!("{:04}", dt.iso_thursday().year),
+            'g' => format!("{:02}", dt.iso_thursday().year % 100),
+            'H' => format!("{:02}", dt.hour),
+            'k' => format!("{:2}", dt.hour),
+            'I' => format!("{hour12:02}"),
+            'l' => format!("{hour12:2}"),
+            'j' => format!("{:03}", dt.days_after_jan01() + 1),
+            'J' => printf::real("%.16g", dt.jd as f64 / DAY as f64),
+            'm' => format!("{:02}", dt.month),
+            'M' => format!("{:02}", dt.minute),
+            'p' => if dt.hour >= 12 { "PM" } else { "AM" }.to_string(),
+            'P' => if dt.hour >= 12 { "pm" } else { "am" }.to_string(),
+            'R' => format!("{:02}:{:02}", dt.hour, dt.minute),
+            's' if dt.subsec => format!("{:.3}", dt.unix_subsec()),
+            's' => dt.unix_seconds().to_string(),
+            'S' => format!("{:02}", dt.second as i32),
+            'T' => format!("{:02}:{:02}:{:02}", dt.hour, dt.minute, dt.second as i32),
+            'u' => match dt.days_after_sunday() {
+                0 => 7,
+                day => day,
+            }
+            .to_string(),
+            'w' => dt.days_after_sunday().to_string(),
+            'U' => format!(
+                "{:02}",
+                (dt.days_after_jan01() - dt.days_after_sunday() + 7) / 7
+            ),
+            'V' => format!("{:02}", dt.iso_thursday().days_after_jan01() / 7 + 1),
+            'W' => format!(
+                "{:02}",
+                (dt.days_after_jan01() - dt.days_after_monday() + 7) / 7
+            ),
+            'Y' => format!("{:04}", dt.year),
+            '%' => "%".to_string(),
+            _ => return None,
+        };
+        out.push_str(&text);
+    }
+    Some(out)
+}
+
+/// `timediff(A, B)`: the time to add to B to get A, as
+/// `(+|-)YYYY-MM-DD HH:MM:SS.SSS`.
+fn timediff(a: &Value, b: &Value) -> Option<String> {
+    let mut d1 = DateTime::new(std::slice::from_ref(a))?;
+    let mut d2 = DateTime::new(std::slice::from_ref(b))?;
+    d1.compute_ymd_hms();
+    d2.compute_ymd_hms();
+    let forward = d1.jd >= d2.jd;
+    let (mut years, mut months) = if forward {
+        (d1.year - d2.year, d1.month - d2.month)
+    } else {
+        (d2.year - d1.year, d2.month - d1.month)
+    };
+    if years != 0 {
+        d2.year = d1.year;
+        d2.valid_jd = false;
+        d2.compute_jd();
+    }
+    if months < 0 {
+        years -= 1;
+        months += 12;
+    }
+    if months != 0 {
+        d2.month = d1.month;
+        d2.valid_jd = false;
+        d2.compute_jd();
+    }
+    // Step B back, or forward, a month at a time until it is not past A.
+    while if forward {
+        d1.jd < d2.jd
+    } else {
+        d1.jd > d2.jd
+    } {
+        months -= 1;
+        if months < 0 {
+            months = 11;
+            years -= 1;
+        }
+        if forward {
+            d2.month -= 1;
+            if d2.month < 1 {
+                d2.month = 12;
+                d2.year -= 1;
+            }
+        } else {
+            d2.month += 1;
+            if d2.month > 12 {
+                d2.month = 1;
+                d2.year += 1;
+            }
+        }
+        d2.valid_jd = false;
+        d2.compute_jd();
+    }
+    let difference = if forward {
+        d1.jd - d2.jd
+    } else {
+        d2.jd - d1.jd
+    };
+    // The difference as a time since 0000-01-01 00:00:00.
+    let mut diff = DateTime {
+        jd: difference + 148_699_540_800_000,
+        valid_jd: true,
+        ..DateTime::default()
+    };
+    diff.compute_ymd_hms();
+    Some(format!(
+        "{}{years:04}-{months:02}-{:02} {:02}:{:02}:{}",
+        if forward { '+' } else { '-' },
+        diff.day - 1,
+        diff.hour,
+        diff.minute,
+        printf::real("%06.3f", diff.second)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::datetime::*;
+    use crate::eval::tests::text;
+
+    fn call_with(name: &str, args: &[&str]) -> Value {
+        call(name, &args.iter().map(|arg| text(arg)).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn parses_time_values() {
+        let cases = [
+            ("date", vec!["2023-02-31"], text("2023-03-03")),
+            (
+                "datetime",
+                vec!["2024-01-01T10:00Z"],
+                text("2024-01-01 10:00:00"),
+            ),
+            (
+                "datetime",
+                vec!["2024-01-01 10:00 +02:30"],
+                text("2024-01-01 07:30:00"),
+            ),
+            ("datetime", vec!["24:00"], text("2000-01-02 24:00:00")),
+            ("time", vec!["12:34:56.789", "subsec"], text("12:34:56.789")),
+            ("julianday", vec!["2000-01-01"], Value::Real(2451544.5)),
+            ("unixepoch", vec!["2024-01-01"], Value::Integer(1704067200)),
+            (
+                "datetime",
+                vec!["1700000000", "unixepoch"],
+                text("2023-11-14 22:13:20"),
+            ),
+            ("date", vec!["1e10"], Value::Null),
+            ("date", vec![" 2024-01-01"], Value::Null),
+            ("date", vec!["2024-13-01"], Value::Null),
+        ];
+
+        for (name, args, expected) in cases {
+            assert_eq!(call_with(name, &args), expected, "{name}({args:?})");
+        }
+    }
+
+    #[test]
+    fn applies_modifiers() {
+        let cases = [
+            (vec!["2023-01-31", "+1 month"], "2023-03-03 00:00:00"),
+            (
+                vec!["2023-01-31", "+1 month", "floor"],
+                "2023-02-28 00:00:00",
+            ),
+            (
+                vec!["2024-01-31", "start of month", "+1 month", "-1 day"],
+                "2024-01-31 00:00:00",
+            ),
+            (
+                vec!["2024-05-17 13:14:15", "start of year"],
+                "2024-01-01 00:00:00",
+            ),
+            (vec!["2024-03-05", "weekday 0"], "2024-03-10 00:00:00"),
+            (
+                vec!["2024-01-01", "+1.5 hours", "-30 seconds"],
+                "2024-01-01 01:29:30",
+            ),
+            (
+                vec!["2024-01-01", "-0001-02-03 04:05:06"],
+                "2022-10-28 19:54:54",
+            ),
+            (vec!["2024-01-01", "+10:30"], "2024-01-01 10:30:00"),
+        ];
+
+        for (args, expected) in cases {
+            assert_eq!(call_with("datetime", &args), text(expected), "{args:?}");
+        }
+        for invalid in ["+5", "+5 day ", "weekday 7", "julianday", "+1 02:03:04"] {
+            assert_eq!(
+                call_with("date", &["2024-01-01", invalid]),
+                Value::Null,
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn formats_and_subtracts_times() {
+        assert_eq!(
+            call_with(
+                "strftime",
+                &[
+                    "%J %s %f %j %U %W %V %G %u %w %e %l %p %T",
+                    "2024-03-05 14:07:09.123"
+                ]
+            ),
+            text("2460375.088300035 1709647629 09.123 065 09 10 10 2024 2 2  5  2 PM 14:07:09")
+        );
+        assert_eq!(call_with("strftime", &["%C", "2024-01-01"]), Value::Null);
+        assert_eq!(
+            call_with("timediff", &["2024-03-01", "2023-01-31 12:30:15.5"]),
+            text("+0001-00-29 11:29:44.500")
+        );
+        assert_eq!(
+            call_with("timediff", &["2024-01-01", "2024-03-01"]),
+            text("-0000-02-00 00:00:00.000")
+        );
+    }
+}
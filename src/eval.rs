@@ -0,0 +1,661 @@
+use anyhow::{bail, Result};
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use crate::aggregate::is_aggregate;
+use crate::ast::*;
+use crate::collation::Collation;
+use crate::function;
+use crate::json;
+use crate::query::ResultSet;
+use crate::value::{Affinity, Value};
+
+/// Supplies the values of the columns that expressions refer to.
+pub trait Scope {
+    /// Looks up the value of a column in the current row. `table` is the name
+    /// or alias the column is qualified with, if any.
+    fn column(&self, table: Option<&str>, name: &str) -> Result<Value>;
+
+    /// The affinity of a column, which is unknown for columns that are not
+    /// those of a table.
+    fn affinity(&self, _table: Option<&str>, _name: &str) -> Option<Affinity> {
+        None
+    }
+
+    /// The collating sequence of a column, which only columns of tables and
+    /// subqueries can have.
+    fn collation(&self, _table: Option<&str>, _name: &str) -> Option<Collation> {
+        None
+    }
+
+    /// The value bound to the parameter with the given index, counting from
+    /// 1. Parameters without a value are NULL.
+    fn parameter(&self, _index: usize) -> Value {
+        Value::Null
+    }
+
+    /// The result of an aggregate function call for the current group, if
+    /// the scope is that of a group of rows.
+    fn aggregate(&self, _call: &FunctionCall) -> Option<Value> {
+        None
+    }
+
+    /// Runs a subquery for the current row, which may refer to its columns.
+    /// With `first_only`, only the first row of the result is needed.
+    fn subquery(&self, _select: &Select, _first_only: bool) -> Result<Rc<ResultSet>> {
+        bail!("subqueries are not supported here")
+    }
+
+    /// Whether `LIKE` tells upper and lower case ASCII letters apart.
+    fn case_sensitive_like(&self) -> bool {
+        false
+    }
+}
+
+/// Evaluates an expression against the current row of a scope.
+pub fn evaluate(expr: &Expr, scope: &dyn Scope) -> Result<Value> {
+    let value = match expr {
+        Expr::Literal(value) => value.clone(),
+        Expr::Parameter { index, .. } => scope.parameter(*index),
+        Expr::Column { table, name } => scope.column(table.as_deref(), name)?,
+        Expr::Unary { op, expr } => unary(*op, evaluate(expr, scope)?),
+        Expr::Binary {
+            op: op @ (BinaryOp::And | BinaryOp::Or),
+            left,
+            right,
+        } => {
+            // Skip the right operand if the left one decides the result.
+            let left = evaluate(left, scope)?;
+            let decisive = *op == BinaryOp::Or;
+            if left.truth() == Some(decisive) {
+                return Ok(boolean(Some(decisive)));
+            }
+            binary(*op, left, evaluate(right, scope)?)
+        }
+        Expr::Binary { op, left, right } if op.is_comparison() => {
+            let affinity = comparison_affinity(left, right, scope);
+            let collation = comparison_collation(left, right, scope)?;
+            let left = evaluate(left, scope)?.for_comparison(affinity);
+            let right = evaluate(right, scope)?.for_comparison(affinity);
+            boolean(compare_with(*op, &left, &right, collation))
+        }
+        Expr::Binary {
+            op: op @ (BinaryOp::Extract | BinaryOp::ExtractValue),
+            left,
+            right,
+        } => json::extract(*op, &evaluate(left, scope)?, &evaluate(right, scope)?)?,
+        Expr::Binary { op, left, right } => {
+            binary(*op, evaluate(left, scope)?, evaluate(right, scope)?)
+        }
+        Expr::IsNull { expr, negated } => {
+            boolean(Some(evaluate(expr, scope)?.is_null() != *negated))
+        }
+        Expr::InList {
+            expr,
+            list,
+            negated,
+        } => {
+            // The values of the list are compared with the affinity and the
+            // collation of the expression on the left.
+            let affinity = affinity(expr, scope);
+            let collation = collation(expr, scope)?.unwrap_or_default();
+            let value = evaluate(expr, scope)?.for_comparison(affinity);
+            let items = list
+                .iter()
+                .map(|item| Ok(evaluate(item, scope)?.for_comparison(affinity)))
+                .collect::<Result<Vec<_>>>()?;
+            let found = in_list(&value, &items, collation);
+            boolean(if *negated { found.map(|f| !f) } else { found })
+        }
+        Expr::InSelect {
+            expr,
+            select,
+            negated,
+        } => {
+            let value = evaluate(expr, scope)?;
+            let affinity = affinity(expr, scope);
+            let result = single_column(scope.subquery(select, false)?)?;
+            // The column of the subquery is like the right operand of a
+            // comparison.
+            let explicit = matches!(
+                select.columns.first(),
+                Some(ResultColumn::Expr { expr, .. }) if has_collate(expr)
+            );
+            let collation = if explicit && !has_collate(expr) {
+                result.collations[0]
+            } else {
+                collation(expr, scope)?.or(result.collations[0])
+            };
+            let collation = collation.unwrap_or_default();
+            let value = value.for_comparison(affinity);
+            let items = result
+                .rows
+                .iter()
+                .map(|row| row[0].clone().for_comparison(affinity));
+            let found = in_list(&value, &items.collect::<Vec<_>>(), collation);
+            boolean(if *negated { found.map(|f| !f) } else { found })
+        }
+        Expr::Subquery(select) => {
+            let result = single_column(scope.subquery(select, true)?)?;
+            result
+                .rows
+                .first()
+                .map_or(Value::Null, |row| row[0].clone())
+        }
+        Expr::Exists(select) => boolean(Some(!scope.subquery(select, true)?.rows.is_empty())),
+        Expr::Between {
+            expr,
+            low,
+            high,
+            negated,
+        } => {
+            let value = evaluate(expr, scope)?;
+            let bound = |op, bound: &Expr| -> Result<Option<bool>> {
+                let affinity = comparison_affinity(expr, bound, scope);
+                let collation = comparison_collation(expr, bound, scope)?;
+                let bound = evaluate(bound, scope)?.for_comparison(affinity);
+                let value = value.clone().for_comparison(affinity);
+                Ok(compare_with(op, &value, &bound, collation))
+            };
+            let low = bound(BinaryOp::GtEq, low)?;
+            let high = bound(BinaryOp::LtEq, high)?;
+            let between = and(low, high).truth();
+            boolean(if *negated {
+                between.map(|b| !b)
+            } else {
+                between
+            })
+        }
+        Expr::Like {
+            op,
+            expr,
+            pattern,
+            escape,
+            negated,
+        } => {
+            let text = evaluate(expr, scope)?;
+            let pattern = evaluate(pattern, scope)?;
+            let escape = match escape {
+                Some(escape) => Some(evaluate(escape, scope)?),
+                None => None,
+            };
+            let matched = function::matches_pattern(
+                *op,
+                &pattern,
+                &text,
+                escape.as_ref(),
+                scope.case_sensitive_like(),
+            )?;
+            boolean(if *negated {
+                matched.map(|m| !m)
+            } else {
+                matched
+            })
+        }
+        Expr::Function(call) => match scope.aggregate(call) {
+            Some(value) => value,
+            None if is_aggregate(call) => bail!("misuse of aggregate: {}()", call.name),
+            None if call.over.is_some() => bail!("misuse of window function {}()", call.name),
+            None => function::call(call, scope)?,
+        },
+        Expr::Cast { expr, type_name } => {
+            evaluate(expr, scope)?.cast(Affinity::from_cast_type(type_name.as_deref()))
+        }
+        // The collating sequence only matters to comparisons.
+        Expr::Collate { expr, .. } => evaluate(expr, scope)?,
+        Expr::Case {
+            operand,
+            when_then,
+            else_expr,
+        } => {
+            let operand = match operand {
+                Some(operand) => Some((operand, evaluate(operand, scope)?)),
+                None => None,
+            };
+            for (when, then) in when_then {
+                let matches = match &operand {
+                    Some((operand, value)) => {
+                        let affinity = comparison_affinity(operand, when, scope);
+                        let collation = comparison_collation(operand, when, scope)?;
+                        let when = evaluate(when, scope)?.for_comparison(affinity);
+                        let value = value.clone().for_comparison(affinity);
+                        compare_with(BinaryOp::Eq, &value, &when, collation)
+                    }
+                    None => evaluate(when, scope)?.truth(),
+                };
+                if matches == Some(true) {
+                    return evaluate(then, scope);
+                }
+            }
+            match else_expr {
+                Some(else_expr) => evaluate(else_expr, scope)?,
+                None => Value::Null,
+            }
+        }
+    };
+
+    Ok(value)
+}
+
+/// The affinity of an expression, which decides how its values are converted
+/// when they are compared. Only columns and casts have one.
+pub fn affinity(expr: &Expr, scope: &dyn Scope) -> Option<Affinity> {
+    match expr {
+        Expr::Column { table, name } => scope.affinity(table.as_deref(), name),
+        Expr::Cast { type_name, .. } => Some(Affinity::from_cast_type(type_name.as_deref())),
+        Expr::Collate { expr, .. } => affinity(expr, scope),
+        _ => None,
+    }
+}
+
+/// The affinity that applies to both operands of a comparison.
+pub fn comparison_affinity(left: &Expr, right: &Expr, scope: &dyn Scope) -> Option<Affinity> {
+    Affinity::for_comparison(affinity(left, scope), affinity(right, scope))
+}
+
+/// The collating sequence of an expression: the one given by a `COLLATE`
+/// operator, or else the one of the column the expression consists of. Of
+/// other expressions, only those with a `COLLATE` operator in one of their
+/// operands have a collation.
+pub fn collation(expr: &Expr, scope: &dyn Scope) -> Result<Option<Collation>> {
+    match expr {
+        Expr::Collate { collation, .. } => Ok(Some(Collation::from_name(collation)?)),
+        Expr::Column { table, name } => Ok(scope.collation(table.as_deref(), name)),
+        Expr::Cast { expr, .. }
+        | Expr::Unary {
+            op: UnaryOp::Plus,
+            expr,
+        } => collation(expr, scope),
+        expr => match expr.children().into_iter().find(|child| has_collate(child)) {
+            Some(child) => collation(child, scope),
+            None => Ok(None),
+        },
+    }
+}
+
+/// The collating sequence two operands are compared with. A collation given
+/// by a `COLLATE` operator wins over that of a column, and the left operand
+/// wins over the right one.
+pub fn comparison_collation(left: &Expr, right: &Expr, scope: &dyn Scope) -> Result<Collation> {
+    let collation = if has_collate(left) {
+        collation(left, scope)?
+    } else if has_collate(right) {
+        collation(right, scope)?
+    } else {
+        collation(left, scope)?.or(collation(right, scope)?)
+    };
+    Ok(collation.unwrap_or_default())
+}
+
+/// Whether an expression has a `COLLATE` operator, outside of subqueries.
+fn has_collate(expr: &Expr) -> bool {
+    let mut found = false;
+    expr.walk(&mut |expr| found |= matches!(expr, Expr::Collate { .. }));
+    found
+}
+
+/// Converts the result of a condition to the value SQLite represents it as.
+fn boolean(b: Option<bool>) -> Value {
+    match b {
+        Some(b) => Value::Integer(b as i64),
+        None => Value::Null,
+    }
+}
+
+/// `AND` in SQL's three-valued logic, where unknown is represented as `None`.
+fn and(left: Option<bool>, right: Option<bool>) -> Value {
+    match (left, right) {
+        (Some(false), _) | (_, Some(false)) => boolean(Some(false)),
+        (Some(true), Some(true)) => boolean(Some(true)),
+        _ => Value::Null,
+    }
+}
+
+/// `OR` in SQL's three-valued logic, where unknown is represented as `None`.
+fn or(left: Option<bool>, right: Option<bool>) -> Value {
+    match (left, right) {
+        (Some(true), _) | (_, Some(true)) => boolean(Some(true)),
+        (Some(false), Some(false)) => boolean(Some(false)),
+        _ => Value::Null,
+    }
+}
+
+/// Checks that a subquery used as a value returns a single column.
+fn single_column(result: Rc<ResultSet>) -> Result<Rc<ResultSet>> {
+    let count = result.columns.len();
+    if count != 1 {
+        bail!("sub-select returns {count} columns - expected 1");
+    }
+    Ok(result)
+}
+
+/// Whether the value is in the list. Unknown if it isn't but the list
+/// contains NULL, or if the value is NULL and the list isn't empty.
+fn in_list<'v>(
+    value: &Value,
+    list: impl IntoIterator<Item = &'v Value>,
+    collation: Collation,
+) -> Option<bool> {
+    let mut list = list.into_iter().peekable();
+    if list.peek().is_none() {
+        return Some(false);
+    }
+    if value.is_null() {
+        return None;
+    }
+    let mut saw_null = false;
+    for item in list {
+        if item.is_null() {
+            saw_null = true;
+        } else if value.compare_with(item, collation) == Ordering::Equal {
+            return Some(true);
+        }
+    }
+    if saw_null {
+        None
+    } else {
+        Some(false)
+    }
+}
+
+fn unary(op: UnaryOp, value: Value) -> Value {
+    match op {
+        UnaryOp::Plus => value,
+        UnaryOp::Not => boolean(value.truth().map(|b| !b)),
+        _ if value.is_null() => Value::Null,
+        UnaryOp::Negate => match value.to_numeric() {
+            Value::Integer(i) => match i.checked_neg() {
+                Some(i) => Value::Integer(i),
+                None => Value::Real(-(i as f64)),
+            },
+            Value::Real(r) => Value::Real(-r),
+            _ => unreachable!("Numeric values are integers or reals"),
+        },
+        UnaryOp::BitNot => Value::Integer(!value.to_integer()),
+    }
+}
+
+/// Applies a binary operator to the values of its operands.
+pub fn binary(op: BinaryOp, left: Value, right: Value) -> Value {
+    match op {
+        BinaryOp::Lt
+        | BinaryOp::LtEq
+        | BinaryOp::Gt
+        | BinaryOp::GtEq
+        | BinaryOp::Eq
+        | BinaryOp::NotEq
+        | BinaryOp::Is
+        | BinaryOp::IsNot => boolean(compare(op, &left, &right)),
+        BinaryOp::And => and(left.truth(), right.truth()),
+        BinaryOp::Or => or(left.truth(), right.truth()),
+        _ if left.is_null() || right.is_null() => Value::Null,
+        BinaryOp::Concat => Value::Text(format!("{left}{right}")),
+        BinaryOp::ShiftLeft => Value::Integer(shift_left(left.to_integer(), right.to_integer())),
+        BinaryOp::ShiftRight => Value::Integer(shift_left(
+            left.to_integer(),
+            right.to_integer().saturating_neg(),
+        )),
+        BinaryOp::BitAnd => Value::Integer(left.to_integer() & right.to_integer()),
+        BinaryOp::BitOr => Value::Integer(left.to_integer() | right.to_integer()),
+        BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide => {
+            arithmetic(op, left.to_numeric(), right.to_numeric())
+        }
+        BinaryOp::Modulo => modulo(left.to_numeric(), right.to_numeric()),
+        BinaryOp::Extract | BinaryOp::ExtractValue => {
+            unreachable!("JSON operators can fail, so they are applied in evaluate()")
+        }
+    }
+}
+
+/// Compares two values with a comparison operator, using the binary collation.
+pub fn compare(op: BinaryOp, left: &Value, right: &Value) -> Option<bool> {
+    compare_with(op, left, right, Collation::Binary)
+}
+
+/// Compares two values with a comparison operator and a collation. Unknown if
+/// either is NULL, except for `IS` and `IS NOT`, which treat NULLs as equal
+/// to each other.
+pub fn compare_with(
+    op: BinaryOp,
+    left: &Value,
+    right: &Value,
+    collation: Collation,
+) -> Option<bool> {
+    if matches!(op, BinaryOp::Is | BinaryOp::IsNot) {
+        let equal = match (left.is_null(), right.is_null()) {
+            (true, true) => true,
+            (false, false) => left.compare_with(right, collation) == Ordering::Equal,
+            _ => false,
+        };
+        return Some(equal == (op == BinaryOp::Is));
+    }
+    if left.is_null() || right.is_null() {
+        return None;
+    }
+
+    let ordering = left.compare_with(right, collation);
+    Some(match op {
+        BinaryOp::Lt => ordering.is_lt(),
+        BinaryOp::LtEq => ordering.is_le(),
+        BinaryOp::Gt => ordering.is_gt(),
+        BinaryOp::GtEq => ordering.is_ge(),
+        BinaryOp::Eq => ordering.is_eq(),
+        BinaryOp::NotEq => ordering.is_ne(),
+        _ => unreachable!("{op} is not a comparison operator"),
+    })
+}
+
+/// Shifts left by `amount` bits, or right if it is negative. Shifting right
+/// preserves the sign.
+fn shift_left(value: i64, amount: i64) -> i64 {
+    match amount {
+        64.. => 0,
+        0..=63 => value << amount,
+        -63..=-1 => value >> -amount,
+        _ if value < 0 => -1,
+        _ => 0,
+    }
+}
+
+/// Adds, subtracts, multiplies or divides two numbers. Integer results that
+/// overflow become reals, and division by zero is NULL.
+fn arithmetic(op: BinaryOp, left: Value, right: Value) -> Value {
+    if let (Value::Integer(a), Value::Integer(b)) = (&left, &right) {
+        let (a, b) = (*a, *b);
+        if op == BinaryOp::Divide && b == 0 {
+            return Value::Null;
+        }
+        let result = match op {
+            BinaryOp::Add => a.checked_add(b),
+            BinaryOp::Subtract => a.checked_sub(b),
+            BinaryOp::Multiply => a.checked_mul(b),
+            BinaryOp::Divide => a.checked_div(b),
+            _ => unreachable!("{op} is not an arithmetic operator"),
+        };
+        if let Some(result) = result {
+            return Value::Integer(result);
+        }
+    }
+
+    let (a, b) = (left.to_real(), right.to_real());
+    let result = match op {
+        BinaryOp::Add => a + b,
+        BinaryOp::Subtract => a - b,
+        BinaryOp::Multiply => a * b,
+        BinaryOp::Divide if b == 0.0 => return Value::Null,
+        BinaryOp::Divide => a / b,
+        _ => unreachable!("{op} is not an arithmetic operator"),
+    };
+    if result.is_nan() {
+        Value::Null
+    } else {
+        Value::Real(result)
+    }
+}
+
+/// The remainder of dividing two numbers. Like SQLite, reals are truncated to
+/// integers first, but the result is then a real.
+fn modulo(left: Value, right: Value) -> Value {
+    let is_real = matches!(left, Value::Real(_)) || matches!(right, Value::Real(_));
+    let (a, b) = (left.to_integer(), right.to_integer());
+    if b == 0 {
+        return Value::Null;
+    }
+    // The remainder of dividing by -1 is always 0, but i64::MIN % -1 overflows.
+    let remainder = a % if b == -1 { 1 } else { b };
+    if is_real {
+        Value::Real(remainder as f64)
+    } else {
+        Value::Integer(remainder)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::eval::*;
+    use crate::parser::parse;
+
+    /// A scope with the columns `a` = 1, `b` = NULL and `t` = 'text'.
+    struct TestScope;
+
+    impl Scope for TestScope {
+        fn column(&self, _table: Option<&str>, name: &str) -> Result<Value> {
+            match name {
+                "a" => Ok(Value::Integer(1)),
+                "b" => Ok(Value::Null),
+                "t" => Ok(Value::Text("text".to_string())),
+                _ => bail!("no such column: {name}"),
+            }
+        }
+    }
+
+    /// A scope without any columns.
+    struct EmptyScope;
+
+    impl Scope for EmptyScope {
+        fn column(&self, _table: Option<&str>, name: &str) -> Result<Value> {
+            bail!("no such column: {name}")
+        }
+    }
+
+    /// Evaluates an expression, given as SQL, in a scope.
+    pub fn eval_in(sql: &str, scope: &dyn Scope) -> Result<Value> {
+        let Some(Statement::Select(select)) = parse(&format!("SELECT {sql}"))?.pop() else {
+            panic!("Expected SELECT");
+        };
+        let ResultColumn::Expr { expr, .. } = &select.columns[0] else {
+            panic!("Expected an expression");
+        };
+        evaluate(expr, scope)
+    }
+
+    /// Evaluates an expression, given as SQL, that doesn't refer to any
+    /// column.
+    pub fn eval_constant(sql: &str) -> Result<Value> {
+        eval_in(sql, &EmptyScope)
+    }
+
+    pub fn text(t: &str) -> Value {
+        Value::Text(t.to_string())
+    }
+
+    fn eval(sql: &str) -> Value {
+        eval_in(sql, &TestScope).unwrap()
+    }
+
+    #[test]
+    fn uses_three_valued_logic() {
+        let cases = vec![
+            ("b AND 0", Value::Integer(0)),
+            ("b AND 1", Value::Null),
+            ("b OR 1", Value::Integer(1)),
+            ("b OR 0", Value::Null),
+            ("NOT b", Value::Null),
+            ("NOT 'abc'", Value::Integer(1)),
+            ("a = b", Value::Null),
+            ("b IS NULL", Value::Integer(1)),
+            ("b IS b", Value::Integer(1)),
+            ("a IS NOT b", Value::Integer(1)),
+            ("a IN (2, 1)", Value::Integer(1)),
+            ("a IN (2, NULL)", Value::Null),
+            ("a NOT IN (2, 3)", Value::Integer(1)),
+            ("b IN ()", Value::Integer(0)),
+            ("2 BETWEEN a AND 3", Value::Integer(1)),
+            ("2 NOT BETWEEN b AND 1", Value::Integer(1)),
+            ("2 BETWEEN b AND 3", Value::Null),
+            ("1 = 1.0", Value::Integer(1)),
+            ("t > a", Value::Integer(1)),
+        ];
+
+        for (sql, expected) in cases {
+            assert_eq!(eval(sql), expected, "{sql}");
+        }
+    }
+
+    #[test]
+    fn casts_values() {
+        let cases = vec![
+            ("CAST('12.9abc' AS INTEGER)", Value::Integer(12)),
+            ("CAST('1e3' AS)", Value::Integer(1000)),
+            ("CAST(a AS REAL)", Value::Real(1.0)),
+            ("CAST(2.5 AS VARCHAR(10))", Value::Text("2.5".to_string())),
+            ("CAST(b AS TEXT)", Value::Null),
+            ("CAST(a AS TEXT) = 1", Value::Integer(1)),
+            ("CAST(a AS TEXT) = CAST(1 AS INT)", Value::Integer(1)),
+        ];
+
+        for (sql, expected) in cases {
+            assert_eq!(eval(sql), expected, "{sql}");
+        }
+    }
+
+    #[test]
+    fn does_arithmetic_like_sqlite() {
+        let cases = vec![
+            ("1 + 2 * 3", Value::Integer(7)),
+            ("7 / 2", Value::Integer(3)),
+            ("7.0 / 2", Value::Real(3.5)),
+            ("7 / 0", Value::Null),
+            ("-7 % 3", Value::Integer(-1)),
+            ("5.5 % 2", Value::Real(1.0)),
+            (
+                "9223372036854775807 + 1",
+                Value::Real(9223372036854775808.0),
+            ),
+            (
+                "-9223372036854775808 / -1",
+                Value::Real(9223372036854775808.0),
+            ),
+            ("'12abc' + 0", Value::Integer(12)),
+            ("' 1.5x' * 2", Value::Real(3.0)),
+            ("-'abc'", Value::Integer(0)),
+            ("+'abc'", Value::Text("abc".to_string())),
+            ("a + b", Value::Null),
+            ("~1.9", Value::Integer(-2)),
+            ("1 << 64", Value::Integer(0)),
+            ("8 >> -1", Value::Integer(16)),
+            ("-8 >> 70", Value::Integer(-1)),
+            ("6 & 3 | 8", Value::Integer(10)),
+            ("t || a || 1.0", Value::Text("text11.0".to_string())),
+            ("t || b", Value::Null),
+        ];
+
+        for (sql, expected) in cases {
+            assert_eq!(eval(sql), expected, "{sql}");
+        }
+    }
+
+    #[test]
+    fn evaluates_case_expressions() {
+        let cases = vec![
+            ("CASE a WHEN 2 THEN 'two' WHEN 1 THEN 'one' END", "one"),
+            ("CASE b WHEN NULL THEN 'null' ELSE 'else' END", "else"),
+            ("CASE WHEN b THEN 'b' WHEN a THEN 'a' END", "a"),
+        ];
+
+        for (sql, expected) in cases {
+            assert_eq!(eval(sql), Value::Text(expected.to_string()), "{sql}");
+        }
+        assert_eq!(eval("CASE WHEN 0 THEN 1 END"), Value::Null);
+    }
+}
@@ -0,0 +1,576 @@
+use anyhow::{bail, ensure, Result};
+use std::collections::hash_map::RandomState;
+use std::fmt::Write;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::aggregate::is_aggregate_function;
+use crate::ast::*;
+use crate::collation::Collation;
+use crate::datetime;
+use crate::eval::{self, compare_with, evaluate, Scope};
+use crate::json;
+use crate::pattern;
+use crate::printf;
+use crate::value::Value;
+use crate::window::is_window_function;
+
+/// The largest string or blob a function may return, like SQLite's default
+/// `SQLITE_MAX_LENGTH`.
+pub const MAX_LENGTH: i64 = 1_000_000_000;
+
+/// The collation a function call compares its arguments with, which is that
+/// of the first argument that has one.
+pub fn argument_collation(call: &FunctionCall, scope: &dyn Scope) -> Result<Collation> {
+    for arg in &call.args {
+        if let Some(collation) = eval::collation(arg, scope)? {
+            return Ok(collation);
+        }
+    }
+    Ok(Collation::Binary)
+}
+
+/// The numbers of arguments the scalar function with the given name takes, if
+/// there is such a function.
+fn arity(name: &str) -> Option<(usize, usize)> {
+    let any = usize::MAX;
+    Some(match name.to_ascii_lowercase().as_str() {
+        "random" => (0, 0),
+        "length" | "upper" | "lower" | "abs" | "typeof" | "hex" | "quote" | "unicode"
+        | "zeroblob" | "randomblob" | "likely" | "unlikely" => (1, 1),
+        "instr" | "ifnull" | "nullif" => (2, 2),
+        "replace" => (3, 3),
+        "trim" | "ltrim" | "rtrim" | "round" | "unhex" => (1, 2),
+        "substr" | "substring" => (2, 3),
+        "coalesce" | "iif" | "min" | "max" => (2, any),
+        "printf" | "format" | "char" => (0, any),
+        "date" | "time" | "datetime" | "julianday" | "unixepoch" => (0, any),
+        "strftime" => (1, any),
+        "timediff" => (2, 2),
+        "like" => (2, 3),
+        "glob" | "regexp" => (2, 2),
+        "json" | "json_quote" => (1, 1),
+        "json_valid" | "json_type" | "json_array_length" => (1, 2),
+        "json_extract" | "json_set" | "json_insert" | "json_replace" | "json_remove" => (1, any),
+        "json_array" | "json_object" => (0, any),
+        _ => return None,
+    })
+}
+
+/// Whether there is a scalar function with the given name.
+pub fn is_scalar_function(name: &str) -> bool {
+    arity(name).is_some()
+}
+
+/// Checks that the calls of scalar functions in an expression name functions
+/// that exist, with as many arguments as they take. Calls of aggregate and
+/// window functions are checked where those are evaluated.
+pub fn check_calls(expr: &Expr) -> Result<()> {
+    let mut result = Ok(());
+    expr.walk(&mut |expr| match expr {
+        Expr::Function(call)
+            if result.is_ok()
+                && call.over.is_none()
+                && !is_aggregate_function(call)
+                && !is_window_function(&call.name) =>
+        {
+            result = check_call(call);
+        }
+        _ => {}
+    });
+    result
+}
+
+fn check_call(call: &FunctionCall) -> Result<()> {
+    let Some((min, max)) = arity(&call.name) else {
+        bail!("no such function: {}", call.name);
+    };
+    ensure!(
+        !call.star && (min..=max).contains(&call.args.len()),
+        "wrong number of arguments to function {}()",
+        call.name
+    );
+    Ok(())
+}
+
+/// Evaluates a call of a scalar function.
+pub fn call(call: &FunctionCall, scope: &dyn Scope) -> Result<Value> {
+    check_call(call)?;
+    let name = call.name.to_ascii_lowercase();
+
+    // These only evaluate the arguments they need.
+    match name.as_str() {
+        "coalesce" | "ifnull" => {
+            for arg in &call.args {
+                let value = evaluate(arg, scope)?;
+                if !value.is_null() {
+                    return Ok(value);
+                }
+            }
+            return Ok(Value::Null);
+        }
+        "iif" => {
+            for pair in call.args.chunks(2) {
+                match pair {
+                    [condition, then] => {
+                        if evaluate(condition, scope)?.truth() == Some(true) {
+                            return evaluate(then, scope);
+                        }
+                    }
+                    [otherwise] => return evaluate(otherwise, scope),
+                    _ => unreachable!("Chunks have one or two arguments"),
+                }
+            }
+            return Ok(Value::Null);
+        }
+        "likely" | "unlikely" => return evaluate(&call.args[0], scope),
+        _ => {}
+    }
+
+    let args = call
+        .args
+        .iter()
+        .map(|arg| evaluate(arg, scope))
+        .collect::<Result<Vec<_>>>()?;
+    let collation = match name.as_str() {
+        "nullif" | "min" | "max" => argument_collation(call, scope)?,
+        _ => Collation::Binary,
+    };
+    // The functions that return NULL if any of their arguments is NULL.
+    let null_in_null_out = [
+        "length",
+        "upper",
+        "lower",
+        "trim",
+        "ltrim",
+        "rtrim",
+        "replace",
+        "instr",
+        "substr",
+        "substring",
+        "unicode",
+        "unhex",
+        "min",
+        "max",
+    ];
+    if null_in_null_out.contains(&name.as_str()) && args.iter().any(Value::is_null) {
+        return Ok(Value::Null);
+    }
+
+    Ok(match name.as_str() {
+        "length" => match &args[0] {
+            Value::Blob(b) => Value::Integer(b.len() as i64),
+            // The length of text is up to the first NUL character.
+            value => {
+                let text = value.to_string();
+                let text = text.split('\0').next().unwrap_or_default();
+                Value::Integer(text.chars().count() as i64)
+            }
+        },
+        "substr" | "substring" => substr(&args),
+        "upper" => Value::Text(args[0].to_string().to_ascii_uppercase()),
+        "lower" => Value::Text(args[0].to_string().to_ascii_lowercase()),
+        "trim" | "ltrim" | "rtrim" => {
+            let text = args[0].to_string();
+            let chars = args.get(1).map_or(" ".to_string(), Value::to_string);
+            let is_trimmed = |c: char| chars.contains(c);
+            Value::Text(
+                match name.as_str() {
+                    "ltrim" => text.trim_start_matches(is_trimmed),
+                    "rtrim" => text.trim_end_matches(is_trimmed),
+                    _ => text.trim_matches(is_trimmed),
+                }
+                .to_string(),
+            )
+        }
+        "replace" => {
+            let pattern = args[1].to_string();
+            if pattern.is_empty() {
+                return Ok(args[0].clone());
+            }
+            Value::Text(args[0].to_string().replace(&pattern, &args[2].to_string()))
+        }
+        "instr" => Value::Integer(match (&args[0], &args[1]) {
+            (Value::Blob(haystack), Value::Blob(needle)) => {
+                if needle.is_empty() {
+                    1
+                } else {
+                    haystack
+                        .windows(needle.len())
+                        .position(|window| window == needle.as_slice())
+                        .map_or(0, |i| i as i64 + 1)
+                }
+            }
+            (haystack, needle) => {
+                let haystack = haystack.to_string();
+                haystack
+                    .find(&needle.to_string())
+                    .map_or(0, |i| haystack[..i].chars().count() as i64 + 1)
+            }
+        }),
+        "abs" => match &args[0] {
+            Value::Null => Value::Null,
+            Value::Integer(i) => match i.checked_abs() {
+                Some(i) => Value::Integer(i),
+                None => bail!("integer overflow"),
+            },
+            value => Value::Real(value.to_real().abs()),
+        },
+        "round" => {
+            if args.iter().any(Value::is_null) {
+                return Ok(Value::Null);
+            }
+            let digits = args.get(1).map_or(0, Value::to_integer).clamp(0, 30);
+            Value::Real(round(args[0].to_real(), digits as usize))
+        }
+        "nullif" => match compare_with(BinaryOp::Eq, &args[0], &args[1], collation) {
+            Some(true) => Value::Null,
+            _ => args[0].clone(),
+        },
+        "typeof" => Value::Text(args[0].type_name().to_string()),
+        "hex" => Value::Text(hex(&bytes(&args[0]))),
+        "unhex" => match unhex(&args[0].to_string(), &args.get(1).map(Value::to_string)) {
+            Some(bytes) => Value::Blob(bytes),
+            None => Value::Null,
+        },
+        "quote" => Value::Text(quote(&args[0])),
+        "printf" | "format" => match args.first() {
+            None | Some(Value::Null) => Value::Null,
+            Some(format) => Value::Text(printf::format(&format.to_string(), &args[1..])?),
+        },
+        "char" => Value::Text(
+            args.iter()
+                .map(|arg| {
+                    u32::try_from(arg.to_integer())
+                        .ok()
+                        .and_then(char::from_u32)
+                        .unwrap_or(char::REPLACEMENT_CHARACTER)
+                })
+                .collect(),
+        ),
+        "unicode" => match args[0].to_string().chars().next() {
+            Some(c) => Value::Integer(c as i64),
+            None => Value::Null,
+        },
+        "zeroblob" => Value::Blob(vec![0; blob_length(&args[0], 0)?]),
+        "randomblob" => Value::Blob(
+            (0..blob_length(&args[0], 1)?)
+                .map(|_| random() as u8)
+                .collect(),
+        ),
+        "random" => Value::Integer(random() as i64),
+        "date" | "time" | "datetime" | "julianday" | "unixepoch" | "strftime" | "timediff" => {
+            datetime::call(&name, &args)
+        }
+        "json" | "json_valid" | "json_extract" | "json_type" | "json_array_length"
+        | "json_array" | "json_object" | "json_set" | "json_insert" | "json_replace"
+        | "json_remove" | "json_quote" => json::call(&name, &call.args, &args)?,
+        // The arguments are in the opposite order of those of the operators.
+        "like" | "glob" | "regexp" => {
+            let op = match name.as_str() {
+                "like" => PatternOp::Like,
+                "glob" => PatternOp::Glob,
+                _ => PatternOp::Regexp,
+            };
+            let matched = matches_pattern(
+                op,
+                &args[0],
+                &args[1],
+                args.get(2),
+                scope.case_sensitive_like(),
+            )?;
+            matched.map_or(Value::Null, |m| Value::Integer(m as i64))
+        }
+        // On ties, the first of the arguments wins.
+        "min" => args
+            .into_iter()
+            .reduce(|min, arg| {
+                if arg.compare_with(&min, collation).is_lt() {
+                    arg
+                } else {
+                    min
+                }
+            })
+            .expect("min() has arguments"),
+        "max" => args
+            .into_iter()
+            .reduce(|max, arg| {
+                if arg.compare_with(&max, collation).is_gt() {
+                    arg
+                } else {
+                    max
+                }
+            })
+            .expect("max() has arguments"),
+        _ => unreachable!("{name}() is a checked scalar function"),
+    })
+}
+
+/// Whether the text matches the pattern of a `LIKE`, `GLOB` or `REGEXP`
+/// operator, or `None` if any of the operands is NULL.
+pub fn matches_pattern(
+    op: PatternOp,
+    pattern: &Value,
+    text: &Value,
+    escape: Option<&Value>,
+    case_sensitive_like: bool,
+) -> Result<Option<bool>> {
+    if pattern.is_null() || text.is_null() || escape.is_some_and(Value::is_null) {
+        return Ok(None);
+    }
+    let (pattern, text) = (pattern.to_string(), text.to_string());
+    let matched = match op {
+        PatternOp::Like => {
+            let escape = match escape.map(Value::to_string) {
+                Some(escape) => {
+                    let mut chars = escape.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => Some(c),
+                        _ => bail!("ESCAPE expression must be a single character"),
+                    }
+                }
+                None => None,
+            };
+            pattern::like(&pattern, &text, escape, case_sensitive_like)?
+        }
+        PatternOp::Glob => pattern::glob(&pattern, &text)?,
+        PatternOp::Regexp => pattern::regexp(&pattern, &text)?,
+    };
+    Ok(Some(matched))
+}
+
+/// The bytes of a blob, or of the text of any other value.
+fn bytes(value: &Value) -> Vec<u8> {
+    match value {
+        Value::Null => vec![],
+        Value::Blob(b) => b.clone(),
+        value => value.to_string().into_bytes(),
+    }
+}
+
+/// The bytes as upper case hexadecimal digits.
+fn hex(bytes: &[u8]) -> String {
+    let mut hex = String::new();
+    for byte in bytes {
+        write!(hex, "{byte:02X}").expect("Writing to a string succeeds");
+    }
+    hex
+}
+
+/// `substr(X, Y[, Z])`: the `Z` characters of `X` starting at the `Y`-th,
+/// counting from 1, or from the end if `Y` is negative. A negative `Z` takes
+/// the characters before the `Y`-th instead. Blobs are taken byte by byte.
+fn substr(args: &[Value]) -> Value {
+    let len = match &args[0] {
+        Value::Blob(b) => b.len(),
+        value => value.to_string().chars().count(),
+    } as i64;
+    let mut start = args[1].to_integer();
+    let mut count = args.get(2).map_or(i64::MAX, Value::to_integer);
+    let before = count < 0;
+    count = count.saturating_abs();
+
+    if start < 0 {
+        start += len;
+        if start < 0 {
+            count = count.saturating_add(start).max(0);
+            start = 0;
+        }
+    } else if start > 0 {
+        start -= 1;
+    } else if count > 0 {
+        // Position 0 is before the first character.
+        count -= 1;
+    }
+    if before {
+        start -= count;
+        if start < 0 {
+            count += start;
+            start = 0;
+        }
+    }
+
+    let start = start.min(len) as usize;
+    let count = count.clamp(0, len) as usize;
+    match &args[0] {
+        Value::Blob(b) => Value::Blob(b.iter().skip(start).take(count).copied().collect()),
+        value => Value::Text(value.to_string().chars().skip(start).take(count).collect()),
+    }
+}
+
+/// Rounds half away from zero to the given number of decimal digits, based on
+/// the decimal digits of the real rather than its exact binary value.
+fn round(r: f64, digits: usize) -> f64 {
+    // Such reals have no fractional part.
+    if r.abs() > 4503599627370496.0 {
+        return r;
+    }
+    if digits == 0 {
+        return (if r < 0.0 { r - 0.5 } else { r + 0.5 }) as i64 as f64;
+    }
+    printf::real(&format!("%!.{digits}f"), r)
+        .parse()
+        .unwrap_or(r)
+}
+
+/// `unhex(X[, Y])`: the blob of the hexadecimal digits in `X`, which may be
+/// separated by the characters in `Y`. NULL if `X` has other characters, or
+/// an odd number of digits.
+fn unhex(text: &str, ignored: &Option<String>) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if ignored.as_ref().is_some_and(|ignored| ignored.contains(c)) {
+            continue;
+        }
+        let high = c.to_digit(16)?;
+        let low = chars.next()?.to_digit(16)?;
+        bytes.push((high * 16 + low) as u8);
+    }
+    Some(bytes)
+}
+
+/// `quote(X)`: the value as an SQL literal. Reals are written with enough
+/// digits to read back as the same value.
+pub fn quote(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Integer(i) => i.to_string(),
+        Value::Real(r) => {
+            let text = printf::real("%!.15g", *r);
+            if text.parse() == Ok(*r) {
+                text
+            } else {
+                printf::real("%!.20e", *r)
+            }
+        }
+        Value::Text(t) => format!("'{}'", t.replace('\'', "''")),
+        Value::Blob(b) => format!("X'{}'", hex(b)),
+    }
+}
+
+/// The length of the blob `zeroblob()` or `randomblob()` is called for.
+fn blob_length(value: &Value, min: i64) -> Result<usize> {
+    let length = value.to_integer().max(min);
+    ensure!(length <= MAX_LENGTH, "string or blob too big");
+    Ok(length as usize)
+}
+
+/// A pseudo-random number, from hashing a counter with a randomly keyed
+/// hasher.
+fn random() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::tests::{eval_constant, text};
+    use crate::function::*;
+
+    #[test]
+    fn evaluates_string_functions() {
+        let cases = [
+            ("length('héllo')", Value::Integer(5)),
+            ("length(x'0102')", Value::Integer(2)),
+            ("length(12.50)", Value::Integer(4)),
+            ("length(NULL)", Value::Null),
+            ("substr('hello', -3)", text("llo")),
+            ("substr('hello', 0, 2)", text("h")),
+            ("substr('hello', 2, -1)", text("h")),
+            ("substr('hello', -2, -2)", text("el")),
+            ("substr('hello', -10, 7)", text("he")),
+            ("substr(12345, 2, 3)", text("234")),
+            ("substr(x'01020304', 2, 2)", Value::Blob(vec![2, 3])),
+            ("substr('hello', NULL)", Value::Null),
+            ("upper('abcé')", text("ABCé")),
+            ("trim('  a  ')", text("a")),
+            ("ltrim('xxaxx', 'x')", text("axx")),
+            ("rtrim('xxaxx', 'x')", text("xxa")),
+            ("replace('banana', 'an', 'AN')", text("bANANa")),
+            ("replace(123, '', 'x')", Value::Integer(123)),
+            ("instr('héllo', 'l')", Value::Integer(3)),
+            ("instr(123, 2)", Value::Integer(2)),
+            ("instr('abc', 'z')", Value::Integer(0)),
+            ("hex(12)", text("3132")),
+            ("hex(1.5)", text("312E35")),
+            ("unhex('4a 4b', ' ')", Value::Blob(b"JK".to_vec())),
+            ("unhex('4a4')", Value::Null),
+            ("quote('it''s')", text("'it''s'")),
+            ("quote(x'00ff')", text("X'00FF'")),
+            ("quote(0.1)", text("0.1")),
+            ("quote(1.0 / 3)", text("3.333333333333333148e-01")),
+            ("quote(1e100)", text("1.0e+100")),
+            ("char(72, 105)", text("Hi")),
+            ("unicode('é')", Value::Integer(233)),
+            ("unicode('')", Value::Null),
+            (
+                "printf('%5.2f|%-3s|%d', 1.23456, 'a', '12abc')",
+                text(" 1.23|a  |12"),
+            ),
+        ];
+
+        for (sql, expected) in cases {
+            assert_eq!(eval_constant(sql).unwrap(), expected, "{sql}");
+        }
+    }
+
+    #[test]
+    fn evaluates_numeric_and_null_functions() {
+        let cases = [
+            ("abs(-3)", Value::Integer(3)),
+            ("abs('-3')", Value::Real(3.0)),
+            ("abs(NULL)", Value::Null),
+            ("round(0.125, 2)", Value::Real(0.13)),
+            ("round(2.5)", Value::Real(3.0)),
+            ("round(-2.5)", Value::Real(-3.0)),
+            ("round(2.675, 2)", Value::Real(2.67)),
+            ("round(1.5, -1)", Value::Real(2.0)),
+            ("coalesce(NULL, NULL, 3, 4)", Value::Integer(3)),
+            ("ifnull(NULL, 'b')", text("b")),
+            ("nullif(1, 1.0)", Value::Null),
+            ("nullif(1, 2)", Value::Integer(1)),
+            ("iif(0, 1, 0, 2, 3)", Value::Integer(3)),
+            ("iif(0, 2)", Value::Null),
+            ("typeof(1.0)", text("real")),
+            ("max(1.0, 1)", Value::Real(1.0)),
+            ("min(3, 'a', 2)", Value::Integer(2)),
+            ("max(3, NULL)", Value::Null),
+            ("length(zeroblob(3))", Value::Integer(3)),
+            ("length(randomblob(0))", Value::Integer(1)),
+            ("typeof(random())", text("integer")),
+            ("likely(5)", Value::Integer(5)),
+        ];
+
+        for (sql, expected) in cases {
+            assert_eq!(eval_constant(sql).unwrap(), expected, "{sql}");
+        }
+    }
+
+    #[test]
+    fn reports_invalid_calls() {
+        let cases = [
+            ("nosuch(1)", "no such function: nosuch"),
+            (
+                "coalesce(1)",
+                "wrong number of arguments to function coalesce()",
+            ),
+            (
+                "ifnull(1, 2, 3)",
+                "wrong number of arguments to function ifnull()",
+            ),
+            ("abs(*)", "wrong number of arguments to function abs()"),
+            ("abs(-9223372036854775807 - 1)", "integer overflow"),
+        ];
+
+        for (sql, expected) in cases {
+            assert_eq!(
+                eval_constant(sql).unwrap_err().to_string(),
+                expected,
+                "{sql}"
+            );
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use anyhow::{anyhow, bail, Result};
+
+use crate::ast::{ColumnDef, Expr, Statement};
+use crate::btree_page::{Cell, Record};
+use crate::collation::Collation;
+use crate::database::Database;
+use crate::parser::parse;
+use crate::schema::SchemaType;
+use crate::sort::{compare_keys, SortOrder};
+use crate::value::Value;
+
+/// An index on columns of a table, as described by its entry in the schema.
+pub struct Index {
+    rootpage: u32,
+    /// The positions of the indexed columns in the table, in key order.
+    pub columns: Vec<usize>,
+    /// How the entries are ordered by each of the indexed columns.
+    pub order: Vec<SortOrder>,
+}
+
+impl Index {
+    /// Loads the indexes on a table with a rowid that can be used to look up
+    /// its rows by the values of columns. Indexes on expressions, partial
+    /// indexes and indexes with collations that are not built in are left
+    /// out, as are the indexes SQLite creates for constraints, which have no
+    /// SQL to describe them.
+    pub fn load_all(db: &Database, table: &str, columns: &[ColumnDef]) -> Result<Vec<Self>> {
+        let mut indexes = Vec::new();
+        for entry in db.schema.of_kind(SchemaType::Index) {
+            let Some(sql) = &entry.sql else {
+                continue;
+            };
+            if !entry.tbl_name.eq_ignore_ascii_case(table) {
+                continue;
+            }
+            let definition = match parse(sql)?.pop() {
+                Some(Statement::CreateIndex(definition)) => definition,
+                _ => bail!("Invalid schema SQL for index {}", entry.name),
+            };
+            if definition.where_clause.is_some() {
+                continue;
+            }
+
+            let mut index = Index {
+                rootpage: entry.rootpage,
+                columns: Vec::new(),
+                order: Vec::new(),
+            };
+            for term in &definition.columns {
+                let (expr, collation) = match &term.expr {
+                    Expr::Collate { expr, collation } => (&**expr, Some(collation.as_str())),
+                    expr => (expr, None),
+                };
+                let Expr::Column { table: None, name } = expr else {
+                    break;
+                };
+                let Some(column) = columns
+                    .iter()
+                    .position(|c| c.name.eq_ignore_ascii_case(name))
+                else {
+                    break;
+                };
+                // Without an explicit collation, the index uses the one of the column.
+                let collation = collation.or(columns[column].collation());
+                let Ok(collation) = collation.map_or(Ok(Collation::Binary), Collation::from_name)
+                else {
+                    break;
+                };
+                index.columns.push(column);
+                index
+                    .order
+                    .push(SortOrder::new(term.descending, None, collation));
+            }
+            if index.columns.len() == definition.columns.len() {
+                indexes.push(index);
+            }
+        }
+
+        Ok(indexes)
+    }
+
+    /// Iterates over the rowids of the rows whose indexed columns start with
+    /// the values of `key`, in index order.
+    pub fn rowids<'db>(
+        &self,
+        db: &'db Database,
+        key: Vec<Value>,
+    ) -> Result<impl Iterator<Item = Result<i64>> + 'db> {
+        let entries = entries_with_prefix(db, self.rootpage, self.order.clone(), key)?;
+        Ok(entries.map(|record| {
+            // Index entries end with the rowid of the row they belong to.
+            match record?.values.pop().map(Value::from) {
+                Some(Value::Integer(rowid)) => Ok(rowid),
+                _ => Err(anyhow!("Index entry without rowid")),
+            }
+        }))
+    }
+}
+
+/// Iterates over the records of the entries of an index B-tree whose leading
+/// values equal `key`, by the order of the columns of the B-tree.
+pub fn entries_with_prefix(
+    db: &Database,
+    rootpage: u32,
+    order: Vec<SortOrder>,
+    key: Vec<Value>,
+) -> Result<impl Iterator<Item = Result<Record>> + '_> {
+    let compare = move |record: &Record| {
+        let values = record
+            .values
+            .iter()
+            .take(key.len())
+            .map(|value| Value::from(value.clone()))
+            .collect::<Vec<_>>();
+        compare_keys(&order, &values, &key)
+    };
+    let cursor = db.seek(rootpage, &compare)?;
+
+    Ok(cursor.map_while(move |cell| match cell {
+        Ok(Cell::IndexLeaf { payload, .. } | Cell::IndexInterior { payload, .. }) => {
+            compare(&payload).is_eq().then_some(Ok(payload))
+        }
+        Ok(_) => Some(Err(anyhow!("Unexpected cell type in index B-tree"))),
+        Err(e) => Some(Err(e)),
+    }))
+}
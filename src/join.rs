@@ -0,0 +1,711 @@
+use anyhow::{anyhow, bail, ensure, Result};
+use std::rc::Rc;
+
+use crate::ast::*;
+use crate::collation::Collation;
+use crate::cte;
+use crate::eval::{affinity, comparison_collation, evaluate, Scope};
+use crate::json;
+use crate::query::{run, Context, ResultSet};
+use crate::table::{is_rowid_name, ColumnRef, Lookup, Row, Table};
+use crate::value::{Affinity, Value};
+use crate::view;
+
+/// A table in the `FROM` clause of a query.
+pub struct Source<'a> {
+    /// The name the table is referred to by, which is its alias if it has
+    /// one. A subquery without an alias has no name.
+    name: Option<&'a str>,
+    /// The names of the columns, in order.
+    pub columns: Vec<String>,
+    /// How many of the last columns are hidden, which `*` and `NATURAL`
+    /// joins leave out.
+    hidden: usize,
+    /// The collations of the columns.
+    collations: Vec<Collation>,
+    rows: Rows<'a>,
+    /// Whether the table is on the right of a `LEFT JOIN`.
+    left: bool,
+    /// The terms of the condition the table is joined on, including the
+    /// equalities of `USING` and `NATURAL` joins.
+    pub on: Vec<Expr>,
+    /// The columns the table is joined on by `USING` or `NATURAL`. Without a
+    /// table name, these refer to the column of the table on the left, and
+    /// `*` leaves them out.
+    using: Vec<String>,
+}
+
+/// Where the rows of a table in the `FROM` clause come from.
+enum Rows<'a> {
+    Table(Table),
+    /// The rows of a subquery, which it returns before the join starts, and
+    /// the affinities of its columns.
+    Subquery {
+        rows: Vec<Row>,
+        affinities: Vec<Option<Affinity>>,
+    },
+    /// A call of `json_each()` or `json_tree()`, whose arguments may refer
+    /// to the tables before it, so it returns its rows for each of their
+    /// rows.
+    Function {
+        tree: bool,
+        args: &'a [Expr],
+    },
+}
+
+impl<'a> Source<'a> {
+    /// Loads the tables in the `FROM` clause of a query, from left to right.
+    /// A query that only needs the first `needed` rows of its only table
+    /// says so.
+    pub fn load_all(
+        select: &'a Select,
+        needed: Option<usize>,
+        context: Context,
+    ) -> Result<Vec<Self>> {
+        let Some(from) = &select.from else {
+            return Ok(Vec::new());
+        };
+        cte::check_recursive_references(select, context)?;
+        let mut sources = vec![Self::load(from, needed, context)?];
+
+        for join in &select.joins {
+            let mut source = Self::load(&join.table, None, context)?;
+            source.left = join.operator == JoinOperator::Left;
+            let using = match (&join.constraint, join.natural) {
+                (Some(_), true) => bail!("a NATURAL join may not have an ON or USING clause"),
+                (Some(JoinConstraint::On(condition)), false) => {
+                    conjuncts(condition, &mut source.on);
+                    Vec::new()
+                }
+                (Some(JoinConstraint::Using(columns)), false) => columns.clone(),
+                (None, true) => source
+                    .visible_columns()
+                    .iter()
+                    .filter(|name| sources.iter().any(|s| s.column_index(name).is_some()))
+                    .cloned()
+                    .collect(),
+                (None, false) => Vec::new(),
+            };
+
+            for name in using {
+                let left = sources.iter().find(|s| s.column_index(&name).is_some());
+                let (Some(left), Some(_)) = (left, source.column_index(&name)) else {
+                    bail!("cannot join using column {name} - column not present in both tables");
+                };
+                let column = |source: &Source| Expr::Column {
+                    table: source.name.map(str::to_string),
+                    name: name.clone(),
+                };
+                source.on.push(Expr::Binary {
+                    op: BinaryOp::Eq,
+                    left: Box::new(column(left)),
+                    right: Box::new(column(&source)),
+                });
+                source.using.push(name);
+            }
+            sources.push(source);
+        }
+
+        Ok(sources)
+    }
+
+    fn load(from: &'a TableOrSubquery, needed: Option<usize>, context: Context) -> Result<Self> {
+        let (name, (columns, collations, rows)) = match from {
+            TableOrSubquery::Table(table) => {
+                let name = table.alias.as_ref().unwrap_or(&table.name);
+                let cte = match table.schema {
+                    Some(_) => None,
+                    None => cte::lookup(&table.name, needed, context)?,
+                };
+                let subquery = match cte {
+                    Some(result) => Some(result),
+                    None => view::lookup(&table.name, context)?,
+                };
+                match subquery {
+                    Some(result) => (Some(name.as_str()), subquery_rows(result)),
+                    None => {
+                        let loaded = Table::load(context.db, &table.name)?;
+                        let columns = loaded.columns.iter().map(|c| c.name.clone()).collect();
+                        // Columns with collations that are not built in
+                        // compare as if they had none.
+                        let collations = loaded
+                            .columns
+                            .iter()
+                            .map(|c| {
+                                let collation = c.collation().map(Collation::from_name);
+                                collation.and_then(Result::ok).unwrap_or_default()
+                            })
+                            .collect();
+                        (
+                            Some(name.as_str()),
+                            (columns, collations, Rows::Table(loaded)),
+                        )
+                    }
+                }
+            }
+            TableOrSubquery::Subquery { select, alias } => (
+                alias.as_deref(),
+                subquery_rows(run(select, false, context)?),
+            ),
+            TableOrSubquery::Function { name, args, alias } => {
+                let tree = match name.to_ascii_lowercase().as_str() {
+                    "json_each" => false,
+                    "json_tree" => true,
+                    _ => bail!("no such table: {name}"),
+                };
+                ensure!(args.len() <= 2, "too many arguments on {name}() - max 2");
+                let columns = json::TABLE_COLUMNS.map(str::to_string);
+                (
+                    Some(alias.as_ref().unwrap_or(name).as_str()),
+                    (
+                        columns.to_vec(),
+                        vec![Collation::Binary; columns.len()],
+                        Rows::Function { tree, args },
+                    ),
+                )
+            }
+        };
+        let hidden = match rows {
+            Rows::Function { .. } => json::HIDDEN_COLUMNS,
+            _ => 0,
+        };
+        Ok(Self {
+            name,
+            columns,
+            hidden,
+            collations,
+            rows,
+            left: false,
+            on: Vec::new(),
+            using: Vec::new(),
+        })
+    }
+
+    pub fn is_named(&self, name: &str) -> bool {
+        self.name.is_some_and(|n| n.eq_ignore_ascii_case(name))
+    }
+
+    /// Whether `*` leaves out the column, as it is joined on by `USING` or
+    /// `NATURAL` and so already part of the table on the left.
+    pub fn is_using(&self, name: &str) -> bool {
+        self.using.iter().any(|c| c.eq_ignore_ascii_case(name))
+    }
+
+    /// The columns other than the hidden ones.
+    pub fn visible_columns(&self) -> &[String] {
+        &self.columns[..self.columns.len() - self.hidden]
+    }
+
+    /// Finds the position of a column by its name, ignoring case.
+    fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns
+            .iter()
+            .position(|c| c.eq_ignore_ascii_case(name))
+    }
+
+    fn resolve(&self, name: &str) -> Option<ColumnRef> {
+        let has_rowid = matches!(&self.rows, Rows::Table(table) if table.has_rowid());
+        match self.column_index(name) {
+            Some(index) => Some(ColumnRef::Column(index)),
+            None if has_rowid && is_rowid_name(name) => Some(ColumnRef::Rowid),
+            None => None,
+        }
+    }
+
+    /// The affinity of a column. The columns of a subquery have the affinity
+    /// of the expressions they are the result of, if any.
+    pub fn affinity(&self, column: ColumnRef) -> Option<Affinity> {
+        match (&self.rows, column) {
+            (Rows::Table(table), ColumnRef::Column(index)) => Some(table.columns[index].affinity),
+            (Rows::Table(_), ColumnRef::Rowid) => Some(Affinity::Integer),
+            (Rows::Subquery { affinities, .. }, ColumnRef::Column(index)) => affinities[index],
+            (Rows::Subquery { .. }, ColumnRef::Rowid) => None,
+            (Rows::Function { .. }, _) => None,
+        }
+    }
+
+    /// The collation of a column, which is binary unless the column is
+    /// declared with another one. The rowid is an integer, so it has none.
+    pub fn collation(&self, column: ColumnRef) -> Option<Collation> {
+        match column {
+            ColumnRef::Column(index) => Some(self.collations[index]),
+            ColumnRef::Rowid => None,
+        }
+    }
+
+    /// A row of NULLs, which stands in for the rows of the table on the right
+    /// of a `LEFT JOIN` when there are no matching rows.
+    pub fn null_row(&self) -> Row {
+        Row {
+            rowid: None,
+            values: vec![Value::Null; self.columns.len()],
+        }
+    }
+}
+
+/// The columns, their collations and the rows of the result of a subquery,
+/// CTE or view, as a table.
+fn subquery_rows<'a>(result: ResultSet) -> (Vec<String>, Vec<Collation>, Rows<'a>) {
+    let rows = result.rows.into_iter().map(|values| Row {
+        rowid: None,
+        values,
+    });
+    let collations = result.collations.into_iter().map(Option::unwrap_or_default);
+    (
+        unique_names(result.columns),
+        collations.collect(),
+        Rows::Subquery {
+            rows: rows.collect(),
+            affinities: result.affinities,
+        },
+    )
+}
+
+/// Makes the names of the columns of a subquery unique, by adding `:1`,
+/// `:2` and so on to the names of later columns with the same name.
+fn unique_names(columns: Vec<String>) -> Vec<String> {
+    let mut names: Vec<String> = Vec::with_capacity(columns.len());
+    for column in columns {
+        let mut name = column.clone();
+        let mut n = 0;
+        while names.iter().any(|other| other.eq_ignore_ascii_case(&name)) {
+            n += 1;
+            name = format!("{column}:{n}");
+        }
+        names.push(name);
+    }
+    names
+}
+
+/// Finds the table in the `FROM` clause a column refers to, and the column,
+/// or `None` if no table has the column.
+pub fn resolve_column(
+    sources: &[Source],
+    table: Option<&str>,
+    name: &str,
+) -> Result<Option<(usize, ColumnRef)>> {
+    let mut found = None;
+    for (i, source) in sources.iter().enumerate() {
+        match table {
+            Some(table) if !source.is_named(table) => continue,
+            None if source.is_using(name) => continue,
+            _ => {}
+        }
+        if let Some(column) = source.resolve(name) {
+            if found.is_some() {
+                match table {
+                    Some(table) => bail!("ambiguous column name: {table}.{name}"),
+                    None => bail!("ambiguous column name: {name}"),
+                }
+            }
+            found = Some((i, column));
+        }
+    }
+    Ok(found)
+}
+
+pub fn no_such_column(table: Option<&str>, name: &str) -> anyhow::Error {
+    match table {
+        Some(table) => anyhow!("no such column: {table}.{name}"),
+        None => anyhow!("no such column: {name}"),
+    }
+}
+
+/// The scope of expressions evaluated for a row of each of the tables in the
+/// `FROM` clause, or for the single row of a query without tables.
+pub struct RowScope<'a> {
+    pub context: Context<'a>,
+    pub sources: &'a [Source<'a>],
+    pub rows: &'a [Row],
+}
+
+impl Scope for RowScope<'_> {
+    fn column(&self, table: Option<&str>, name: &str) -> Result<Value> {
+        let Some((source, column)) = resolve_column(self.sources, table, name)? else {
+            return self.context.outer_column(table, name);
+        };
+        // A subquery in the condition of a LEFT JOIN is checked before the
+        // tables to the right of it are joined.
+        let Some(row) = self.rows.get(source) else {
+            bail!("ON clause references tables to its right");
+        };
+        match column {
+            ColumnRef::Column(index) => Ok(row.values[index].clone()),
+            ColumnRef::Rowid => Ok(row.rowid.map_or(Value::Null, Value::Integer)),
+        }
+    }
+
+    fn affinity(&self, table: Option<&str>, name: &str) -> Option<Affinity> {
+        match resolve_column(self.sources, table, name) {
+            Ok(Some((source, column))) => self.sources[source].affinity(column),
+            Ok(None) => self.context.outer_affinity(table, name),
+            Err(_) => None,
+        }
+    }
+
+    fn collation(&self, table: Option<&str>, name: &str) -> Option<Collation> {
+        match resolve_column(self.sources, table, name) {
+            Ok(Some((source, column))) => self.sources[source].collation(column),
+            Ok(None) => self.context.outer_collation(table, name),
+            Err(_) => None,
+        }
+    }
+
+    fn parameter(&self, index: usize) -> Value {
+        let parameters = &self.context.state.parameters;
+        parameters.get(index - 1).cloned().unwrap_or(Value::Null)
+    }
+
+    fn subquery(&self, select: &Select, first_only: bool) -> Result<Rc<ResultSet>> {
+        self.context.subquery(select, first_only, self)
+    }
+
+    fn case_sensitive_like(&self) -> bool {
+        self.context.db.case_sensitive_like
+    }
+}
+
+/// How to find the rows of a table in the join, and the conditions to check
+/// for them.
+#[derive(Default)]
+struct Level {
+    /// For the table on the right of a `LEFT JOIN`, the terms of its join
+    /// condition, which decide whether a row on the left has matching rows.
+    on: Vec<Expr>,
+    /// The terms of the `WHERE` clause and of the conditions of inner joins
+    /// that refer to this table last.
+    filters: Vec<Expr>,
+    /// A lookup for the rows that satisfy equality terms, with its key.
+    lookup: Option<(Lookup, Vec<KeyPart>)>,
+}
+
+/// An expression giving a value of the key of a lookup, and the affinity the
+/// value is compared with.
+type KeyPart = (Expr, Option<Affinity>);
+
+/// Iterates over the combinations of rows of the tables in the `FROM` clause
+/// that satisfy the join conditions and the `WHERE` clause.
+///
+/// The tables are joined by nested loops, from left to right. Each term of
+/// the conditions is checked as soon as the rows of all tables it refers to
+/// are known. For each table, equality terms with the tables before it are
+/// used to look up the matching rows by rowid, primary key or index, rather
+/// than scanning the whole table for every combination of rows before it.
+pub struct JoinRows<'a> {
+    sources: &'a [Source<'a>],
+    levels: Vec<Level>,
+    context: Context<'a>,
+    /// The current row of each table up to the one being scanned.
+    rows: Vec<Row>,
+    /// The scans of the tables up to the current one.
+    scans: Vec<Scan<'a>>,
+    /// Whether the single row of a query without tables is still to come.
+    empty_row: bool,
+}
+
+struct Scan<'a> {
+    rows: Box<dyn Iterator<Item = Result<Row>> + 'a>,
+    /// Whether any row satisfied the join condition of a `LEFT JOIN`.
+    matched: bool,
+}
+
+impl<'a> JoinRows<'a> {
+    pub fn new(
+        sources: &'a [Source<'a>],
+        condition: Option<&Expr>,
+        context: Context<'a>,
+    ) -> Result<Self> {
+        let mut levels = sources.iter().map(|_| Level::default()).collect::<Vec<_>>();
+        let mut filters = Vec::new();
+        if let Some(condition) = condition {
+            conjuncts(condition, &mut filters);
+        }
+        for (i, source) in sources.iter().enumerate() {
+            if !source.left {
+                // The condition of an inner join is like part of the WHERE clause.
+                filters.extend(source.on.iter().cloned());
+                continue;
+            }
+            for term in &source.on {
+                // A subquery may only refer to the tables up to this one,
+                // which is checked when it runs.
+                if !has_subquery(term) && last_source(term, sources)?.is_some_and(|last| last > i) {
+                    bail!("ON clause references tables to its right");
+                }
+                levels[i].on.push(term.clone());
+            }
+        }
+        let mut constants = Vec::new();
+        for term in filters {
+            // Subqueries may refer to any table, so terms with subqueries
+            // are checked once the rows of all tables are known.
+            let last = match has_subquery(&term) {
+                true => sources.len().checked_sub(1),
+                false => last_source(&term, sources)?,
+            };
+            match last {
+                Some(i) => levels[i].filters.push(term),
+                None => constants.push(term),
+            }
+        }
+
+        let scope = RowScope {
+            context,
+            sources,
+            rows: &[],
+        };
+        for (i, level) in levels.iter_mut().enumerate() {
+            // Terms of the WHERE clause can't narrow down the rows of a
+            // LEFT JOIN, as rows on the left without a match are kept.
+            let terms = if sources[i].left {
+                &level.on
+            } else {
+                &level.filters
+            };
+            let mut equalities = Vec::new();
+            for term in terms {
+                let Some((column, expr)) = equality(term, i, sources)? else {
+                    continue;
+                };
+                // The B-tree can only be searched for the value if the
+                // comparison converts it to one the column would store.
+                let column_affinity = sources[i].affinity(column);
+                let affinity = Affinity::for_comparison(column_affinity, affinity(expr, &scope));
+                let usable = match affinity {
+                    Some(Affinity::Text) => column_affinity == Some(Affinity::Text),
+                    Some(affinity) if affinity.is_numeric() => {
+                        column_affinity.is_some_and(Affinity::is_numeric)
+                    }
+                    _ => true,
+                };
+                if usable {
+                    // Keys must also compare with the collation of the term.
+                    let Expr::Binary { left, right, .. } = term else {
+                        unreachable!("Equalities are binary expressions");
+                    };
+                    let collation = comparison_collation(left, right, &scope)?;
+                    equalities.push((column, expr, affinity, collation));
+                }
+            }
+            let Rows::Table(table) = &sources[i].rows else {
+                continue;
+            };
+            let columns = equalities
+                .iter()
+                .map(|(column, .., collation)| (*column, *collation))
+                .collect::<Vec<_>>();
+            level.lookup = table.lookup_for(&columns).map(|(lookup, key)| {
+                let key = key.iter().map(|&i| {
+                    let (_, expr, affinity, _) = equalities[i];
+                    (expr.clone(), affinity)
+                });
+                (lookup, key.collect())
+            });
+        }
+
+        let mut join = Self {
+            sources,
+            levels,
+            context,
+            rows: Vec::new(),
+            scans: Vec::new(),
+            empty_row: false,
+        };
+        // Terms that refer to no table are checked only once.
+        if join.check(&constants)? {
+            if sources.is_empty() {
+                join.empty_row = true;
+            } else {
+                let scan = join.open(0)?;
+                join.scans.push(scan);
+            }
+        }
+
+        Ok(join)
+    }
+
+    /// Starts scanning a table for the current rows of the tables before it.
+    fn open(&self, level: usize) -> Result<Scan<'a>> {
+        let db = self.context.db;
+        let scope = RowScope {
+            context: self.context,
+            sources: self.sources,
+            rows: &self.rows,
+        };
+        let rows = match (&self.sources[level].rows, &self.levels[level].lookup) {
+            (Rows::Table(table), Some((lookup, key))) => {
+                let key = key
+                    .iter()
+                    .map(|(expr, affinity)| Ok(evaluate(expr, &scope)?.for_comparison(*affinity)))
+                    .collect::<Result<_>>()?;
+                table.lookup(db, *lookup, key)?
+            }
+            (Rows::Table(table), None) => Box::new(table.rows(db)),
+            (Rows::Subquery { rows, .. }, _) => Box::new(rows.iter().cloned().map(Ok)),
+            (Rows::Function { tree, args }, _) => {
+                let args = args
+                    .iter()
+                    .map(|arg| evaluate(arg, &scope))
+                    .collect::<Result<Vec<_>>>()?;
+                let rows = json::table_rows(*tree, &args)?;
+                Box::new(rows.into_iter().map(|values| {
+                    Ok(Row {
+                        rowid: None,
+                        values,
+                    })
+                }))
+            }
+        };
+        Ok(Scan {
+            rows,
+            matched: false,
+        })
+    }
+
+    /// Checks whether all terms are true for the current rows.
+    fn check(&self, terms: &[Expr]) -> Result<bool> {
+        let scope = RowScope {
+            context: self.context,
+            sources: self.sources,
+            rows: &self.rows,
+        };
+        for term in terms {
+            if evaluate(term, &scope)?.truth() != Some(true) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn advance(&mut self) -> Result<Option<Vec<Row>>> {
+        if self.empty_row {
+            self.empty_row = false;
+            return Ok(Some(Vec::new()));
+        }
+
+        loop {
+            let level = self.rows.len();
+            let Some(scan) = self.scans.last_mut() else {
+                return Ok(None);
+            };
+            match scan.rows.next().transpose()? {
+                Some(row) => {
+                    self.rows.push(row);
+                    if !self.check(&self.levels[level].on)? {
+                        self.rows.pop();
+                        continue;
+                    }
+                    self.scans[level].matched = true;
+                }
+                None if self.sources[level].left && !scan.matched => {
+                    scan.matched = true;
+                    self.rows.push(self.sources[level].null_row());
+                }
+                None => {
+                    self.scans.pop();
+                    self.rows.pop();
+                    continue;
+                }
+            }
+            if !self.check(&self.levels[level].filters)? {
+                self.rows.pop();
+                continue;
+            }
+
+            if self.rows.len() == self.sources.len() {
+                let rows = self.rows.clone();
+                self.rows.pop();
+                return Ok(Some(rows));
+            }
+            let scan = self.open(level + 1)?;
+            self.scans.push(scan);
+        }
+    }
+}
+
+impl Iterator for JoinRows<'_> {
+    type Item = Result<Vec<Row>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.advance().transpose()
+    }
+}
+
+/// Splits a condition into the terms joined by `AND`.
+fn conjuncts(expr: &Expr, terms: &mut Vec<Expr>) {
+    match expr {
+        Expr::Binary {
+            op: BinaryOp::And,
+            left,
+            right,
+        } => {
+            conjuncts(left, terms);
+            conjuncts(right, terms);
+        }
+        expr => terms.push(expr.clone()),
+    }
+}
+
+/// The position of the last table in the `FROM` clause that an expression
+/// refers to, if it refers to any.
+fn last_source(expr: &Expr, sources: &[Source]) -> Result<Option<usize>> {
+    let mut last = None;
+    let mut result = Ok(());
+    expr.walk(&mut |expr| {
+        if let Expr::Column { table, name } = expr {
+            // Columns of no table are those of the queries this one is
+            // nested in, which are constant for its rows.
+            match resolve_column(sources, table.as_deref(), name) {
+                Ok(Some((source, _))) => last = last.max(Some(source)),
+                Ok(None) => {}
+                Err(e) if result.is_ok() => result = Err(e),
+                Err(_) => {}
+            }
+        }
+    });
+    result.map(|()| last)
+}
+
+/// Whether an expression contains a subquery.
+fn has_subquery(expr: &Expr) -> bool {
+    let mut found = false;
+    expr.walk(&mut |expr| {
+        found |= matches!(
+            expr,
+            Expr::Subquery(_) | Expr::InSelect { .. } | Expr::Exists(_)
+        );
+    });
+    found
+}
+
+/// Checks whether a term compares a column of the table at `level` for
+/// equality with an expression of the tables before it, and returns the
+/// column and the expression if so.
+fn equality<'e>(
+    term: &'e Expr,
+    level: usize,
+    sources: &[Source],
+) -> Result<Option<(ColumnRef, &'e Expr)>> {
+    let Expr::Binary {
+        op: BinaryOp::Eq,
+        left,
+        right,
+    } = term
+    else {
+        return Ok(None);
+    };
+    for (column, other) in [(left, right), (right, left)] {
+        let Expr::Column { table, name } = &**column else {
+            continue;
+        };
+        let Some((source, column)) = resolve_column(sources, table.as_deref(), name)? else {
+            continue;
+        };
+        let before =
+            !has_subquery(other) && last_source(other, sources)?.map_or(true, |last| last < level);
+        if source == level && before {
+            return Ok(Some((column, other)));
+        }
+    }
+    Ok(None)
+}
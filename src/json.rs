@@ -0,0 +1,1226 @@
+use anyhow::{anyhow, bail, ensure, Result};
+use std::fmt;
+
+use crate::ast::*;
+use crate::printf;
+use crate::value::Value;
+
+/// How deeply arrays and objects may be nested, like SQLite's
+/// `JSON_MAX_DEPTH`.
+const MAX_DEPTH: usize = 1000;
+
+/// The columns of the rows of `json_each()` and `json_tree()`. The last two
+/// are the arguments of the call, which `*` leaves out.
+pub const TABLE_COLUMNS: [&str; 10] = [
+    "key", "value", "type", "atom", "id", "parent", "fullkey", "path", "json", "root",
+];
+
+/// How many of [`TABLE_COLUMNS`] are hidden.
+pub const HIDDEN_COLUMNS: usize = 2;
+
+/// A JSON value. Numbers and strings keep the text they are written with, so
+/// that JSON is written back the way it was given, other than for the
+/// extensions of JSON5, which are written as plain JSON.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    True,
+    False,
+    /// A number, as written in JSON.
+    Number(String),
+    /// A string, as written in JSON but without the quotes, so with escapes.
+    String(String),
+    Array(Vec<Json>),
+    /// The members of an object in order, with their keys written like
+    /// strings. Keys may repeat, but only the first member with a key can be
+    /// looked up.
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// The value as an SQL value: numbers, text or NULL, with `true` and
+    /// `false` as 1 and 0. Arrays and objects are JSON text.
+    fn to_value(&self) -> Value {
+        match self {
+            Json::Null => Value::Null,
+            Json::True => Value::Integer(1),
+            Json::False => Value::Integer(0),
+            Json::Number(number) => {
+                let integer = is_integer(number).then(|| number.parse().ok()).flatten();
+                match integer {
+                    Some(i) => Value::Integer(i),
+                    None => Value::Real(number.parse().unwrap_or_default()),
+                }
+            }
+            Json::String(string) => Value::Text(unescape(string)),
+            json => Value::Text(json.to_string()),
+        }
+    }
+
+    /// The name `json_type()` gives the type of the value.
+    fn type_name(&self) -> &'static str {
+        match self {
+            Json::Null => "null",
+            Json::True => "true",
+            Json::False => "false",
+            Json::Number(number) if is_integer(number) => "integer",
+            Json::Number(_) => "real",
+            Json::String(_) => "text",
+            Json::Array(_) => "array",
+            Json::Object(_) => "object",
+        }
+    }
+
+    /// Finds the value a path leads to.
+    fn lookup(&self, steps: &[Step]) -> Option<&Json> {
+        let Some((step, rest)) = steps.split_first() else {
+            return Some(self);
+        };
+        let child = match (self, step) {
+            (Json::Object(members), Step::Key(key)) => members
+                .iter()
+                .find(|(k, _)| key_matches(k, key))
+                .map(|(_, value)| value),
+            (Json::Array(items), step) => step.index(items.len()).and_then(|i| items.get(i)),
+            _ => None,
+        };
+        child?.lookup(rest)
+    }
+
+    /// Sets the value a path leads to, for `json_set()`, `json_insert()` and
+    /// `json_replace()`.
+    fn edit(&mut self, steps: &[Step], value: Json, edit: Edit) {
+        let Some((step, rest)) = steps.split_first() else {
+            if edit != Edit::Insert {
+                *self = value;
+            }
+            return;
+        };
+        match (self, step) {
+            (Json::Object(members), Step::Key(key)) => {
+                match members.iter_mut().find(|(k, _)| key_matches(k, key)) {
+                    Some((_, child)) => child.edit(rest, value, edit),
+                    None if edit != Edit::Replace => {
+                        if let Some(child) = Json::create(rest, value) {
+                            members.push((escape(key), child));
+                        }
+                    }
+                    None => {}
+                }
+            }
+            (Json::Array(items), step) => match step.index(items.len()) {
+                Some(i) if i < items.len() => items[i].edit(rest, value, edit),
+                Some(i) if i == items.len() && edit != Edit::Replace => {
+                    if let Some(child) = Json::create(rest, value) {
+                        items.push(child);
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// The value to add where a path leads past the end of an array or to a
+    /// missing key, with the objects and arrays the rest of the path leads
+    /// through. Arrays are only made for paths to their first element.
+    fn create(steps: &[Step], value: Json) -> Option<Json> {
+        let Some((step, rest)) = steps.split_first() else {
+            return Some(value);
+        };
+        let child = Json::create(rest, value)?;
+        match step {
+            Step::Key(key) => Some(Json::Object(vec![(escape(key), child)])),
+            Step::Index(0) | Step::FromEnd(0) => Some(Json::Array(vec![child])),
+            _ => None,
+        }
+    }
+
+    /// Removes the value a path leads to, if there is one. The path must not
+    /// be empty.
+    fn remove(&mut self, steps: &[Step]) {
+        let Some((step, rest)) = steps.split_first() else {
+            return;
+        };
+        match (self, step) {
+            (Json::Object(members), Step::Key(key)) => {
+                if let Some(i) = members.iter().position(|(k, _)| key_matches(k, key)) {
+                    if rest.is_empty() {
+                        members.remove(i);
+                    } else {
+                        members[i].1.remove(rest);
+                    }
+                }
+            }
+            (Json::Array(items), step) => {
+                if let Some(i) = step.index(items.len()).filter(|&i| i < items.len()) {
+                    if rest.is_empty() {
+                        items.remove(i);
+                    } else {
+                        items[i].remove(rest);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The size of the value in JSONB, SQLite's binary format for JSON. The
+    /// rows of `json_each()` and `json_tree()` are numbered by the offsets
+    /// of their values in it.
+    fn size(&self) -> usize {
+        let payload = self.payload_size();
+        header_size(payload) + payload
+    }
+
+    fn payload_size(&self) -> usize {
+        match self {
+            Json::Null | Json::True | Json::False => 0,
+            Json::Number(text) | Json::String(text) => text.len(),
+            Json::Array(items) => items.iter().map(Json::size).sum(),
+            Json::Object(members) => members
+                .iter()
+                .map(|(key, value)| header_size(key.len()) + key.len() + value.size())
+                .sum(),
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    /// Writes the value as JSON without any whitespace.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::True => f.write_str("true"),
+            Json::False => f.write_str("false"),
+            Json::Number(number) => f.write_str(number),
+            Json::String(string) => write!(f, "\"{string}\""),
+            Json::Array(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_str("]")
+            }
+            Json::Object(members) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "\"{key}\":{value}")?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+/// The size of the header of a JSONB element, which holds the size of its
+/// payload in as few bytes as it fits in.
+fn header_size(payload: usize) -> usize {
+    match payload {
+        0..=11 => 1,
+        12..=0xff => 2,
+        0x100..=0xffff => 3,
+        0x1_0000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+fn is_integer(number: &str) -> bool {
+    !number.contains(['.', 'e', 'E'])
+}
+
+fn malformed() -> anyhow::Error {
+    anyhow!("malformed JSON")
+}
+
+/// Parses JSON text, which may use the extensions of JSON5. Also returns
+/// whether it does.
+pub fn parse(text: &str) -> Result<(Json, bool)> {
+    let mut parser = Parser {
+        text,
+        pos: 0,
+        json5: false,
+        depth: 0,
+    };
+    let json = parser.value()?;
+    parser.whitespace()?;
+    ensure!(parser.pos == text.len(), malformed());
+    Ok((json, parser.json5))
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    /// Whether the text used any of the extensions of JSON5 so far.
+    json5: bool,
+    /// How many arrays and objects the parser is in.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let eaten = self.peek() == Some(c);
+        if eaten {
+            self.pos += c.len_utf8();
+        }
+        eaten
+    }
+
+    /// Takes the characters while they satisfy `f`.
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c| !f(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    /// Skips whitespace, and with JSON5, comments.
+    fn whitespace(&mut self) -> Result<()> {
+        loop {
+            let rest = self.rest();
+            match self.peek() {
+                Some(' ' | '\t' | '\n' | '\r') => self.pos += 1,
+                Some('/') if rest.starts_with("/*") => {
+                    let end = rest[2..].find("*/").ok_or_else(malformed)?;
+                    self.pos += end + 4;
+                    self.json5 = true;
+                }
+                Some('/') if rest.starts_with("//") => {
+                    let end = rest.find(['\n', '\r', '\u{2028}', '\u{2029}']);
+                    self.pos += end.unwrap_or(rest.len());
+                    self.json5 = true;
+                }
+                Some(c) if is_json5_space(c) => {
+                    self.pos += c.len_utf8();
+                    self.json5 = true;
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Json> {
+        self.whitespace()?;
+        match self.peek().ok_or_else(malformed)? {
+            '{' => self.nested(Parser::object),
+            '[' => self.nested(Parser::array),
+            quote @ ('"' | '\'') => Ok(Json::String(self.string(quote)?)),
+            '-' | '+' | '.' | '0'..='9' => self.number(),
+            _ => match self.take_while(is_identifier_char) {
+                "true" => Ok(Json::True),
+                "false" => Ok(Json::False),
+                "null" => Ok(Json::Null),
+                "Infinity" => {
+                    self.json5 = true;
+                    Ok(Json::Number("9e999".to_string()))
+                }
+                "NaN" => {
+                    self.json5 = true;
+                    Ok(Json::Null)
+                }
+                _ => Err(malformed()),
+            },
+        }
+    }
+
+    /// Parses an array or object, which may not be nested too deeply.
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<Json>) -> Result<Json> {
+        self.depth += 1;
+        ensure!(self.depth <= MAX_DEPTH, malformed());
+        let json = parse(self)?;
+        self.depth -= 1;
+        Ok(json)
+    }
+
+    fn array(&mut self) -> Result<Json> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        loop {
+            self.whitespace()?;
+            if self.eat(']') {
+                // Only JSON5 allows a comma after the last item.
+                self.json5 |= !items.is_empty();
+                return Ok(Json::Array(items));
+            }
+            items.push(self.value()?);
+            self.whitespace()?;
+            if !self.eat(',') {
+                ensure!(self.eat(']'), malformed());
+                return Ok(Json::Array(items));
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json> {
+        self.pos += 1;
+        let mut members = Vec::new();
+        loop {
+            self.whitespace()?;
+            if self.eat('}') {
+                self.json5 |= !members.is_empty();
+                return Ok(Json::Object(members));
+            }
+            let key = match self.peek() {
+                Some(quote @ ('"' | '\'')) => self.string(quote)?,
+                // JSON5 allows keys that are identifiers without quotes.
+                Some(c) if is_identifier_char(c) && !c.is_ascii_digit() => {
+                    self.json5 = true;
+                    self.take_while(is_identifier_char).to_string()
+                }
+                _ => bail!(malformed()),
+            };
+            self.whitespace()?;
+            ensure!(self.eat(':'), malformed());
+            members.push((key, self.value()?));
+            self.whitespace()?;
+            if !self.eat(',') {
+                ensure!(self.eat('}'), malformed());
+                return Ok(Json::Object(members));
+            }
+        }
+    }
+
+    /// Parses a string in the given quotes, returning it as it would be
+    /// written in double quotes in plain JSON.
+    fn string(&mut self, quote: char) -> Result<String> {
+        self.pos += 1;
+        self.json5 |= quote == '\'';
+        let mut string = String::new();
+        loop {
+            match self.bump().ok_or_else(malformed)? {
+                c if c == quote => return Ok(string),
+                '"' => string.push_str("\\\""),
+                '\\' => {
+                    let escaped = self.bump().ok_or_else(malformed)?;
+                    match escaped {
+                        '"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't' => {
+                            string.push('\\');
+                            string.push(escaped);
+                        }
+                        'u' => {
+                            let hex = self.rest().get(..4);
+                            let hex = hex.filter(|hex| hex.chars().all(|c| c.is_ascii_hexdigit()));
+                            string.push_str("\\u");
+                            string.push_str(hex.ok_or_else(malformed)?);
+                            self.pos += 4;
+                        }
+                        _ => {
+                            self.json5 = true;
+                            match escaped {
+                                '\'' => string.push('\''),
+                                'v' => string.push_str("\\u000b"),
+                                '0' => string.push_str("\\u0000"),
+                                'x' => {
+                                    let hex = self.rest().get(..2);
+                                    let hex = hex
+                                        .filter(|hex| hex.chars().all(|c| c.is_ascii_hexdigit()));
+                                    string.push_str("\\u00");
+                                    string.push_str(hex.ok_or_else(malformed)?);
+                                    self.pos += 2;
+                                }
+                                // A backslash at the end of a line continues
+                                // the string on the next one.
+                                '\r' => {
+                                    self.eat('\n');
+                                }
+                                '\n' | '\u{2028}' | '\u{2029}' => {}
+                                _ => bail!(malformed()),
+                            }
+                        }
+                    }
+                }
+                c if c < ' ' => {
+                    self.json5 = true;
+                    string.push_str(&escape(&c.to_string()));
+                }
+                c => string.push(c),
+            }
+        }
+    }
+
+    /// Parses a number, returning it as it would be written in plain JSON.
+    fn number(&mut self) -> Result<Json> {
+        let mut number = String::new();
+        if self.eat('-') {
+            number.push('-');
+        } else if self.eat('+') {
+            self.json5 = true;
+        }
+        if self.rest().starts_with("Infinity") {
+            self.pos += "Infinity".len();
+            self.json5 = true;
+            number.push_str("9e999");
+            return Ok(Json::Number(number));
+        }
+        if self.rest().starts_with("0x") || self.rest().starts_with("0X") {
+            self.pos += 2;
+            self.json5 = true;
+            let digits = self.take_while(|c| c.is_ascii_hexdigit());
+            let value = u64::from_str_radix(digits, 16).map_err(|_| malformed())?;
+            number.push_str(&value.to_string());
+            return Ok(Json::Number(number));
+        }
+
+        let integer = self.take_while(|c| c.is_ascii_digit());
+        ensure!(
+            !(integer.len() > 1 && integer.starts_with('0')),
+            malformed()
+        );
+        if integer.is_empty() {
+            // JSON5 allows a number to start with its decimal point.
+            ensure!(self.peek() == Some('.'), malformed());
+            self.json5 = true;
+            number.push('0');
+        }
+        number.push_str(integer);
+        if self.eat('.') {
+            number.push('.');
+            let fraction = self.take_while(|c| c.is_ascii_digit());
+            if fraction.is_empty() {
+                ensure!(!integer.is_empty(), malformed());
+                self.json5 = true;
+                number.push('0');
+            }
+            number.push_str(fraction);
+        }
+        if let Some(e @ ('e' | 'E')) = self.peek() {
+            self.pos += 1;
+            number.push(e);
+            if let Some(sign @ ('+' | '-')) = self.peek() {
+                self.pos += 1;
+                number.push(sign);
+            }
+            let exponent = self.take_while(|c| c.is_ascii_digit());
+            ensure!(!exponent.is_empty(), malformed());
+            number.push_str(exponent);
+        }
+        Ok(Json::Number(number))
+    }
+}
+
+/// Whitespace that JSON5 allows, other than that of plain JSON.
+fn is_json5_space(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0b}' | '\u{0c}' | '\u{a0}' | '\u{1680}' | '\u{2000}'
+            ..='\u{200a}'
+                | '\u{2028}'
+                | '\u{2029}'
+                | '\u{202f}'
+                | '\u{205f}'
+                | '\u{3000}'
+                | '\u{feff}'
+    )
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Writes text as the contents of a JSON string.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\u{08}' => escaped.push_str("\\b"),
+            '\u{0c}' => escaped.push_str("\\f"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c < ' ' => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reads the contents of a JSON string, resolving its escapes. Escapes of
+/// unpaired surrogates become replacement characters.
+fn unescape(string: &str) -> String {
+    if !string.contains('\\') {
+        return string.to_string();
+    }
+    let mut text = String::with_capacity(string.len());
+    let mut chars = string.chars();
+    let hex = |chars: &mut std::str::Chars| {
+        let digits = chars.as_str().get(..4)?;
+        let unit = u32::from_str_radix(digits, 16).ok()?;
+        chars.nth(3);
+        Some(unit)
+    };
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('b') => text.push('\u{08}'),
+            Some('f') => text.push('\u{0c}'),
+            Some('n') => text.push('\n'),
+            Some('r') => text.push('\r'),
+            Some('t') => text.push('\t'),
+            Some('u') => {
+                let unit = hex(&mut chars).unwrap_or(0xfffd);
+                let c = if (0xd800..0xdc00).contains(&unit) && chars.as_str().starts_with("\\u") {
+                    let mut next = chars.clone();
+                    next.nth(1);
+                    match hex(&mut next) {
+                        Some(low @ 0xdc00..=0xdfff) => {
+                            chars = next;
+                            char::from_u32(0x10000 + ((unit - 0xd800) << 10) + (low - 0xdc00))
+                        }
+                        _ => None,
+                    }
+                } else {
+                    char::from_u32(unit)
+                };
+                text.push(c.unwrap_or(char::REPLACEMENT_CHARACTER));
+            }
+            Some(c) => text.push(c),
+            None => {}
+        }
+    }
+    text
+}
+
+/// Whether the key of a member of an object, written like a string, is the
+/// given text.
+fn key_matches(key: &str, text: &str) -> bool {
+    if key.contains('\\') {
+        unescape(key) == text
+    } else {
+        key == text
+    }
+}
+
+/// A step of a JSON path, like `$.a[2]`.
+#[derive(Clone, Debug, PartialEq)]
+enum Step {
+    /// `.key` or `."key"`, to a member of an object.
+    Key(String),
+    /// `[N]`, to an element of an array.
+    Index(usize),
+    /// `[#-N]`, to an element of an array counting from its end. `[#]` is
+    /// just past the last element.
+    FromEnd(usize),
+}
+
+impl Step {
+    /// The index of the element of an array of the given length the step
+    /// leads to, which may be past its end.
+    fn index(&self, len: usize) -> Option<usize> {
+        match self {
+            Step::Key(_) => None,
+            Step::Index(i) => Some(*i),
+            Step::FromEnd(n) => len.checked_sub(*n),
+        }
+    }
+}
+
+fn parse_path(path: &str) -> Result<Vec<Step>> {
+    let bad = || anyhow!("bad JSON path: '{path}'");
+    let index = |digits: &str| -> Result<usize> {
+        ensure!(digits.chars().all(|c| c.is_ascii_digit()), bad());
+        digits.parse().map_err(|_| bad())
+    };
+    let mut rest = path.strip_prefix('$').ok_or_else(bad)?;
+    let mut steps = Vec::new();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let key = match after.strip_prefix('"') {
+                Some(quoted) => {
+                    let end = quoted.find('"').ok_or_else(bad)?;
+                    rest = &quoted[end + 1..];
+                    &quoted[..end]
+                }
+                None => {
+                    let end = after.find(['.', '[']).unwrap_or(after.len());
+                    ensure!(end > 0, bad());
+                    rest = &after[end..];
+                    &after[..end]
+                }
+            };
+            steps.push(Step::Key(key.to_string()));
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(bad)?;
+            let inner = &after[..end];
+            rest = &after[end + 1..];
+            steps.push(match inner.strip_prefix('#') {
+                Some("") => Step::FromEnd(0),
+                Some(from_end) => {
+                    let n = from_end.strip_prefix('-').ok_or_else(bad)?;
+                    Step::FromEnd(index(n)?)
+                }
+                None => Step::Index(index(inner)?),
+            });
+        } else {
+            return Err(bad());
+        }
+    }
+    Ok(steps)
+}
+
+/// Parses a path given as an argument, which is NULL if the argument is.
+fn path_argument(value: &Value) -> Result<Option<Vec<Step>>> {
+    match value {
+        Value::Null => Ok(None),
+        value => parse_path(&value.to_string()).map(Some),
+    }
+}
+
+/// Parses JSON given as an argument, which is NULL if the argument is.
+fn json_argument(value: &Value) -> Result<Option<Json>> {
+    let text = match value {
+        Value::Null => return Ok(None),
+        Value::Blob(bytes) => String::from_utf8(bytes.clone()).map_err(|_| malformed())?,
+        value => value.to_string(),
+    };
+    Ok(Some(parse(&text)?.0))
+}
+
+/// Whether the values of an expression are JSON rather than text, which
+/// SQLite keeps track of as the JSON subtype of values. Functions that take
+/// values to add to JSON add such values as they are, and other text as
+/// strings.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Subtype {
+    Text,
+    Json,
+    /// JSON for arrays and objects only, like the results of
+    /// `json_extract()`.
+    Containers,
+}
+
+impl Subtype {
+    /// The subtype of the values of an expression, which is JSON for calls
+    /// of the functions that return JSON and for `->`. Like in SQLite, values
+    /// lose their subtype when they are stored in a column.
+    pub fn of(expr: &Expr) -> Self {
+        match expr {
+            Expr::Collate { expr, .. } => Self::of(expr),
+            Expr::Binary {
+                op: BinaryOp::Extract,
+                ..
+            } => Self::Json,
+            Expr::Function(call) => match call.name.to_ascii_lowercase().as_str() {
+                "json" | "json_array" | "json_object" | "json_set" | "json_insert"
+                | "json_replace" | "json_remove" | "json_quote" | "json_group_array"
+                | "json_group_object" => Self::Json,
+                "json_extract" => Self::Containers,
+                _ => Self::Text,
+            },
+            _ => Self::Text,
+        }
+    }
+
+    /// Converts a value of this subtype to JSON. Blobs can't be.
+    pub fn to_json(self, value: &Value) -> Result<Json> {
+        let is_json = match (self, value) {
+            (Subtype::Json, _) => true,
+            (Subtype::Containers, Value::Text(text)) => {
+                text.starts_with(['[', '{']) && parse(text).is_ok()
+            }
+            _ => false,
+        };
+        Ok(match value {
+            Value::Null => Json::Null,
+            Value::Integer(i) => Json::Number(i.to_string()),
+            Value::Real(r) if r.is_nan() => Json::Null,
+            Value::Real(r) if r.is_infinite() => {
+                let sign = if *r < 0.0 { "-" } else { "" };
+                Json::Number(format!("{sign}9.0e+999"))
+            }
+            Value::Real(r) if *r == 0.0 => Json::Number("0.0".to_string()),
+            Value::Real(r) => Json::Number(printf::real("%!.15g", *r)),
+            Value::Text(text) if is_json => parse(text)?.0,
+            Value::Text(text) => Json::String(escape(text)),
+            Value::Blob(_) => bail!("JSON cannot hold BLOB values"),
+        })
+    }
+}
+
+/// How `json_set()`, `json_insert()` and `json_replace()` change JSON.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Edit {
+    /// Sets the value, adding it if it is missing.
+    Set,
+    /// Only adds values that are missing.
+    Insert,
+    /// Only replaces values that are there.
+    Replace,
+}
+
+/// Evaluates a call of one of the scalar JSON functions, given the
+/// expressions of its arguments and their values.
+pub fn call(name: &str, exprs: &[Expr], args: &[Value]) -> Result<Value> {
+    let argument = |i: usize| Subtype::of(&exprs[i]).to_json(&args[i]);
+    let text = |json: Json| Value::Text(json.to_string());
+
+    Ok(match name {
+        "json" => json_argument(&args[0])?.map_or(Value::Null, text),
+        "json_valid" => {
+            let flags = args.get(1).map_or(1, Value::to_integer);
+            ensure!(
+                (1..=15).contains(&flags),
+                "FLAGS parameter to json_valid() must be between 1 and 15"
+            );
+            let valid = match &args[0] {
+                Value::Null => return Ok(Value::Null),
+                Value::Blob(bytes) => std::str::from_utf8(bytes).ok().map(parse),
+                value => Some(parse(&value.to_string())),
+            };
+            // Flag 1 accepts plain JSON, and 2 also accepts JSON5.
+            let valid = match valid {
+                Some(Ok((_, json5))) => flags & 2 != 0 || (flags & 1 != 0 && !json5),
+                _ => false,
+            };
+            Value::Integer(valid as i64)
+        }
+        "json_extract" => {
+            let Some(json) = json_argument(&args[0])? else {
+                return Ok(Value::Null);
+            };
+            match &args[1..] {
+                [] => Value::Null,
+                [path] => match path_argument(path)? {
+                    Some(steps) => json.lookup(&steps).map_or(Value::Null, Json::to_value),
+                    None => Value::Null,
+                },
+                // With more than one path, the values are returned in an array.
+                paths => {
+                    let mut values = Vec::new();
+                    for path in paths {
+                        let Some(steps) = path_argument(path)? else {
+                            return Ok(Value::Null);
+                        };
+                        values.push(json.lookup(&steps).cloned().unwrap_or(Json::Null));
+                    }
+                    text(Json::Array(values))
+                }
+            }
+        }
+        "json_type" | "json_array_length" => {
+            let Some(json) = json_argument(&args[0])? else {
+                return Ok(Value::Null);
+            };
+            let steps = match args.get(1).map(path_argument).transpose()? {
+                Some(Some(steps)) => steps,
+                Some(None) => return Ok(Value::Null),
+                None => Vec::new(),
+            };
+            match (json.lookup(&steps), name) {
+                (None, _) => Value::Null,
+                (Some(json), "json_type") => Value::Text(json.type_name().to_string()),
+                (Some(Json::Array(items)), _) => Value::Integer(items.len() as i64),
+                (Some(_), _) => Value::Integer(0),
+            }
+        }
+        "json_array" => {
+            let items = (0..args.len()).map(argument).collect::<Result<_>>()?;
+            text(Json::Array(items))
+        }
+        "json_object" => {
+            ensure!(
+                args.len() % 2 == 0,
+                "json_object() requires an even number of arguments"
+            );
+            let mut members = Vec::new();
+            for i in (0..args.len()).step_by(2) {
+                let Value::Text(key) = &args[i] else {
+                    bail!("json_object() labels must be TEXT");
+                };
+                members.push((escape(key), argument(i + 1)?));
+            }
+            text(Json::Object(members))
+        }
+        "json_set" | "json_insert" | "json_replace" => {
+            ensure!(
+                args.len() % 2 == 1,
+                "{name}() needs an odd number of arguments"
+            );
+            let edit = match name {
+                "json_set" => Edit::Set,
+                "json_insert" => Edit::Insert,
+                _ => Edit::Replace,
+            };
+            let Some(mut json) = json_argument(&args[0])? else {
+                return Ok(Value::Null);
+            };
+            for i in (1..args.len()).step_by(2) {
+                if let Some(steps) = path_argument(&args[i])? {
+                    json.edit(&steps, argument(i + 1)?, edit);
+                }
+            }
+            text(json)
+        }
+        "json_remove" => {
+            let Some(mut json) = json_argument(&args[0])? else {
+                return Ok(Value::Null);
+            };
+            for path in &args[1..] {
+                match path_argument(path)? {
+                    Some(steps) if !steps.is_empty() => json.remove(&steps),
+                    // Removing the whole value leaves nothing.
+                    _ => return Ok(Value::Null),
+                }
+            }
+            text(json)
+        }
+        "json_quote" => text(argument(0)?),
+        _ => unreachable!("{name}() is a JSON function"),
+    })
+}
+
+/// Evaluates `json -> path`, which returns the value the path leads to as
+/// JSON, or `json ->> path`, which returns it as an SQL value. Rather than a
+/// path, the operand on the right may be the key of a member of an object,
+/// or the index of an element of an array.
+pub fn extract(op: BinaryOp, json: &Value, path: &Value) -> Result<Value> {
+    if json.is_null() || path.is_null() {
+        return Ok(Value::Null);
+    }
+    let path = match path {
+        Value::Integer(i) if *i < 0 => format!("$[#{i}]"),
+        Value::Integer(i) => format!("$[{i}]"),
+        path => {
+            let path = path.to_string();
+            if path.starts_with('$') {
+                path
+            } else if path.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                format!("$.{path}")
+            } else if path.len() >= 3 && path.starts_with('[') && path.ends_with(']') {
+                format!("${path}")
+            } else {
+                format!("$.\"{path}\"")
+            }
+        }
+    };
+    let json = json_argument(json)?.expect("NULL is handled above");
+    let Some(value) = json.lookup(&parse_path(&path)?) else {
+        return Ok(Value::Null);
+    };
+    Ok(match op {
+        BinaryOp::Extract => Value::Text(value.to_string()),
+        _ => value.to_value(),
+    })
+}
+
+/// A value in JSON that `json_each()` or `json_tree()` returns a row for.
+struct Element<'a> {
+    json: &'a Json,
+    /// Where the value starts in JSONB.
+    offset: usize,
+    /// The `id` of the row, which is where the key starts for members of
+    /// objects, and otherwise where the value starts.
+    id: usize,
+    /// The key of the member or the index of the element, or NULL for the
+    /// value the path leads to.
+    key: Value,
+    /// The path to the value.
+    fullkey: String,
+    /// The path to the array or object the value is in.
+    path: String,
+}
+
+impl<'a> Element<'a> {
+    /// The elements of an array or the members of an object.
+    fn children(&self) -> Vec<Element<'a>> {
+        let mut offset = self.offset + header_size(self.json.payload_size());
+        let mut children = Vec::new();
+        match self.json {
+            Json::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    children.push(Element {
+                        json: item,
+                        offset,
+                        id: offset,
+                        key: Value::Integer(i as i64),
+                        fullkey: format!("{}[{i}]", self.fullkey),
+                        path: self.fullkey.clone(),
+                    });
+                    offset += item.size();
+                }
+            }
+            Json::Object(members) => {
+                for (key, value) in members {
+                    let key_size = header_size(key.len()) + key.len();
+                    // Keys that aren't plain words are quoted in paths, with
+                    // their escapes.
+                    let mut chars = key.chars();
+                    let plain = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+                        && chars.all(|c| c.is_ascii_alphanumeric());
+                    let fullkey = if plain {
+                        format!("{}.{key}", self.fullkey)
+                    } else {
+                        format!("{}.\"{key}\"", self.fullkey)
+                    };
+                    children.push(Element {
+                        json: value,
+                        offset: offset + key_size,
+                        id: offset,
+                        key: Value::Text(unescape(key)),
+                        fullkey,
+                        path: self.fullkey.clone(),
+                    });
+                    offset += key_size + value.size();
+                }
+            }
+            _ => {}
+        }
+        children
+    }
+
+    /// The row for the element, in the order of [`TABLE_COLUMNS`].
+    fn row(&self, key: Value, parent: Option<usize>, path: &str, args: &[Value]) -> Vec<Value> {
+        let value = self.json.to_value();
+        let atom = match self.json {
+            Json::Array(_) | Json::Object(_) => Value::Null,
+            _ => value.clone(),
+        };
+        vec![
+            key,
+            value,
+            Value::Text(self.json.type_name().to_string()),
+            atom,
+            Value::Integer(self.id as i64),
+            parent.map_or(Value::Null, |parent| Value::Integer(parent as i64)),
+            Value::Text(self.fullkey.clone()),
+            Value::Text(path.to_string()),
+            args[0].clone(),
+            args.get(1)
+                .map_or(Value::Text("$".to_string()), Value::clone),
+        ]
+    }
+
+    /// Adds the rows of `json_tree()` for the element and everything in it.
+    fn tree_rows(&self, parent: Option<usize>, args: &[Value], rows: &mut Vec<Vec<Value>>) {
+        rows.push(self.row(self.key.clone(), parent, &self.path, args));
+        for child in self.children() {
+            child.tree_rows(Some(self.id), args, rows);
+        }
+    }
+}
+
+/// The rows of the table-valued function `json_each(json[, path])`, which
+/// has a row for each element or member of the array or object the path
+/// leads to, or `json_tree()`, which has rows for everything in it.
+pub fn table_rows(tree: bool, args: &[Value]) -> Result<Vec<Vec<Value>>> {
+    let Some(json) = args.first().map(json_argument).transpose()?.flatten() else {
+        return Ok(Vec::new());
+    };
+    let steps = match args.get(1).map(path_argument).transpose()? {
+        Some(Some(steps)) => steps,
+        Some(None) => return Ok(Vec::new()),
+        None => Vec::new(),
+    };
+
+    let mut element = Element {
+        json: &json,
+        offset: 0,
+        id: 0,
+        key: Value::Null,
+        fullkey: "$".to_string(),
+        path: "$".to_string(),
+    };
+    for step in &steps {
+        let mut children = element.children();
+        let found = match (element.json, step) {
+            (Json::Object(_), Step::Key(key)) => children
+                .iter()
+                .position(|child| matches!(&child.key, Value::Text(k) if k == key)),
+            (Json::Array(_), step) => step.index(children.len()),
+            _ => None,
+        };
+        match found.filter(|&i| i < children.len()) {
+            Some(i) => element = children.swap_remove(i),
+            None => return Ok(Vec::new()),
+        }
+    }
+
+    let mut rows = Vec::new();
+    if tree {
+        element.tree_rows(None, args, &mut rows);
+    } else if matches!(element.json, Json::Array(_) | Json::Object(_)) {
+        for child in element.children() {
+            rows.push(child.row(child.key.clone(), None, &child.path, args));
+        }
+    } else {
+        rows.push(element.row(Value::Null, None, &element.fullkey, args));
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::tests::{eval_constant, text};
+    use crate::json::*;
+
+    #[test]
+    fn minifies_json_and_json5() {
+        let cases = [
+            (
+                r#" [1, 2.50 , -3e2, "xA\n", {"a" : null}] "#,
+                r#"[1,2.50,-3e2,"xA\n",{"a":null}]"#,
+            ),
+            (
+                "{a:1, 'b':[0x1F,.5,5.,+3,Infinity,NaN,], /* c */ \"t\":\"\t\"}",
+                r#"{"a":1,"b":[31,0.5,5.0,3,9e999,null],"t":"\t"}"#,
+            ),
+        ];
+        for (json, expected) in cases {
+            assert_eq!(parse(json).unwrap().0.to_string(), expected, "{json}");
+        }
+
+        assert!(!parse("[1, 2]").unwrap().1);
+        assert!(parse("[1, 2,]").unwrap().1);
+        for json in ["01", "-01", "1.", ".1", "1e", "[1", "{\"a\"}", "\"a", ""] {
+            let plain = parse(json).map(|(_, json5)| !json5);
+            assert!(!plain.unwrap_or(false), "{json}");
+        }
+        let nested = format!("{}{}", "[".repeat(1001), "]".repeat(1001));
+        assert!(parse(&nested).is_err());
+    }
+
+    #[test]
+    fn extracts_values_by_path() {
+        let json = r#"'{"a":[1,2.5,{"b":true}],"c d":"x","":null}'"#;
+        let cases = [
+            ("'$.a[2].b'", Value::Integer(1)),
+            ("'$.a[#-2]'", Value::Real(2.5)),
+            ("'$.\"c d\"'", text("x")),
+            ("'$.a'", text(r#"[1,2.5,{"b":true}]"#)),
+            ("'$.a[3]'", Value::Null),
+            ("'$.a[0]', '$.\"\"', '$.z'", text("[1,null,null]")),
+        ];
+        for (paths, expected) in cases {
+            let sql = format!("json_extract({json}, {paths})");
+            assert_eq!(eval_constant(&sql).unwrap(), expected, "{sql}");
+        }
+
+        let json = r#"'{"a":{"b":[5,"s"]},"k-1":2}'"#;
+        let cases = [
+            ("-> 'a'", text(r#"{"b":[5,"s"]}"#)),
+            ("-> '$.a.b[1]'", text(r#""s""#)),
+            ("->> '$.a.b[1]'", text("s")),
+            ("->> 'k-1'", Value::Integer(2)),
+            ("-> 'a' -> 'b' ->> -1", text("s")),
+            ("-> 'z'", Value::Null),
+        ];
+        for (op, expected) in cases {
+            let sql = format!("{json} {op}");
+            assert_eq!(eval_constant(&sql).unwrap(), expected, "{sql}");
+        }
+
+        for path in ["'a'", "'$['", "'$[-1]'", "'$.'"] {
+            let sql = format!("json_extract('[1]', {path})");
+            let expected = format!("bad JSON path: {path}");
+            assert_eq!(eval_constant(&sql).unwrap_err().to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn builds_and_edits_json() {
+        let cases = [
+            (
+                "json_array(1, 1.0, 'a', NULL, json_array(2), '[3]')",
+                r#"[1,1.0,"a",null,[2],"[3]"]"#,
+            ),
+            (
+                r#"json_object('a', 1, 'b"', json('{"c":[]}'))"#,
+                r#"{"a":1,"b\"":{"c":[]}}"#,
+            ),
+            (
+                r#"json_set('{"a":1,"b":[1]}', '$.a', 9, '$.c.d', 4, '$.b[#]', 2, '$.e[3]', 0)"#,
+                r#"{"a":9,"b":[1,2],"c":{"d":4}}"#,
+            ),
+            (
+                r#"json_insert('{"a":1}', '$.a', 9, '$.b', 2)"#,
+                r#"{"a":1,"b":2}"#,
+            ),
+            (
+                r#"json_replace('{"a":1}', '$.a', 9, '$.b', 2)"#,
+                r#"{"a":9}"#,
+            ),
+            (r#"json_remove('[1,2,3]', '$[1]', '$[0]')"#, "[3]"),
+            (r#"json_quote('a"b')"#, r#""a\"b""#),
+        ];
+        for (sql, expected) in cases {
+            assert_eq!(eval_constant(sql).unwrap(), text(expected), "{sql}");
+        }
+
+        let errors = [
+            ("json('[1')", "malformed JSON"),
+            (
+                "json_object('a')",
+                "json_object() requires an even number of arguments",
+            ),
+            ("json_object(1, 2)", "json_object() labels must be TEXT"),
+            (
+                "json_set('{}', '$.a')",
+                "json_set() needs an odd number of arguments",
+            ),
+            ("json_array(x'00')", "JSON cannot hold BLOB values"),
+        ];
+        for (sql, expected) in errors {
+            assert_eq!(
+                eval_constant(sql).unwrap_err().to_string(),
+                expected,
+                "{sql}"
+            );
+        }
+    }
+
+    #[test]
+    fn walks_json_like_json_each_and_json_tree() {
+        let json = text(r#"{"a":1,"b c":[2,{"d":null}]}"#);
+        let rows = |tree, args: &[Value]| {
+            let rows = table_rows(tree, args).unwrap().into_iter().map(|row| {
+                let values = row[..8].iter().map(|value| value.to_string());
+                values.collect::<Vec<_>>().join("|")
+            });
+            rows.collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            rows(false, &[json.clone()]),
+            [
+                "a|1|integer|1|2||$.a|$",
+                r#"b c|[2,{"d":null}]|array||6||$."b c"|$"#,
+            ]
+        );
+        assert_eq!(
+            rows(true, &[json.clone(), text("$.\"b c\"")]),
+            [
+                r#"b c|[2,{"d":null}]|array||6||$."b c"|$"#,
+                "0|2|integer|2|11|6|$.\"b c\"[0]|$.\"b c\"",
+                r#"1|{"d":null}|object||13|6|$."b c"[1]|$."b c""#,
+                "d||null||14|13|$.\"b c\"[1].d|$.\"b c\"[1]",
+            ]
+        );
+        assert_eq!(rows(false, &[text("5")]), ["|5|integer|5|0||$|$"]);
+        assert!(rows(false, &[json.clone(), text("$.z")]).is_empty());
+        assert!(rows(false, &[Value::Null]).is_empty());
+    }
+}
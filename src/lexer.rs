@@ -0,0 +1,457 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+/// A token of an SQL statement.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    /// A keyword or an unquoted identifier. Which one it is depends on
+    /// where it appears, so that is left to the parser.
+    Word(String),
+    /// An identifier in `"double quotes"`, `[brackets]` or `` `backticks` ``.
+    QuotedIdentifier(String),
+    /// A string literal in 'single quotes'.
+    String(String),
+    /// A blob literal like `x'CAFE'`.
+    Blob(Vec<u8>),
+    /// A parameter like `?`, `?3`, `:name`, `@name` or `$name`, whose value
+    /// is bound when the statement runs.
+    Variable(String),
+    Integer(i64),
+    Real(f64),
+    /// The literal 9223372036854775808, which is too large for 64 bits and
+    /// so is a real, unless it is negated, which gives the smallest 64-bit
+    /// integer.
+    MinIntegerMagnitude,
+    LeftParen,
+    RightParen,
+    Comma,
+    Semicolon,
+    Dot,
+    Star,
+    Plus,
+    Minus,
+    Slash,
+    Percent,
+    /// `||`
+    Concat,
+    /// `=` or `==`
+    Eq,
+    /// `!=` or `<>`
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    /// `&`
+    BitAnd,
+    /// `|`
+    BitOr,
+    /// `~`
+    Tilde,
+    /// `<<`
+    ShiftLeft,
+    /// `>>`
+    ShiftRight,
+    /// `->`
+    Arrow,
+    /// `->>`
+    DoubleArrow,
+}
+
+impl Token {
+    /// Checks whether the token is the given keyword. Keywords are case-insensitive.
+    pub fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self, Token::Word(w) if w.eq_ignore_ascii_case(keyword))
+    }
+}
+
+impl fmt::Display for Token {
+    /// Formats the token like it would appear in SQL, for error messages.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(w) => write!(f, "{w}"),
+            Token::QuotedIdentifier(i) => write!(f, "\"{}\"", i.replace('"', "\"\"")),
+            Token::String(s) => write!(f, "'{}'", s.replace('\'', "''")),
+            Token::Blob(b) => {
+                write!(f, "x'")?;
+                for byte in b {
+                    write!(f, "{byte:02X}")?;
+                }
+                write!(f, "'")
+            }
+            Token::Variable(v) => write!(f, "{v}"),
+            Token::Integer(i) => write!(f, "{i}"),
+            Token::Real(r) => write!(f, "{r}"),
+            Token::MinIntegerMagnitude => write!(f, "9223372036854775808"),
+            Token::LeftParen => write!(f, "("),
+            Token::RightParen => write!(f, ")"),
+            Token::Comma => write!(f, ","),
+            Token::Semicolon => write!(f, ";"),
+            Token::Dot => write!(f, "."),
+            Token::Star => write!(f, "*"),
+            Token::Plus => write!(f, "+"),
+            Token::Minus => write!(f, "-"),
+            Token::Slash => write!(f, "/"),
+            Token::Percent => write!(f, "%"),
+            Token::Concat => write!(f, "||"),
+            Token::Eq => write!(f, "="),
+            Token::NotEq => write!(f, "!="),
+            Token::Lt => write!(f, "<"),
+            Token::LtEq => write!(f, "<="),
+            Token::Gt => write!(f, ">"),
+            Token::GtEq => write!(f, ">="),
+            Token::BitAnd => write!(f, "&"),
+            Token::BitOr => write!(f, "|"),
+            Token::Tilde => write!(f, "~"),
+            Token::ShiftLeft => write!(f, "<<"),
+            Token::ShiftRight => write!(f, ">>"),
+            Token::Arrow => write!(f, "->"),
+            Token::DoubleArrow => write!(f, "->>"),
+        }
+    }
+}
+
+/// Splits SQL text into tokens, skipping whitespace and comments.
+pub fn tokenize(sql: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let c = chars[pos];
+        let next = chars.get(pos + 1).copied();
+        let token = match c {
+            c if c.is_whitespace() => {
+                pos += 1;
+                continue;
+            }
+            '-' if next == Some('-') => {
+                // A comment until the end of the line.
+                while pos < chars.len() && chars[pos] != '\n' {
+                    pos += 1;
+                }
+                continue;
+            }
+            '/' if next == Some('*') => {
+                // A comment until the next "*/", or the end of the input.
+                pos += 2;
+                while pos < chars.len() && !(chars[pos] == '*' && chars.get(pos + 1) == Some(&'/'))
+                {
+                    pos += 1;
+                }
+                pos += 2;
+                continue;
+            }
+            '\'' => {
+                let (s, read) = quoted(&chars[pos..], '\'')?;
+                pos += read;
+                Token::String(s)
+            }
+            '"' | '`' => {
+                let (s, read) = quoted(&chars[pos..], c)?;
+                pos += read;
+                Token::QuotedIdentifier(s)
+            }
+            '[' => {
+                let Some(len) = chars[pos..].iter().position(|&c| c == ']') else {
+                    bail!(
+                        "unrecognized token: \"{}\"",
+                        chars[pos..].iter().collect::<String>()
+                    );
+                };
+                let ident = chars[pos + 1..pos + len].iter().collect();
+                pos += len + 1;
+                Token::QuotedIdentifier(ident)
+            }
+            'x' | 'X' if next == Some('\'') => {
+                let (hex, read) = quoted(&chars[pos + 1..], '\'')?;
+                pos += read + 1;
+                Token::Blob(parse_blob(&hex)?)
+            }
+            c if c.is_ascii_digit() || (c == '.' && next.is_some_and(|n| n.is_ascii_digit())) => {
+                let (number, read) = number(&chars[pos..])?;
+                pos += read;
+                number
+            }
+            '?' | ':' | '@' | '$' => {
+                // A `?` may be followed by a number, the others by a name.
+                let len = chars[pos + 1..]
+                    .iter()
+                    .position(|&n| match c {
+                        '?' => !n.is_ascii_digit(),
+                        _ => !is_identifier_char(n),
+                    })
+                    .unwrap_or(chars.len() - pos - 1);
+                if c != '?' && len == 0 {
+                    bail!("unrecognized token: \"{c}\"");
+                }
+                let variable = chars[pos..pos + 1 + len].iter().collect();
+                pos += 1 + len;
+                Token::Variable(variable)
+            }
+            c if is_identifier_start(c) => {
+                let len = chars[pos..]
+                    .iter()
+                    .position(|&c| !is_identifier_char(c))
+                    .unwrap_or(chars.len() - pos);
+                let word = chars[pos..pos + len].iter().collect();
+                pos += len;
+                Token::Word(word)
+            }
+            _ => {
+                let (token, len) = match (c, next) {
+                    ('|', Some('|')) => (Token::Concat, 2),
+                    ('=', Some('=')) => (Token::Eq, 2),
+                    ('!', Some('=')) => (Token::NotEq, 2),
+                    ('<', Some('>')) => (Token::NotEq, 2),
+                    ('<', Some('=')) => (Token::LtEq, 2),
+                    ('>', Some('=')) => (Token::GtEq, 2),
+                    ('<', Some('<')) => (Token::ShiftLeft, 2),
+                    ('>', Some('>')) => (Token::ShiftRight, 2),
+                    ('-', Some('>')) if chars.get(pos + 2) == Some(&'>') => (Token::DoubleArrow, 3),
+                    ('-', Some('>')) => (Token::Arrow, 2),
+                    ('(', _) => (Token::LeftParen, 1),
+                    (')', _) => (Token::RightParen, 1),
+                    (',', _) => (Token::Comma, 1),
+                    (';', _) => (Token::Semicolon, 1),
+                    ('.', _) => (Token::Dot, 1),
+                    ('*', _) => (Token::Star, 1),
+                    ('+', _) => (Token::Plus, 1),
+                    ('-', _) => (Token::Minus, 1),
+                    ('/', _) => (Token::Slash, 1),
+                    ('%', _) => (Token::Percent, 1),
+                    ('=', _) => (Token::Eq, 1),
+                    ('<', _) => (Token::Lt, 1),
+                    ('>', _) => (Token::Gt, 1),
+                    ('&', _) => (Token::BitAnd, 1),
+                    ('|', _) => (Token::BitOr, 1),
+                    ('~', _) => (Token::Tilde, 1),
+                    _ => bail!("unrecognized token: \"{c}\""),
+                };
+                pos += len;
+                token
+            }
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || !c.is_ascii()
+}
+
+fn is_identifier_char(c: char) -> bool {
+    is_identifier_start(c) || c.is_ascii_digit() || c == '$'
+}
+
+/// Reads text enclosed in `quote` characters, where a doubled quote stands
+/// for the quote character itself. Returns the text and the number of
+/// characters read, including the quotes.
+fn quoted(chars: &[char], quote: char) -> Result<(String, usize)> {
+    let mut text = String::new();
+    let mut pos = 1;
+    loop {
+        match chars.get(pos) {
+            Some(&c) if c == quote => {
+                if chars.get(pos + 1) == Some(&quote) {
+                    text.push(quote);
+                    pos += 2;
+                } else {
+                    return Ok((text, pos + 1));
+                }
+            }
+            Some(&c) => {
+                text.push(c);
+                pos += 1;
+            }
+            None => bail!(
+                "unrecognized token: \"{}\"",
+                chars.iter().collect::<String>()
+            ),
+        }
+    }
+}
+
+fn parse_blob(hex: &str) -> Result<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("unrecognized token: \"x'{hex}'\"");
+    }
+    Ok((0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).expect("Checked for hex digits"))
+        .collect())
+}
+
+/// Reads a numeric literal. Integers too large for 64 bits become reals,
+/// except for hexadecimal literals, which must fit, and for the magnitude of
+/// the smallest 64-bit integer, which the parser decides about.
+fn number(chars: &[char]) -> Result<(Token, usize)> {
+    let word_len = chars
+        .iter()
+        .position(|&c| !is_identifier_char(c) && c != '.')
+        .unwrap_or(chars.len());
+
+    if chars.len() > 2 && chars[0] == '0' && (chars[1] == 'x' || chars[1] == 'X') {
+        let hex: String = chars[2..word_len].iter().collect();
+        return match u64::from_str_radix(&hex, 16) {
+            // Hexadecimal literals are interpreted as two's complement.
+            Ok(i) if hex.len() <= 16 => Ok((Token::Integer(i as i64), word_len)),
+            _ => bail!("hex literal too big: 0x{hex}"),
+        };
+    }
+
+    let mut pos = 0;
+    let mut is_real = false;
+    while pos < chars.len() && chars[pos].is_ascii_digit() {
+        pos += 1;
+    }
+    if chars.get(pos) == Some(&'.') {
+        is_real = true;
+        pos += 1;
+        while pos < chars.len() && chars[pos].is_ascii_digit() {
+            pos += 1;
+        }
+    }
+    if matches!(chars.get(pos), Some('e' | 'E')) {
+        is_real = true;
+        pos += 1;
+        if matches!(chars.get(pos), Some('+' | '-')) {
+            pos += 1;
+        }
+        let digits_start = pos;
+        while pos < chars.len() && chars[pos].is_ascii_digit() {
+            pos += 1;
+        }
+        if pos == digits_start {
+            bail!(
+                "unrecognized token: \"{}\"",
+                chars[..pos].iter().collect::<String>()
+            );
+        }
+    }
+    if chars.get(pos).is_some_and(|&c| is_identifier_char(c)) {
+        bail!(
+            "unrecognized token: \"{}\"",
+            chars[..word_len].iter().collect::<String>()
+        );
+    }
+
+    let text: String = chars[..pos].iter().collect();
+    let token = match text.parse::<i64>() {
+        Ok(i) if !is_real => Token::Integer(i),
+        _ if !is_real && text.trim_start_matches('0') == "9223372036854775808" => {
+            Token::MinIntegerMagnitude
+        }
+        _ => Token::Real(text.parse()?),
+    };
+
+    Ok((token, pos))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lexer::*;
+
+    #[test]
+    fn tokenizes_literals_and_identifiers() {
+        let tokens = tokenize(
+            "select \"my \"\"col\"\"\", [a b], `c`, 'it''s', X'cafe', 42, 1.5e3, .5, 0x10",
+        )
+        .unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Word("select".to_string()),
+                Token::QuotedIdentifier("my \"col\"".to_string()),
+                Token::Comma,
+                Token::QuotedIdentifier("a b".to_string()),
+                Token::Comma,
+                Token::QuotedIdentifier("c".to_string()),
+                Token::Comma,
+                Token::String("it's".to_string()),
+                Token::Comma,
+                Token::Blob(vec![0xca, 0xfe]),
+                Token::Comma,
+                Token::Integer(42),
+                Token::Comma,
+                Token::Real(1500.0),
+                Token::Comma,
+                Token::Real(0.5),
+                Token::Comma,
+                Token::Integer(16),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_comments() {
+        let tokens = tokenize("a -- comment\n/* another\n comment */ b /* unterminated").unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![Token::Word("a".to_string()), Token::Word("b".to_string())]
+        );
+    }
+
+    #[test]
+    fn tokenizes_operators() {
+        let tokens = tokenize("a||b<>c!=d<=e>=f==g<<h>>i->j->>k").unwrap();
+        let operators = tokens
+            .into_iter()
+            .filter(|t| !matches!(t, Token::Word(_)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            operators,
+            vec![
+                Token::Concat,
+                Token::NotEq,
+                Token::NotEq,
+                Token::LtEq,
+                Token::GtEq,
+                Token::Eq,
+                Token::ShiftLeft,
+                Token::ShiftRight,
+                Token::Arrow,
+                Token::DoubleArrow,
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenizes_variables() {
+        let tokens = tokenize("? ?12 :id @name $x1 ?a").unwrap();
+
+        assert_eq!(
+            tokens,
+            ["?", "?12", ":id", "@name", "$x1", "?"]
+                .map(|v| Token::Variable(v.to_string()))
+                .into_iter()
+                .chain([Token::Word("a".to_string())])
+                .collect::<Vec<_>>()
+        );
+        assert!(tokenize("SELECT :").is_err());
+    }
+
+    #[test]
+    fn large_integers_become_reals() {
+        assert_eq!(
+            tokenize("9223372036854775809 9223372036854775808.0").unwrap(),
+            vec![Token::Real(9223372036854775808.0); 2]
+        );
+        assert_eq!(
+            tokenize("9223372036854775808").unwrap(),
+            vec![Token::MinIntegerMagnitude]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_tokens() {
+        for sql in ["'unterminated", "x'abc'", "12abc", "1e", "0x", "#"] {
+            assert!(tokenize(sql).is_err(), "{sql}");
+        }
+    }
+}
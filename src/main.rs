@@ -1,15 +1,54 @@
 use anyhow::{bail, Result};
-use std::fs::File;
-use std::io::prelude::*;
-use std::os::unix::fs::FileExt;
-use std::path::Path;
 
-use btree_page::*;
+use ast::*;
+use collation::Collation;
+use database::Database;
+use schema::SchemaType;
+use statement::{Bindings, PreparedStatement};
+use value::Value;
 
+mod aggregate;
+mod ast;
 mod btree_page;
+mod collation;
+mod cte;
+mod database;
+mod datetime;
+mod eval;
+mod function;
+mod index;
+mod join;
+mod json;
+mod lexer;
+mod parser;
+mod pattern;
+mod pragma;
+mod printf;
+mod query;
+mod schema;
+mod sort;
+mod statement;
+mod table;
+mod value;
 mod varint;
+mod view;
+mod window;
+
+/// The size of the stack commands run on. Nested expressions are parsed and
+/// evaluated by recursion, which needs more stack than the main thread has
+/// for the most deeply nested expressions allowed.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
 
 fn main() -> Result<()> {
+    let thread = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run)?;
+    thread
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+fn run() -> Result<()> {
     // Parse arguments
     let args = std::env::args().collect::<Vec<_>>();
     match args.len() {
@@ -18,29 +57,109 @@ fn main() -> Result<()> {
         _ => {}
     }
 
-    // Parse command and act accordingly
-    let command = &args[2];
-    match command.as_str() {
-        ".dbinfo" => {
-            let db_info = dot_dbinfo(&args[1])?;
+    let mut db = Database::open(&args[1])?;
+
+    // Like SQLite's shell, run each command in turn, so that parameters set
+    // by `.param` apply to the statements after it.
+    let mut bindings = Bindings::default();
+    for command in &args[2..] {
+        if command.starts_with('.') {
+            dot_command(command, &mut db, &mut bindings)?;
+            continue;
+        }
+        for statement in PreparedStatement::prepare_all(command)? {
+            let values = bindings.values_for(&statement);
+            for row in statement.execute(&mut db, &values)? {
+                let row = row.iter().map(Value::to_string).collect::<Vec<_>>();
+                println!("{}", row.join("|"));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn dot_command(command: &str, db: &mut Database, bindings: &mut Bindings) -> Result<()> {
+    let words = dot_arguments(command);
+    let words = words.iter().map(String::as_str).collect::<Vec<_>>();
+    match words[..] {
+        [".dbinfo"] => {
+            let db_info = dot_dbinfo(db);
             println!("database page size: {}", db_info.db_page_size);
             println!("number of tables: {}", db_info.num_tables);
         }
-        ".tables" => {
-            let tables = dot_tables(&args[1])?;
-            println!("{tables}");
+        [".tables", ref pattern @ ..] if pattern.len() <= 1 => {
+            let tables = dot_tables(db, pattern.first().copied())?;
+            if !tables.is_empty() {
+                println!("{tables}");
+            }
         }
-        n if n.starts_with("SELECT COUNT(*) FROM ") => {
-            let (_, table) = n.rsplit_once(' ').expect("Pattern matched whitespace");
-            let rows = count_rows(table, &args[1])?;
-            println!("{rows}");
+        [".tables", ..] => bail!("Usage: .tables ?LIKE-PATTERN?"),
+        [".schema", ref pattern @ ..] if pattern.len() <= 1 => {
+            for sql in dot_schema(db, pattern.first().copied())? {
+                println!("{sql};");
+            }
         }
+        [".schema", ..] => bail!("Usage: .schema ?LIKE-PATTERN?"),
+        [".param" | ".parameter", ref args @ ..] => match args {
+            ["set", name, value] => {
+                let value = parameter_value(db, value);
+                bindings.set(name, value);
+            }
+            ["unset", name] => bindings.unset(name),
+            ["clear"] => bindings.clear(),
+            ["list"] => {
+                let width = bindings.iter().map(|(name, _)| name.len()).max();
+                for (name, value) in bindings.iter() {
+                    let width = width.unwrap_or(0);
+                    println!("{name:width$} {}", function::quote(value));
+                }
+            }
+            // The bindings aren't kept in a table, so there is none to create.
+            ["init"] => {}
+            _ => bail!("Usage: .parameter clear|init|list|set PARAMETER VALUE|unset PARAMETER"),
+        },
         _ => bail!("Missing or invalid command passed: {}", command),
     }
-
     Ok(())
 }
 
+/// Splits a dot command into its arguments, which are separated by spaces
+/// unless they are enclosed in single or double quotes.
+fn dot_arguments(command: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut chars = command.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(first) = chars.next() else {
+            return arguments;
+        };
+        let argument = match first {
+            '\'' | '"' => chars.by_ref().take_while(|&c| c != first).collect(),
+            _ => std::iter::once(first)
+                .chain(std::iter::from_fn(|| chars.next_if(|c| !c.is_whitespace())))
+                .collect(),
+        };
+        arguments.push(argument);
+    }
+}
+
+/// The value of `.param set`, which is the value of an SQL expression, or
+/// else the text itself.
+fn parameter_value(db: &Database, text: &str) -> Value {
+    expression_value(db, text).unwrap_or_else(|_| Value::Text(text.to_string()))
+}
+
+fn expression_value(db: &Database, text: &str) -> Result<Value> {
+    let [Statement::Select(select)] = &parser::parse(&format!("SELECT {text}"))?[..] else {
+        bail!("Expected a single expression");
+    };
+    match &query::execute(select, db, &[])?.rows[..] {
+        [row] if row.len() == 1 => Ok(row[0].clone()),
+        _ => bail!("Expected a single value"),
+    }
+}
+
 struct DbInfo {
     /// Database page size in bytes.
     /// Must be a power of two between 512 and 32768 inclusive, or 65536.
@@ -51,290 +170,69 @@ struct DbInfo {
     num_tables: u16,
 }
 
-fn dot_dbinfo(db_file: impl AsRef<Path>) -> Result<DbInfo> {
-    let mut file = File::open(db_file)?;
-    let mut header = [0; 100];
-    file.read_exact(&mut header)?;
-
-    let header = DbHeader::parse(&header)?;
-
-    let mut page = vec![0; header.page_size as usize];
-    file.read_exact_at(&mut page, 0)?;
-
-    let mut num_tables = 0;
-    let mut remaining_pages = Vec::new();
-
-    let mut btree = BTreePage::parse(&page[100..], true)?;
-
-    loop {
-        match btree.header.page_type {
-            BTreePageType::InteriorIndex => todo!(),
-            BTreePageType::InteriorTable => {
-                for cell in btree.cells {
-                    let Cell::TableInterior { left_child, .. } = cell else {
-                        bail!("Unexpected cell type");
-                    };
-                    remaining_pages.push(left_child - 1);
-                }
-                let rightmost = btree
-                    .header
-                    .right_most
-                    .expect("Right-most pointer should exist in interior page");
-                remaining_pages.push(rightmost - 1);
-            }
-            BTreePageType::LeafIndex => todo!(),
-            BTreePageType::LeafTable => {
-                for cell in btree.cells {
-                    let Cell::TableLeaf { payload, .. } = cell else {
-                        bail!("Unexpected cell type");
-                    };
-                    let RecordValue::N13AndOdd(ref s) = payload.values[0] else {
-                        bail!("Unexpected record value");
-                    };
-                    if s == "table" {
-                        num_tables += 1;
-                    }
-                }
-            }
-        }
-
-        if remaining_pages.is_empty() {
-            break;
-        }
-
-        let next_page = remaining_pages
-            .pop()
-            .expect("We checked that remaining pages is not empty");
-        file.read_exact_at(&mut page, next_page as u64 * header.page_size as u64)?;
-        btree = BTreePage::parse(&page[0..], false)?;
+fn dot_dbinfo(db: &Database) -> DbInfo {
+    DbInfo {
+        db_page_size: db.page_size,
+        num_tables: db.schema.tables().count() as u16,
     }
-
-    Ok(DbInfo {
-        db_page_size: header.page_size,
-        num_tables,
-    })
 }
 
-fn dot_tables(db_file: impl AsRef<Path>) -> Result<String> {
-    let mut file = File::open(db_file)?;
-    let mut header = [0; 100];
-    file.read_exact(&mut header)?;
-
-    let header = DbHeader::parse(&header)?;
-
-    let mut page = vec![0; header.page_size as usize];
-    file.read_exact_at(&mut page, 0)?;
-
-    let mut tables = String::new();
-    let mut remaining_pages = Vec::new();
-
-    let mut btree = BTreePage::parse(&page[100..], true)?;
-
-    loop {
-        match btree.header.page_type {
-            BTreePageType::InteriorIndex => todo!(),
-            BTreePageType::InteriorTable => {
-                for cell in btree.cells {
-                    let Cell::TableInterior { left_child, .. } = cell else {
-                        bail!("Unexpected cell type");
-                    };
-                    remaining_pages.push(left_child - 1);
-                }
-                let rightmost = btree
-                    .header
-                    .right_most
-                    .expect("Right-most pointer should exist in interior page");
-                remaining_pages.push(rightmost - 1);
-            }
-            BTreePageType::LeafIndex => todo!(),
-            BTreePageType::LeafTable => {
-                for cell in btree.cells {
-                    let Cell::TableLeaf { payload, .. } = cell else {
-                        bail!("Unexpected cell type");
-                    };
-                    let RecordValue::N13AndOdd(ref s) = payload.values[0] else {
-                        bail!("Unexpected record value");
-                    };
-                    if s == "table" {
-                        let RecordValue::N13AndOdd(ref n) = payload.values[2] else {
-                            bail!("Unexpected record value");
-                        };
-                        if !n.starts_with("sqlite_") {
-                            tables.push_str(&format!(" {}", n));
-                        }
-                    }
-                }
-            }
-        }
-
-        if remaining_pages.is_empty() {
-            break;
-        }
-
-        let next_page = remaining_pages
-            .pop()
-            .expect("We checked that remaining pages is not empty");
-        file.read_exact_at(&mut page, next_page as u64 * header.page_size as u64)?;
-        btree = BTreePage::parse(&page[0..], false)?;
+/// Whether a name matches the pattern of a dot command, which is a `LIKE`
+/// pattern with `\` as the escape character.
+fn matches_pattern(name: &str, pattern: Option<&str>) -> Result<bool> {
+    match pattern {
+        Some(pattern) => pattern::like(pattern, name, Some('\\'), false),
+        None => Ok(true),
     }
-
-    Ok(tables.trim().to_string())
 }
 
-fn count_rows(table: &str, db_file: impl AsRef<Path>) -> Result<usize> {
-    let mut file = File::open(db_file)?;
-    let mut header = [0; 100];
-    file.read_exact(&mut header)?;
-
-    let header = DbHeader::parse(&header)?;
-
-    let mut page = vec![0; header.page_size as usize];
-    file.read_exact_at(&mut page, 0)?;
-
-    let mut remaining_pages = Vec::new();
-
-    let mut btree = BTreePage::parse(&page[100..], true)?;
-
-    let rootpage: u64;
-    'outer: loop {
-        match btree.header.page_type {
-            BTreePageType::InteriorIndex => todo!(),
-            BTreePageType::InteriorTable => {
-                for cell in btree.cells {
-                    let Cell::TableInterior { left_child, .. } = cell else {
-                        bail!("Unexpected cell type");
-                    };
-                    remaining_pages.push(left_child - 1);
-                }
-                let rightmost = btree
-                    .header
-                    .right_most
-                    .expect("Right-most pointer should exist in interior page");
-                remaining_pages.push(rightmost - 1);
-            }
-            BTreePageType::LeafIndex => todo!(),
-            BTreePageType::LeafTable => {
-                for cell in btree.cells {
-                    let Cell::TableLeaf { payload, .. } = cell else {
-                        bail!("Unexpected cell type");
-                    };
-                    let RecordValue::N13AndOdd(ref s) = payload.values[0] else {
-                        bail!("Unexpected record value");
-                    };
-                    if s == "table" {
-                        let RecordValue::N13AndOdd(ref n) = payload.values[2] else {
-                            bail!("Unexpected record value");
-                        };
-                        if n == table {
-                            // Found the entry for the table we're looking for.
-                            let serial_type = payload.serial_types[3];
-                            match serial_type {
-                                SerialType::I8 => {
-                                    let RecordValue::I8(rp) = payload.values[3] else {
-                                        panic!("We checked the SerialType");
-                                    };
-                                    rootpage = rp as u64 - 1;
-                                    break 'outer;
-                                }
-                                SerialType::I16 => {
-                                    let RecordValue::I16(rp) = payload.values[3] else {
-                                        panic!("We checked the SerialType");
-                                    };
-                                    rootpage = rp as u64 - 1;
-                                    break 'outer;
-                                }
-                                SerialType::I24 => {
-                                    let RecordValue::I24(rp) = payload.values[3] else {
-                                        panic!("We checked the SerialType");
-                                    };
-                                    rootpage = rp as u64 - 1;
-                                    break 'outer;
-                                }
-                                SerialType::I32 => {
-                                    let RecordValue::I32(rp) = payload.values[3] else {
-                                        panic!("We checked the SerialType");
-                                    };
-                                    rootpage = rp as u64 - 1;
-                                    break 'outer;
-                                }
-                                SerialType::I48 => {
-                                    let RecordValue::I48(rp) = payload.values[3] else {
-                                        panic!("We checked the SerialType");
-                                    };
-                                    rootpage = rp as u64 - 1;
-                                    break 'outer;
-                                }
-                                SerialType::I64 => {
-                                    let RecordValue::I64(rp) = payload.values[3] else {
-                                        panic!("We checked the SerialType");
-                                    };
-                                    rootpage = rp as u64 - 1;
-                                    break 'outer;
-                                }
-                                _ => bail!("Unexpected serial type for rootpage"),
-                            }
-                        }
-                    }
-                }
-            }
+/// The names of the tables and views that match the pattern, other than
+/// SQLite's internal tables, sorted without regard to case.
+fn dot_tables(db: &Database, pattern: Option<&str>) -> Result<String> {
+    let mut names = Vec::new();
+    let tables = db.schema.entries.iter();
+    for table in tables.filter(|e| matches!(e.kind, SchemaType::Table | SchemaType::View)) {
+        if !table.name.starts_with("sqlite_") && matches_pattern(&table.name, pattern)? {
+            names.push(table.name.as_str());
         }
-
-        if remaining_pages.is_empty() {
-            bail!("Table not found in database");
-        }
-
-        let next_page = remaining_pages
-            .pop()
-            .expect("We checked that remaining pages is not empty");
-        file.read_exact_at(&mut page, next_page as u64 * header.page_size as u64)?;
-        btree = BTreePage::parse(&page[0..], false)?;
     }
+    names.sort_by(|a, b| Collation::NoCase.compare(a, b));
+    Ok(names.join(" "))
+}
 
-    // Go load the rootpage of the table we're looking for.
-    file.read_exact_at(&mut page, rootpage * header.page_size as u64)?;
-
-    let mut rows = 0;
-    remaining_pages.clear();
-    loop {
-        btree = BTreePage::parse(&page[0..], false)?;
-        match btree.header.page_type {
-            BTreePageType::InteriorIndex => todo!(),
-            BTreePageType::InteriorTable => {
-                for cell in btree.cells {
-                    let Cell::TableInterior { left_child, .. } = cell else {
-                        bail!("Unexpected cell type");
-                    };
-                    remaining_pages.push(left_child - 1);
-                }
-                let rightmost = btree
-                    .header
-                    .right_most
-                    .expect("Right-most pointer should exist in interior page");
-                remaining_pages.push(rightmost - 1);
-            }
-            BTreePageType::LeafIndex => todo!(),
-            BTreePageType::LeafTable => {
-                rows += btree.header.num_cells as usize;
-            }
-        }
-
-        if remaining_pages.is_empty() {
-            break;
+/// The SQL of the schema entries whose name or table name matches the
+/// pattern, in the order they are stored in.
+fn dot_schema<'a>(db: &'a Database, pattern: Option<&str>) -> Result<Vec<&'a str>> {
+    let mut statements = Vec::new();
+    for entry in &db.schema.entries {
+        let Some(sql) = &entry.sql else {
+            continue;
+        };
+        if matches_pattern(&entry.name, pattern)? || matches_pattern(&entry.tbl_name, pattern)? {
+            statements.push(sql.as_str());
         }
-
-        let next_page = remaining_pages
-            .pop()
-            .expect("We checked that remaining pages is not empty");
-        file.read_exact_at(&mut page, next_page as u64 * header.page_size as u64)?;
     }
-
-    Ok(rows)
+    Ok(statements)
 }
 
 #[cfg(test)]
 mod tests {
     use crate::*;
 
+    fn count_rows(table: &str, db: &Database) -> Result<i64> {
+        let sql = format!("SELECT COUNT(*) FROM \"{table}\"");
+        let Some(Statement::Select(select)) = parser::parse(&sql)?.pop() else {
+            panic!("Expected SELECT");
+        };
+        match query::execute(&select, db, &[])?.rows[..] {
+            [ref row] => match row[..] {
+                [Value::Integer(count)] => Ok(count),
+                _ => panic!("Expected a count"),
+            },
+            _ => panic!("Expected a single row"),
+        }
+    }
+
     #[test]
     fn dbinfo_outputs_correct_page_size() {
         let test_db_files = vec![
@@ -344,7 +242,7 @@ mod tests {
         ];
 
         for (db, expected) in test_db_files {
-            let db_info = dot_dbinfo(db).unwrap();
+            let db_info = dot_dbinfo(&Database::open(db).unwrap());
 
             assert_eq!(db_info.db_page_size, expected, "{db}");
         }
@@ -352,14 +250,10 @@ mod tests {
 
     #[test]
     fn dbinfo_outputs_correct_num_tables() {
-        let test_db_files = vec![
-            ("sample.db", 3),
-            ("superheroes.db", 2),
-            ("companies.db", 2),
-        ];
+        let test_db_files = vec![("sample.db", 3), ("superheroes.db", 2), ("companies.db", 2)];
 
         for (db, expected) in test_db_files {
-            let db_info = dot_dbinfo(db).unwrap();
+            let db_info = dot_dbinfo(&Database::open(db).unwrap());
 
             assert_eq!(db_info.num_tables, expected, "{db}");
         }
@@ -374,7 +268,7 @@ mod tests {
         ];
 
         for (db, expected) in test_db_files {
-            let tables = dot_tables(db).unwrap();
+            let tables = dot_tables(&Database::open(db).unwrap(), None).unwrap();
 
             assert_eq!(tables, expected, "{db}");
         }
@@ -389,11 +283,60 @@ mod tests {
         ];
 
         for (db, tables) in test_db_files {
+            let database = Database::open(db).unwrap();
             for (table, expected) in tables {
-                let rows = count_rows(table, db).unwrap();
+                let rows = count_rows(table, &database).unwrap();
 
                 assert_eq!(rows, expected, "DB: {db}, table: {table}");
             }
         }
     }
+
+    #[test]
+    fn count_rows_counts_rows_of_tables_without_rowid() {
+        let db = Database::open("without_rowid.db").unwrap();
+        let tables = vec![("pears", 5), ("grapes", 1500), ("plums", 3)];
+
+        for (table, expected) in tables {
+            let rows = count_rows(table, &db).unwrap();
+
+            assert_eq!(rows, expected, "table: {table}");
+        }
+    }
+
+    #[test]
+    fn tables_lists_tables_without_rowid() {
+        let tables = dot_tables(&Database::open("without_rowid.db").unwrap(), None).unwrap();
+
+        assert_eq!(tables, "grapes pears plums");
+    }
+
+    #[test]
+    fn tables_lists_views() {
+        let db = Database::open("views.db").unwrap();
+
+        assert_eq!(
+            dot_tables(&db, None).unwrap(),
+            "adult_names adults by_city first_loop mismatched people second_loop"
+        );
+        assert_eq!(
+            dot_tables(&db, Some("adult%")).unwrap(),
+            "adult_names adults"
+        );
+    }
+
+    #[test]
+    fn tables_and_schema_filter_by_pattern() {
+        let db = Database::open("joins.db").unwrap();
+
+        assert_eq!(dot_tables(&db, Some("C%")).unwrap(), "cities customers");
+        assert_eq!(
+            dot_schema(&db, Some("ord%")).unwrap(),
+            vec![
+                "CREATE TABLE orders (id INTEGER PRIMARY KEY, customer_id INTEGER, amount INTEGER, status TEXT)",
+                "CREATE INDEX orders_customer ON orders (customer_id)",
+                "CREATE INDEX orders_status_amount ON orders (status, amount DESC)",
+            ]
+        );
+    }
 }
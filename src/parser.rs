@@ -0,0 +1,2434 @@
+use anyhow::{anyhow, bail, ensure, Result};
+
+use crate::ast::*;
+use crate::lexer::{tokenize, Token};
+use crate::value::{Affinity, Value};
+
+/// Keywords that can't be used as identifiers or aliases without quoting them.
+const RESERVED: &[&str] = &[
+    "ALL",
+    "AND",
+    "AS",
+    "ASC",
+    "BETWEEN",
+    "BY",
+    "CASE",
+    "COLLATE",
+    "CROSS",
+    "DESC",
+    "DISTINCT",
+    "ELSE",
+    "END",
+    "ESCAPE",
+    "EXCEPT",
+    "EXISTS",
+    "FROM",
+    "GLOB",
+    "GROUP",
+    "HAVING",
+    "IN",
+    "INNER",
+    "INTERSECT",
+    "IS",
+    "ISNULL",
+    "JOIN",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "MATCH",
+    "NATURAL",
+    "NOT",
+    "NOTNULL",
+    "NULL",
+    "OFFSET",
+    "ON",
+    "OR",
+    "ORDER",
+    "OUTER",
+    "REGEXP",
+    "SELECT",
+    "THEN",
+    "UNION",
+    "USING",
+    "VALUES",
+    "WHEN",
+    "WHERE",
+    "WINDOW",
+    "WITH",
+];
+
+/// The largest number a parameter like `?NNN` may have.
+const MAX_PARAMETER: usize = 32766;
+
+/// How deeply expressions may be nested, like SQLite's default
+/// `SQLITE_MAX_EXPR_DEPTH`.
+const MAX_EXPR_DEPTH: usize = 1000;
+
+/// Parses a list of SQL statements separated by semicolons.
+pub fn parse(sql: &str) -> Result<Vec<Statement>> {
+    let statements = parse_with_parameters(sql)?;
+    Ok(statements
+        .into_iter()
+        .map(|(statement, _)| statement)
+        .collect())
+}
+
+/// Parses a list of SQL statements separated by semicolons, along with the
+/// names of the parameters of each statement by index, starting at 1. A
+/// parameter written as a bare `?` has no name.
+pub fn parse_with_parameters(sql: &str) -> Result<Vec<(Statement, Vec<Option<String>>)>> {
+    let mut parser = Parser {
+        tokens: tokenize(sql)?,
+        pos: 0,
+        parameters: Vec::new(),
+        depth: 0,
+    };
+
+    let mut statements = Vec::new();
+    loop {
+        while parser.consume(&Token::Semicolon) {}
+        if parser.peek().is_none() {
+            break;
+        }
+        let statement = parser.statement()?;
+        statements.push((statement, std::mem::take(&mut parser.parameters)));
+        if parser.peek().is_some() && !parser.consume(&Token::Semicolon) {
+            return Err(parser.error());
+        }
+    }
+
+    Ok(statements)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// The names of the parameters of the statement being parsed, by index.
+    parameters: Vec<Option<String>>,
+    /// How many expressions the parser is in.
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        self.peek().is_some_and(|t| t.is_keyword(keyword))
+    }
+
+    /// Consumes the next token if it is the given keyword.
+    fn consume_keyword(&mut self, keyword: &str) -> bool {
+        if self.peek_keyword(keyword) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        if self.consume_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    /// Consumes the next token if it is the given token.
+    fn consume(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &Token) -> Result<()> {
+        if self.consume(token) {
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    /// A syntax error at the current token, worded like SQLite's.
+    fn error(&self) -> anyhow::Error {
+        match self.peek() {
+            Some(token) => anyhow!("near \"{token}\": syntax error"),
+            None => anyhow!("incomplete input"),
+        }
+    }
+
+    fn statement(&mut self) -> Result<Statement> {
+        if self.peek().is_some_and(starts_select) {
+            Ok(Statement::Select(Box::new(self.select()?)))
+        } else if self.peek_keyword("CREATE") {
+            let is_word = |token: Option<&Token>, words: &[&str]| {
+                matches!(token, Some(Token::Word(w))
+                    if words.iter().any(|word| w.eq_ignore_ascii_case(word)))
+            };
+            let temporary = is_word(self.peek_at(1), &["TEMP", "TEMPORARY"]);
+            if is_word(self.peek_at(1), &["INDEX", "UNIQUE"]) {
+                Ok(Statement::CreateIndex(Box::new(self.create_index()?)))
+            } else if is_word(self.peek_at(1 + usize::from(temporary)), &["VIEW"]) {
+                Ok(Statement::CreateView(Box::new(self.create_view()?)))
+            } else {
+                Ok(Statement::CreateTable(Box::new(self.create_table()?)))
+            }
+        } else if self.consume_keyword("PRAGMA") {
+            Ok(Statement::Pragma(self.pragma()?))
+        } else {
+            Err(self.error())
+        }
+    }
+
+    /// Parses a `PRAGMA` statement after the `PRAGMA` keyword.
+    fn pragma(&mut self) -> Result<Pragma> {
+        let mut schema = None;
+        let mut name = self.name()?;
+        if self.consume(&Token::Dot) {
+            schema = Some(name);
+            name = self.name()?;
+        }
+        let value = if self.consume(&Token::Eq) {
+            Some(self.pragma_value()?)
+        } else if self.consume(&Token::LeftParen) {
+            let value = self.pragma_value()?;
+            self.expect(&Token::RightParen)?;
+            Some(value)
+        } else {
+            None
+        };
+        Ok(Pragma {
+            schema,
+            name,
+            value,
+        })
+    }
+
+    /// Parses the value of a pragma, which may be a keyword like `ON`.
+    fn pragma_value(&mut self) -> Result<String> {
+        let sign = if self.consume(&Token::Minus) {
+            "-"
+        } else {
+            self.consume(&Token::Plus);
+            ""
+        };
+        let value = match self.peek() {
+            Some(Token::Integer(i)) => format!("{sign}{i}"),
+            Some(Token::Real(r)) => format!("{sign}{r}"),
+            Some(token @ Token::MinIntegerMagnitude) => format!("{sign}{token}"),
+            Some(Token::Word(w) | Token::QuotedIdentifier(w) | Token::String(w))
+                if sign.is_empty() =>
+            {
+                w.clone()
+            }
+            _ => return Err(self.error()),
+        };
+        self.pos += 1;
+        Ok(value)
+    }
+
+    fn select(&mut self) -> Result<Select> {
+        let with = if self.consume_keyword("WITH") {
+            Some(self.with()?)
+        } else {
+            None
+        };
+        let mut select = self.select_core()?;
+        select.with = with;
+        while let Some(operator) = self.compound_operator() {
+            select.compound.push(CompoundSelect {
+                operator,
+                select: self.select_core()?,
+            });
+        }
+
+        if self.consume_keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            select.order_by.push(self.ordering_term()?);
+            while self.consume(&Token::Comma) {
+                select.order_by.push(self.ordering_term()?);
+            }
+        }
+
+        if self.consume_keyword("LIMIT") {
+            let first = self.expr()?;
+            if self.consume_keyword("OFFSET") {
+                select.limit = Some(first);
+                select.offset = Some(self.expr()?);
+            } else if self.consume(&Token::Comma) {
+                // "LIMIT offset, limit"
+                select.offset = Some(first);
+                select.limit = Some(self.expr()?);
+            } else {
+                select.limit = Some(first);
+            }
+        }
+
+        Ok(select)
+    }
+
+    /// Parses the common table expressions of a `WITH` clause.
+    fn with(&mut self) -> Result<With> {
+        let recursive = self.consume_keyword("RECURSIVE");
+        let mut ctes = Vec::new();
+        loop {
+            let name = self.name()?;
+            let columns = if self.consume(&Token::LeftParen) {
+                let columns = self.name_list()?;
+                self.expect(&Token::RightParen)?;
+                columns
+            } else {
+                Vec::new()
+            };
+            self.expect_keyword("AS")?;
+            // Hints on whether to materialize the result make no difference here.
+            if self.consume_keyword("NOT") {
+                self.expect_keyword("MATERIALIZED")?;
+            } else {
+                self.consume_keyword("MATERIALIZED");
+            }
+            self.expect(&Token::LeftParen)?;
+            let select = self.select()?;
+            self.expect(&Token::RightParen)?;
+            ctes.push(CommonTableExpr {
+                name,
+                columns,
+                select: Box::new(select),
+            });
+            if !self.consume(&Token::Comma) {
+                return Ok(With { recursive, ctes });
+            }
+        }
+    }
+
+    /// Parses a `SELECT` up to where a compound operator, `ORDER BY` or
+    /// `LIMIT` may follow.
+    fn select_core(&mut self) -> Result<Select> {
+        self.expect_keyword("SELECT")?;
+        let distinct = if self.consume_keyword("DISTINCT") {
+            true
+        } else {
+            self.consume_keyword("ALL");
+            false
+        };
+
+        let mut columns = vec![self.result_column()?];
+        while self.consume(&Token::Comma) {
+            columns.push(self.result_column()?);
+        }
+
+        let mut joins = Vec::new();
+        let from = if self.consume_keyword("FROM") {
+            let from = self.table_or_subquery()?;
+            while let Some((operator, natural)) = self.join_operator()? {
+                let table = self.table_or_subquery()?;
+                let constraint = if self.consume_keyword("ON") {
+                    Some(JoinConstraint::On(self.expr()?))
+                } else if self.consume_keyword("USING") {
+                    self.expect(&Token::LeftParen)?;
+                    let columns = self.name_list()?;
+                    self.expect(&Token::RightParen)?;
+                    Some(JoinConstraint::Using(columns))
+                } else {
+                    None
+                };
+                joins.push(Join {
+                    operator,
+                    natural,
+                    table,
+                    constraint,
+                });
+            }
+            Some(from)
+        } else {
+            None
+        };
+
+        let where_clause = if self.consume_keyword("WHERE") {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+
+        let mut group_by = Vec::new();
+        if self.consume_keyword("GROUP") {
+            self.expect_keyword("BY")?;
+            group_by = self.expr_list()?;
+        }
+        let having = if self.consume_keyword("HAVING") {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+
+        let mut windows = Vec::new();
+        if self.consume_keyword("WINDOW") {
+            loop {
+                let name = self.name()?;
+                self.expect_keyword("AS")?;
+                windows.push((name, self.window()?));
+                if !self.consume(&Token::Comma) {
+                    break;
+                }
+            }
+        }
+
+        Ok(Select {
+            with: None,
+            distinct,
+            columns,
+            from,
+            joins,
+            where_clause,
+            group_by,
+            having,
+            windows,
+            compound: Vec::new(),
+            order_by: Vec::new(),
+            limit: None,
+            offset: None,
+        })
+    }
+
+    fn compound_operator(&mut self) -> Option<CompoundOperator> {
+        if self.consume_keyword("UNION") {
+            if self.consume_keyword("ALL") {
+                Some(CompoundOperator::UnionAll)
+            } else {
+                Some(CompoundOperator::Union)
+            }
+        } else if self.consume_keyword("INTERSECT") {
+            Some(CompoundOperator::Intersect)
+        } else if self.consume_keyword("EXCEPT") {
+            Some(CompoundOperator::Except)
+        } else {
+            None
+        }
+    }
+
+    fn result_column(&mut self) -> Result<ResultColumn> {
+        if self.consume(&Token::Star) {
+            return Ok(ResultColumn::Star);
+        }
+        if self.peek_at(1) == Some(&Token::Dot) && self.peek_at(2) == Some(&Token::Star) {
+            let table = self.identifier()?;
+            self.pos += 2;
+            return Ok(ResultColumn::TableStar(table));
+        }
+
+        let expr = self.expr()?;
+        let alias = self.alias()?;
+
+        Ok(ResultColumn::Expr { expr, alias })
+    }
+
+    /// Parses an optional `[AS] alias`.
+    fn alias(&mut self) -> Result<Option<String>> {
+        if self.consume_keyword("AS") {
+            return match self.next() {
+                // SQLite also accepts string literals as aliases.
+                Some(Token::String(s)) => Ok(Some(s)),
+                _ => {
+                    self.pos -= 1;
+                    Ok(Some(self.identifier()?))
+                }
+            };
+        }
+        match self.peek() {
+            Some(Token::Word(w)) if !is_reserved(w) => Ok(Some(self.identifier()?)),
+            Some(Token::QuotedIdentifier(_)) => Ok(Some(self.identifier()?)),
+            Some(Token::String(s)) => {
+                let alias = s.clone();
+                self.pos += 1;
+                Ok(Some(alias))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn table_or_subquery(&mut self) -> Result<TableOrSubquery> {
+        if self.peek() == Some(&Token::LeftParen) && self.peek_at(1).is_some_and(starts_select) {
+            self.pos += 1;
+            let select = self.select()?;
+            self.expect(&Token::RightParen)?;
+            return Ok(TableOrSubquery::Subquery {
+                select: Box::new(select),
+                alias: self.alias()?,
+            });
+        }
+        if self.peek_at(1) == Some(&Token::LeftParen) {
+            let name = self.identifier()?;
+            self.pos += 1;
+            let args = match self.peek() {
+                Some(Token::RightParen) => Vec::new(),
+                _ => self.expr_list()?,
+            };
+            self.expect(&Token::RightParen)?;
+            return Ok(TableOrSubquery::Function {
+                name,
+                args,
+                alias: self.alias()?,
+            });
+        }
+        Ok(TableOrSubquery::Table(self.table_name()?))
+    }
+
+    fn table_name(&mut self) -> Result<TableName> {
+        let mut schema = None;
+        let mut name = self.identifier()?;
+        if self.consume(&Token::Dot) {
+            schema = Some(name);
+            name = self.identifier()?;
+        }
+        // RIGHT and FULL are not reserved, but start a join rather than an
+        // alias when followed by JOIN or OUTER.
+        let join_follows = matches!(self.peek(), Some(Token::Word(w))
+            if w.eq_ignore_ascii_case("RIGHT") || w.eq_ignore_ascii_case("FULL"))
+            && matches!(self.peek_at(1), Some(Token::Word(w))
+                if w.eq_ignore_ascii_case("JOIN") || w.eq_ignore_ascii_case("OUTER"));
+        let alias = if join_follows { None } else { self.alias()? };
+
+        Ok(TableName {
+            schema,
+            name,
+            alias,
+        })
+    }
+
+    /// Parses the operator that joins a table to the ones before it, if there
+    /// is one, returning the kind of join and whether it is `NATURAL`.
+    fn join_operator(&mut self) -> Result<Option<(JoinOperator, bool)>> {
+        if self.consume(&Token::Comma) {
+            return Ok(Some((JoinOperator::Inner, false)));
+        }
+        let natural = self.consume_keyword("NATURAL");
+        let operator = if self.consume_keyword("LEFT") {
+            self.consume_keyword("OUTER");
+            JoinOperator::Left
+        } else if self.consume_keyword("INNER") {
+            JoinOperator::Inner
+        } else if self.consume_keyword("CROSS") {
+            JoinOperator::Cross
+        } else if self.peek_keyword("RIGHT") || self.peek_keyword("FULL") {
+            bail!("RIGHT and FULL OUTER JOINs are not supported");
+        } else if natural || self.peek_keyword("JOIN") {
+            JoinOperator::Inner
+        } else {
+            return Ok(None);
+        };
+        self.expect_keyword("JOIN")?;
+
+        Ok(Some((operator, natural)))
+    }
+
+    fn ordering_term(&mut self) -> Result<OrderingTerm> {
+        let expr = self.expr()?;
+        let descending = self.sort_order();
+        let nulls_first = if self.consume_keyword("NULLS") {
+            if self.consume_keyword("FIRST") {
+                Some(true)
+            } else {
+                self.expect_keyword("LAST")?;
+                Some(false)
+            }
+        } else {
+            None
+        };
+
+        Ok(OrderingTerm {
+            expr,
+            descending,
+            nulls_first,
+        })
+    }
+
+    /// Parses an optional `ASC` or `DESC`, returning whether it is descending.
+    fn sort_order(&mut self) -> bool {
+        if self.consume_keyword("DESC") {
+            true
+        } else {
+            self.consume_keyword("ASC");
+            false
+        }
+    }
+
+    fn create_index(&mut self) -> Result<CreateIndex> {
+        self.expect_keyword("CREATE")?;
+        let unique = self.consume_keyword("UNIQUE");
+        self.expect_keyword("INDEX")?;
+        let if_not_exists = if self.consume_keyword("IF") {
+            self.expect_keyword("NOT")?;
+            self.expect_keyword("EXISTS")?;
+            true
+        } else {
+            false
+        };
+        let mut schema = None;
+        let mut name = self.name()?;
+        if self.consume(&Token::Dot) {
+            schema = Some(name);
+            name = self.name()?;
+        }
+        self.expect_keyword("ON")?;
+        let table = self.name()?;
+
+        self.expect(&Token::LeftParen)?;
+        let mut columns = vec![self.ordering_term()?];
+        while self.consume(&Token::Comma) {
+            columns.push(self.ordering_term()?);
+        }
+        self.expect(&Token::RightParen)?;
+        let where_clause = if self.consume_keyword("WHERE") {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+
+        Ok(CreateIndex {
+            unique,
+            if_not_exists,
+            schema,
+            name,
+            table,
+            columns,
+            where_clause,
+        })
+    }
+
+    fn create_table(&mut self) -> Result<CreateTable> {
+        self.expect_keyword("CREATE")?;
+        let temporary = self.consume_keyword("TEMP") || self.consume_keyword("TEMPORARY");
+        self.expect_keyword("TABLE")?;
+        let if_not_exists = if self.consume_keyword("IF") {
+            self.expect_keyword("NOT")?;
+            self.expect_keyword("EXISTS")?;
+            true
+        } else {
+            false
+        };
+        let mut schema = None;
+        let mut name = self.name()?;
+        if self.consume(&Token::Dot) {
+            schema = Some(name);
+            name = self.name()?;
+        }
+
+        self.expect(&Token::LeftParen)?;
+        let mut columns = vec![self.column_def()?];
+        let mut constraints = Vec::new();
+        while self.consume(&Token::Comma) {
+            if constraints.is_empty() && !self.at_table_constraint() {
+                columns.push(self.column_def()?);
+                continue;
+            }
+            constraints.push(self.table_constraint()?);
+            // Unlike columns, table constraints don't need to be separated by commas.
+            while self.at_table_constraint() {
+                constraints.push(self.table_constraint()?);
+            }
+        }
+        self.expect(&Token::RightParen)?;
+
+        let (mut without_rowid, mut strict) = (false, false);
+        if self.peek_keyword("WITHOUT") || self.peek_keyword("STRICT") {
+            loop {
+                if self.consume_keyword("STRICT") {
+                    strict = true;
+                } else {
+                    self.expect_keyword("WITHOUT")?;
+                    self.expect_keyword("ROWID")?;
+                    without_rowid = true;
+                }
+                if !self.consume(&Token::Comma) {
+                    break;
+                }
+            }
+        }
+        if strict {
+            // Columns of type ANY in strict tables store values as they are given.
+            for column in &mut columns {
+                if column
+                    .declared_type
+                    .as_ref()
+                    .is_some_and(|t| t.eq_ignore_ascii_case("ANY"))
+                {
+                    column.affinity = Affinity::Blob;
+                }
+            }
+        }
+
+        Ok(CreateTable {
+            temporary,
+            if_not_exists,
+            schema,
+            name,
+            columns,
+            constraints,
+            without_rowid,
+            strict,
+        })
+    }
+
+    fn create_view(&mut self) -> Result<CreateView> {
+        self.expect_keyword("CREATE")?;
+        let temporary = self.consume_keyword("TEMP") || self.consume_keyword("TEMPORARY");
+        self.expect_keyword("VIEW")?;
+        let if_not_exists = if self.consume_keyword("IF") {
+            self.expect_keyword("NOT")?;
+            self.expect_keyword("EXISTS")?;
+            true
+        } else {
+            false
+        };
+        let mut schema = None;
+        let mut name = self.name()?;
+        if self.consume(&Token::Dot) {
+            schema = Some(name);
+            name = self.name()?;
+        }
+        let columns = if self.consume(&Token::LeftParen) {
+            let columns = self.name_list()?;
+            self.expect(&Token::RightParen)?;
+            columns
+        } else {
+            Vec::new()
+        };
+        self.expect_keyword("AS")?;
+        let select = self.select()?;
+        if !self.parameters.is_empty() {
+            bail!("parameters are not allowed in views");
+        }
+
+        Ok(CreateView {
+            temporary,
+            if_not_exists,
+            schema,
+            name,
+            columns,
+            select: Box::new(select),
+        })
+    }
+
+    fn column_def(&mut self) -> Result<ColumnDef> {
+        let name = self.name()?;
+        let declared_type = self.type_name()?;
+        let affinity = Affinity::from_declared_type(declared_type.as_deref().unwrap_or_default());
+        let mut constraints = Vec::new();
+        while let Some(constraint) = self.column_constraint()? {
+            constraints.push(constraint);
+        }
+
+        Ok(ColumnDef {
+            name,
+            declared_type,
+            affinity,
+            constraints,
+        })
+    }
+
+    /// Parses an optional type name, like `UNSIGNED BIG INT` or `DECIMAL(10,5)`.
+    fn type_name(&mut self) -> Result<Option<String>> {
+        let mut words = Vec::new();
+        loop {
+            match self.peek() {
+                Some(Token::Word(w)) if !is_reserved(w) && !is_constraint_keyword(w) => {
+                    words.push(w.clone());
+                }
+                Some(Token::QuotedIdentifier(i)) => words.push(i.clone()),
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        if words.is_empty() {
+            return Ok(None);
+        }
+
+        let mut type_name = words.join(" ");
+        if self.consume(&Token::LeftParen) {
+            let mut sizes = vec![self.signed_number()?];
+            if self.consume(&Token::Comma) {
+                sizes.push(self.signed_number()?);
+            }
+            self.expect(&Token::RightParen)?;
+            type_name = format!("{type_name}({})", sizes.join(","));
+        }
+
+        Ok(Some(type_name))
+    }
+
+    /// Parses a number with an optional sign, as it appears in a type name.
+    fn signed_number(&mut self) -> Result<String> {
+        let sign = match self.peek() {
+            Some(Token::Minus) => "-",
+            Some(Token::Plus) => "+",
+            _ => "",
+        };
+        if !sign.is_empty() {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some(token @ (Token::Integer(_) | Token::Real(_) | Token::MinIntegerMagnitude)) => {
+                let number = format!("{sign}{token}");
+                self.pos += 1;
+                Ok(number)
+            }
+            _ => Err(self.error()),
+        }
+    }
+
+    /// Parses the next column constraint, if there is one.
+    fn column_constraint(&mut self) -> Result<Option<ColumnConstraint>> {
+        let name = if self.consume_keyword("CONSTRAINT") {
+            Some(self.name()?)
+        } else {
+            None
+        };
+
+        let kind = if self.consume_keyword("PRIMARY") {
+            self.expect_keyword("KEY")?;
+            let descending = self.sort_order();
+            self.conflict_clause()?;
+            ColumnConstraintKind::PrimaryKey {
+                descending,
+                autoincrement: self.consume_keyword("AUTOINCREMENT"),
+            }
+        } else if self.consume_keyword("NOT") {
+            self.expect_keyword("NULL")?;
+            self.conflict_clause()?;
+            ColumnConstraintKind::NotNull
+        } else if self.consume_keyword("NULL") {
+            self.conflict_clause()?;
+            ColumnConstraintKind::Null
+        } else if self.consume_keyword("UNIQUE") {
+            self.conflict_clause()?;
+            ColumnConstraintKind::Unique
+        } else if self.consume_keyword("CHECK") {
+            ColumnConstraintKind::Check(self.parenthesized_expr()?)
+        } else if self.consume_keyword("DEFAULT") {
+            ColumnConstraintKind::Default(self.default_value()?)
+        } else if self.consume_keyword("COLLATE") {
+            ColumnConstraintKind::Collate(self.name()?)
+        } else if self.consume_keyword("REFERENCES") {
+            ColumnConstraintKind::References(self.foreign_key()?)
+        } else if self.peek_keyword("GENERATED") || self.peek_keyword("AS") {
+            if self.consume_keyword("GENERATED") {
+                self.expect_keyword("ALWAYS")?;
+            }
+            self.expect_keyword("AS")?;
+            let expr = self.parenthesized_expr()?;
+            let stored = if self.consume_keyword("STORED") {
+                true
+            } else {
+                self.consume_keyword("VIRTUAL");
+                false
+            };
+            ColumnConstraintKind::Generated { expr, stored }
+        } else if name.is_some() {
+            return Err(self.error());
+        } else {
+            return Ok(None);
+        };
+
+        Ok(Some(ColumnConstraint { name, kind }))
+    }
+
+    /// Parses the value of a `DEFAULT` constraint, which is a literal, a signed
+    /// number, a bare word like `CURRENT_TIMESTAMP`, or an expression in parentheses.
+    fn default_value(&mut self) -> Result<Expr> {
+        match self.peek() {
+            Some(Token::LeftParen) => self.parenthesized_expr(),
+            Some(Token::Plus | Token::Minus) => self.unary_expr(),
+            _ => self.primary_expr(),
+        }
+    }
+
+    fn parenthesized_expr(&mut self) -> Result<Expr> {
+        self.expect(&Token::LeftParen)?;
+        let expr = self.expr()?;
+        self.expect(&Token::RightParen)?;
+        Ok(expr)
+    }
+
+    fn at_table_constraint(&self) -> bool {
+        ["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"]
+            .iter()
+            .any(|k| self.peek_keyword(k))
+    }
+
+    fn table_constraint(&mut self) -> Result<TableConstraint> {
+        let name = if self.consume_keyword("CONSTRAINT") {
+            Some(self.name()?)
+        } else {
+            None
+        };
+
+        let kind = if self.consume_keyword("PRIMARY") {
+            self.expect_keyword("KEY")?;
+            let columns = self.indexed_columns()?;
+            self.conflict_clause()?;
+            TableConstraintKind::PrimaryKey(columns)
+        } else if self.consume_keyword("UNIQUE") {
+            let columns = self.indexed_columns()?;
+            self.conflict_clause()?;
+            TableConstraintKind::Unique(columns)
+        } else if self.consume_keyword("CHECK") {
+            TableConstraintKind::Check(self.parenthesized_expr()?)
+        } else if self.consume_keyword("FOREIGN") {
+            self.expect_keyword("KEY")?;
+            self.expect(&Token::LeftParen)?;
+            let columns = self.name_list()?;
+            self.expect(&Token::RightParen)?;
+            self.expect_keyword("REFERENCES")?;
+            TableConstraintKind::ForeignKey {
+                columns,
+                references: self.foreign_key()?,
+            }
+        } else {
+            return Err(self.error());
+        };
+
+        Ok(TableConstraint { name, kind })
+    }
+
+    /// Parses a parenthesized list of key columns, like `(a COLLATE NOCASE, b DESC)`.
+    fn indexed_columns(&mut self) -> Result<Vec<IndexedColumn>> {
+        self.expect(&Token::LeftParen)?;
+        let mut columns = Vec::new();
+        loop {
+            let name = self.name()?;
+            let collation = if self.consume_keyword("COLLATE") {
+                Some(self.name()?)
+            } else {
+                None
+            };
+            columns.push(IndexedColumn {
+                name,
+                collation,
+                descending: self.sort_order(),
+            });
+            if !self.consume(&Token::Comma) {
+                break;
+            }
+        }
+        // SQLite accepts AUTOINCREMENT here too, but it only matters when inserting.
+        self.consume_keyword("AUTOINCREMENT");
+        self.expect(&Token::RightParen)?;
+
+        Ok(columns)
+    }
+
+    /// Parses the part of a `REFERENCES` clause after the keyword.
+    fn foreign_key(&mut self) -> Result<ForeignKey> {
+        let table = self.name()?;
+        let mut columns = Vec::new();
+        if self.consume(&Token::LeftParen) {
+            columns = self.name_list()?;
+            self.expect(&Token::RightParen)?;
+        }
+
+        let (mut on_delete, mut on_update) = (None, None);
+        loop {
+            if self.consume_keyword("ON") {
+                let target = if self.consume_keyword("DELETE") {
+                    &mut on_delete
+                } else {
+                    self.expect_keyword("UPDATE")?;
+                    &mut on_update
+                };
+                *target = Some(self.foreign_key_action()?);
+            } else if self.consume_keyword("MATCH") {
+                // SQLite parses MATCH clauses but ignores them.
+                self.name()?;
+            } else if self.peek_keyword("DEFERRABLE")
+                || (self.peek_keyword("NOT")
+                    && self.peek_at(1).is_some_and(|t| t.is_keyword("DEFERRABLE")))
+            {
+                // When constraints are checked only matters to transactions that write.
+                self.consume_keyword("NOT");
+                self.expect_keyword("DEFERRABLE")?;
+                if self.consume_keyword("INITIALLY") && !self.consume_keyword("DEFERRED") {
+                    self.expect_keyword("IMMEDIATE")?;
+                }
+            } else {
+                break;
+            }
+        }
+
+        Ok(ForeignKey {
+            table,
+            columns,
+            on_delete,
+            on_update,
+        })
+    }
+
+    fn foreign_key_action(&mut self) -> Result<ForeignKeyAction> {
+        if self.consume_keyword("SET") {
+            if self.consume_keyword("NULL") {
+                Ok(ForeignKeyAction::SetNull)
+            } else {
+                self.expect_keyword("DEFAULT")?;
+                Ok(ForeignKeyAction::SetDefault)
+            }
+        } else if self.consume_keyword("CASCADE") {
+            Ok(ForeignKeyAction::Cascade)
+        } else if self.consume_keyword("RESTRICT") {
+            Ok(ForeignKeyAction::Restrict)
+        } else {
+            self.expect_keyword("NO")?;
+            self.expect_keyword("ACTION")?;
+            Ok(ForeignKeyAction::NoAction)
+        }
+    }
+
+    /// Skips an optional `ON CONFLICT` clause. It only decides how failed
+    /// writes are resolved, which never happens here.
+    fn conflict_clause(&mut self) -> Result<()> {
+        if self.consume_keyword("ON") {
+            self.expect_keyword("CONFLICT")?;
+            let resolutions = ["ROLLBACK", "ABORT", "FAIL", "IGNORE", "REPLACE"];
+            if !resolutions.iter().any(|r| self.consume_keyword(r)) {
+                return Err(self.error());
+            }
+        }
+        Ok(())
+    }
+
+    fn name_list(&mut self) -> Result<Vec<String>> {
+        let mut names = vec![self.name()?];
+        while self.consume(&Token::Comma) {
+            names.push(self.name()?);
+        }
+        Ok(names)
+    }
+
+    /// Parses the name of a table, column, constraint or collation. Besides
+    /// identifiers, SQLite accepts string literals here.
+    fn name(&mut self) -> Result<String> {
+        if let Some(Token::String(s)) = self.peek() {
+            let name = s.clone();
+            self.pos += 1;
+            return Ok(name);
+        }
+        self.identifier()
+    }
+
+    /// Parses an identifier, which may be quoted.
+    fn identifier(&mut self) -> Result<String> {
+        match self.peek() {
+            Some(Token::Word(w)) if !is_reserved(w) => {
+                let ident = w.clone();
+                self.pos += 1;
+                Ok(ident)
+            }
+            Some(Token::QuotedIdentifier(i)) => {
+                let ident = i.clone();
+                self.pos += 1;
+                Ok(ident)
+            }
+            _ => Err(self.error()),
+        }
+    }
+
+    fn expr_list(&mut self) -> Result<Vec<Expr>> {
+        let mut exprs = vec![self.expr()?];
+        while self.consume(&Token::Comma) {
+            exprs.push(self.expr()?);
+        }
+        Ok(exprs)
+    }
+
+    /// Parses an expression. Each of the following methods handles one level
+    /// of operator precedence, from the loosest binding `OR` to the tightest
+    /// binding unary operators.
+    fn expr(&mut self) -> Result<Expr> {
+        self.nested(|parser| {
+            let mut left = parser.and_expr()?;
+            while parser.consume_keyword("OR") {
+                let right = parser.and_expr()?;
+                left = parser.binary(BinaryOp::Or, left, right)?;
+            }
+            Ok(left)
+        })
+    }
+
+    /// Parses an expression nested in the current one, unless expressions
+    /// are nested too deeply already.
+    fn nested(&mut self, parse: impl FnOnce(&mut Self) -> Result<Expr>) -> Result<Expr> {
+        self.depth += 1;
+        ensure!(self.depth <= MAX_EXPR_DEPTH, too_deep());
+        let expr = parse(self);
+        self.depth -= 1;
+        expr
+    }
+
+    /// Joins two operands with a binary operator, unless that makes the
+    /// expression too deep. Chains of operators are parsed in a loop rather
+    /// than by recursion, so they are checked here.
+    fn binary(&self, op: BinaryOp, left: Expr, right: Expr) -> Result<Expr> {
+        let expr = binary(op, left, right);
+        ensure!(expr.height() <= MAX_EXPR_DEPTH, too_deep());
+        Ok(expr)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr> {
+        let mut left = self.not_expr()?;
+        while self.consume_keyword("AND") {
+            let right = self.not_expr()?;
+            left = self.binary(BinaryOp::And, left, right)?;
+        }
+        Ok(left)
+    }
+
+    fn not_expr(&mut self) -> Result<Expr> {
+        if self.consume_keyword("NOT") {
+            let expr = self.nested(Parser::not_expr)?;
+            return Ok(Expr::Unary {
+                op: UnaryOp::Not,
+                expr: Box::new(expr),
+            });
+        }
+        self.equality_expr()
+    }
+
+    fn equality_expr(&mut self) -> Result<Expr> {
+        let mut left = self.comparison_expr()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Eq) => Some(BinaryOp::Eq),
+                Some(Token::NotEq) => Some(BinaryOp::NotEq),
+                _ => None,
+            };
+            if let Some(op) = op {
+                self.pos += 1;
+                let right = self.comparison_expr()?;
+                left = self.binary(op, left, right)?;
+                continue;
+            }
+
+            if self.consume_keyword("IS") {
+                let op = if self.consume_keyword("NOT") {
+                    BinaryOp::IsNot
+                } else {
+                    BinaryOp::Is
+                };
+                let right = self.comparison_expr()?;
+                left = self.binary(op, left, right)?;
+                continue;
+            }
+            if self.consume_keyword("ISNULL") {
+                left = Expr::IsNull {
+                    expr: Box::new(left),
+                    negated: false,
+                };
+                continue;
+            }
+            if self.consume_keyword("NOTNULL") {
+                left = Expr::IsNull {
+                    expr: Box::new(left),
+                    negated: true,
+                };
+                continue;
+            }
+
+            let negated = self.peek_keyword("NOT");
+            let next = if negated {
+                self.peek_at(1)
+            } else {
+                self.peek()
+            };
+            let Some(next) = next else {
+                break;
+            };
+            if next.is_keyword("NULL") && negated {
+                self.pos += 2;
+                left = Expr::IsNull {
+                    expr: Box::new(left),
+                    negated: true,
+                };
+            } else if next.is_keyword("IN") {
+                self.pos += if negated { 2 } else { 1 };
+                self.expect(&Token::LeftParen)?;
+                if self.peek().is_some_and(starts_select) {
+                    let select = self.select()?;
+                    self.expect(&Token::RightParen)?;
+                    left = Expr::InSelect {
+                        expr: Box::new(left),
+                        select: Box::new(select),
+                        negated,
+                    };
+                    continue;
+                }
+                let list = if self.peek() == Some(&Token::RightParen) {
+                    Vec::new()
+                } else {
+                    self.expr_list()?
+                };
+                self.expect(&Token::RightParen)?;
+                left = Expr::InList {
+                    expr: Box::new(left),
+                    list,
+                    negated,
+                };
+            } else if next.is_keyword("BETWEEN") {
+                self.pos += if negated { 2 } else { 1 };
+                let low = self.comparison_expr()?;
+                self.expect_keyword("AND")?;
+                let high = self.comparison_expr()?;
+                left = Expr::Between {
+                    expr: Box::new(left),
+                    low: Box::new(low),
+                    high: Box::new(high),
+                    negated,
+                };
+            } else if let Some(op) = [
+                ("LIKE", PatternOp::Like),
+                ("GLOB", PatternOp::Glob),
+                ("REGEXP", PatternOp::Regexp),
+            ]
+            .into_iter()
+            .find_map(|(keyword, op)| next.is_keyword(keyword).then_some(op))
+            {
+                self.pos += if negated { 2 } else { 1 };
+                let pattern = self.comparison_expr()?;
+                let escape = if self.consume_keyword("ESCAPE") {
+                    // Only LIKE takes an escape character, as a third
+                    // argument of its function.
+                    if op != PatternOp::Like {
+                        bail!(
+                            "wrong number of arguments to function {}()",
+                            op.function_name()
+                        );
+                    }
+                    Some(Box::new(self.comparison_expr()?))
+                } else {
+                    None
+                };
+                left = Expr::Like {
+                    op,
+                    expr: Box::new(left),
+                    pattern: Box::new(pattern),
+                    escape,
+                    negated,
+                };
+            } else {
+                break;
+            }
+        }
+        Ok(left)
+    }
+
+    fn comparison_expr(&mut self) -> Result<Expr> {
+        let mut left = self.bitwise_expr()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Lt) => BinaryOp::Lt,
+                Some(Token::LtEq) => BinaryOp::LtEq,
+                Some(Token::Gt) => BinaryOp::Gt,
+                Some(Token::GtEq) => BinaryOp::GtEq,
+                _ => break,
+            };
+            self.pos += 1;
+            let right = self.bitwise_expr()?;
+            left = self.binary(op, left, right)?;
+        }
+        Ok(left)
+    }
+
+    fn bitwise_expr(&mut self) -> Result<Expr> {
+        let mut left = self.additive_expr()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::BitAnd) => BinaryOp::BitAnd,
+                Some(Token::BitOr) => BinaryOp::BitOr,
+                Some(Token::ShiftLeft) => BinaryOp::ShiftLeft,
+                Some(Token::ShiftRight) => BinaryOp::ShiftRight,
+                _ => break,
+            };
+            self.pos += 1;
+            let right = self.additive_expr()?;
+            left = self.binary(op, left, right)?;
+        }
+        Ok(left)
+    }
+
+    fn additive_expr(&mut self) -> Result<Expr> {
+        let mut left = self.multiplicative_expr()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Plus) => BinaryOp::Add,
+                Some(Token::Minus) => BinaryOp::Subtract,
+                _ => break,
+            };
+            self.pos += 1;
+            let right = self.multiplicative_expr()?;
+            left = self.binary(op, left, right)?;
+        }
+        Ok(left)
+    }
+
+    fn multiplicative_expr(&mut self) -> Result<Expr> {
+        let mut left = self.concat_expr()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Star) => BinaryOp::Multiply,
+                Some(Token::Slash) => BinaryOp::Divide,
+                Some(Token::Percent) => BinaryOp::Modulo,
+                _ => break,
+            };
+            self.pos += 1;
+            let right = self.concat_expr()?;
+            left = self.binary(op, left, right)?;
+        }
+        Ok(left)
+    }
+
+    fn concat_expr(&mut self) -> Result<Expr> {
+        let mut left = self.collate_expr()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Concat) => BinaryOp::Concat,
+                Some(Token::Arrow) => BinaryOp::Extract,
+                Some(Token::DoubleArrow) => BinaryOp::ExtractValue,
+                _ => break,
+            };
+            self.pos += 1;
+            let right = self.collate_expr()?;
+            left = self.binary(op, left, right)?;
+        }
+        Ok(left)
+    }
+
+    fn collate_expr(&mut self) -> Result<Expr> {
+        let mut expr = self.unary_expr()?;
+        while self.consume_keyword("COLLATE") {
+            expr = Expr::Collate {
+                expr: Box::new(expr),
+                collation: self.name()?,
+            };
+        }
+        Ok(expr)
+    }
+
+    fn unary_expr(&mut self) -> Result<Expr> {
+        let op = match self.peek() {
+            Some(Token::Minus) => UnaryOp::Negate,
+            Some(Token::Plus) => UnaryOp::Plus,
+            Some(Token::Tilde) => UnaryOp::BitNot,
+            _ => return self.primary_expr(),
+        };
+        self.pos += 1;
+        let start = self.pos;
+        let expr = self.nested(Parser::unary_expr)?;
+        // Negating 9223372036854775808 gives the smallest 64-bit integer,
+        // even with parentheses or unary plus in between.
+        let operand = self.tokens[start..self.pos]
+            .iter()
+            .filter(|token| !matches!(token, Token::LeftParen | Token::RightParen | Token::Plus));
+        if op == UnaryOp::Negate && operand.eq([&Token::MinIntegerMagnitude]) {
+            return Ok(Expr::Literal(Value::Integer(i64::MIN)));
+        }
+
+        Ok(Expr::Unary {
+            op,
+            expr: Box::new(expr),
+        })
+    }
+
+    /// Numbers a parameter like SQLite does: a bare `?` takes the number
+    /// after the largest one so far, `?NNN` takes the number NNN, and a name
+    /// takes the number of an earlier parameter by that name, if any.
+    fn parameter(&mut self, name: String) -> Result<Expr> {
+        let index = match name.strip_prefix('?') {
+            Some("") => {
+                self.parameters.push(None);
+                self.parameters.len()
+            }
+            Some(number) => {
+                let index = match number.parse::<usize>() {
+                    Ok(index) if (1..=MAX_PARAMETER).contains(&index) => index,
+                    _ => bail!("variable number must be between ?1 and ?{MAX_PARAMETER}"),
+                };
+                if index > self.parameters.len() {
+                    self.parameters.resize(index, None);
+                }
+                self.parameters[index - 1].get_or_insert_with(|| name.clone());
+                index
+            }
+            None => {
+                let existing = self
+                    .parameters
+                    .iter()
+                    .position(|p| p.as_ref() == Some(&name));
+                match existing {
+                    Some(i) => i + 1,
+                    None => {
+                        self.parameters.push(Some(name.clone()));
+                        self.parameters.len()
+                    }
+                }
+            }
+        };
+        Ok(Expr::Parameter { index, name })
+    }
+
+    fn primary_expr(&mut self) -> Result<Expr> {
+        let Some(token) = self.next() else {
+            return Err(anyhow!("incomplete input"));
+        };
+        match token {
+            Token::Integer(i) => Ok(Expr::Literal(Value::Integer(i))),
+            Token::Real(r) => Ok(Expr::Literal(Value::Real(r))),
+            Token::MinIntegerMagnitude => Ok(Expr::Literal(Value::Real(9223372036854775808.0))),
+            Token::String(s) => Ok(Expr::Literal(Value::Text(s))),
+            Token::Blob(b) => Ok(Expr::Literal(Value::Blob(b))),
+            Token::Variable(name) => self.parameter(name),
+            Token::LeftParen if self.peek().is_some_and(starts_select) => {
+                let select = self.select()?;
+                self.expect(&Token::RightParen)?;
+                Ok(Expr::Subquery(Box::new(select)))
+            }
+            Token::LeftParen => {
+                let expr = self.expr()?;
+                self.expect(&Token::RightParen)?;
+                Ok(expr)
+            }
+            Token::Word(w) if w.eq_ignore_ascii_case("NULL") => Ok(Expr::Literal(Value::Null)),
+            Token::Word(w) if w.eq_ignore_ascii_case("CASE") => self.case_expr(),
+            Token::Word(w) if w.eq_ignore_ascii_case("EXISTS") => {
+                self.expect(&Token::LeftParen)?;
+                let select = self.select()?;
+                self.expect(&Token::RightParen)?;
+                Ok(Expr::Exists(Box::new(select)))
+            }
+            Token::Word(w) if w.eq_ignore_ascii_case("CAST") && self.consume(&Token::LeftParen) => {
+                let expr = self.expr()?;
+                self.expect_keyword("AS")?;
+                let type_name = self.type_name()?;
+                self.expect(&Token::RightParen)?;
+                Ok(Expr::Cast {
+                    expr: Box::new(expr),
+                    type_name,
+                })
+            }
+            Token::Word(w) if self.peek() == Some(&Token::LeftParen) => self.function_call(w),
+            Token::Word(_) | Token::QuotedIdentifier(_) => {
+                self.pos -= 1;
+                self.column_ref()
+            }
+            _ => {
+                self.pos -= 1;
+                Err(self.error())
+            }
+        }
+    }
+
+    /// Parses `[[schema.]table.]column`.
+    fn column_ref(&mut self) -> Result<Expr> {
+        let mut parts = vec![self.identifier()?];
+        while parts.len() < 3 && self.consume(&Token::Dot) {
+            parts.push(self.identifier()?);
+        }
+        let name = parts.pop().expect("There is at least one part");
+        // The schema name is irrelevant, as there is only one database.
+        let table = parts.pop();
+
+        Ok(Expr::Column { table, name })
+    }
+
+    fn function_call(&mut self, name: String) -> Result<Expr> {
+        self.expect(&Token::LeftParen)?;
+        let mut call = FunctionCall {
+            name,
+            args: Vec::new(),
+            distinct: false,
+            star: false,
+            over: None,
+        };
+        if self.consume(&Token::Star) {
+            call.star = true;
+        } else if self.peek() != Some(&Token::RightParen) {
+            call.distinct = self.consume_keyword("DISTINCT");
+            call.args = self.expr_list()?;
+        }
+        self.expect(&Token::RightParen)?;
+
+        if self.consume_keyword("OVER") {
+            call.over = Some(if self.peek() == Some(&Token::LeftParen) {
+                self.window()?
+            } else {
+                Window {
+                    base: Some(self.name()?),
+                    partition_by: Vec::new(),
+                    order_by: Vec::new(),
+                    frame: None,
+                }
+            });
+        }
+
+        Ok(Expr::Function(call))
+    }
+
+    /// Parses `([base] [PARTITION BY ...] [ORDER BY ...] [frame])`.
+    fn window(&mut self) -> Result<Window> {
+        self.expect(&Token::LeftParen)?;
+        let starts_clause = ["PARTITION", "ORDER", "ROWS", "RANGE", "GROUPS"]
+            .iter()
+            .any(|keyword| self.peek_keyword(keyword));
+        let base = match self.peek() {
+            Some(Token::RightParen) => None,
+            _ if starts_clause => None,
+            _ => Some(self.name()?),
+        };
+
+        let mut partition_by = Vec::new();
+        if self.consume_keyword("PARTITION") {
+            self.expect_keyword("BY")?;
+            partition_by = self.expr_list()?;
+        }
+        let mut order_by = Vec::new();
+        if self.consume_keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            order_by.push(self.ordering_term()?);
+            while self.consume(&Token::Comma) {
+                order_by.push(self.ordering_term()?);
+            }
+        }
+        let frame = self.frame()?;
+        self.expect(&Token::RightParen)?;
+
+        Ok(Window {
+            base,
+            partition_by,
+            order_by,
+            frame,
+        })
+    }
+
+    /// Parses an optional frame specification of a window.
+    fn frame(&mut self) -> Result<Option<Frame>> {
+        let units = if self.consume_keyword("ROWS") {
+            FrameUnits::Rows
+        } else if self.consume_keyword("RANGE") {
+            FrameUnits::Range
+        } else if self.consume_keyword("GROUPS") {
+            FrameUnits::Groups
+        } else {
+            return Ok(None);
+        };
+
+        let (start, end) = if self.consume_keyword("BETWEEN") {
+            let start = self.frame_bound(true)?;
+            self.expect_keyword("AND")?;
+            (start, self.frame_bound(false)?)
+        } else {
+            (self.frame_bound(true)?, FrameBound::CurrentRow)
+        };
+        // The frame may not end before it starts, going by the kind of bounds.
+        let rank = |bound: &FrameBound| match bound {
+            FrameBound::UnboundedPreceding => 0,
+            FrameBound::Preceding(_) => 1,
+            FrameBound::CurrentRow => 2,
+            FrameBound::Following(_) => 3,
+            FrameBound::UnboundedFollowing => 4,
+        };
+        if rank(&start) > rank(&end) {
+            bail!("unsupported frame specification");
+        }
+
+        let exclude = if self.consume_keyword("EXCLUDE") {
+            if self.consume_keyword("NO") {
+                self.expect_keyword("OTHERS")?;
+                FrameExclude::NoOthers
+            } else if self.consume_keyword("CURRENT") {
+                self.expect_keyword("ROW")?;
+                FrameExclude::CurrentRow
+            } else if self.consume_keyword("GROUP") {
+                FrameExclude::Group
+            } else {
+                self.expect_keyword("TIES")?;
+                FrameExclude::Ties
+            }
+        } else {
+            FrameExclude::NoOthers
+        };
+
+        Ok(Some(Frame {
+            units,
+            start,
+            end,
+            exclude,
+        }))
+    }
+
+    /// Parses the start or end of a frame. A frame may not start at
+    /// `UNBOUNDED FOLLOWING` or end at `UNBOUNDED PRECEDING`.
+    fn frame_bound(&mut self, start: bool) -> Result<FrameBound> {
+        if self.consume_keyword("UNBOUNDED") {
+            if start && self.consume_keyword("PRECEDING") {
+                return Ok(FrameBound::UnboundedPreceding);
+            }
+            if !start && self.consume_keyword("FOLLOWING") {
+                return Ok(FrameBound::UnboundedFollowing);
+            }
+            return Err(self.error());
+        }
+        if self.consume_keyword("CURRENT") {
+            self.expect_keyword("ROW")?;
+            return Ok(FrameBound::CurrentRow);
+        }
+        let offset = Box::new(self.expr()?);
+        if self.consume_keyword("PRECEDING") {
+            Ok(FrameBound::Preceding(offset))
+        } else {
+            self.expect_keyword("FOLLOWING")?;
+            Ok(FrameBound::Following(offset))
+        }
+    }
+
+    fn case_expr(&mut self) -> Result<Expr> {
+        let operand = if self.peek_keyword("WHEN") {
+            None
+        } else {
+            Some(Box::new(self.expr()?))
+        };
+
+        let mut when_then = Vec::new();
+        while self.consume_keyword("WHEN") {
+            let when = self.expr()?;
+            self.expect_keyword("THEN")?;
+            let then = self.expr()?;
+            when_then.push((when, then));
+        }
+        if when_then.is_empty() {
+            return Err(self.error());
+        }
+
+        let else_expr = if self.consume_keyword("ELSE") {
+            Some(Box::new(self.expr()?))
+        } else {
+            None
+        };
+        self.expect_keyword("END")?;
+
+        Ok(Expr::Case {
+            operand,
+            when_then,
+            else_expr,
+        })
+    }
+}
+
+/// Whether a token starts a `SELECT` statement, as opposed to an expression.
+fn starts_select(token: &Token) -> bool {
+    token.is_keyword("SELECT") || token.is_keyword("WITH")
+}
+
+fn is_reserved(word: &str) -> bool {
+    RESERVED.iter().any(|r| r.eq_ignore_ascii_case(word))
+}
+
+/// Whether a word starts a column constraint, which ends the type name before it.
+fn is_constraint_keyword(word: &str) -> bool {
+    let keywords = [
+        "CONSTRAINT",
+        "PRIMARY",
+        "UNIQUE",
+        "CHECK",
+        "DEFAULT",
+        "REFERENCES",
+        "GENERATED",
+    ];
+    keywords.iter().any(|k| k.eq_ignore_ascii_case(word))
+}
+
+fn too_deep() -> anyhow::Error {
+    anyhow!("Expression tree is too large (maximum depth {MAX_EXPR_DEPTH})")
+}
+
+fn binary(op: BinaryOp, left: Expr, right: Expr) -> Expr {
+    Expr::Binary {
+        op,
+        left: Box::new(left),
+        right: Box::new(right),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::*;
+
+    fn parse_select(sql: &str) -> Select {
+        let mut statements = parse(sql).unwrap();
+        assert_eq!(statements.len(), 1);
+        let Some(Statement::Select(select)) = statements.pop() else {
+            panic!("Expected a SELECT statement");
+        };
+        *select
+    }
+
+    fn column(name: &str) -> Expr {
+        Expr::Column {
+            table: None,
+            name: name.to_string(),
+        }
+    }
+
+    fn int(i: i64) -> Expr {
+        Expr::Literal(Value::Integer(i))
+    }
+
+    fn table_name(table: &TableOrSubquery) -> &str {
+        match table {
+            TableOrSubquery::Table(table) => &table.name,
+            _ => panic!("Expected a table name"),
+        }
+    }
+
+    #[test]
+    fn parses_count_in_any_case() {
+        for sql in [
+            "SELECT COUNT(*) FROM apples",
+            "select count(*) from apples;",
+            "  Select\n Count( * )  From \"apples\" ; ",
+            "SELECT COUNT(*) FROM [apples] -- comment",
+            "SELECT /* comment */ COUNT(*) FROM `apples`",
+        ] {
+            let select = parse_select(sql);
+
+            let [ResultColumn::Expr {
+                expr: Expr::Function(call),
+                alias: None,
+            }] = &select.columns[..]
+            else {
+                panic!("Unexpected columns for {sql}");
+            };
+            assert!(call.name.eq_ignore_ascii_case("count"), "{sql}");
+            assert!(call.star, "{sql}");
+            assert_eq!(table_name(&select.from.unwrap()), "apples", "{sql}");
+        }
+    }
+
+    #[test]
+    fn parses_all_clauses() {
+        let select = parse_select(
+            "SELECT DISTINCT a AS x, t.b y, 'lit' FROM main.t AS u WHERE a > 1 \
+             GROUP BY a, b HAVING count(*) > 2 ORDER BY x DESC NULLS LAST, 2 LIMIT 5, 10",
+        );
+
+        assert!(select.distinct);
+        assert_eq!(
+            select.columns,
+            vec![
+                ResultColumn::Expr {
+                    expr: column("a"),
+                    alias: Some("x".to_string())
+                },
+                ResultColumn::Expr {
+                    expr: Expr::Column {
+                        table: Some("t".to_string()),
+                        name: "b".to_string()
+                    },
+                    alias: Some("y".to_string())
+                },
+                ResultColumn::Expr {
+                    expr: Expr::Literal(Value::Text("lit".to_string())),
+                    alias: None
+                },
+            ]
+        );
+        assert_eq!(
+            select.from,
+            Some(TableOrSubquery::Table(TableName {
+                schema: Some("main".to_string()),
+                name: "t".to_string(),
+                alias: Some("u".to_string()),
+            }))
+        );
+        assert_eq!(
+            select.where_clause,
+            Some(binary(BinaryOp::Gt, column("a"), int(1)))
+        );
+        assert_eq!(select.group_by, vec![column("a"), column("b")]);
+        assert!(select.having.is_some());
+        assert_eq!(
+            select.order_by,
+            vec![
+                OrderingTerm {
+                    expr: column("x"),
+                    descending: true,
+                    nulls_first: Some(false),
+                },
+                OrderingTerm {
+                    expr: int(2),
+                    descending: false,
+                    nulls_first: None,
+                },
+            ]
+        );
+        assert_eq!(select.limit, Some(int(10)));
+        assert_eq!(select.offset, Some(int(5)));
+    }
+
+    #[test]
+    fn respects_operator_precedence() {
+        let select = parse_select("SELECT 1 + 2 * 3 = 7 AND NOT a OR b");
+        let ResultColumn::Expr { expr, .. } = &select.columns[0] else {
+            panic!("Unexpected column");
+        };
+
+        let sum = binary(
+            BinaryOp::Add,
+            int(1),
+            binary(BinaryOp::Multiply, int(2), int(3)),
+        );
+        let not_a = Expr::Unary {
+            op: UnaryOp::Not,
+            expr: Box::new(column("a")),
+        };
+        let expected = binary(
+            BinaryOp::Or,
+            binary(BinaryOp::And, binary(BinaryOp::Eq, sum, int(7)), not_a),
+            column("b"),
+        );
+        assert_eq!(*expr, expected);
+    }
+
+    #[test]
+    fn negates_the_magnitude_of_the_smallest_integer_to_an_integer() {
+        let select = parse_select(
+            "SELECT -9223372036854775808, -(+9223372036854775808), 9223372036854775808, \
+             -9223372036854775809",
+        );
+        let exprs = select.columns.iter().map(|column| match column {
+            ResultColumn::Expr { expr, .. } => expr.clone(),
+            _ => panic!("Unexpected column"),
+        });
+        let real = |r| Expr::Literal(Value::Real(r));
+        let negated = |expr| Expr::Unary {
+            op: UnaryOp::Negate,
+            expr: Box::new(expr),
+        };
+
+        assert_eq!(
+            exprs.collect::<Vec<_>>(),
+            [
+                int(i64::MIN),
+                int(i64::MIN),
+                real(9223372036854775808.0),
+                negated(real(9223372036854775808.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_postfix_operators() {
+        let select = parse_select(
+            "SELECT a NOT IN (1, 2), b BETWEEN 1 AND 2 AND c, d IS NOT NULL, e NOTNULL, \
+             CASE f WHEN 1 THEN 'one' ELSE 'many' END, -g || 'x'",
+        );
+        let exprs = select
+            .columns
+            .into_iter()
+            .map(|c| match c {
+                ResultColumn::Expr { expr, .. } => expr,
+                _ => panic!("Unexpected column"),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            exprs[0],
+            Expr::InList {
+                expr: Box::new(column("a")),
+                list: vec![int(1), int(2)],
+                negated: true,
+            }
+        );
+        assert_eq!(
+            exprs[1],
+            binary(
+                BinaryOp::And,
+                Expr::Between {
+                    expr: Box::new(column("b")),
+                    low: Box::new(int(1)),
+                    high: Box::new(int(2)),
+                    negated: false,
+                },
+                column("c")
+            )
+        );
+        assert_eq!(
+            exprs[2],
+            binary(BinaryOp::IsNot, column("d"), Expr::Literal(Value::Null))
+        );
+        assert_eq!(
+            exprs[3],
+            Expr::IsNull {
+                expr: Box::new(column("e")),
+                negated: true,
+            }
+        );
+        assert!(matches!(exprs[4], Expr::Case { .. }));
+        assert_eq!(
+            exprs[5],
+            binary(
+                BinaryOp::Concat,
+                Expr::Unary {
+                    op: UnaryOp::Negate,
+                    expr: Box::new(column("g")),
+                },
+                Expr::Literal(Value::Text("x".to_string()))
+            )
+        );
+    }
+
+    #[test]
+    fn parses_column_definitions() {
+        let sql = "CREATE TABLE \"t\" (
+            id integer primary key autoincrement,
+            name VARCHAR(255) NOT NULL COLLATE nocase,
+            price DECIMAL(10, -2) DEFAULT -1.5 CHECK (price >= 0),
+            owner REFERENCES users(id) ON DELETE SET NULL DEFERRABLE INITIALLY DEFERRED,
+            total AS (price * 2) STORED,
+            note
+        )";
+        let Some(Statement::CreateTable(table)) = parse(sql).unwrap().pop() else {
+            panic!("Expected CREATE TABLE");
+        };
+
+        let columns = table
+            .columns
+            .iter()
+            .map(|c| (c.name.as_str(), c.declared_type.as_deref(), c.affinity))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            columns,
+            vec![
+                ("id", Some("integer"), Affinity::Integer),
+                ("name", Some("VARCHAR(255)"), Affinity::Text),
+                ("price", Some("DECIMAL(10,-2)"), Affinity::Numeric),
+                ("owner", None, Affinity::Blob),
+                ("total", None, Affinity::Blob),
+                ("note", None, Affinity::Blob),
+            ]
+        );
+
+        let kinds = |i: usize| {
+            table.columns[i]
+                .constraints
+                .iter()
+                .map(|c| c.kind.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            kinds(0),
+            vec![ColumnConstraintKind::PrimaryKey {
+                descending: false,
+                autoincrement: true
+            }]
+        );
+        assert_eq!(
+            kinds(1),
+            vec![
+                ColumnConstraintKind::NotNull,
+                ColumnConstraintKind::Collate("nocase".to_string())
+            ]
+        );
+        assert_eq!(
+            kinds(2),
+            vec![
+                ColumnConstraintKind::Default(Expr::Unary {
+                    op: UnaryOp::Negate,
+                    expr: Box::new(Expr::Literal(Value::Real(1.5)))
+                }),
+                ColumnConstraintKind::Check(binary(BinaryOp::GtEq, column("price"), int(0)))
+            ]
+        );
+        assert_eq!(
+            kinds(3),
+            vec![ColumnConstraintKind::References(ForeignKey {
+                table: "users".to_string(),
+                columns: vec!["id".to_string()],
+                on_delete: Some(ForeignKeyAction::SetNull),
+                on_update: None,
+            })]
+        );
+        assert_eq!(
+            kinds(4),
+            vec![ColumnConstraintKind::Generated {
+                expr: binary(BinaryOp::Multiply, column("price"), int(2)),
+                stored: true
+            }]
+        );
+        assert!(kinds(5).is_empty());
+        assert_eq!(table.primary_key()[0].name, "id");
+    }
+
+    #[test]
+    fn parses_table_constraints_and_options() {
+        let sql = "CREATE TABLE IF NOT EXISTS main.t (
+            a, b ANY, c,
+            CONSTRAINT pk PRIMARY KEY (b, a COLLATE nocase DESC) ON CONFLICT REPLACE
+            UNIQUE (c),
+            FOREIGN KEY (c) REFERENCES other
+        ) without
+          rowid, STRICT";
+        let Some(Statement::CreateTable(table)) = parse(sql).unwrap().pop() else {
+            panic!("Expected CREATE TABLE");
+        };
+
+        assert!(table.if_not_exists);
+        assert_eq!(table.schema.as_deref(), Some("main"));
+        assert!(table.without_rowid && table.strict);
+        assert_eq!(table.columns.len(), 3);
+        // ANY columns of strict tables don't convert values.
+        assert_eq!(table.columns[1].affinity, Affinity::Blob);
+        assert_eq!(table.constraints.len(), 3);
+        assert_eq!(table.constraints[0].name.as_deref(), Some("pk"));
+        assert_eq!(
+            table.primary_key(),
+            vec![
+                IndexedColumn {
+                    name: "b".to_string(),
+                    collation: None,
+                    descending: false,
+                },
+                IndexedColumn {
+                    name: "a".to_string(),
+                    collation: Some("nocase".to_string()),
+                    descending: true,
+                },
+            ]
+        );
+
+        let Some(Statement::CreateTable(table)) =
+            parse("CREATE TABLE t (a DEFAULT 'without rowid')")
+                .unwrap()
+                .pop()
+        else {
+            panic!("Expected CREATE TABLE");
+        };
+        assert!(!table.without_rowid && !table.strict);
+    }
+
+    #[test]
+    fn finds_rowid_alias() {
+        let cases = vec![
+            ("CREATE TABLE t (a, id INTEGER PRIMARY KEY)", Some(1)),
+            (
+                "CREATE TABLE t (id integer, PRIMARY KEY (id DESC))",
+                Some(0),
+            ),
+            ("CREATE TABLE t (id INTEGER PRIMARY KEY DESC)", None),
+            ("CREATE TABLE t (id INT PRIMARY KEY)", None),
+            ("CREATE TABLE t (id INTEGER, b, PRIMARY KEY (id, b))", None),
+            (
+                "CREATE TABLE t (id INTEGER PRIMARY KEY) WITHOUT ROWID",
+                None,
+            ),
+        ];
+
+        for (sql, expected) in cases {
+            let Some(Statement::CreateTable(table)) = parse(sql).unwrap().pop() else {
+                panic!("Expected CREATE TABLE");
+            };
+            assert_eq!(table.rowid_alias(), expected, "{sql}");
+        }
+    }
+
+    #[test]
+    fn parses_compound_selects() {
+        let select = parse_select(
+            "SELECT a FROM t UNION ALL SELECT b FROM u EXCEPT SELECT 1 ORDER BY 1 LIMIT 2",
+        );
+
+        let operators = select.compound.iter().map(|c| c.operator);
+        assert_eq!(
+            operators.collect::<Vec<_>>(),
+            vec![CompoundOperator::UnionAll, CompoundOperator::Except]
+        );
+        assert_eq!(
+            table_name(select.compound[0].select.from.as_ref().unwrap()),
+            "u"
+        );
+        // ORDER BY and LIMIT belong to the whole compound.
+        assert_eq!(select.order_by.len(), 1);
+        assert_eq!(select.limit, Some(int(2)));
+        assert!(select.compound[1].select.order_by.is_empty());
+    }
+
+    #[test]
+    fn parses_joins() {
+        let select = parse_select(
+            "SELECT * FROM a, b NATURAL LEFT OUTER JOIN c CROSS JOIN d ON x JOIN e USING (y, z)",
+        );
+
+        let joins = select
+            .joins
+            .iter()
+            .map(|join| (table_name(&join.table), join.operator, join.natural))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            joins,
+            vec![
+                ("b", JoinOperator::Inner, false),
+                ("c", JoinOperator::Left, true),
+                ("d", JoinOperator::Cross, false),
+                ("e", JoinOperator::Inner, false),
+            ]
+        );
+        assert!(matches!(
+            select.joins[2].constraint,
+            Some(JoinConstraint::On(_))
+        ));
+        let Some(JoinConstraint::Using(columns)) = &select.joins[3].constraint else {
+            panic!("Expected USING");
+        };
+        assert_eq!(columns, &["y", "z"]);
+    }
+
+    #[test]
+    fn parses_subqueries() {
+        let select = parse_select(
+            "SELECT (SELECT 1), EXISTS (SELECT 2) FROM (SELECT 3) AS s WHERE a NOT IN (SELECT b FROM t)",
+        );
+
+        let exprs = select.columns.iter().map(|column| match column {
+            ResultColumn::Expr { expr, .. } => expr,
+            _ => panic!("Expected an expression"),
+        });
+        let exprs = exprs.collect::<Vec<_>>();
+        assert!(matches!(exprs[0], Expr::Subquery(_)));
+        assert!(matches!(exprs[1], Expr::Exists(_)));
+        assert!(matches!(
+            &select.from,
+            Some(TableOrSubquery::Subquery { alias: Some(alias), .. }) if alias == "s"
+        ));
+        let Some(Expr::InSelect {
+            select: subquery,
+            negated: true,
+            ..
+        }) = &select.where_clause
+        else {
+            panic!("Expected NOT IN (SELECT ...)");
+        };
+        assert_eq!(subquery.to_string(), "SELECT b FROM t");
+        // A parenthesized expression is not a subquery.
+        assert_eq!(
+            parse_select("SELECT (1)").columns[0],
+            parse_select("SELECT 1").columns[0]
+        );
+    }
+
+    #[test]
+    fn parses_windows() {
+        let sql = "SELECT sum(a) OVER (w ORDER BY b DESC ROWS BETWEEN 2 PRECEDING AND CURRENT ROW \
+                   EXCLUDE TIES), rank() OVER w FROM t WINDOW w AS (PARTITION BY c)";
+        let select = parse_select(sql);
+
+        let ResultColumn::Expr {
+            expr: Expr::Function(call),
+            ..
+        } = &select.columns[0]
+        else {
+            panic!("Expected a function call");
+        };
+        let window = call.over.as_ref().unwrap();
+        assert_eq!(window.base.as_deref(), Some("w"));
+        assert!(window.order_by[0].descending);
+        let frame = window.frame.as_ref().unwrap();
+        assert_eq!(frame.units, FrameUnits::Rows);
+        assert_eq!(frame.start, FrameBound::Preceding(Box::new(int(2))));
+        assert_eq!(frame.end, FrameBound::CurrentRow);
+        assert_eq!(frame.exclude, FrameExclude::Ties);
+        assert_eq!(select.windows[0].0, "w");
+        assert_eq!(select.windows[0].1.partition_by, [column("c")]);
+        assert_eq!(select.to_string(), sql);
+
+        let error = |sql| parse(sql).unwrap_err().to_string();
+        assert_eq!(
+            error("SELECT sum(a) OVER (ROWS BETWEEN 1 FOLLOWING AND CURRENT ROW) FROM t"),
+            "unsupported frame specification"
+        );
+        assert_eq!(
+            error("SELECT sum(a) OVER (ROWS BETWEEN CURRENT ROW AND UNBOUNDED PRECEDING) FROM t"),
+            "near \"PRECEDING\": syntax error"
+        );
+    }
+
+    #[test]
+    fn parses_with() {
+        let select = parse_select(
+            "WITH RECURSIVE a(x, y) AS (SELECT 1, 2), b AS NOT MATERIALIZED (SELECT * FROM a) \
+             SELECT * FROM b",
+        );
+
+        let with = select.with.as_ref().unwrap();
+        assert!(with.recursive);
+        assert_eq!(with.ctes[0].name, "a");
+        assert_eq!(with.ctes[0].columns, ["x", "y"]);
+        assert_eq!(with.ctes[1].select.to_string(), "SELECT * FROM a");
+        assert_eq!(
+            select.to_string(),
+            "WITH RECURSIVE a(x, y) AS (SELECT 1, 2), b AS (SELECT * FROM a) SELECT * FROM b"
+        );
+    }
+
+    #[test]
+    fn parses_create_index() {
+        let statements =
+            parse("CREATE UNIQUE INDEX IF NOT EXISTS i ON t (a COLLATE nocase, b DESC) WHERE c")
+                .unwrap();
+
+        let [Statement::CreateIndex(index)] = &statements[..] else {
+            panic!("Expected CREATE INDEX");
+        };
+        assert!(index.unique && index.if_not_exists);
+        assert_eq!((index.name.as_str(), index.table.as_str()), ("i", "t"));
+        assert_eq!(index.columns.len(), 2);
+        assert!(index.columns[1].descending);
+        assert!(index.where_clause.is_some());
+    }
+
+    #[test]
+    fn parses_create_view() {
+        let statements =
+            parse("CREATE TEMP VIEW IF NOT EXISTS main.v(a, b) AS SELECT x, y FROM t").unwrap();
+
+        let [Statement::CreateView(view)] = &statements[..] else {
+            panic!("Expected CREATE VIEW");
+        };
+        assert!(view.temporary && view.if_not_exists);
+        assert_eq!(
+            (view.schema.as_deref(), view.name.as_str()),
+            (Some("main"), "v")
+        );
+        assert_eq!(view.columns, ["a", "b"]);
+        assert_eq!(view.select.columns.len(), 2);
+        assert_eq!(
+            parse("CREATE VIEW v AS SELECT ?").unwrap_err().to_string(),
+            "parameters are not allowed in views"
+        );
+    }
+
+    #[test]
+    fn limits_the_depth_of_expressions() {
+        let parse_nested = |depth: usize, open: &str, close: &str| {
+            let sql = format!("SELECT {}1{}", open.repeat(depth), close.repeat(depth));
+            // Nested expressions need more stack than tests run with.
+            let thread = std::thread::Builder::new()
+                .stack_size(crate::STACK_SIZE)
+                .spawn(move || parse(&sql).map(|_| ()).map_err(|e| e.to_string()))
+                .unwrap();
+            thread.join().unwrap()
+        };
+        let too_deep = Err("Expression tree is too large (maximum depth 1000)".to_string());
+
+        assert_eq!(parse_nested(999, "(", ")"), Ok(()));
+        assert_eq!(parse_nested(1000, "(", ")"), too_deep);
+        assert_eq!(parse_nested(999, "abs(", ")"), Ok(()));
+        assert_eq!(parse_nested(5000, "abs(", ")"), too_deep);
+        assert_eq!(parse_nested(999, "- ", ""), Ok(()));
+        assert_eq!(parse_nested(1000, "NOT ", ""), too_deep);
+        // Chains of operators are as deep as they are long.
+        assert_eq!(parse_nested(999, "", "+1"), Ok(()));
+        assert_eq!(parse_nested(1000, "", " AND 1"), too_deep);
+    }
+
+    #[test]
+    fn parses_json_operators_and_table_functions() {
+        let select =
+            parse_select("SELECT 'x' || j -> 'a' ->> 0 + 1 FROM t, json_each(t.j, '$.a') AS e");
+
+        // `->` and `->>` bind like `||`, from left to right and more tightly
+        // than `+`.
+        let ResultColumn::Expr {
+            expr:
+                Expr::Binary {
+                    op: BinaryOp::Add,
+                    left,
+                    ..
+                },
+            ..
+        } = &select.columns[0]
+        else {
+            panic!("Expected +");
+        };
+        let Expr::Binary {
+            op: BinaryOp::ExtractValue,
+            left,
+            ..
+        } = &**left
+        else {
+            panic!("Expected ->>");
+        };
+        let Expr::Binary {
+            op: BinaryOp::Extract,
+            left,
+            ..
+        } = &**left
+        else {
+            panic!("Expected ->");
+        };
+        assert!(matches!(
+            **left,
+            Expr::Binary {
+                op: BinaryOp::Concat,
+                ..
+            }
+        ));
+        let [Join {
+            table: TableOrSubquery::Function { name, args, alias },
+            ..
+        }] = &select.joins[..]
+        else {
+            panic!("Expected a table-valued function");
+        };
+        assert_eq!((name.as_str(), alias.as_deref()), ("json_each", Some("e")));
+        assert_eq!(args.len(), 2);
+        assert!(matches!(
+            parse_select("SELECT * FROM json_tree()").from,
+            Some(TableOrSubquery::Function { args, alias: None, .. }) if args.is_empty()
+        ));
+    }
+
+    #[test]
+    fn parses_pattern_operators() {
+        let select = parse_select("SELECT a NOT LIKE 'x!%' ESCAPE '!', b GLOB 'y*' COLLATE nocase");
+        let exprs = select
+            .columns
+            .into_iter()
+            .map(|c| match c {
+                ResultColumn::Expr { expr, .. } => expr,
+                _ => panic!("Unexpected column"),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            exprs[0],
+            Expr::Like {
+                op: PatternOp::Like,
+                expr: Box::new(column("a")),
+                pattern: Box::new(Expr::Literal(Value::Text("x!%".to_string()))),
+                escape: Some(Box::new(Expr::Literal(Value::Text("!".to_string())))),
+                negated: true,
+            }
+        );
+        assert!(matches!(
+            &exprs[1],
+            Expr::Like { op: PatternOp::Glob, pattern, escape: None, .. }
+                if matches!(**pattern, Expr::Collate { .. })
+        ));
+        assert_eq!(
+            parse("SELECT a REGEXP 'x' ESCAPE 'y'")
+                .unwrap_err()
+                .to_string(),
+            "wrong number of arguments to function regexp()"
+        );
+    }
+
+    #[test]
+    fn parses_pragmas() {
+        let statements =
+            parse("PRAGMA main.case_sensitive_like = ON; PRAGMA cache_size(-2000)").unwrap();
+
+        assert_eq!(
+            statements,
+            vec![
+                Statement::Pragma(Pragma {
+                    schema: Some("main".to_string()),
+                    name: "case_sensitive_like".to_string(),
+                    value: Some("ON".to_string()),
+                }),
+                Statement::Pragma(Pragma {
+                    schema: None,
+                    name: "cache_size".to_string(),
+                    value: Some("-2000".to_string()),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_multiple_statements() {
+        let statements = parse("SELECT 1; ; select 2;").unwrap();
+
+        assert_eq!(statements.len(), 2);
+    }
+
+    #[test]
+    fn numbers_parameters() {
+        let mut statements =
+            parse_with_parameters("SELECT ?, :a, ?5, ?, @b, :a, ?2; SELECT $c").unwrap();
+        let names = |names: &[Option<&str>]| {
+            names
+                .iter()
+                .map(|name| name.map(str::to_string))
+                .collect::<Vec<_>>()
+        };
+
+        let (statement, parameters) = statements.remove(0);
+        assert_eq!(
+            parameters,
+            names(&[None, Some(":a"), None, None, Some("?5"), None, Some("@b")])
+        );
+        let Statement::Select(select) = statement else {
+            panic!("Expected SELECT");
+        };
+        let indexes = select
+            .columns
+            .iter()
+            .map(|column| match column {
+                ResultColumn::Expr {
+                    expr: Expr::Parameter { index, .. },
+                    ..
+                } => *index,
+                _ => panic!("Expected a parameter"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(indexes, [1, 2, 5, 6, 7, 2, 2]);
+        // Each statement numbers its parameters on its own.
+        assert_eq!(statements[0].1, names(&[Some("$c")]));
+
+        for sql in ["SELECT ?0", "SELECT ?32767"] {
+            assert_eq!(
+                parse(sql).unwrap_err().to_string(),
+                "variable number must be between ?1 and ?32766"
+            );
+        }
+    }
+
+    #[test]
+    fn reports_syntax_errors() {
+        let errors = vec![
+            ("SELECT FROM t", "near \"FROM\": syntax error"),
+            ("SELECT a FROM", "incomplete input"),
+            ("SELECT a b c FROM t", "near \"c\": syntax error"),
+            ("DELETE FROM t", "near \"DELETE\": syntax error"),
+            (
+                "CREATE TABLE t (a, PRIMARY KEY (a),)",
+                "near \")\": syntax error",
+            ),
+            ("CREATE TABLE t (a) WITHOUT", "incomplete input"),
+        ];
+
+        for (sql, expected) in errors {
+            assert_eq!(parse(sql).unwrap_err().to_string(), expected, "{sql}");
+        }
+    }
+}
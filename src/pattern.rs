@@ -0,0 +1,596 @@
+use anyhow::{bail, Result};
+
+/// The longest `LIKE` or `GLOB` pattern, in bytes, like SQLite's default
+/// `SQLITE_MAX_LIKE_PATTERN_LENGTH`.
+const MAX_PATTERN_LENGTH: usize = 50000;
+
+/// A piece of a `LIKE` or `GLOB` pattern.
+enum Piece {
+    /// `%` or `*`: any number of characters.
+    Any,
+    /// `_` or `?`: a single character.
+    One,
+    Char(char),
+    /// `[...]` in a `GLOB` pattern: a character in, or with `^` not in, the
+    /// given ranges.
+    Set {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+    },
+    /// What a pattern ending in an escape character or an unclosed `[`
+    /// ends in, which nothing matches.
+    Never,
+}
+
+/// `text LIKE pattern [ESCAPE escape]`: `%` matches any number of
+/// characters, `_` any single one, and ASCII letters match either case
+/// unless `case_sensitive`. The escape character makes the character after
+/// it match only itself.
+pub fn like(pattern: &str, text: &str, escape: Option<char>, case_sensitive: bool) -> Result<bool> {
+    check_length(pattern)?;
+    let mut pieces = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        pieces.push(match c {
+            c if Some(c) == escape => chars.next().map_or(Piece::Never, Piece::Char),
+            '%' => Piece::Any,
+            '_' => Piece::One,
+            c => Piece::Char(c),
+        });
+    }
+    let fold = |c: char| {
+        if case_sensitive {
+            c
+        } else {
+            c.to_ascii_lowercase()
+        }
+    };
+    Ok(matches(&pieces, text, |piece, c| match piece {
+        Piece::Char(p) => fold(*p) == fold(c),
+        _ => false,
+    }))
+}
+
+/// `text GLOB pattern`: `*` matches any number of characters, `?` any single
+/// one, and `[...]` one of a set of characters, like in Unix file names.
+/// Letters match their case only.
+pub fn glob(pattern: &str, text: &str) -> Result<bool> {
+    check_length(pattern)?;
+    let mut pieces = Vec::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        pieces.push(match c {
+            '*' => Piece::Any,
+            '?' => Piece::One,
+            '[' => {
+                let negated = chars.next_if_eq(&'^').is_some();
+                let mut ranges = Vec::new();
+                // A `]` right at the start is part of the set.
+                if chars.next_if_eq(&']').is_some() {
+                    ranges.push((']', ']'));
+                }
+                let mut closed = false;
+                while let Some(c) = chars.next() {
+                    if c == ']' {
+                        closed = true;
+                        break;
+                    }
+                    // A `-` between two characters makes a range of them.
+                    let range_end = match (c, ranges.last(), chars.peek()) {
+                        ('-', Some(_), Some(&end)) if end != ']' => Some(end),
+                        _ => None,
+                    };
+                    match range_end {
+                        Some(end) => {
+                            chars.next();
+                            let (start, _) = ranges.pop().expect("A range has a start");
+                            ranges.push((start, end));
+                        }
+                        None => ranges.push((c, c)),
+                    }
+                }
+                if closed {
+                    Piece::Set { ranges, negated }
+                } else {
+                    Piece::Never
+                }
+            }
+            c => Piece::Char(c),
+        });
+    }
+    Ok(matches(&pieces, text, |piece, c| match piece {
+        Piece::Char(p) => *p == c,
+        Piece::Set { ranges, negated } => {
+            ranges
+                .iter()
+                .any(|&(start, end)| (start..=end).contains(&c))
+                != *negated
+        }
+        _ => false,
+    }))
+}
+
+fn check_length(pattern: &str) -> Result<()> {
+    if pattern.len() > MAX_PATTERN_LENGTH {
+        bail!("LIKE or GLOB pattern too complex");
+    }
+    Ok(())
+}
+
+/// Matches text against the pieces of a pattern. On a mismatch after `Any`,
+/// it backtracks to let that `Any` match one more character, which is all
+/// the backtracking needed as every other piece matches a single character.
+fn matches(pieces: &[Piece], text: &str, matches_char: impl Fn(&Piece, char) -> bool) -> bool {
+    let text = text.chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    loop {
+        match pieces.get(p) {
+            Some(Piece::Any) => {
+                p += 1;
+                backtrack = Some((p, t));
+                continue;
+            }
+            Some(piece) if t < text.len() => {
+                let matched = match piece {
+                    Piece::One => true,
+                    Piece::Never => false,
+                    piece => matches_char(piece, text[t]),
+                };
+                if matched {
+                    p += 1;
+                    t += 1;
+                    continue;
+                }
+            }
+            None if t == text.len() => return true,
+            _ => {}
+        }
+        match backtrack {
+            Some((after_any, start)) if start < text.len() => {
+                backtrack = Some((after_any, start + 1));
+                p = after_any;
+                t = start + 1;
+            }
+            _ => return false,
+        }
+    }
+}
+
+/// An instruction of a compiled regular expression, which is run by
+/// simulating all the paths through the program at once, like SQLite's
+/// `regexp` extension does.
+#[derive(Clone, Debug)]
+enum Op {
+    /// Matches the character, where `'\0'` is the end of the text.
+    Char(char),
+    Any,
+    Word(bool),
+    Digit(bool),
+    Space(bool),
+    /// A set of characters, or with `true` the characters not in it.
+    Set(Vec<(char, char)>, bool),
+    /// Continues both after this instruction and at the relative offset.
+    Fork(isize),
+    Goto(isize),
+    AtStart,
+    WordBoundary,
+    Accept,
+}
+
+/// `text REGEXP pattern`: whether the regular expression matches any part of
+/// the text. The syntax is that of SQLite's `regexp` extension: `X*`, `X+`,
+/// `X?`, `X{m,n}`, `(X)`, `X|Y`, `^`, `$`, `.`, `[...]`, `[^...]`, `\d`,
+/// `\w`, `\s` and their negations, `\b`, and escaped characters.
+pub fn regexp(pattern: &str, text: &str) -> Result<bool> {
+    let mut compiler = Compiler {
+        pattern: pattern.chars().collect(),
+        pos: 0,
+        ops: Vec::new(),
+    };
+    // A pattern that isn't anchored at the start may match anywhere.
+    if compiler.peek() == Some('^') {
+        compiler.pos += 1;
+    } else {
+        compiler.ops.extend([Op::Fork(3), Op::Any, Op::Goto(-2)]);
+    }
+    compiler.alternatives()?;
+    if compiler.peek().is_some() {
+        bail!("unrecognized character");
+    }
+    compiler.ops.push(Op::Accept);
+    Ok(run(&compiler.ops, text))
+}
+
+struct Compiler {
+    pattern: Vec<char>,
+    pos: usize,
+    ops: Vec<Op>,
+}
+
+impl Compiler {
+    fn peek(&self) -> Option<char> {
+        self.pattern.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    /// Compiles `X|Y|...`.
+    fn alternatives(&mut self) -> Result<()> {
+        let start = self.ops.len();
+        self.sequence()?;
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            let end = self.ops.len();
+            self.ops.insert(start, Op::Fork((end + 2 - start) as isize));
+            let goto = self.ops.len();
+            self.ops.push(Op::Goto(0));
+            self.sequence()?;
+            self.ops[goto] = Op::Goto((self.ops.len() - goto) as isize);
+        }
+        Ok(())
+    }
+
+    /// Compiles a sequence of atoms, each of which may be repeated, up to
+    /// the end of the pattern or the group, or the next `|`.
+    fn sequence(&mut self) -> Result<()> {
+        // Where the instructions of the last atom start.
+        let mut previous = None;
+        while let Some(c) = self.next() {
+            let start = self.ops.len();
+            match c {
+                '|' | ')' => {
+                    self.pos -= 1;
+                    return Ok(());
+                }
+                '(' => {
+                    self.alternatives()?;
+                    if self.next() != Some(')') {
+                        bail!("unmatched '('");
+                    }
+                }
+                '.' => self.ops.push(Op::Any),
+                '*' | '+' | '?' | '{' => {
+                    let Some(previous) = previous else {
+                        if c == '{' {
+                            bail!("'{{m,n}}' without operand");
+                        }
+                        bail!("'{c}' without operand");
+                    };
+                    self.repeat(c, previous)?;
+                    continue;
+                }
+                '$' => self.ops.push(Op::Char('\0')),
+                '^' => self.ops.push(Op::AtStart),
+                '[' => self.set()?,
+                '\\' => {
+                    let op = match self.peek() {
+                        Some('b') => Some(Op::WordBoundary),
+                        Some('d') => Some(Op::Digit(true)),
+                        Some('D') => Some(Op::Digit(false)),
+                        Some('s') => Some(Op::Space(true)),
+                        Some('S') => Some(Op::Space(false)),
+                        Some('w') => Some(Op::Word(true)),
+                        Some('W') => Some(Op::Word(false)),
+                        _ => None,
+                    };
+                    match op {
+                        Some(op) => {
+                            self.pos += 1;
+                            self.ops.push(op);
+                        }
+                        None => {
+                            let c = self.escaped()?;
+                            self.ops.push(Op::Char(c));
+                        }
+                    }
+                }
+                c => self.ops.push(Op::Char(c)),
+            }
+            previous = Some(start);
+        }
+        Ok(())
+    }
+
+    /// Repeats the atom whose instructions start at `start`.
+    fn repeat(&mut self, operator: char, start: usize) -> Result<()> {
+        let len = (self.ops.len() - start) as isize;
+        match operator {
+            '*' => {
+                self.ops.insert(start, Op::Goto(len + 1));
+                self.ops.push(Op::Fork(-len));
+            }
+            '+' => self.ops.push(Op::Fork(-len)),
+            '?' => self.ops.insert(start, Op::Fork(len + 1)),
+            _ => {
+                let number = |compiler: &mut Self| {
+                    let mut n = 0usize;
+                    while let Some(digit) = compiler.peek().and_then(|c| c.to_digit(10)) {
+                        n = n.saturating_mul(10).saturating_add(digit as usize);
+                        compiler.pos += 1;
+                    }
+                    n
+                };
+                let min = number(self);
+                let max = if self.peek() == Some(',') {
+                    self.pos += 1;
+                    number(self)
+                } else {
+                    min
+                };
+                if self.next() != Some('}') {
+                    bail!("unmatched '{{'");
+                }
+                if max < min {
+                    bail!("n less than m in '{{m,n}}'");
+                }
+                if max == 0 {
+                    bail!("both m and n are zero in '{{m,n}}'");
+                }
+                let atom = self.ops[start..].to_vec();
+                self.ops.truncate(start);
+                // The required copies, then the optional ones, each of which
+                // may be skipped along with the rest.
+                for _ in 0..min {
+                    self.ops.extend(atom.iter().cloned());
+                }
+                for _ in min..max {
+                    self.ops.push(Op::Fork(len + 1));
+                    self.ops.extend(atom.iter().cloned());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Compiles `[...]` or `[^...]`, after the `[`.
+    fn set(&mut self) -> Result<()> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut ranges = Vec::new();
+        loop {
+            let Some(mut c) = self.next() else {
+                bail!("unclosed '['");
+            };
+            if c == '[' && self.peek() == Some(':') {
+                bail!("POSIX character classes not supported");
+            }
+            if c == '\\' {
+                c = self.escaped()?;
+            }
+            if self.peek() == Some('-') {
+                self.pos += 1;
+                let mut end = self.next().unwrap_or('\0');
+                if end == '\\' {
+                    end = self.escaped()?;
+                }
+                ranges.push((c, end));
+            } else {
+                ranges.push((c, c));
+            }
+            if self.peek() == Some(']') {
+                self.pos += 1;
+                break;
+            }
+        }
+        self.ops.push(Op::Set(ranges, negated));
+        Ok(())
+    }
+
+    /// The character escaped by a `\`, after it: `\xHH`, `\uHHHH`, a C escape
+    /// like `\t`, or a character that has a special meaning otherwise.
+    fn escaped(&mut self) -> Result<char> {
+        let Some(c) = self.peek() else {
+            // A trailing backslash matches the end of the text, like `$`.
+            return Ok('\0');
+        };
+        for (prefix, digits) in [('u', 4), ('x', 2)] {
+            if c == prefix && self.pos + digits < self.pattern.len() {
+                let hex = &self.pattern[self.pos + 1..=self.pos + digits];
+                let code = hex
+                    .iter()
+                    .try_fold(0, |code, c| c.to_digit(16).map(|digit| code * 16 + digit));
+                if let Some(c) = code.and_then(char::from_u32) {
+                    self.pos += digits + 1;
+                    return Ok(c);
+                }
+            }
+        }
+        let translated = match c {
+            'a' => '\x07',
+            'f' => '\x0c',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'v' => '\x0b',
+            '\\' | '(' | ')' | '*' | '.' | '+' | '?' | '[' | '$' | '^' | '{' | '|' | '}' | ']' => c,
+            _ => bail!("unknown \\ escape"),
+        };
+        self.pos += 1;
+        Ok(translated)
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn is_space_char(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0b' | '\x0c')
+}
+
+/// Runs a compiled regular expression over the text, keeping track of the
+/// set of instructions the paths through the program are at.
+fn run(ops: &[Op], text: &str) -> bool {
+    // Adds an instruction to a set, unless it is already there.
+    fn add(set: &mut Vec<usize>, op: usize) {
+        if !set.contains(&op) {
+            set.push(op);
+        }
+    }
+
+    // The end of the text is matched as `'\0'`, after which a last step
+    // with no character finds out whether any path got to the end.
+    let chars = text.chars().chain(['\0']).map(Some).chain([None]);
+    let mut previous = None;
+    let mut next = vec![0];
+    for c in chars {
+        if next.is_empty() {
+            break;
+        }
+        let mut current = std::mem::take(&mut next);
+        let mut i = 0;
+        while i < current.len() {
+            let pc = current[i];
+            i += 1;
+            let matches_char =
+                |matches: &dyn Fn(char) -> bool| c.is_some_and(|c| c != '\0' && matches(c));
+            let advance = match &ops[pc] {
+                Op::Char(expected) => c == Some(*expected),
+                Op::Any => matches_char(&|_| true),
+                Op::Word(positive) => matches_char(&|c| is_word_char(c) == *positive),
+                Op::Digit(positive) => matches_char(&|c| c.is_ascii_digit() == *positive),
+                Op::Space(positive) => matches_char(&|c| is_space_char(c) == *positive),
+                Op::Set(ranges, negated) => matches_char(&|c| {
+                    ranges
+                        .iter()
+                        .any(|&(start, end)| (start..=end).contains(&c))
+                        != *negated
+                }),
+                Op::Fork(offset) => {
+                    add(&mut current, pc.wrapping_add_signed(*offset));
+                    add(&mut current, pc + 1);
+                    false
+                }
+                Op::Goto(offset) => {
+                    add(&mut current, pc.wrapping_add_signed(*offset));
+                    false
+                }
+                Op::AtStart => {
+                    if previous.is_none() {
+                        add(&mut current, pc + 1);
+                    }
+                    false
+                }
+                Op::WordBoundary => {
+                    let before = previous.is_some_and(is_word_char);
+                    if c.is_some_and(is_word_char) != before {
+                        add(&mut current, pc + 1);
+                    }
+                    false
+                }
+                Op::Accept => return true,
+            };
+            if advance {
+                add(&mut next, pc + 1);
+            }
+        }
+        previous = c;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pattern::*;
+
+    #[test]
+    fn matches_like_patterns() {
+        let cases = [
+            ("a%", "ABC", None, false, true),
+            ("a%", "ABC", None, true, false),
+            ("_b_", "abc", None, false, true),
+            ("%b", "abc", None, false, false),
+            ("%", "", None, false, true),
+            ("é%", "É", None, false, false),
+            ("100\\%", "100%", Some('\\'), false, true),
+            ("100\\%", "1000", Some('\\'), false, false),
+            ("a\\", "a", Some('\\'), false, false),
+            ("%a%b%c%", "xxaxxbxxcxx", None, false, true),
+            ("a%%b", "ab", None, false, true),
+        ];
+
+        for (pattern, text, escape, case_sensitive, expected) in cases {
+            assert_eq!(
+                like(pattern, text, escape, case_sensitive).unwrap(),
+                expected,
+                "{text} LIKE {pattern}"
+            );
+        }
+    }
+
+    #[test]
+    fn matches_glob_patterns() {
+        let cases = [
+            ("a*", "abc", true),
+            ("A*", "abc", false),
+            ("?b?", "abc", true),
+            ("[a-c]x", "bx", true),
+            ("[^a-c]x", "bx", false),
+            ("[]]", "]", true),
+            ("[a-]", "-", true),
+            ("[abc", "a", false),
+            ("*.rs", "main.rs", true),
+        ];
+
+        for (pattern, text, expected) in cases {
+            assert_eq!(
+                glob(pattern, text).unwrap(),
+                expected,
+                "{text} GLOB {pattern}"
+            );
+        }
+    }
+
+    #[test]
+    fn matches_regular_expressions() {
+        let cases = [
+            ("b", "abc", true),
+            ("^b", "abc", false),
+            ("^a{2}$", "aa", true),
+            ("a{2}", "a{2}", false),
+            ("a{,2}", "a", true),
+            ("(ab|cd)c", "abc", true),
+            ("^(a|ab)$", "ab", true),
+            ("\\bworld\\b", "hello world", true),
+            ("\\w\\d\\s", "a1 ", true),
+            ("[]x]", "x", true),
+            ("^.$", "é", true),
+            ("\\x41\\u0042", "AB", true),
+            ("a**", "a", true),
+            ("", "a", true),
+            ("^(a*)*$", "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaab", false),
+        ];
+
+        for (pattern, text, expected) in cases {
+            assert_eq!(
+                regexp(pattern, text).unwrap(),
+                expected,
+                "{text} REGEXP {pattern}"
+            );
+        }
+
+        let errors = [
+            ("(a", "unmatched '('"),
+            ("[a", "unclosed '['"),
+            ("*a", "'*' without operand"),
+            ("a\\q", "unknown \\ escape"),
+            ("a)", "unrecognized character"),
+            ("a{3,2}", "n less than m in '{m,n}'"),
+            ("a{2", "unmatched '{'"),
+        ];
+        for (pattern, expected) in errors {
+            assert_eq!(
+                regexp(pattern, "").unwrap_err().to_string(),
+                expected,
+                "{pattern}"
+            );
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use anyhow::{bail, Result};
+
+use crate::ast::Pragma;
+use crate::database::Database;
+use crate::value::Value;
+
+/// Runs a `PRAGMA` statement, returning the rows it outputs. Like SQLite,
+/// pragmas that aren't known do nothing.
+pub fn execute(pragma: &Pragma, db: &mut Database) -> Result<Vec<Vec<Value>>> {
+    if let Some(schema) = &pragma.schema {
+        if !schema.eq_ignore_ascii_case("main") && !schema.eq_ignore_ascii_case("temp") {
+            bail!("unknown database {schema}");
+        }
+    }
+    if pragma.name.eq_ignore_ascii_case("case_sensitive_like") {
+        if let Some(value) = &pragma.value {
+            db.case_sensitive_like = boolean(value);
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// Reads the value of a boolean pragma: a number, which is true unless it is
+/// zero, or `on`, `yes` or `true`. Anything else is false.
+fn boolean(value: &str) -> bool {
+    match value.parse::<f64>() {
+        Ok(number) => number != 0.0,
+        Err(_) => ["on", "yes", "true"]
+            .iter()
+            .any(|word| value.eq_ignore_ascii_case(word)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Statement;
+    use crate::parser;
+    use crate::pragma::*;
+
+    fn run(sql: &str, db: &mut Database) -> Result<Vec<Vec<Value>>> {
+        let Some(Statement::Pragma(pragma)) = parser::parse(sql)?.pop() else {
+            panic!("Expected PRAGMA");
+        };
+        execute(&pragma, db)
+    }
+
+    #[test]
+    fn sets_case_sensitive_like() {
+        let mut db = Database::open("sample.db").unwrap();
+        let cases = [
+            ("PRAGMA case_sensitive_like = yes", true),
+            ("PRAGMA case_sensitive_like", true),
+            ("PRAGMA main.case_sensitive_like = 0", false),
+            ("PRAGMA case_sensitive_like(2)", true),
+            ("PRAGMA case_sensitive_like = 'maybe'", false),
+        ];
+
+        for (sql, expected) in cases {
+            assert!(run(sql, &mut db).unwrap().is_empty(), "{sql}");
+            assert_eq!(db.case_sensitive_like, expected, "{sql}");
+        }
+        assert!(run("PRAGMA no_such_pragma = 1", &mut db).is_ok());
+        assert_eq!(
+            run("PRAGMA other.case_sensitive_like = 1", &mut db)
+                .unwrap_err()
+                .to_string(),
+            "unknown database other"
+        );
+    }
+}
@@ -0,0 +1,554 @@
+use anyhow::{ensure, Result};
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::function::MAX_LENGTH;
+use crate::value::Value;
+
+/// How many significant digits of a real are printed, beyond which it is
+/// padded with zeros. The `!` flag allows for more.
+const SIGNIFICANT_DIGITS: usize = 16;
+const SIGNIFICANT_DIGITS_ALT: usize = 19;
+
+/// How many significant digits of a real are read before it is rounded,
+/// which is enough for rounding them to agree with rounding its exact value.
+const EXACT_DIGITS: usize = 40;
+
+/// The largest precision of a real, like SQLite's
+/// `SQLITE_FP_PRECISION_LIMIT`. Larger precisions are taken as this one.
+const FP_PRECISION_LIMIT: usize = 100_000_000;
+
+/// A conversion specification: `%[flags][width][.precision]conversion`.
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    zero: bool,
+    /// The `#` flag.
+    alternate: bool,
+    /// The `!` flag.
+    alternate2: bool,
+    thousands: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+/// Formats values like SQLite's `printf()` function. Missing arguments are
+/// taken as NULL, and formatting stops at an unknown conversion. Fails if the
+/// result would be longer than a string may be.
+pub fn format(format: &str, args: &[Value]) -> Result<String> {
+    let mut out = String::new();
+    let mut args = args.iter();
+    let mut next_arg = || args.next().cloned().unwrap_or(Value::Null);
+    let mut chars = format.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let mut spec = Spec::default();
+        while let Some(&flag) = chars.peek() {
+            match flag {
+                '-' => spec.left = true,
+                '+' => spec.plus = true,
+                ' ' => spec.space = true,
+                '0' => spec.zero = true,
+                '#' => spec.alternate = true,
+                '!' => spec.alternate2 = true,
+                ',' => spec.thousands = true,
+                _ => break,
+            }
+            chars.next();
+        }
+        // Like in SQLite, widths and precisions from arguments are cut to 32
+        // bits, and negative ones count as positive, other than the smallest.
+        if chars.next_if_eq(&'*').is_some() {
+            let width = next_arg().to_integer() as i32;
+            spec.left |= width < 0;
+            spec.width = match width {
+                i32::MIN => 0,
+                width => width.unsigned_abs() as usize,
+            };
+        } else {
+            spec.width = number(&mut chars);
+        }
+        if chars.next_if_eq(&'.').is_some() {
+            spec.precision = if chars.next_if_eq(&'*').is_some() {
+                match next_arg().to_integer() as i32 {
+                    i32::MIN => None,
+                    precision => Some(precision.unsigned_abs() as usize),
+                }
+            } else {
+                Some(number(&mut chars))
+            };
+        }
+        // Length modifiers make no difference, as all integers are 64 bits.
+        while chars.next_if(|&c| c == 'l' || c == 'h').is_some() {}
+
+        let Some(conversion) = chars.next() else {
+            break;
+        };
+        let (prefix, body) = match conversion {
+            'd' | 'i' => {
+                let value = next_arg().to_integer();
+                let mut digits = value.unsigned_abs().to_string();
+                pad_digits(&mut digits, spec.precision)?;
+                if spec.thousands {
+                    digits = group_thousands(&digits);
+                }
+                (sign(value < 0, &spec), digits)
+            }
+            'u' | 'x' | 'X' | 'o' | 'p' => {
+                let value = next_arg().to_integer() as u64;
+                let mut digits = match conversion {
+                    'u' => value.to_string(),
+                    'x' | 'p' => format!("{value:x}"),
+                    'X' => format!("{value:X}"),
+                    _ => format!("{value:o}"),
+                };
+                pad_digits(&mut digits, spec.precision)?;
+                if spec.thousands && conversion == 'u' {
+                    digits = group_thousands(&digits);
+                }
+                let prefix = match conversion {
+                    'x' if spec.alternate && value != 0 => "0x",
+                    'X' if spec.alternate && value != 0 => "0X",
+                    'o' if spec.alternate && value != 0 => "0",
+                    _ => "",
+                };
+                (prefix, digits)
+            }
+            'f' | 'e' | 'E' | 'g' | 'G' => {
+                let value = next_arg().to_real();
+                (
+                    sign(value < 0.0, &spec),
+                    format_real(value.abs(), conversion, &spec),
+                )
+            }
+            's' | 'z' => {
+                let text = next_arg().to_string();
+                ("", truncate(text, spec.precision))
+            }
+            'q' | 'Q' | 'w' => {
+                let quote = if conversion == 'w' { '"' } else { '\'' };
+                let text = match next_arg() {
+                    Value::Null if conversion == 'Q' => "NULL".to_string(),
+                    Value::Null => "(NULL)".to_string(),
+                    value => {
+                        let text = truncate(value.to_string(), spec.precision);
+                        let escaped = text.replace(quote, &format!("{quote}{quote}"));
+                        if conversion == 'Q' {
+                            format!("'{escaped}'")
+                        } else {
+                            escaped
+                        }
+                    }
+                };
+                ("", text)
+            }
+            'c' => {
+                let text = next_arg().to_string();
+                let c = text.chars().next().map(String::from).unwrap_or_default();
+                let count = spec.precision.unwrap_or(1).max(1);
+                check_length(c.len() * count)?;
+                ("", c.repeat(count))
+            }
+            'r' => {
+                let value = next_arg().to_integer();
+                let suffix = match (value.unsigned_abs() % 100, value.unsigned_abs() % 10) {
+                    (11..=13, _) => "th",
+                    (_, 1) => "st",
+                    (_, 2) => "nd",
+                    (_, 3) => "rd",
+                    _ => "th",
+                };
+                (
+                    sign(value < 0, &spec),
+                    format!("{}{suffix}", value.unsigned_abs()),
+                )
+            }
+            '%' => ("", "%".to_string()),
+            'n' => continue,
+            _ => break,
+        };
+
+        let len = prefix.chars().count() + body.chars().count();
+        let padding = spec.width.saturating_sub(len);
+        let numeric = !matches!(conversion, 's' | 'z' | 'q' | 'Q' | 'w' | 'c' | '%');
+        check_length(out.len() + prefix.len() + body.len() + padding)?;
+        if spec.left {
+            out.push_str(prefix);
+            out.push_str(&body);
+            out.extend(std::iter::repeat(' ').take(padding));
+        } else if spec.zero && numeric {
+            out.push_str(prefix);
+            out.extend(std::iter::repeat('0').take(padding));
+            out.push_str(&body);
+        } else {
+            out.extend(std::iter::repeat(' ').take(padding));
+            out.push_str(prefix);
+            out.push_str(&body);
+        }
+    }
+
+    Ok(out)
+}
+
+/// Formats a real with a format that can't make the result too long, like
+/// the ones other functions use.
+pub fn real(format_string: &str, value: f64) -> String {
+    format(format_string, &[Value::Real(value)]).expect("The format has no large width")
+}
+
+/// Fails if a string of the given length in bytes would be too long.
+fn check_length(len: usize) -> Result<()> {
+    ensure!(len < MAX_LENGTH as usize, "string or blob too big");
+    Ok(())
+}
+
+/// Parses the digits of a width or precision, which are 0 if there are none.
+/// Like in SQLite, the number wraps around to 32 bits without the sign bit.
+fn number(chars: &mut Peekable<Chars>) -> usize {
+    let mut number = 0u32;
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        let digit = digit.to_digit(10).expect("The character is a digit");
+        number = number.wrapping_mul(10).wrapping_add(digit);
+    }
+    (number & 0x7fff_ffff) as usize
+}
+
+/// The sign to print a number with, if any.
+fn sign(negative: bool, spec: &Spec) -> &'static str {
+    if negative {
+        "-"
+    } else if spec.plus {
+        "+"
+    } else if spec.space {
+        " "
+    } else {
+        ""
+    }
+}
+
+/// Pads the digits of an integer with zeros to the given precision.
+fn pad_digits(digits: &mut String, precision: Option<usize>) -> Result<()> {
+    if let Some(precision) = precision {
+        if digits.len() < precision {
+            check_length(precision)?;
+            digits.insert_str(0, &"0".repeat(precision - digits.len()));
+        }
+    }
+    Ok(())
+}
+
+/// Separates groups of three digits with commas.
+fn group_thousands(digits: &str) -> String {
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Cuts text off after the number of characters given by the precision.
+fn truncate(text: String, precision: Option<usize>) -> String {
+    match precision {
+        Some(precision) => text.chars().take(precision).collect(),
+        None => text,
+    }
+}
+
+/// The decimal digits of a non-negative real, and the power of ten of the
+/// first of them. The digits are rounded once, to the number of significant
+/// digits that are printed.
+struct Decimal {
+    digits: Vec<u8>,
+    exponent: i32,
+}
+
+impl Decimal {
+    fn new(value: f64) -> Self {
+        if value == 0.0 {
+            return Self {
+                digits: vec![0],
+                exponent: 0,
+            };
+        }
+        let scientific = format!("{:.*e}", EXACT_DIGITS - 1, value);
+        let (mantissa, exponent) = scientific
+            .split_once('e')
+            .expect("Scientific notation has an exponent");
+        Self {
+            digits: mantissa
+                .bytes()
+                .filter(u8::is_ascii_digit)
+                .map(|b| b - b'0')
+                .collect(),
+            exponent: exponent.parse().expect("The exponent is an integer"),
+        }
+    }
+
+    /// Rounds half away from zero to the given number of significant digits.
+    fn round(&mut self, significant: i64) {
+        if significant >= self.digits.len() as i64 {
+            return;
+        }
+        let round_up = significant >= 0 && self.digits[significant as usize] >= 5;
+        self.digits.truncate(significant.max(0) as usize);
+        if round_up {
+            let mut i = self.digits.len();
+            loop {
+                if i == 0 {
+                    self.digits.insert(0, 1);
+                    self.exponent += 1;
+                    break;
+                }
+                i -= 1;
+                if self.digits[i] == 9 {
+                    self.digits[i] = 0;
+                } else {
+                    self.digits[i] += 1;
+                    break;
+                }
+            }
+        }
+        if self.digits.is_empty() {
+            self.digits.push(0);
+            self.exponent = 0;
+        }
+    }
+
+    /// The digit for the given power of ten.
+    fn digit(&self, power: i32) -> char {
+        let index = self.exponent - power;
+        let digit = usize::try_from(index)
+            .ok()
+            .and_then(|index| self.digits.get(index));
+        char::from(b'0' + digit.copied().unwrap_or(0))
+    }
+
+    /// Formats the digits with the given number of decimals.
+    fn fixed(&self, decimals: usize) -> String {
+        let mut text = String::new();
+        for power in (0..=self.exponent.max(0)).rev() {
+            text.push(self.digit(power));
+        }
+        if decimals > 0 {
+            text.push('.');
+            for power in 1..=decimals as i32 {
+                text.push(self.digit(-power));
+            }
+        }
+        text
+    }
+
+    /// Formats the digits in scientific notation, with the given number of
+    /// decimals in the mantissa.
+    fn scientific(&self, decimals: usize, upper: bool) -> (String, String) {
+        let mut mantissa = String::new();
+        mantissa.push(self.digit(self.exponent));
+        if decimals > 0 {
+            mantissa.push('.');
+            for i in 1..=decimals as i32 {
+                mantissa.push(self.digit(self.exponent - i));
+            }
+        }
+        let e = if upper { 'E' } else { 'e' };
+        let sign = if self.exponent < 0 { '-' } else { '+' };
+        (mantissa, format!("{e}{sign}{:02}", self.exponent.abs()))
+    }
+}
+
+/// Formats a non-negative real with the `f`, `e` or `g` conversions.
+fn format_real(value: f64, conversion: char, spec: &Spec) -> String {
+    if value.is_nan() {
+        return "NaN".to_string();
+    }
+    if value.is_infinite() {
+        return "Inf".to_string();
+    }
+    let significant = if spec.alternate2 {
+        SIGNIFICANT_DIGITS_ALT
+    } else {
+        SIGNIFICANT_DIGITS
+    };
+    let significant = significant as i64;
+    let mut decimal = Decimal::new(value);
+    let precision = spec.precision.unwrap_or(6).min(FP_PRECISION_LIMIT);
+
+    match conversion {
+        'f' => {
+            decimal.round((decimal.exponent as i64 + 1 + precision as i64).min(significant));
+            let mut text = decimal.fixed(precision);
+            if precision == 0 && (spec.alternate || spec.alternate2) {
+                text.push('.');
+            }
+            text
+        }
+        'e' | 'E' => {
+            decimal.round((precision as i64 + 1).min(significant));
+            let (mut mantissa, exponent) = decimal.scientific(precision, conversion == 'E');
+            if spec.alternate2 {
+                mantissa = trim_zeros(&mantissa, true);
+            }
+            format!("{mantissa}{exponent}")
+        }
+        _ => {
+            let precision = precision.max(1);
+            decimal.round((precision as i64).min(significant));
+            let exponent = decimal.exponent;
+            let keep_zeros = spec.alternate;
+            if exponent < -4 || exponent >= precision as i32 {
+                let decimals = precision - 1;
+                let (mantissa, exponent) = decimal.scientific(decimals, conversion == 'G');
+                let mantissa = if keep_zeros {
+                    mantissa
+                } else {
+                    trim_zeros(&mantissa, spec.alternate2)
+                };
+                format!("{mantissa}{exponent}")
+            } else {
+                let decimals = (precision as i32 - 1 - exponent) as usize;
+                let text = decimal.fixed(decimals);
+                if keep_zeros {
+                    text
+                } else {
+                    trim_zeros(&text, spec.alternate2)
+                }
+            }
+        }
+    }
+}
+
+/// Removes trailing zeros after the decimal point, and the point if no
+/// digits are left after it, unless `keep_point`, which keeps one digit.
+fn trim_zeros(text: &str, keep_point: bool) -> String {
+    let Some((integer, fraction)) = text.split_once('.') else {
+        return if keep_point {
+            format!("{text}.0")
+        } else {
+            text.to_string()
+        };
+    };
+    let fraction = fraction.trim_end_matches('0');
+    match (fraction.is_empty(), keep_point) {
+        (false, _) => format!("{integer}.{fraction}"),
+        (true, true) => format!("{integer}.0"),
+        (true, false) => integer.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::printf::*;
+
+    #[test]
+    fn formats_like_sqlite() {
+        let int = Value::Integer;
+        let real = Value::Real;
+        let text = |t: &str| Value::Text(t.to_string());
+        let cases = vec![
+            (
+                "%d|%5d|%-5d|%05d|%+d|% d",
+                vec![int(42); 6],
+                "42|   42|42   |00042|+42| 42",
+            ),
+            (
+                "%x|%X|%#o|%,d|%u",
+                vec![int(255), int(255), int(8), int(1234567), int(-3)],
+                "ff|FF|010|1,234,567|18446744073709551613",
+            ),
+            (
+                "%f|%.2f|%10.3f|%e|%.3E",
+                vec![
+                    real(1.23456),
+                    real(1.23456),
+                    real(1.23456),
+                    real(31415.9),
+                    real(31415.9),
+                ],
+                "1.234560|1.23|     1.235|3.141590e+04|3.142E+04",
+            ),
+            (
+                "%g|%G|%g|%.3g|%#g|%!g",
+                vec![
+                    real(0.0001),
+                    real(1e-10),
+                    real(1e6),
+                    real(1.23456),
+                    real(1.5),
+                    real(1.0),
+                ],
+                "0.0001|1E-10|1e+06|1.23|1.50000|1.0",
+            ),
+            (
+                "%.0f|%.0f|%.20f|%08.2f",
+                vec![real(2.5), real(3.5), real(0.1), real(-1.23456)],
+                "3|4|0.10000000000000000000|-0001.23",
+            ),
+            // Reals are rounded once, from their exact digits.
+            (
+                "%.2f|%.2f|%.3f|%.2e",
+                vec![real(1.555), real(2.675), real(1.0005), real(1.555)],
+                "1.55|2.67|1.000|1.55e+00",
+            ),
+            (
+                "%s|%.2s|%5s|%-5s|%c",
+                vec![
+                    text("abc"),
+                    text("abc"),
+                    text("ab"),
+                    text("ab"),
+                    text("xyz"),
+                ],
+                "abc|ab|   ab|ab   |x",
+            ),
+            (
+                "%q|%Q|%Q|%w",
+                vec![text("it's"), text("it's"), Value::Null, text("a\"b")],
+                "it''s|'it''s'|NULL|a\"\"b",
+            ),
+            (
+                "%*d|%-*d|%.*f|%5%|%d %s",
+                vec![int(5), int(1), int(4), int(2), int(2), real(1.23456)],
+                "    1|2   |1.23|    %|0 ",
+            ),
+            (
+                "%d|%d|%s|%r %r",
+                vec![text("abc"), real(3.9), real(1.5), int(1), int(12)],
+                "0|3|1.5|1st 12th",
+            ),
+        ];
+
+        for (format_string, args, expected) in cases {
+            assert_eq!(
+                format(format_string, &args).unwrap(),
+                expected,
+                "{format_string}"
+            );
+        }
+
+        // Large widths and precisions wrap around or are cut to 32 bits like
+        // in SQLite, and results may not be too long.
+        let length = |format_string, args: &[Value]| {
+            format(format_string, args)
+                .map(|text| text.len())
+                .map_err(|e| e.to_string())
+        };
+        let too_big = Err("string or blob too big".to_string());
+        assert_eq!(length("%.99999999999d", &[int(1)]), too_big);
+        assert_eq!(length("%99999999999999999999999d", &[int(1)]), too_big);
+        assert_eq!(length("%1000000000d", &[int(1)]), too_big);
+        assert_eq!(length("%.1000000000c", &[text("a")]), too_big);
+        assert_eq!(length("%.2147483648d", &[int(1)]), Ok(1));
+        assert_eq!(length("%-*d", &[int(i64::MIN), int(1)]), Ok(1));
+        assert_eq!(length("%*d", &[int(4294967301), int(1)]), Ok(5));
+        assert_eq!(length("%.*s", &[int(-1), text("abc")]), Ok(1));
+        assert_eq!(length("%.*d", &[int(-5), int(1)]), Ok(5));
+    }
+}
@@ -0,0 +1,1698 @@
+use anyhow::{bail, ensure, Result};
+use std::cell::{Cell, RefCell};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::rc::Rc;
+
+use crate::aggregate::{is_aggregate, Aggregate};
+use crate::ast::*;
+use crate::collation::Collation;
+use crate::cte::{self, CteScope, RecursiveRow};
+use crate::database::Database;
+use crate::eval::{affinity, collation, evaluate, Scope};
+use crate::function::{argument_collation, check_calls};
+use crate::join::{no_such_column, resolve_column, JoinRows, RowScope, Source};
+use crate::sort::{SortOrder, Sorter};
+use crate::table::{ColumnRef, Row};
+use crate::value::{parse_number, Affinity, SortKey, Value};
+use crate::window::{evaluate_windows, windows_in, WindowScope};
+
+/// The rows returned by a query, along with the names of its columns.
+pub struct ResultSet {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+    /// The collations of the columns that have one, which queries on the
+    /// result compare the values of the columns with.
+    pub collations: Vec<Option<Collation>>,
+    /// The affinities of the columns that have one, which values are
+    /// converted with when they are compared to the columns.
+    pub affinities: Vec<Option<Affinity>>,
+}
+
+/// A column of the result.
+#[derive(Clone)]
+enum Projection<'a> {
+    /// A column of a table, as selected by `*`.
+    Column {
+        source: usize,
+        column: usize,
+    },
+    Expr(&'a Expr),
+}
+
+impl<'a> Projection<'a> {
+    fn expr(&self) -> Option<&'a Expr> {
+        match self {
+            Projection::Expr(expr) => Some(expr),
+            Projection::Column { .. } => None,
+        }
+    }
+}
+
+/// Runs a `SELECT` statement against the database, with the values bound to
+/// its parameters by index.
+pub fn execute(select: &Select, db: &Database, parameters: &[Value]) -> Result<ResultSet> {
+    let state = RunState {
+        parameters: parameters.to_vec(),
+        ..RunState::default()
+    };
+    run(select, false, Context::new(db, &state))
+}
+
+/// What a query runs in: the database, and for a subquery, the query it is
+/// nested in.
+#[derive(Copy, Clone)]
+pub struct Context<'a> {
+    pub db: &'a Database,
+    pub outer: Option<&'a Outer<'a>>,
+    /// The common table expressions the query may refer to.
+    pub ctes: Option<&'a CteScope<'a>>,
+    /// For a recursive `SELECT` of a recursive CTE, the row it runs for,
+    /// which is all its `FROM` clause sees of the CTE.
+    pub recursive: Option<&'a RecursiveRow<'a>>,
+    pub state: &'a RunState,
+}
+
+/// What a statement keeps track of while it runs.
+#[derive(Default)]
+pub struct RunState {
+    /// The values bound to the parameters of the statement, by index.
+    pub parameters: Vec<Value>,
+    /// The results of the subqueries of the statement that don't refer to the
+    /// queries they are nested in, by the address of their `SELECT`.
+    cache: RefCell<HashMap<*const Select, Rc<ResultSet>>>,
+    /// The common table expressions being run, which may not refer to
+    /// themselves other than recursively.
+    pub running: RefCell<Vec<*const CommonTableExpr>>,
+    /// The views the statement refers to, by the name of their schema entry.
+    /// Each is parsed once, so that the results of its subqueries are cached
+    /// like those of the statement's own.
+    pub views: RefCell<HashMap<String, Rc<CreateView>>>,
+    /// The views being run, which may not refer to themselves.
+    pub running_views: RefCell<Vec<*const CreateView>>,
+}
+
+/// The query a subquery is nested in, whose current row the subquery may
+/// refer to.
+pub struct Outer<'a> {
+    scope: &'a dyn Scope,
+    /// Whether the subquery referred to a column of the outer query, so that
+    /// its result may differ from row to row.
+    correlated: Cell<bool>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(db: &'a Database, state: &'a RunState) -> Self {
+        Self {
+            db,
+            outer: None,
+            ctes: None,
+            recursive: None,
+            state,
+        }
+    }
+
+    /// Looks up a column that is not in any table of the query in the
+    /// queries it is nested in.
+    pub fn outer_column(&self, table: Option<&str>, name: &str) -> Result<Value> {
+        let Some(outer) = self.outer else {
+            return Err(no_such_column(table, name));
+        };
+        outer.correlated.set(true);
+        outer.scope.column(table, name)
+    }
+
+    /// The affinity of a column of the queries this one is nested in.
+    pub fn outer_affinity(&self, table: Option<&str>, name: &str) -> Option<Affinity> {
+        self.outer?.scope.affinity(table, name)
+    }
+
+    /// The collation of a column of the queries this one is nested in.
+    pub fn outer_collation(&self, table: Option<&str>, name: &str) -> Option<Collation> {
+        self.outer?.scope.collation(table, name)
+    }
+
+    /// Runs a subquery for the current row of `scope`. Subqueries that don't
+    /// refer to the row only run once per statement.
+    pub fn subquery(
+        &self,
+        select: &Select,
+        first_only: bool,
+        scope: &dyn Scope,
+    ) -> Result<Rc<ResultSet>> {
+        let key = select as *const Select;
+        if let Some(result) = self.state.cache.borrow().get(&key) {
+            return Ok(result.clone());
+        }
+
+        let outer = Outer {
+            scope,
+            correlated: Cell::new(false),
+        };
+        let context = Context {
+            outer: Some(&outer),
+            ..*self
+        };
+        let result = Rc::new(run(select, first_only, context)?);
+        if !outer.correlated.get() {
+            self.state.cache.borrow_mut().insert(key, result.clone());
+        }
+        Ok(result)
+    }
+}
+
+/// Runs a `SELECT`, returning only its first row with `first_only`.
+pub fn run(select: &Select, first_only: bool, context: Context) -> Result<ResultSet> {
+    // Only the FROM clause of a recursive SELECT itself sees the row it runs
+    // for, not its subqueries.
+    cte::check_nested_references(select, context)?;
+    let context = Context {
+        recursive: None,
+        ..context
+    };
+    let mut scope = None;
+    let context = CteScope::enter(select, context, &mut scope)?;
+
+    let mut limits = Limits::new(select, context)?;
+    if first_only {
+        limits.limit = Some(limits.limit.map_or(1, |limit| limit.min(1)));
+    }
+    if !select.compound.is_empty() {
+        return execute_compound(select, limits, context);
+    }
+    if let Some(result) = count_star(select, context)? {
+        return Ok(result);
+    }
+    execute_core(select, &select.order_by, limits, context)
+}
+
+/// Runs a `SELECT` without compound operators, sorting its rows by the given
+/// terms.
+pub fn execute_core(
+    select: &Select,
+    order_by: &[OrderingTerm],
+    limits: Limits,
+    context: Context,
+) -> Result<ResultSet> {
+    let sources = Source::load_all(select, scan_limit(select, order_by, limits), context)?;
+    let (columns, projections) = resolve_columns(&select.columns, &sources)?;
+    // Collations only depend on which columns expressions refer to, not on
+    // their values.
+    let scope = RowScope {
+        context,
+        sources: &sources,
+        rows: &[],
+    };
+    let collations = projections
+        .iter()
+        .map(|projection| projection_collation(projection, &scope))
+        .collect::<Result<Vec<_>>>()?;
+    let affinities = projections
+        .iter()
+        .map(|projection| projection_affinity(projection, &scope))
+        .collect();
+    let group_by = select
+        .group_by
+        .iter()
+        .enumerate()
+        .map(|(i, term)| resolve_term(term, i, "GROUP BY", false, &columns, &projections, &sources))
+        .collect::<Result<Vec<_>>>()?;
+    let mut sort_keys = Vec::new();
+    let mut sort_order = Vec::new();
+    for (i, term) in order_by.iter().enumerate() {
+        let (expr, collation) = match &term.expr {
+            Expr::Collate { expr, collation } => (&**expr, Some(Collation::from_name(collation)?)),
+            expr => (expr, None),
+        };
+        let key = resolve_term(expr, i, "ORDER BY", true, &columns, &projections, &sources)?;
+        // Without a COLLATE of its own, a term sorts by the collation of
+        // the column or expression it refers to.
+        let collation = match collation {
+            Some(collation) => collation,
+            None => projection_collation(&key, &scope)?.unwrap_or_default(),
+        };
+        sort_keys.push(key);
+        sort_order.push(SortOrder::new(term.descending, term.nulls_first, collation));
+    }
+
+    // Report unknown columns even if there are no rows to evaluate them for.
+    let expressions = projections
+        .iter()
+        .chain(&group_by)
+        .chain(&sort_keys)
+        .filter_map(Projection::expr)
+        .chain(&select.where_clause)
+        .chain(sources.iter().flat_map(|source| &source.on))
+        .chain(&select.having);
+    for expr in expressions {
+        check_columns(expr, &sources, context)?;
+        check_calls(expr)?;
+    }
+    let mut windows = Vec::new();
+    for expr in projections
+        .iter()
+        .chain(&sort_keys)
+        .filter_map(Projection::expr)
+    {
+        windows.extend(windows_in(expr)?);
+    }
+    let elsewhere = select
+        .where_clause
+        .iter()
+        .chain(&select.group_by)
+        .chain(&select.having)
+        .chain(sources.iter().flat_map(|source| &source.on));
+    for expr in elsewhere {
+        if let Some(call) = windows_in(expr)?.first() {
+            bail!("misuse of window function {}()", call.name);
+        }
+    }
+    if let Some(condition) = &select.where_clause {
+        if let Some(call) = aggregates_in(condition).first() {
+            bail!("misuse of aggregate: {}()", call.name);
+        }
+    }
+    for term in sources.iter().flat_map(|source| &source.on) {
+        if let Some(call) = aggregates_in(term).first() {
+            bail!("misuse of aggregate function {}()", call.name);
+        }
+    }
+
+    let mut aggregates = projections
+        .iter()
+        .filter_map(Projection::expr)
+        .chain(&select.having)
+        .flat_map(aggregates_in)
+        .collect::<Vec<_>>();
+    let grouped = !aggregates.is_empty() || !group_by.is_empty() || select.having.is_some();
+    let sort_aggregates = sort_keys
+        .iter()
+        .filter_map(Projection::expr)
+        .flat_map(aggregates_in);
+    if grouped {
+        aggregates.extend(sort_aggregates);
+    } else if let Some(call) = sort_aggregates.into_iter().next() {
+        bail!("misuse of aggregate: {}()", call.name);
+    }
+    for call in &aggregates {
+        if let Some(nested) = call.args.iter().flat_map(aggregates_in).next() {
+            bail!("misuse of aggregate function {}()", nested.name);
+        }
+    }
+
+    let distinct = select.distinct.then(|| {
+        collations
+            .iter()
+            .map(|collation| collation.unwrap_or_default())
+            .collect()
+    });
+
+    let mut output = Output::new(sort_order, limits, distinct);
+    let rows = JoinRows::new(&sources, select.where_clause.as_ref(), context)?;
+    // With window functions, the rows are kept until they have been
+    // evaluated over all of them, along with the results of the aggregates.
+    let mut window_rows = Vec::new();
+    if !grouped {
+        for row in rows {
+            if output.is_full() {
+                break;
+            }
+            let rows = row?;
+            if !windows.is_empty() {
+                window_rows.push((rows, Vec::new()));
+                continue;
+            }
+            let scope = RowScope {
+                context,
+                sources: &sources,
+                rows: &rows,
+            };
+            output.push(
+                project(&sort_keys, &scope, &rows)?,
+                project(&projections, &scope, &rows)?,
+            )?;
+        }
+    } else {
+        let groups = group_rows(rows, &sources, context, &group_by, &aggregates)?;
+        for group in groups {
+            if output.is_full() {
+                break;
+            }
+            let rows = group
+                .rows
+                .unwrap_or_else(|| sources.iter().map(Source::null_row).collect());
+            let results = group
+                .aggregates
+                .iter()
+                .map(Aggregate::finish)
+                .collect::<Vec<_>>();
+            let scope = GroupScope {
+                rows: RowScope {
+                    context,
+                    sources: &sources,
+                    rows: &rows,
+                },
+                aggregates: &aggregates,
+                results: &results,
+            };
+            if let Some(condition) = &select.having {
+                if evaluate(condition, &scope)?.truth() != Some(true) {
+                    continue;
+                }
+            }
+            if !windows.is_empty() {
+                window_rows.push((rows, results));
+                continue;
+            }
+            output.push(
+                project(&sort_keys, &scope, &rows)?,
+                project(&projections, &scope, &rows)?,
+            )?;
+        }
+    }
+    if !windows.is_empty() {
+        let scopes = window_rows
+            .iter()
+            .map(|(rows, results)| GroupScope {
+                rows: RowScope {
+                    context,
+                    sources: &sources,
+                    rows,
+                },
+                aggregates: &aggregates,
+                results,
+            })
+            .collect::<Vec<_>>();
+        let dyn_scopes = scopes
+            .iter()
+            .map(|scope| scope as &dyn Scope)
+            .collect::<Vec<_>>();
+        let (order, results) = evaluate_windows(&windows, &select.windows, &dyn_scopes)?;
+        for i in order {
+            if output.is_full() {
+                break;
+            }
+            let rows = &window_rows[i].0;
+            let scope = WindowScope {
+                inner: &scopes[i],
+                calls: &windows,
+                results: &results[i],
+            };
+            output.push(
+                project(&sort_keys, &scope, rows)?,
+                project(&projections, &scope, rows)?,
+            )?;
+        }
+    }
+    let rows = output.finish()?;
+
+    Ok(ResultSet {
+        columns,
+        rows,
+        collations,
+        affinities,
+    })
+}
+
+/// Runs a compound `SELECT`, combining the rows of its `SELECT`s from left to
+/// right, before sorting and limiting the combined rows.
+fn execute_compound(select: &Select, limits: Limits, context: Context) -> Result<ResultSet> {
+    let first = execute_core(select, &[], Limits::default(), context)?;
+    let mut collations = first.collations;
+    let mut affinities = first.affinities;
+    let columns = first.columns;
+    let mut rows = first.rows;
+    let mut selects = vec![(select, columns.clone())];
+    // SQLite keeps the first of duplicates when it merges the rows of the
+    // SELECTs sorted in ascending order, and the last otherwise.
+    let keep_first = select.order_by.first().is_some_and(|term| !term.descending);
+
+    for CompoundSelect {
+        operator,
+        select: next,
+    } in &select.compound
+    {
+        let result = execute_core(next, &[], Limits::default(), context)?;
+        ensure!(
+            result.columns.len() == columns.len(),
+            "SELECTs to the left and right of {operator} do not have the same number of result columns"
+        );
+        // A column has the collation of the leftmost SELECT that gives one.
+        for (collation, next) in collations.iter_mut().zip(result.collations) {
+            *collation = collation.or(next);
+        }
+        for (affinity, next) in affinities.iter_mut().zip(result.affinities) {
+            *affinity = compound_affinity(*affinity, next);
+        }
+        let key_collations = collations
+            .iter()
+            .map(|collation| collation.unwrap_or_default())
+            .collect::<Vec<_>>();
+        rows = combine(*operator, rows, result.rows, &key_collations, keep_first);
+        selects.push((next, result.columns));
+    }
+
+    let mut sort_keys = Vec::new();
+    let mut sort_order = Vec::new();
+    for (i, term) in select.order_by.iter().enumerate() {
+        let (expr, collation) = match &term.expr {
+            Expr::Collate { expr, collation } => (&**expr, Some(Collation::from_name(collation)?)),
+            expr => (expr, None),
+        };
+        let column = resolve_compound_term(expr, i, &selects)?;
+        let collation = collation.or(collations[column]).unwrap_or_default();
+        sort_keys.push(column);
+        sort_order.push(SortOrder::new(term.descending, term.nulls_first, collation));
+    }
+
+    let mut output = Output::new(sort_order, limits, None);
+    for row in rows {
+        if output.is_full() {
+            break;
+        }
+        let keys = sort_keys.iter().map(|&i| row[i].clone()).collect();
+        output.push(keys, row)?;
+    }
+    let rows = output.finish()?;
+
+    Ok(ResultSet {
+        columns,
+        rows,
+        collations,
+        affinities,
+    })
+}
+
+/// Combines the rows on the left and right of a compound operator. All
+/// operators except `UNION ALL` remove duplicate rows, and return the rows
+/// in order. Of duplicates on the same side, the row seen first is kept with
+/// `keep_first`, and the row seen last otherwise. `UNION` keeps the row on
+/// the right of duplicates on both sides.
+fn combine(
+    operator: CompoundOperator,
+    mut left: Vec<Vec<Value>>,
+    right: Vec<Vec<Value>>,
+    collations: &[Collation],
+    keep_first: bool,
+) -> Vec<Vec<Value>> {
+    if operator == CompoundOperator::UnionAll {
+        left.extend(right);
+        return left;
+    }
+
+    let distinct = |rows: Vec<Vec<Value>>| {
+        let mut distinct = BTreeMap::new();
+        for row in rows {
+            let key = distinct_key(&row, collations);
+            if keep_first {
+                distinct.entry(key).or_insert(row);
+            } else {
+                distinct.insert(key, row);
+            }
+        }
+        distinct
+    };
+    let mut rows = distinct(left);
+    let right_keys = || {
+        right
+            .iter()
+            .map(|row| distinct_key(row, collations))
+            .collect::<BTreeSet<_>>()
+    };
+    match operator {
+        CompoundOperator::Union => {
+            rows.extend(distinct(right));
+        }
+        CompoundOperator::Intersect => {
+            let right = right_keys();
+            rows.retain(|key, _| right.contains(key));
+        }
+        CompoundOperator::Except => {
+            let right = right_keys();
+            rows.retain(|key, _| !right.contains(key));
+        }
+        CompoundOperator::UnionAll => unreachable!(),
+    }
+    rows.into_values().collect()
+}
+
+/// The key of a row for finding duplicates, which is the same for rows whose
+/// values compare as equal by the collations of their columns.
+pub fn distinct_key(values: &[Value], collations: &[Collation]) -> SortKey {
+    let values = values
+        .iter()
+        .zip(collations)
+        .map(|(value, collation)| match value {
+            Value::Text(text) => Value::Text(collation.key(text).into_owned()),
+            value => value.clone(),
+        });
+    SortKey(values.collect())
+}
+
+/// The collation of a result column, or of a `GROUP BY` or `ORDER BY` term.
+fn projection_collation(projection: &Projection, scope: &RowScope) -> Result<Option<Collation>> {
+    match projection {
+        Projection::Column { source, column } => {
+            Ok(scope.sources[*source].collation(ColumnRef::Column(*column)))
+        }
+        Projection::Expr(expr) => collation(expr, scope),
+    }
+}
+
+fn projection_affinity(projection: &Projection, scope: &RowScope) -> Option<Affinity> {
+    match projection {
+        Projection::Column { source, column } => {
+            scope.sources[*source].affinity(ColumnRef::Column(*column))
+        }
+        Projection::Expr(expr) => affinity(expr, scope),
+    }
+}
+
+/// The affinity of a column of a compound `SELECT`, given the one of the
+/// `SELECT`s to its left and the one of the next `SELECT`. This is the
+/// affinity of the leftmost `SELECT`, unless a `SELECT` to its right may give
+/// values that the affinity doesn't suit: numbers for text, or text for
+/// numbers. Values of columns without an affinity may be of any type.
+pub fn compound_affinity(left: Option<Affinity>, next: Option<Affinity>) -> Option<Affinity> {
+    let left = left?;
+    let suits = match next? {
+        Affinity::Text => left == Affinity::Text,
+        Affinity::Blob => false,
+        next => next.is_numeric() && left.is_numeric(),
+    };
+    suits.then_some(left)
+}
+
+/// Answers `SELECT count(*) FROM table` by adding up the number of cells on
+/// the pages of the table, without reading any of its records.
+fn count_star(select: &Select, context: Context) -> Result<Option<ResultSet>> {
+    let [ResultColumn::Expr {
+        expr: expr @ Expr::Function(call),
+        alias,
+    }] = &select.columns[..]
+    else {
+        return Ok(None);
+    };
+    let Some(TableOrSubquery::Table(from)) = &select.from else {
+        return Ok(None);
+    };
+    if context
+        .ctes
+        .and_then(|ctes| ctes.find(&from.name))
+        .is_some()
+    {
+        return Ok(None);
+    }
+    let only_from = select.joins.is_empty()
+        && select.where_clause.is_none()
+        && select.group_by.is_empty()
+        && select.having.is_none()
+        && select.limit.is_none()
+        && select.offset.is_none();
+    if !is_aggregate(call) || !call.name.eq_ignore_ascii_case("count") || !call.star || !only_from {
+        return Ok(None);
+    }
+
+    // Views are counted by running them, which also reports missing tables.
+    let Some(table) = context.db.schema.table(&from.name) else {
+        return Ok(None);
+    };
+    let count = context.db.count_entries(table.rootpage)?;
+
+    Ok(Some(ResultSet {
+        columns: vec![alias.clone().unwrap_or_else(|| expr.to_string())],
+        rows: vec![vec![Value::Integer(count as i64)]],
+        collations: vec![None],
+        affinities: vec![None],
+    }))
+}
+
+/// Computes the values of the result columns for a row of each table.
+fn project(projections: &[Projection], scope: &dyn Scope, rows: &[Row]) -> Result<Vec<Value>> {
+    projections
+        .iter()
+        .map(|projection| match projection {
+            Projection::Column { source, column } => Ok(rows[*source].values[*column].clone()),
+            Projection::Expr(expr) => evaluate(expr, scope),
+        })
+        .collect()
+}
+
+/// How many rows of the result to skip and to return at most, as given by
+/// `OFFSET` and `LIMIT`.
+#[derive(Copy, Clone, Default)]
+pub struct Limits {
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+impl Limits {
+    pub fn new(select: &Select, context: Context) -> Result<Self> {
+        // Negative limits mean there is no limit, and negative offsets are
+        // treated as 0.
+        let limit = match &select.limit {
+            Some(limit) => usize::try_from(constant_integer(limit, context)?).ok(),
+            None => None,
+        };
+        let offset = match &select.offset {
+            Some(offset) => usize::try_from(constant_integer(offset, context)?).unwrap_or(0),
+            None => 0,
+        };
+        Ok(Self { offset, limit })
+    }
+}
+
+/// How many rows a query needs from the table it selects from when it only
+/// needs the first ones: when it doesn't join, filter, group, sort or
+/// aggregate them, and it has a `LIMIT`.
+fn scan_limit(select: &Select, order_by: &[OrderingTerm], limits: Limits) -> Option<usize> {
+    let aggregates = select.columns.iter().any(|column| match column {
+        ResultColumn::Expr { expr, .. } => {
+            !aggregates_in(expr).is_empty() || !windows_in(expr).is_ok_and(|w| w.is_empty())
+        }
+        _ => false,
+    });
+    let plain = select.joins.is_empty()
+        && select.where_clause.is_none()
+        && select.group_by.is_empty()
+        && select.having.is_none()
+        && !select.distinct
+        && order_by.is_empty()
+        && !aggregates;
+    let limit = limits.limit?.saturating_add(limits.offset);
+    plain.then_some(limit)
+}
+
+/// Collects the rows of the result, sorting them by the `ORDER BY` terms if
+/// there are any, and skipping and limiting them by `OFFSET` and `LIMIT`.
+struct Output {
+    sorter: Option<Sorter>,
+    rows: Vec<Vec<Value>>,
+    /// How many more rows to skip, for rows that are not sorted.
+    offset: usize,
+    limit: Option<usize>,
+    /// With `DISTINCT`, the collations of the columns and the keys of the
+    /// rows so far.
+    distinct: Option<(Vec<Collation>, BTreeSet<SortKey>)>,
+}
+
+impl Output {
+    fn new(order: Vec<SortOrder>, limits: Limits, distinct: Option<Vec<Collation>>) -> Self {
+        let Limits { offset, limit } = limits;
+        // Sorting only needs to keep the rows up to the limit.
+        let sorter = (!order.is_empty())
+            .then(|| Sorter::new(order, limit.map(|limit| limit.saturating_add(offset))));
+
+        Self {
+            sorter,
+            rows: Vec::new(),
+            offset,
+            limit,
+            distinct: distinct.map(|collations| (collations, BTreeSet::new())),
+        }
+    }
+
+    /// Whether the rows that are not sorted have reached the limit, so that
+    /// no more rows need to be computed.
+    fn is_full(&self) -> bool {
+        self.sorter.is_none() && self.limit.is_some_and(|limit| self.rows.len() >= limit)
+    }
+
+    /// Adds a row with the keys it is sorted by, unless it is a duplicate
+    /// of an earlier row with `DISTINCT`.
+    fn push(&mut self, keys: Vec<Value>, values: Vec<Value>) -> Result<()> {
+        if let Some((collations, seen)) = &mut self.distinct {
+            if !seen.insert(distinct_key(&values, collations)) {
+                return Ok(());
+            }
+        }
+        match &mut self.sorter {
+            Some(sorter) => sorter.push(keys, values)?,
+            None if self.offset > 0 => self.offset -= 1,
+            None => self.rows.push(values),
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Vec<Vec<Value>>> {
+        match self.sorter {
+            Some(sorter) => sorter
+                .finish()?
+                .skip(self.offset)
+                .take(self.limit.unwrap_or(usize::MAX))
+                .collect(),
+            None => Ok(self.rows),
+        }
+    }
+}
+
+/// Evaluates a `LIMIT` or `OFFSET` expression, which must be an integer.
+fn constant_integer(expr: &Expr, context: Context) -> Result<i64> {
+    let value = evaluate(
+        expr,
+        &RowScope {
+            context,
+            sources: &[],
+            rows: &[],
+        },
+    )?;
+    let value = match value {
+        Value::Text(text) => parse_number(&text).unwrap_or(Value::Text(text)),
+        value => value,
+    };
+    match value {
+        Value::Integer(i) => Ok(i),
+        Value::Real(r) if r.fract() == 0.0 && r >= i64::MIN as f64 && r < i64::MAX as f64 => {
+            Ok(r as i64)
+        }
+        _ => bail!("datatype mismatch"),
+    }
+}
+
+/// The rows of a query with the same values for the `GROUP BY` terms.
+struct Group {
+    /// The rows the bare columns of the query take their values from, if the
+    /// group has any rows.
+    rows: Option<Vec<Row>>,
+    aggregates: Vec<Aggregate>,
+}
+
+/// Divides the rows into groups by the values of the `GROUP BY` terms, in the
+/// order of those values by their collations, and computes the aggregates of
+/// each group. Without any terms, all rows form a single group, even if there
+/// are none.
+fn group_rows(
+    rows: impl Iterator<Item = Result<Vec<Row>>>,
+    sources: &[Source],
+    context: Context,
+    group_by: &[Projection],
+    aggregates: &[&FunctionCall],
+) -> Result<Vec<Group>> {
+    let scope = RowScope {
+        context,
+        sources,
+        rows: &[],
+    };
+    let key_collations = group_by
+        .iter()
+        .map(|term| Ok(projection_collation(term, &scope)?.unwrap_or_default()))
+        .collect::<Result<Vec<_>>>()?;
+    let aggregate_collations = aggregates
+        .iter()
+        .map(|call| argument_collation(call, &scope))
+        .collect::<Result<Vec<_>>>()?;
+    let new_group = || -> Result<Group> {
+        Ok(Group {
+            rows: None,
+            aggregates: aggregates
+                .iter()
+                .zip(&aggregate_collations)
+                .map(|(call, &collation)| Aggregate::new(call, collation))
+                .collect::<Result<_>>()?,
+        })
+    };
+    // With a single call to min() or max(), bare columns take their values
+    // from the row with the smallest or largest value. Otherwise, they take
+    // them from the first row of the group.
+    let from_extreme = matches!(aggregates, [call] if ["min", "max"]
+        .iter()
+        .any(|name| call.name.eq_ignore_ascii_case(name)));
+
+    let mut groups = BTreeMap::new();
+    if group_by.is_empty() {
+        groups.insert(SortKey(Vec::new()), new_group()?);
+    }
+    for row in rows {
+        let rows = row?;
+        let scope = RowScope {
+            context,
+            sources,
+            rows: &rows,
+        };
+        let key = distinct_key(&project(group_by, &scope, &rows)?, &key_collations);
+        let group = match groups.entry(key) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(new_group()?),
+        };
+
+        let mut holds_result = false;
+        for (aggregate, call) in group.aggregates.iter_mut().zip(aggregates) {
+            let args = if call.star {
+                Vec::new()
+            } else {
+                call.args
+                    .iter()
+                    .map(|arg| evaluate(arg, &scope))
+                    .collect::<Result<_>>()?
+            };
+            holds_result |= aggregate.step(&args)?;
+        }
+        if group.rows.is_none() || (from_extreme && holds_result) {
+            group.rows = Some(rows);
+        }
+    }
+
+    Ok(groups.into_values().collect())
+}
+
+/// The scope of expressions evaluated for a group of rows, where aggregate
+/// function calls have the values computed over the group.
+struct GroupScope<'a> {
+    rows: RowScope<'a>,
+    aggregates: &'a [&'a FunctionCall],
+    results: &'a [Value],
+}
+
+impl Scope for GroupScope<'_> {
+    fn column(&self, table: Option<&str>, name: &str) -> Result<Value> {
+        self.rows.column(table, name)
+    }
+
+    fn affinity(&self, table: Option<&str>, name: &str) -> Option<Affinity> {
+        self.rows.affinity(table, name)
+    }
+
+    fn collation(&self, table: Option<&str>, name: &str) -> Option<Collation> {
+        self.rows.collation(table, name)
+    }
+
+    fn parameter(&self, index: usize) -> Value {
+        self.rows.parameter(index)
+    }
+
+    fn aggregate(&self, call: &FunctionCall) -> Option<Value> {
+        let index = self
+            .aggregates
+            .iter()
+            .position(|aggregate| std::ptr::eq(*aggregate, call))?;
+        Some(self.results[index].clone())
+    }
+
+    fn subquery(&self, select: &Select, first_only: bool) -> Result<Rc<ResultSet>> {
+        self.rows.context.subquery(select, first_only, self)
+    }
+
+    fn case_sensitive_like(&self) -> bool {
+        self.rows.case_sensitive_like()
+    }
+}
+
+/// Finds the calls to aggregate functions in an expression.
+pub fn aggregates_in(expr: &Expr) -> Vec<&FunctionCall> {
+    let mut calls = Vec::new();
+    expr.walk(&mut |expr| {
+        if let Expr::Function(call) = expr {
+            if is_aggregate(call) {
+                calls.push(call);
+            }
+        }
+    });
+    calls
+}
+
+/// Resolves a `GROUP BY` or `ORDER BY` term. A constant integer refers to the
+/// result column at that position, and a name to the result column with that
+/// alias. `GROUP BY` prefers columns of the table over aliases, while
+/// `ORDER BY` prefers aliases.
+fn resolve_term<'a>(
+    term: &'a Expr,
+    index: usize,
+    clause: &str,
+    prefer_alias: bool,
+    columns: &[String],
+    projections: &[Projection<'a>],
+    sources: &[Source],
+) -> Result<Projection<'a>> {
+    match term {
+        Expr::Literal(Value::Integer(position)) => {
+            let position = column_position(*position, index, clause, projections.len())?;
+            Ok(projections[position].clone())
+        }
+        Expr::Column { table: None, name }
+            if prefer_alias || !matches!(resolve_column(sources, None, name), Ok(Some(_))) =>
+        {
+            match columns.iter().position(|c| c.eq_ignore_ascii_case(name)) {
+                Some(i) => Ok(projections[i].clone()),
+                None => Ok(Projection::Expr(term)),
+            }
+        }
+        _ => Ok(Projection::Expr(term)),
+    }
+}
+
+/// Resolves an `ORDER BY` term of a compound `SELECT` to the position of a
+/// result column. The term must be a position, or match the name or the
+/// expression of a result column of one of the `SELECT`s, which are tried
+/// from left to right.
+pub fn resolve_compound_term(
+    term: &Expr,
+    index: usize,
+    selects: &[(&Select, Vec<String>)],
+) -> Result<usize> {
+    let count = selects[0].1.len();
+    if let Expr::Literal(Value::Integer(position)) = term {
+        return column_position(*position, index, "ORDER BY", count);
+    }
+
+    for (select, columns) in selects {
+        let by_name = match term {
+            Expr::Column { table: None, name } => {
+                columns.iter().position(|c| c.eq_ignore_ascii_case(name))
+            }
+            _ => None,
+        };
+        // Positions of result expressions are only known without stars.
+        let by_expr = || {
+            select
+                .columns
+                .iter()
+                .map(|column| match column {
+                    ResultColumn::Expr { expr, .. } => Some(expr),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()?
+                .iter()
+                .position(|expr| *expr == term)
+        };
+        if let Some(position) = by_name.or_else(by_expr) {
+            return Ok(position);
+        }
+    }
+    bail!(
+        "{} ORDER BY term does not match any column in the result set",
+        ordinal(index + 1)
+    )
+}
+
+/// Checks the position of a result column given by a `GROUP BY` or
+/// `ORDER BY` term, and returns its index.
+fn column_position(position: i64, index: usize, clause: &str, count: usize) -> Result<usize> {
+    if !(1..=count as i64).contains(&position) {
+        bail!(
+            "{} {clause} term out of range - should be between 1 and {count}",
+            ordinal(index + 1)
+        );
+    }
+    Ok(position as usize - 1)
+}
+
+/// Formats a number as an English ordinal, like `1st` or `12th`.
+fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{n}{suffix}")
+}
+
+/// Checks that all columns the expression refers to exist, in the tables of
+/// the query or of the queries it is nested in, and that none of them is
+/// ambiguous.
+fn check_columns(expr: &Expr, sources: &[Source], context: Context) -> Result<()> {
+    let mut result = Ok(());
+    expr.walk(&mut |expr| {
+        if let Expr::Column { table, name } = expr {
+            let resolved = match resolve_column(sources, table.as_deref(), name) {
+                Ok(Some(_)) => Ok(()),
+                Ok(None) => context.outer_column(table.as_deref(), name).map(drop),
+                Err(e) => Err(e),
+            };
+            if result.is_ok() {
+                result = resolved;
+            }
+        }
+    });
+    result
+}
+
+/// Expands the result columns of a query, returning the names of the columns
+/// and how to compute each of them.
+fn resolve_columns<'a>(
+    result_columns: &'a [ResultColumn],
+    sources: &[Source],
+) -> Result<(Vec<String>, Vec<Projection<'a>>)> {
+    let mut names = Vec::new();
+    let mut projections = Vec::new();
+
+    for column in result_columns {
+        match column {
+            ResultColumn::Star | ResultColumn::TableStar(_) => {
+                if sources.is_empty() {
+                    match column {
+                        ResultColumn::TableStar(name) => bail!("no such table: {name}"),
+                        _ => bail!("no tables specified"),
+                    }
+                }
+                let mut found = false;
+                for (i, source) in sources.iter().enumerate() {
+                    // `*` leaves out the columns of joins with USING, which
+                    // are already there for the table on the left.
+                    let star = match column {
+                        ResultColumn::TableStar(name) if !source.is_named(name) => continue,
+                        ResultColumn::TableStar(_) => false,
+                        _ => true,
+                    };
+                    found = true;
+                    for (j, column) in source.visible_columns().iter().enumerate() {
+                        if star && source.is_using(column) {
+                            continue;
+                        }
+                        names.push(column.clone());
+                        projections.push(Projection::Column {
+                            source: i,
+                            column: j,
+                        });
+                    }
+                }
+                if let (ResultColumn::TableStar(name), false) = (column, found) {
+                    bail!("no such table: {name}");
+                }
+            }
+            ResultColumn::Expr { expr, alias } => {
+                let name = match (alias, expr) {
+                    (Some(alias), _) => alias.clone(),
+                    (None, Expr::Column { name, .. }) => name.clone(),
+                    (None, expr) => expr.to_string(),
+                };
+                names.push(name);
+                projections.push(Projection::Expr(expr));
+            }
+        }
+    }
+
+    Ok((names, projections))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::parser::parse;
+    use crate::query::*;
+
+    /// Runs a `SELECT` statement against a test database.
+    pub fn run(db: &str, sql: &str) -> Result<ResultSet> {
+        let db = Database::open(db).unwrap();
+        let Some(Statement::Select(select)) = parse(sql).unwrap().pop() else {
+            panic!("Expected SELECT");
+        };
+        execute(&select, &db, &[])
+    }
+
+    /// Runs a `SELECT` statement against a test database, returning its rows
+    /// with the values separated by `|`.
+    pub fn rows(db: &str, sql: &str) -> Vec<String> {
+        let result = run(db, sql).unwrap();
+        let rows = result.rows.iter().map(|row| {
+            let values = row.iter().map(|value| value.to_string());
+            values.collect::<Vec<_>>().join("|")
+        });
+        rows.collect()
+    }
+
+    #[test]
+    fn projects_columns_in_requested_order() {
+        let result = run("without_rowid.db", "SELECT weight, Name FROM pears").unwrap();
+
+        assert_eq!(result.columns, vec!["weight", "Name"]);
+        assert_eq!(
+            result.rows[0],
+            vec![Value::Integer(200), Value::Text("Anjou".to_string())]
+        );
+        assert_eq!(result.rows.len(), 5);
+    }
+
+    #[test]
+    fn expands_stars_and_aliases() {
+        let result = run("without_rowid.db", "SELECT p.*, 'x' AS tag FROM pears AS p").unwrap();
+
+        assert_eq!(result.columns, vec!["color", "name", "weight", "tag"]);
+        assert_eq!(result.rows[0][3], Value::Text("x".to_string()));
+    }
+
+    #[test]
+    fn filters_rows() {
+        let result = run(
+            "without_rowid.db",
+            "SELECT name, weight * 2 FROM pears WHERE color IN ('Red', 'Green') AND weight < 200",
+        )
+        .unwrap();
+        assert_eq!(result.columns, vec!["name", "weight * 2"]);
+        assert_eq!(
+            result.rows,
+            vec![
+                vec![Value::Text("Bartlett".to_string()), Value::Integer(360)],
+                vec![Value::Text("Bartlett".to_string()), Value::Integer(350)],
+            ]
+        );
+    }
+
+    #[test]
+    fn selects_without_a_table() {
+        let result = run("without_rowid.db", "SELECT 1 + 1, 'a' || 'b'").unwrap();
+
+        assert_eq!(
+            result.rows,
+            vec![vec![Value::Integer(2), Value::Text("ab".to_string())]]
+        );
+    }
+
+    #[test]
+    fn groups_rows_and_filters_groups() {
+        let result = run(
+            "without_rowid.db",
+            "SELECT name AS n, count(*), max(weight) FROM pears GROUP BY n HAVING sum(weight) > 170",
+        )
+        .unwrap();
+
+        let text = |t: &str| Value::Text(t.to_string());
+        assert_eq!(result.columns, vec!["n", "count(*)", "max(weight)"]);
+        assert_eq!(
+            result.rows,
+            vec![
+                vec![text("Anjou"), Value::Integer(1), Value::Integer(200)],
+                vec![text("Bartlett"), Value::Integer(2), Value::Integer(180)],
+                vec![text("Comice"), Value::Integer(1), Value::Integer(230)],
+            ]
+        );
+    }
+
+    #[test]
+    fn aggregates_all_rows_without_group_by() {
+        let result = run("without_rowid.db", "SELECT min(weight), color FROM pears").unwrap();
+        // Bare columns come from the row with the smallest weight.
+        assert_eq!(
+            result.rows,
+            vec![vec![Value::Integer(160), Value::Text("Brown".to_string())]]
+        );
+
+        let result = run(
+            "without_rowid.db",
+            "SELECT count(*), sum(weight) FROM pears WHERE 0",
+        )
+        .unwrap();
+        assert_eq!(result.rows, vec![vec![Value::Integer(0), Value::Null]]);
+
+        let error = run("without_rowid.db", "SELECT 1 FROM pears WHERE count(*) > 1")
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "misuse of aggregate: count()");
+        let error = run("without_rowid.db", "SELECT name FROM pears GROUP BY 2")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "1st GROUP BY term out of range - should be between 1 and 1"
+        );
+    }
+
+    #[test]
+    fn sorts_and_limits_rows() {
+        let names = |sql| {
+            run("without_rowid.db", sql)
+                .unwrap()
+                .rows
+                .into_iter()
+                .map(|row| row[0].to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names("SELECT name, weight FROM pears ORDER BY name DESC, 2 LIMIT 3"),
+            vec!["Comice", "Bosc", "Bartlett"]
+        );
+        assert_eq!(
+            names("SELECT name FROM pears ORDER BY weight LIMIT 2 OFFSET 1"),
+            vec!["Bartlett", "Bartlett"]
+        );
+        // ORDER BY prefers result column aliases over columns of the table.
+        assert_eq!(
+            names("SELECT color AS name FROM pears ORDER BY name COLLATE NOCASE LIMIT -1, 1"),
+            vec!["Brown"]
+        );
+        assert_eq!(
+            names("SELECT color FROM pears GROUP BY color ORDER BY count(*) DESC, 1"),
+            vec!["Green", "Brown", "Red", "Yellow"]
+        );
+        assert_eq!(
+            names(
+                "SELECT name FROM pears
+                 ORDER BY CASE WHEN color = 'Green' THEN NULL ELSE 1 END DESC NULLS FIRST, weight"
+            ),
+            vec!["Bartlett", "Anjou", "Bosc", "Bartlett", "Comice"]
+        );
+    }
+
+    #[test]
+    fn reports_invalid_sorts_and_limits() {
+        let error = |sql| run("without_rowid.db", sql).err().unwrap().to_string();
+
+        assert_eq!(
+            error("SELECT name FROM pears ORDER BY 1, 3"),
+            "2nd ORDER BY term out of range - should be between 1 and 1"
+        );
+        assert_eq!(
+            error("SELECT name FROM pears ORDER BY sum(weight)"),
+            "misuse of aggregate: sum()"
+        );
+        assert_eq!(
+            error("SELECT name FROM pears ORDER BY name COLLATE foo"),
+            "no such collation sequence: foo"
+        );
+        assert_eq!(
+            error("SELECT name FROM pears LIMIT 1.5"),
+            "datatype mismatch"
+        );
+    }
+
+    #[test]
+    fn removes_duplicate_rows() {
+        let rows = |sql| run("without_rowid.db", sql).unwrap().rows;
+        let text = |t: &str| Value::Text(t.to_string());
+
+        assert_eq!(
+            rows("SELECT DISTINCT name FROM pears"),
+            ["Anjou", "Bartlett", "Bosc", "Comice"].map(|t| vec![text(t)])
+        );
+        // Integers and reals with the same value are duplicates.
+        assert_eq!(
+            rows("SELECT DISTINCT weight / 100, 2.0 FROM pears"),
+            vec![
+                vec![Value::Integer(2), Value::Real(2.0)],
+                vec![Value::Integer(1), Value::Real(2.0)],
+            ]
+        );
+        assert_eq!(
+            rows(
+                "SELECT DISTINCT CASE WHEN weight > 175 THEN 'HEAVY' ELSE 'heavy' END
+                 COLLATE NOCASE FROM pears"
+            ),
+            vec![vec![text("HEAVY")]]
+        );
+    }
+
+    #[test]
+    fn combines_compound_selects() {
+        let names = |sql| {
+            let result = run("without_rowid.db", sql).unwrap();
+            let rows = result.rows.iter().map(|row| row[0].to_string());
+            (result.columns, rows.collect::<Vec<_>>())
+        };
+
+        assert_eq!(
+            names(
+                "SELECT color AS c FROM pears UNION SELECT name FROM pears ORDER BY c DESC LIMIT 3"
+            ),
+            (
+                vec!["c".to_string()],
+                vec!["Yellow".to_string(), "Red".to_string(), "Green".to_string()]
+            )
+        );
+        // The leftmost SELECT with a collation decides it, and columns have
+        // the binary collation unless declared with another one.
+        assert_eq!(
+            names("SELECT color || '' FROM pears UNION ALL SELECT 'green' COLLATE NOCASE INTERSECT SELECT 'RED'").1,
+            vec!["Red"]
+        );
+        assert!(names("SELECT color FROM pears UNION ALL SELECT 'green' COLLATE NOCASE INTERSECT SELECT 'RED'")
+            .1
+            .is_empty());
+        assert_eq!(
+            names("SELECT name FROM pears EXCEPT SELECT 'Bosc' EXCEPT SELECT 'Comice'").1,
+            vec!["Anjou", "Bartlett"]
+        );
+        // Of duplicates on both sides, UNION keeps the one on the right.
+        assert_eq!(names("SELECT 2 UNION SELECT 2.0").1, vec!["2.0"]);
+        assert_eq!(names("SELECT 2 UNION SELECT 2.0 ORDER BY 1").1, vec!["2.0"]);
+        // Of duplicates on the same side, the first one is kept when sorting
+        // in ascending order, and the last one otherwise.
+        for operator in ["UNION", "INTERSECT"] {
+            let sql = format!(
+                "SELECT word FROM words WHERE id < 6 {operator} \
+                 SELECT word FROM words WHERE id < 6"
+            );
+            assert_eq!(
+                rows("collations.db", &format!("{sql} ORDER BY 1")),
+                ["apple", "banana"]
+            );
+            assert_eq!(rows("collations.db", &sql), ["APPLE", "Banana"]);
+        }
+        assert_eq!(
+            rows(
+                "collations.db",
+                "SELECT word FROM words WHERE id < 6 EXCEPT SELECT 'x' ORDER BY 1"
+            ),
+            ["apple", "banana"]
+        );
+    }
+
+    #[test]
+    fn reports_invalid_compound_selects() {
+        let error = |sql| run("without_rowid.db", sql).err().unwrap().to_string();
+
+        assert_eq!(
+            error("SELECT color, name FROM pears UNION ALL SELECT 1"),
+            "SELECTs to the left and right of UNION ALL do not have the same number of result columns"
+        );
+        assert_eq!(
+            error("SELECT color FROM pears UNION SELECT name FROM pears ORDER BY color || 'x'"),
+            "1st ORDER BY term does not match any column in the result set"
+        );
+    }
+
+    #[test]
+    fn joins_tables() {
+        assert_eq!(
+            rows(
+                "joins.db",
+                "SELECT c.name, o.amount FROM customers c JOIN orders o ON o.customer_id = c.id \
+                  WHERE o.id < 4 ORDER BY o.id"
+            ),
+            ["Bob|3", "Carol|6", "Dave|9"]
+        );
+        // Eve has no orders.
+        assert_eq!(
+            rows(
+                "joins.db",
+                "SELECT name, count(amount) FROM customers LEFT JOIN orders \
+                  ON customer_id = customers.id AND status = 'open' GROUP BY 1"
+            ),
+            ["Alice|143", "Bob|142", "Carol|143", "Dave|143", "Eve|0"]
+        );
+        assert_eq!(
+            rows("joins.db", "SELECT * FROM customers NATURAL JOIN cities"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            rows(
+                "joins.db",
+                "SELECT * FROM customers, cities USING (name) WHERE id = 0"
+            ),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            rows(
+                "joins.db",
+                "SELECT a.*, b.id FROM customers a LEFT JOIN cities ON city = cities.name \
+                  JOIN customers b USING (city) WHERE country IS NULL"
+            ),
+            ["3|Carol|Oslo|3"]
+        );
+        assert_eq!(
+            rows(
+                "joins.db",
+                "SELECT * FROM customers a JOIN customers b USING (id, name) WHERE id = 2"
+            ),
+            ["2|Bob|Berlin|Berlin"]
+        );
+    }
+
+    #[test]
+    fn reports_invalid_joins() {
+        let error = |sql| run("joins.db", sql).err().unwrap().to_string();
+
+        assert_eq!(
+            error("SELECT id FROM customers a JOIN customers b ON a.id = b.id"),
+            "ambiguous column name: id"
+        );
+        assert_eq!(
+            error("SELECT * FROM customers JOIN cities USING (country)"),
+            "cannot join using column country - column not present in both tables"
+        );
+        assert_eq!(
+            error("SELECT * FROM customers NATURAL JOIN cities ON 1"),
+            "a NATURAL join may not have an ON or USING clause"
+        );
+        assert_eq!(
+            error("SELECT * FROM customers LEFT JOIN cities ON orders.id = 1 JOIN orders"),
+            "ON clause references tables to its right"
+        );
+    }
+
+    #[test]
+    fn runs_subqueries() {
+        assert_eq!(
+            rows(
+                "joins.db",
+                "SELECT name, (SELECT count(*) FROM orders WHERE customer_id = c.id) \
+                  FROM customers c WHERE id > 3"
+            ),
+            ["Dave|428", "Eve|0"]
+        );
+        assert_eq!(
+            rows(
+                "joins.db",
+                "SELECT name FROM customers c WHERE NOT EXISTS \
+                  (SELECT 1 FROM orders WHERE customer_id = c.id)"
+            ),
+            ["Eve"]
+        );
+        // The subquery returns NULL for orders without a customer.
+        assert_eq!(
+            rows(
+                "joins.db",
+                "SELECT name FROM customers WHERE id NOT IN (SELECT customer_id FROM orders)"
+            ),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            rows(
+                "joins.db",
+                "SELECT id FROM orders WHERE amount = (SELECT max(amount) FROM orders) \
+                  AND id < 1000"
+            ),
+            ["333"]
+        );
+        assert_eq!(
+            rows(
+                "joins.db",
+                "SELECT c.name, t.n FROM customers c \
+                  JOIN (SELECT customer_id, count(*) AS n FROM orders GROUP BY 1) AS t \
+                  ON t.customer_id = c.id WHERE c.id < 3"
+            ),
+            ["Alice|429", "Bob|429"]
+        );
+        assert_eq!(
+            rows("joins.db", "SELECT * FROM (SELECT 1 AS a, 2 AS a)"),
+            ["1|2"]
+        );
+    }
+
+    #[test]
+    fn runs_uncorrelated_subqueries_once() {
+        let db = Database::open("joins.db").unwrap();
+        let Some(Statement::Select(select)) = parse(
+            "SELECT (SELECT max(amount) FROM orders), \
+             (SELECT count(*) FROM orders WHERE customer_id = c.id) FROM customers c",
+        )
+        .unwrap()
+        .pop() else {
+            panic!("Expected SELECT");
+        };
+        let state = RunState::default();
+        let context = Context::new(&db, &state);
+
+        let result = crate::query::run(&select, false, context).unwrap();
+
+        assert_eq!(result.rows.len(), 5);
+        // Only the result of the subquery that doesn't refer to the customer
+        // is kept for the next rows.
+        assert_eq!(state.cache.borrow().len(), 1);
+    }
+
+    #[test]
+    fn reports_invalid_subqueries() {
+        let error = |sql| run("joins.db", sql).err().unwrap().to_string();
+
+        assert_eq!(
+            error("SELECT 1 IN (SELECT id, name FROM customers)"),
+            "sub-select returns 2 columns - expected 1"
+        );
+        assert_eq!(
+            error("SELECT (SELECT nosuch FROM orders) FROM customers"),
+            "no such column: nosuch"
+        );
+        assert_eq!(
+            error("SELECT rowid FROM (SELECT id FROM customers)"),
+            "no such column: rowid"
+        );
+    }
+
+    #[test]
+    fn selects_the_rowid() {
+        let result = run("without_rowid.db", "SELECT oid, id, _ROWID_ FROM plums").unwrap();
+
+        assert_eq!(result.rows[2], vec![Value::Integer(3); 3]);
+    }
+
+    #[test]
+    fn compares_columns_by_affinity() {
+        let ids = |sql| {
+            let result = run("affinity.db", sql).unwrap();
+            let mut ids = result
+                .rows
+                .iter()
+                .map(|row| row[0].to_integer())
+                .collect::<Vec<_>>();
+            ids.sort();
+            ids
+        };
+
+        // TEXT columns compare numbers as text, numeric columns compare text
+        // as numbers, and columns without affinity compare values as they are.
+        assert_eq!(ids("SELECT id FROM readings WHERE label = 5"), [1]);
+        assert_eq!(ids("SELECT id FROM readings WHERE amount = ' 7 '"), [5]);
+        assert_eq!(ids("SELECT id FROM readings WHERE ratio = '7'"), [4, 5]);
+        assert_eq!(
+            ids("SELECT id FROM readings WHERE anything = 10"),
+            Vec::<i64>::new()
+        );
+        assert_eq!(
+            ids("SELECT id FROM readings WHERE label = amount"),
+            [1, 2, 3, 4, 5]
+        );
+        assert_eq!(
+            ids("SELECT id FROM readings WHERE label IN (5, 10)"),
+            [1, 2]
+        );
+        assert_eq!(
+            ids("SELECT id FROM readings WHERE amount BETWEEN '3' AND '6'"),
+            [1, 4]
+        );
+        assert_eq!(
+            ids("SELECT id FROM readings WHERE +label = 5"),
+            Vec::<i64>::new()
+        );
+        assert_eq!(
+            ids("SELECT id FROM readings WHERE CAST(label AS INT) = '5'"),
+            [1]
+        );
+        // Lookups by rowid and index convert the key the same way.
+        assert_eq!(ids("SELECT id FROM readings WHERE id = ' 2'"), [2]);
+        assert_eq!(ids("SELECT amount FROM readings WHERE label = 10"), [10]);
+        let result = run(
+            "joins.db",
+            "SELECT count(*) FROM orders WHERE customer_id = '3'",
+        );
+        assert_eq!(result.unwrap().rows, [[Value::Integer(429)]]);
+
+        // Reals stored as integers are read back as reals.
+        let result = run("affinity.db", "SELECT ratio FROM readings WHERE id = 1").unwrap();
+        assert_eq!(result.rows, [[Value::Real(5.0)]]);
+    }
+
+    #[test]
+    fn compares_text_by_collation() {
+        // `word` is declared NOCASE, `padded` RTRIM and `plain` BINARY, with
+        // an index on `plain` that is NOCASE.
+        assert_eq!(
+            rows(
+                "collations.db",
+                "SELECT id FROM words WHERE word = 'APPLE' ORDER BY id"
+            ),
+            ["1", "2", "3"]
+        );
+        assert_eq!(
+            rows(
+                "collations.db",
+                "SELECT id FROM words WHERE plain = 'APPLE'"
+            ),
+            ["3"]
+        );
+        assert_eq!(
+            rows(
+                "collations.db",
+                "SELECT id FROM words WHERE plain = 'APPLE' COLLATE NOCASE ORDER BY id"
+            ),
+            ["1", "2", "3"]
+        );
+        assert_eq!(
+            rows(
+                "collations.db",
+                "SELECT id FROM words WHERE 'APPLE' COLLATE BINARY = word"
+            ),
+            ["3"]
+        );
+        assert_eq!(
+            rows(
+                "collations.db",
+                "SELECT id FROM words WHERE padded = 'apple   ' ORDER BY id"
+            ),
+            ["1", "2", "3"]
+        );
+        // The column on the left decides, even without a declared collation.
+        assert_eq!(
+            rows(
+                "collations.db",
+                "SELECT count(*) FROM words WHERE plain = word"
+            ),
+            ["6"]
+        );
+        assert_eq!(
+            rows(
+                "collations.db",
+                "SELECT count(*) FROM words WHERE word = plain"
+            ),
+            ["7"]
+        );
+
+        assert_eq!(
+            rows(
+                "collations.db",
+                "SELECT word FROM words WHERE id < 6 ORDER BY word DESC, id"
+            ),
+            ["banana", "Banana", "apple", "Apple", "APPLE"]
+        );
+        assert_eq!(
+            rows(
+                "collations.db",
+                "SELECT count(*) FROM words GROUP BY word ORDER BY 1 DESC LIMIT 2"
+            ),
+            ["3", "2"]
+        );
+        assert_eq!(
+            rows(
+                "collations.db",
+                "SELECT DISTINCT plain COLLATE NOCASE FROM words WHERE id < 6"
+            ),
+            ["apple", "banana"]
+        );
+        assert_eq!(
+            rows(
+                "collations.db",
+                "SELECT max(word) FROM (SELECT word FROM words)"
+            ),
+            ["éclair"]
+        );
+
+        let error = run(
+            "collations.db",
+            "SELECT 1 FROM words WHERE word = 'a' COLLATE nope",
+        );
+        assert_eq!(
+            error.err().unwrap().to_string(),
+            "no such collation sequence: nope"
+        );
+    }
+
+    #[test]
+    fn reports_unknown_columns() {
+        let error = run("without_rowid.db", "SELECT nope FROM pears")
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "no such column: nope");
+
+        // Tables without a rowid have no rowid pseudo-column.
+        let error = run("without_rowid.db", "SELECT rowid FROM pears")
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "no such column: rowid");
+
+        // Columns are checked even when no rows match.
+        let error = run("without_rowid.db", "SELECT 1 FROM pears WHERE 0 AND p.name")
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "no such column: p.name");
+    }
+}
@@ -0,0 +1,176 @@
+use anyhow::{anyhow, bail, Result};
+
+use crate::btree_page::*;
+use crate::database::Database;
+
+/// The kind of object described by an entry of the `sqlite_schema` table.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SchemaType {
+    Table,
+    Index,
+    View,
+    Trigger,
+}
+
+impl TryFrom<&str> for SchemaType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        match value {
+            "table" => Ok(Self::Table),
+            "index" => Ok(Self::Index),
+            "view" => Ok(Self::View),
+            "trigger" => Ok(Self::Trigger),
+            _ => Err(anyhow!("Not a valid schema object type: {value}")),
+        }
+    }
+}
+
+/// A row of the `sqlite_schema` table.
+#[derive(Clone, Debug)]
+pub struct SchemaEntry {
+    /// The kind of object this entry describes.
+    pub kind: SchemaType,
+    /// The name of the object.
+    pub name: String,
+    /// The name of the table or view the object is associated with.
+    /// For tables and views, this is the same as [`name`](SchemaEntry::name).
+    pub tbl_name: String,
+    /// The page number of the root page of the object's B-tree.
+    /// Zero for views, triggers and virtual tables.
+    pub rootpage: u32,
+    /// The SQL text that created the object.
+    /// Missing for the indexes SQLite creates automatically for
+    /// UNIQUE and PRIMARY KEY constraints.
+    pub sql: Option<String>,
+}
+
+impl SchemaEntry {
+    fn from_record(record: Record) -> Result<Self> {
+        let [kind, name, tbl_name, rootpage, sql] = <[RecordValue; 5]>::try_from(record.values)
+            .map_err(|values| {
+                anyhow!("Expected 5 columns in schema table, got {}", values.len())
+            })?;
+
+        let RecordValue::N13AndOdd(kind) = kind else {
+            bail!("Unexpected record value for schema object type");
+        };
+        let RecordValue::N13AndOdd(name) = name else {
+            bail!("Unexpected record value for schema object name");
+        };
+        let RecordValue::N13AndOdd(tbl_name) = tbl_name else {
+            bail!("Unexpected record value for schema table name");
+        };
+        let rootpage = match rootpage {
+            RecordValue::Null => 0,
+            rp => rp
+                .as_integer()
+                .and_then(|rp| u32::try_from(rp).ok())
+                .ok_or_else(|| anyhow!("Unexpected record value for rootpage"))?,
+        };
+        let sql = match sql {
+            RecordValue::Null => None,
+            RecordValue::N13AndOdd(sql) => Some(sql),
+            _ => bail!("Unexpected record value for schema SQL"),
+        };
+
+        Ok(Self {
+            kind: kind.as_str().try_into()?,
+            name,
+            tbl_name,
+            rootpage,
+            sql,
+        })
+    }
+}
+
+/// The catalog of all tables, indexes, views and triggers in a database.
+#[derive(Clone, Debug, Default)]
+pub struct Schema {
+    /// The entries in the order they are stored in the `sqlite_schema` table.
+    pub entries: Vec<SchemaEntry>,
+}
+
+impl Schema {
+    /// Reads the `sqlite_schema` table, which is rooted at page 1.
+    pub fn load(db: &Database) -> Result<Self> {
+        let mut entries = Vec::new();
+        for cell in db.cursor(1) {
+            let Cell::TableLeaf { payload, .. } = cell? else {
+                bail!("Unexpected cell type");
+            };
+            entries.push(SchemaEntry::from_record(payload)?);
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Looks up an object by name. Names are compared case-insensitively,
+    /// like SQLite does for identifiers.
+    pub fn find(&self, name: &str) -> Option<&SchemaEntry> {
+        self.entries
+            .iter()
+            .find(|e| e.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Looks up a table by name.
+    pub fn table(&self, name: &str) -> Option<&SchemaEntry> {
+        self.find(name).filter(|e| e.kind == SchemaType::Table)
+    }
+
+    /// Looks up a view by name.
+    pub fn view(&self, name: &str) -> Option<&SchemaEntry> {
+        self.find(name).filter(|e| e.kind == SchemaType::View)
+    }
+
+    /// All tables, including SQLite's internal ones.
+    pub fn tables(&self) -> impl Iterator<Item = &SchemaEntry> {
+        self.of_kind(SchemaType::Table)
+    }
+
+    /// All objects of the given kind.
+    pub fn of_kind(&self, kind: SchemaType) -> impl Iterator<Item = &SchemaEntry> {
+        self.entries.iter().filter(move |e| e.kind == kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::Database;
+    use crate::schema::*;
+
+    #[test]
+    fn loads_typed_entries() {
+        let db = Database::open("sample.db").unwrap();
+
+        let kinds = db
+            .schema
+            .entries
+            .iter()
+            .map(|e| (e.kind, e.name.as_str(), e.tbl_name.as_str(), e.rootpage))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            kinds,
+            vec![
+                (SchemaType::Table, "apples", "apples", 2),
+                (SchemaType::Table, "sqlite_sequence", "sqlite_sequence", 3),
+                (SchemaType::Table, "oranges", "oranges", 4),
+            ]
+        );
+        assert!(db.schema.entries[0]
+            .sql
+            .as_deref()
+            .unwrap()
+            .starts_with("CREATE TABLE apples"));
+    }
+
+    #[test]
+    fn looks_up_names_case_insensitively() {
+        let db = Database::open("sample.db").unwrap();
+
+        assert_eq!(db.schema.table("APPLES").unwrap().name, "apples");
+        assert_eq!(db.schema.find("Oranges").unwrap().rootpage, 4);
+        assert!(db.schema.table("pears").is_none());
+    }
+}
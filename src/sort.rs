@@ -0,0 +1,462 @@
+use anyhow::{bail, Result};
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::rc::Rc;
+use std::sync::atomic::{self, AtomicUsize};
+
+use crate::btree_page::Record;
+use crate::collation::Collation;
+use crate::value::Value;
+
+/// How many bytes of rows a sort may hold in memory before it writes them to
+/// a temporary file.
+const MEMORY_BUDGET: usize = 64 * 1024 * 1024;
+
+/// How to order rows by one of their sort keys.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SortOrder {
+    pub descending: bool,
+    pub nulls_first: bool,
+    pub collation: Collation,
+}
+
+impl SortOrder {
+    /// The order of an `ORDER BY` term. Unless given explicitly, NULLs come
+    /// first in ascending order and last in descending order, as NULL is
+    /// smaller than any other value.
+    pub fn new(descending: bool, nulls_first: Option<bool>, collation: Collation) -> Self {
+        Self {
+            descending,
+            nulls_first: nulls_first.unwrap_or(!descending),
+            collation,
+        }
+    }
+}
+
+/// Compares the sort keys of two rows.
+pub fn compare_keys(order: &[SortOrder], a: &[Value], b: &[Value]) -> Ordering {
+    for ((order, a), b) in order.iter().zip(a).zip(b) {
+        let ordering = match (a.is_null(), b.is_null()) {
+            (true, true) => Ordering::Equal,
+            (true, false) if order.nulls_first => Ordering::Less,
+            (true, false) => Ordering::Greater,
+            (false, true) if order.nulls_first => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) if order.descending => a.compare_with(b, order.collation).reverse(),
+            (false, false) => a.compare_with(b, order.collation),
+        };
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// A row to be sorted, along with the values it is sorted by.
+struct SortRow {
+    keys: Vec<Value>,
+    values: Vec<Value>,
+    /// The position the row was added at, which orders rows with equal keys.
+    seq: u64,
+}
+
+impl SortRow {
+    /// Roughly how many bytes of memory the row takes up.
+    fn size(&self) -> usize {
+        let value_size = |value: &Value| {
+            size_of::<Value>()
+                + match value {
+                    Value::Text(t) => t.len(),
+                    Value::Blob(b) => b.len(),
+                    _ => 0,
+                }
+        };
+        size_of::<Self>()
+            + self
+                .keys
+                .iter()
+                .chain(&self.values)
+                .map(value_size)
+                .sum::<usize>()
+    }
+}
+
+/// A row in a heap, which orders rows by their keys.
+struct HeapRow {
+    row: SortRow,
+    order: Rc<[SortOrder]>,
+    /// The run the row was read from, when merging runs.
+    run: usize,
+}
+
+impl Ord for HeapRow {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_keys(&self.order, &self.row.keys, &other.row.keys)
+            .then(self.row.seq.cmp(&other.row.seq))
+    }
+}
+
+impl PartialOrd for HeapRow {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for HeapRow {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeapRow {}
+
+/// Sorts rows by their keys. Rows with equal keys keep the order they were
+/// added in.
+///
+/// Rows are sorted in memory, unless they take up more than the memory
+/// budget. Then, sorted runs of rows are written to temporary files, which
+/// are merged at the end. With a limit, only the first rows are kept, in a
+/// heap that holds no more rows than the limit, and which is written to a
+/// sorted run in the same way when it takes up more than the budget.
+pub struct Sorter {
+    order: Rc<[SortOrder]>,
+    limit: Option<usize>,
+    budget: usize,
+    rows: Vec<SortRow>,
+    /// The estimated size of `rows`, or with a limit of `top`, in bytes.
+    size: usize,
+    /// With a limit, the first rows so far, with the last of them on top.
+    top: BinaryHeap<HeapRow>,
+    runs: Vec<File>,
+    next_seq: u64,
+}
+
+impl Sorter {
+    pub fn new(order: Vec<SortOrder>, limit: Option<usize>) -> Self {
+        Self::with_budget(order, limit, MEMORY_BUDGET)
+    }
+
+    pub fn with_budget(order: Vec<SortOrder>, limit: Option<usize>, budget: usize) -> Self {
+        Self {
+            order: order.into(),
+            limit,
+            budget,
+            rows: Vec::new(),
+            size: 0,
+            top: BinaryHeap::new(),
+            runs: Vec::new(),
+            next_seq: 0,
+        }
+    }
+
+    /// Adds a row with the keys it is sorted by.
+    pub fn push(&mut self, keys: Vec<Value>, values: Vec<Value>) -> Result<()> {
+        let row = SortRow {
+            keys,
+            values,
+            seq: self.next_seq,
+        };
+        self.next_seq += 1;
+
+        if let Some(limit) = self.limit {
+            self.size += row.size();
+            self.top.push(HeapRow {
+                row,
+                order: self.order.clone(),
+                run: 0,
+            });
+            if self.top.len() > limit {
+                let last = self
+                    .top
+                    .pop()
+                    .expect("The heap has more rows than the limit");
+                self.size -= last.row.size();
+            }
+            if self.size > self.budget {
+                self.rows.extend(self.top.drain().map(|row| row.row));
+                self.spill()?;
+            }
+            return Ok(());
+        }
+
+        self.size += row.size();
+        self.rows.push(row);
+        if self.size > self.budget {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn sort_rows(&mut self) {
+        let order = self.order.clone();
+        // The sort is stable, so rows with equal keys stay in order.
+        self.rows
+            .sort_by(|a, b| compare_keys(&order, &a.keys, &b.keys));
+    }
+
+    /// Writes the rows in memory to a temporary file as a sorted run.
+    fn spill(&mut self) -> Result<()> {
+        self.sort_rows();
+        let mut file = temp_file()?;
+        let mut writer = BufWriter::new(&mut file);
+        for row in self.rows.drain(..) {
+            write_row(&mut writer, row)?;
+        }
+        writer.flush()?;
+        drop(writer);
+        file.seek(SeekFrom::Start(0))?;
+
+        self.runs.push(file);
+        self.size = 0;
+        Ok(())
+    }
+
+    /// Returns the values of the rows in sorted order.
+    pub fn finish(mut self) -> Result<Box<dyn Iterator<Item = Result<Vec<Value>>>>> {
+        if self.limit.is_some() {
+            if self.runs.is_empty() {
+                let rows = self.top.into_sorted_vec();
+                return Ok(Box::new(rows.into_iter().map(|row| Ok(row.row.values))));
+            }
+            self.rows.extend(self.top.drain().map(|row| row.row));
+        }
+        if self.runs.is_empty() {
+            self.sort_rows();
+            return Ok(Box::new(self.rows.into_iter().map(|row| Ok(row.values))));
+        }
+
+        if !self.rows.is_empty() {
+            self.spill()?;
+        }
+        // Each run holds the first rows of those written to it, so the first
+        // rows of all of them are the first rows overall.
+        let limit = self.limit.unwrap_or(usize::MAX);
+        let mut merge = Merge {
+            runs: self.runs.into_iter().map(BufReader::new).collect(),
+            heap: BinaryHeap::new(),
+            order: self.order,
+        };
+        for run in 0..merge.runs.len() {
+            merge.advance(run)?;
+        }
+        Ok(Box::new(merge.take(limit)))
+    }
+}
+
+/// A queue of rows that returns the row with the smallest keys first, and of
+/// rows with equal keys the one added first.
+pub struct PriorityQueue {
+    heap: BinaryHeap<Reverse<HeapRow>>,
+    order: Rc<[SortOrder]>,
+    next_seq: u64,
+}
+
+impl PriorityQueue {
+    pub fn new(order: Vec<SortOrder>) -> Self {
+        Self {
+            heap: BinaryHeap::new(),
+            order: order.into(),
+            next_seq: 0,
+        }
+    }
+
+    pub fn push(&mut self, keys: Vec<Value>, values: Vec<Value>) {
+        let row = SortRow {
+            keys,
+            values,
+            seq: self.next_seq,
+        };
+        self.next_seq += 1;
+        self.heap.push(Reverse(HeapRow {
+            row,
+            order: self.order.clone(),
+            run: 0,
+        }));
+    }
+
+    pub fn pop(&mut self) -> Option<Vec<Value>> {
+        let Reverse(HeapRow { row, .. }) = self.heap.pop()?;
+        Some(row.values)
+    }
+}
+
+/// Merges sorted runs, by repeatedly taking the smallest of their first rows.
+struct Merge {
+    runs: Vec<BufReader<File>>,
+    /// The next row of each run that has rows left.
+    heap: BinaryHeap<Reverse<HeapRow>>,
+    order: Rc<[SortOrder]>,
+}
+
+impl Merge {
+    /// Reads the next row of a run into the heap.
+    fn advance(&mut self, run: usize) -> Result<()> {
+        if let Some(row) = read_row(&mut self.runs[run], self.order.len())? {
+            self.heap.push(Reverse(HeapRow {
+                row,
+                order: self.order.clone(),
+                run,
+            }));
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Merge {
+    type Item = Result<Vec<Value>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse(HeapRow { row, run, .. }) = self.heap.pop()?;
+        match self.advance(run) {
+            Ok(()) => Some(Ok(row.values)),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Writes a row as a record of its position, keys and values, preceded by
+/// the length of the record.
+fn write_row(writer: &mut impl Write, row: SortRow) -> Result<()> {
+    let mut values = vec![Value::Integer(row.seq as i64)];
+    values.extend(row.keys);
+    values.extend(row.values);
+    let record = Record::from_values(values, 4).serialize();
+
+    writer.write_all(&(record.len() as u32).to_le_bytes())?;
+    writer.write_all(&record)?;
+    Ok(())
+}
+
+/// Reads a row written by [`write_row`], or `None` at the end of the run.
+fn read_row(reader: &mut impl Read, key_count: usize) -> Result<Option<SortRow>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    let mut bytes = vec![0; u32::from_le_bytes(len) as usize];
+    reader.read_exact(&mut bytes)?;
+
+    let (record, _) = Record::parse(&bytes, bytes.len());
+    let mut values = record.values.into_iter().map(Value::from);
+    let Some(Value::Integer(seq)) = values.next() else {
+        bail!("Corrupt sort run");
+    };
+    let keys = values.by_ref().take(key_count).collect();
+
+    Ok(Some(SortRow {
+        keys,
+        values: values.collect(),
+        seq: seq as u64,
+    }))
+}
+
+/// Creates a temporary file that is deleted once it is closed.
+fn temp_file() -> Result<File> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "sqlite-sort-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, atomic::Ordering::Relaxed)
+    ));
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    // The file lives on while it is open, and the OS cleans it up after.
+    fs::remove_file(&path)?;
+
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sort::*;
+
+    /// Adds the numbers 0 to 999 shuffled to a sorter, with the number as
+    /// the key and its position as the value.
+    fn fill(order: SortOrder, limit: Option<usize>, budget: usize) -> Sorter {
+        let mut sorter = Sorter::with_budget(vec![order], limit, budget);
+        for i in 0..1000 {
+            let key = (i * 7919) % 1000;
+            // Every tenth number is NULL instead.
+            let key = if key % 10 == 0 {
+                Value::Null
+            } else {
+                Value::Integer(key)
+            };
+            sorter
+                .push(vec![key.clone()], vec![key, Value::Integer(i)])
+                .unwrap();
+        }
+        sorter
+    }
+
+    fn sort(order: SortOrder, limit: Option<usize>, budget: usize) -> Vec<Vec<Value>> {
+        let sorter = fill(order, limit, budget);
+        sorter.finish().unwrap().map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn spills_and_merges_runs_like_an_in_memory_sort() {
+        let order = SortOrder::new(true, None, Collation::Binary);
+        let in_memory = sort(order, None, usize::MAX);
+        let spilled = sort(order, None, 4096);
+
+        assert_eq!(in_memory.len(), 1000);
+        assert!(in_memory == spilled);
+        assert_eq!(in_memory[0][0], Value::Integer(999));
+        // NULLs come last in descending order, in the order they were added.
+        assert!(in_memory[900..].iter().all(|row| row[0].is_null()));
+        assert!(in_memory[900..]
+            .windows(2)
+            .all(|rows| rows[0][1].compare(&rows[1][1]).is_lt()));
+    }
+
+    #[test]
+    fn keeps_only_the_first_rows_with_a_limit() {
+        let order = SortOrder::new(false, Some(false), Collation::Binary);
+        let top = sort(order, Some(5), usize::MAX);
+
+        let keys = top.iter().map(|row| row[0].clone()).collect::<Vec<_>>();
+        assert_eq!(keys, [1, 2, 3, 4, 5].map(Value::Integer));
+        assert!(sort(order, Some(0), usize::MAX).is_empty());
+    }
+
+    #[test]
+    fn spills_the_first_rows_when_they_exceed_the_budget() {
+        let order = SortOrder::new(false, Some(false), Collation::Binary);
+        assert!(!fill(order, Some(500), 4096).runs.is_empty());
+
+        let in_memory = sort(order, None, usize::MAX);
+        let spilled = sort(order, Some(500), 4096);
+        assert_eq!(spilled.len(), 500);
+        assert!(in_memory[..500] == spilled);
+    }
+
+    #[test]
+    fn pops_rows_by_keys_then_in_order_added() {
+        let mut queue = PriorityQueue::new(vec![SortOrder::new(true, None, Collation::Binary)]);
+        for (key, value) in [(1, 1), (3, 2), (1, 3), (2, 4)] {
+            queue.push(vec![Value::Integer(key)], vec![Value::Integer(value)]);
+        }
+
+        let values = std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>();
+        assert_eq!(values, [2, 4, 1, 3].map(|v| vec![Value::Integer(v)]));
+    }
+
+    #[test]
+    fn compares_keys_by_collation() {
+        let order = [SortOrder::new(false, None, Collation::NoCase)];
+        let text = |t: &str| vec![Value::Text(t.to_string())];
+
+        assert_eq!(compare_keys(&order, &text("a"), &text("B")), Ordering::Less);
+        assert_eq!(
+            compare_keys(&order, &text("A"), &text("a")),
+            Ordering::Equal
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+
+use crate::ast::Statement;
+use crate::database::Database;
+use crate::parser;
+use crate::pragma;
+use crate::query;
+use crate::value::Value;
+
+/// A statement that is parsed once and may then run any number of times,
+/// with different values bound to its parameters.
+pub struct PreparedStatement {
+    statement: Statement,
+    /// The names of the parameters by index, starting at 1. Parameters
+    /// written as a bare `?` have no name.
+    parameters: Vec<Option<String>>,
+}
+
+impl PreparedStatement {
+    /// Prepares each of the statements of SQL text.
+    pub fn prepare_all(sql: &str) -> Result<Vec<Self>> {
+        let statements = parser::parse_with_parameters(sql)?;
+        Ok(statements
+            .into_iter()
+            .map(|(statement, parameters)| Self {
+                statement,
+                parameters,
+            })
+            .collect())
+    }
+
+    /// The number of parameters, which is the largest index of any of them.
+    pub fn parameter_count(&self) -> usize {
+        self.parameters.len()
+    }
+
+    /// The name of the parameter with the given index, like `:id` or `?2`.
+    pub fn parameter_name(&self, index: usize) -> Option<&str> {
+        self.parameters.get(index.checked_sub(1)?)?.as_deref()
+    }
+
+    /// Runs the statement with values bound to its parameters by index,
+    /// returning the rows it outputs. Parameters without a value are NULL.
+    pub fn execute(&self, db: &mut Database, values: &[Value]) -> Result<Vec<Vec<Value>>> {
+        match &self.statement {
+            Statement::Select(select) => Ok(query::execute(select, db, values)?.rows),
+            Statement::Pragma(pragma) => pragma::execute(pragma, db),
+            _ => bail!("attempt to write a readonly database"),
+        }
+    }
+}
+
+/// Values for parameters by their names, which are bound to the parameters
+/// of each statement that runs.
+#[derive(Default)]
+pub struct Bindings {
+    values: BTreeMap<String, Value>,
+}
+
+impl Bindings {
+    pub fn set(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn unset(&mut self, name: &str) {
+        self.values.remove(name);
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    /// The names and values of the bindings, in order of name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    /// The values for the parameters of a statement, by index. Parameters
+    /// written as a bare `?` are bound by their number, like `?1`.
+    pub fn values_for(&self, statement: &PreparedStatement) -> Vec<Value> {
+        (1..=statement.parameter_count())
+            .map(|index| {
+                let value = match statement.parameter_name(index) {
+                    Some(name) => self.values.get(name),
+                    None => self.values.get(&format!("?{index}")),
+                };
+                value.cloned().unwrap_or(Value::Null)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::statement::*;
+
+    #[test]
+    fn runs_prepared_statements_with_different_bindings() {
+        let mut db = Database::open("sample.db").unwrap();
+        let [statement] = &PreparedStatement::prepare_all(
+            "SELECT name FROM apples WHERE id = :id OR color = ? ORDER BY id",
+        )
+        .unwrap()[..] else {
+            panic!("Expected one statement");
+        };
+        assert_eq!(statement.parameter_count(), 2);
+        assert_eq!(statement.parameter_name(1), Some(":id"));
+        assert_eq!(statement.parameter_name(2), None);
+
+        let mut bindings = Bindings::default();
+        let mut names = |bindings: &Bindings| {
+            let rows = statement
+                .execute(&mut db, &bindings.values_for(statement))
+                .unwrap();
+            rows.into_iter()
+                .map(|row| row[0].to_string())
+                .collect::<Vec<_>>()
+        };
+        assert!(names(&bindings).is_empty());
+        bindings.set(":id", Value::Integer(1));
+        assert_eq!(names(&bindings), ["Granny Smith"]);
+        bindings.set(":id", Value::Integer(3));
+        bindings.set("?2", Value::Text("Red".to_string()));
+        assert_eq!(names(&bindings), ["Fuji", "Honeycrisp"]);
+        // Values are never part of the SQL, so quotes in them do no harm.
+        bindings.set(":id", Value::Text("1' OR 1 = 1 --".to_string()));
+        bindings.unset("?2");
+        assert!(names(&bindings).is_empty());
+    }
+}
@@ -0,0 +1,551 @@
+use anyhow::{anyhow, bail, Result};
+
+use crate::ast::{ColumnDef, Statement};
+use crate::btree_page::{Cell, Record};
+use crate::collation::Collation;
+use crate::database::Database;
+use crate::eval::{evaluate, Scope};
+use crate::index::{entries_with_prefix, Index};
+use crate::parser::parse;
+use crate::schema::SchemaEntry;
+use crate::sort::SortOrder;
+use crate::value::{Affinity, Value};
+
+/// A table, as described by its entry in the schema.
+pub struct Table {
+    /// The columns, in declaration order.
+    pub columns: Vec<ColumnDef>,
+    rootpage: u32,
+    /// The positions of the primary key columns, in the order they appear in the key.
+    primary_key: Vec<usize>,
+    /// Whether the table is declared `WITHOUT ROWID`, which means its rows
+    /// are stored in an index B-tree keyed by the primary key.
+    without_rowid: bool,
+    /// The position of the `INTEGER PRIMARY KEY` column, whose value is the rowid.
+    rowid_alias: Option<usize>,
+    /// For tables without a rowid, how their B-tree is ordered by each of the
+    /// primary key columns, unless it uses a collation that is not built in.
+    key_order: Option<Vec<SortOrder>>,
+    /// The indexes that can be used to look up rows of a table with a rowid.
+    indexes: Vec<Index>,
+}
+
+/// A row of a table.
+#[derive(Clone, Debug)]
+pub struct Row {
+    /// The rowid, unless the table is declared `WITHOUT ROWID`.
+    pub rowid: Option<i64>,
+    /// The values of the columns, in declaration order.
+    pub values: Vec<Value>,
+}
+
+/// A column of a table that an expression refers to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColumnRef {
+    Column(usize),
+    Rowid,
+}
+
+/// A way to find the rows of a table with given values in some of its
+/// columns, by descending a B-tree instead of scanning the whole table.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Lookup {
+    /// Seeks the row with the rowid given by the key.
+    Rowid,
+    /// Seeks the rows of a table without a rowid by the leading columns of
+    /// its primary key.
+    PrimaryKey,
+    /// Probes the index at the given position by its leading columns, and
+    /// seeks the rows of its entries by rowid.
+    Index(usize),
+}
+
+/// Checks whether a name refers to the rowid of a table, unless the table has
+/// a column by that name.
+pub fn is_rowid_name(name: &str) -> bool {
+    ["rowid", "oid", "_rowid_"]
+        .iter()
+        .any(|n| n.eq_ignore_ascii_case(name))
+}
+
+impl Table {
+    /// Looks up a table by name in the schema of the database.
+    pub fn load(db: &Database, name: &str) -> Result<Self> {
+        let Some(entry) = db.schema.table(name) else {
+            bail!("no such table: {name}");
+        };
+        let mut table = Self::from_schema(entry)?;
+        if table.has_rowid() {
+            table.indexes = Index::load_all(db, &entry.name, &table.columns)?;
+        }
+        Ok(table)
+    }
+
+    fn from_schema(entry: &SchemaEntry) -> Result<Self> {
+        let Some(sql) = &entry.sql else {
+            bail!("Missing schema SQL for table {}", entry.name);
+        };
+        let definition = match parse(sql)?.pop() {
+            Some(Statement::CreateTable(definition)) => definition,
+            _ => bail!("Invalid schema SQL for table {}", entry.name),
+        };
+        let primary_key = definition
+            .primary_key()
+            .iter()
+            .map(|pk| {
+                definition
+                    .column_index(&pk.name)
+                    .ok_or_else(|| anyhow!("Unknown primary key column: {}", pk.name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let key_order = definition
+            .primary_key()
+            .iter()
+            .zip(&primary_key)
+            .map(|(pk, &column)| {
+                // Without an explicit collation, the key uses the one of the column.
+                let collation = pk
+                    .collation
+                    .as_deref()
+                    .or(definition.columns[column].collation());
+                let collation = collation.map_or(Ok(Collation::Binary), Collation::from_name)?;
+                Ok(SortOrder::new(pk.descending, None, collation))
+            })
+            .collect::<Result<Vec<_>>>()
+            .ok();
+
+        Ok(Self {
+            rowid_alias: definition.rowid_alias(),
+            columns: definition.columns,
+            rootpage: entry.rootpage,
+            primary_key,
+            without_rowid: definition.without_rowid,
+            key_order,
+            indexes: Vec::new(),
+        })
+    }
+
+    pub fn has_rowid(&self) -> bool {
+        !self.without_rowid
+    }
+
+    /// Iterates over all rows of the table, in the order they are stored in its
+    /// B-tree. The values of each row are in the order the columns are declared
+    /// in, regardless of whether the table has a rowid or not.
+    pub fn rows<'a>(&'a self, db: &'a Database) -> impl Iterator<Item = Result<Row>> + 'a {
+        db.cursor(self.rootpage).map(|cell| self.read_row(cell?))
+    }
+
+    /// Chooses how to look up the rows with given values in some columns,
+    /// preferring a rowid over the primary key over an index. The values are
+    /// compared with the given collations, which the B-tree must be ordered
+    /// by. Returns the lookup and the positions in `columns` of the values
+    /// that make up its key, in key order.
+    pub fn lookup_for(&self, columns: &[(ColumnRef, Collation)]) -> Option<(Lookup, Vec<usize>)> {
+        if self.has_rowid() {
+            let rowid = columns.iter().position(|(column, _)| match column {
+                ColumnRef::Rowid => true,
+                ColumnRef::Column(i) => self.rowid_alias == Some(*i),
+            });
+            if let Some(rowid) = rowid {
+                return Some((Lookup::Rowid, vec![rowid]));
+            }
+        }
+
+        // The key is made of the leading columns that have values.
+        let prefix = |key: &[usize], order: &[SortOrder]| {
+            key.iter()
+                .zip(order)
+                .map_while(|(&i, order)| {
+                    columns
+                        .iter()
+                        .position(|&c| c == (ColumnRef::Column(i), order.collation))
+                })
+                .collect::<Vec<_>>()
+        };
+        if self.without_rowid {
+            let key = prefix(&self.primary_key, self.key_order.as_deref()?);
+            return (!key.is_empty()).then_some((Lookup::PrimaryKey, key));
+        }
+        let (index, key) = self
+            .indexes
+            .iter()
+            .map(|index| prefix(&index.columns, &index.order))
+            .enumerate()
+            .max_by_key(|(_, key)| key.len())?;
+        (!key.is_empty()).then_some((Lookup::Index(index), key))
+    }
+
+    /// Iterates over the rows found by a lookup with the given key, which
+    /// holds the values at the positions returned by [`Table::lookup_for`]. As
+    /// a column never equals NULL, a key with NULL finds no rows.
+    pub fn lookup<'a>(
+        &'a self,
+        db: &'a Database,
+        lookup: Lookup,
+        key: Vec<Value>,
+    ) -> Result<Box<dyn Iterator<Item = Result<Row>> + 'a>> {
+        if key.iter().any(Value::is_null) {
+            return Ok(Box::new(std::iter::empty()));
+        }
+        match lookup {
+            Lookup::Rowid => {
+                let rowid = match key[..] {
+                    [Value::Integer(rowid)] => Some(rowid),
+                    [Value::Real(r)] if r.fract() == 0.0 => Some(r as i64),
+                    _ => None,
+                };
+                let row = match rowid {
+                    Some(rowid) => self.row_by_rowid(db, rowid)?,
+                    None => None,
+                };
+                Ok(Box::new(row.map(Ok).into_iter()))
+            }
+            Lookup::PrimaryKey => {
+                let Some(order) = &self.key_order else {
+                    bail!("Cannot look up rows by primary key");
+                };
+                let entries = entries_with_prefix(db, self.rootpage, order.clone(), key)?;
+                Ok(Box::new(
+                    entries.map(|record| self.read_record(None, record?)),
+                ))
+            }
+            Lookup::Index(index) => {
+                let rowids = self.indexes[index].rowids(db, key)?;
+                Ok(Box::new(rowids.filter_map(|rowid| {
+                    match rowid.and_then(|rowid| self.row_by_rowid(db, rowid)) {
+                        Ok(row) => row.map(Ok),
+                        Err(e) => Some(Err(e)),
+                    }
+                })))
+            }
+        }
+    }
+
+    fn row_by_rowid(&self, db: &Database, rowid: i64) -> Result<Option<Row>> {
+        db.seek_rowid(self.rootpage, rowid)?
+            .map(|cell| self.read_row(cell))
+            .transpose()
+    }
+
+    fn read_row(&self, cell: Cell) -> Result<Row> {
+        match cell {
+            Cell::TableLeaf { key, payload, .. } => self.read_record(Some(key.0), payload),
+            Cell::IndexLeaf { payload, .. } | Cell::IndexInterior { payload, .. } => {
+                self.read_record(None, payload)
+            }
+            Cell::TableInterior { .. } => bail!("Unexpected cell type"),
+        }
+    }
+
+    fn read_record(&self, rowid: Option<i64>, record: Record) -> Result<Row> {
+        // Records hold the values of the columns other than the VIRTUAL
+        // generated ones. Tables without a rowid store the primary key
+        // columns first, followed by the remaining columns in the order they
+        // were declared in.
+        let stored = |c: &usize| self.columns[*c].virtual_expr().is_none();
+        let storage_order: Vec<usize> = if self.without_rowid {
+            let rest = (0..self.columns.len()).filter(|c| !self.primary_key.contains(c));
+            self.primary_key
+                .iter()
+                .copied()
+                .chain(rest)
+                .filter(stored)
+                .collect()
+        } else {
+            (0..self.columns.len()).filter(stored).collect()
+        };
+        let mut values = vec![Value::Null; self.columns.len()];
+        let stored_values = record.values.len();
+        for (value, &column) in record.values.into_iter().zip(&storage_order) {
+            values[column] = Value::from(value);
+        }
+        // Rows written before a column was added with ALTER TABLE lack the
+        // values of the newer columns, which have their default instead.
+        for &column in storage_order.iter().skip(stored_values) {
+            values[column] = self.default_value(column)?;
+        }
+        if let (Some(alias), Some(rowid)) = (self.rowid_alias, rowid) {
+            // The record holds NULL in place of the rowid alias.
+            values[alias] = Value::Integer(rowid);
+        }
+        // Reals without a fractional part may be stored as integers to save
+        // space, which columns with REAL affinity turn back into reals.
+        for (value, column) in values.iter_mut().zip(&self.columns) {
+            if let (Value::Integer(i), Affinity::Real) = (&*value, column.affinity) {
+                *value = Value::Real(*i as f64);
+            }
+        }
+        for column in 0..self.columns.len() {
+            if self.columns[column].virtual_expr().is_some() {
+                values[column] = self.generate(column, &values, rowid)?;
+            }
+        }
+
+        Ok(Row { rowid, values })
+    }
+
+    /// The value of a column given by its `DEFAULT` constraint, converted to
+    /// the affinity of the column, or NULL if it has none.
+    fn default_value(&self, column: usize) -> Result<Value> {
+        let definition = &self.columns[column];
+        let Some(expr) = definition.default_expr() else {
+            return Ok(Value::Null);
+        };
+        Ok(evaluate(expr, &ConstantScope)?.apply_affinity(definition.affinity))
+    }
+
+    /// Computes the value of a VIRTUAL generated column of a row, converted
+    /// to the affinity of the column like a stored value.
+    fn generate(&self, column: usize, values: &[Value], rowid: Option<i64>) -> Result<Value> {
+        let definition = &self.columns[column];
+        let expr = definition
+            .virtual_expr()
+            .expect("The column is a VIRTUAL generated column");
+        let scope = GeneratedScope {
+            table: self,
+            values,
+            rowid,
+        };
+        Ok(evaluate(expr, &scope)?.apply_affinity(definition.affinity))
+    }
+}
+
+/// The scope of the `DEFAULT` of a column, which is constant and may not
+/// refer to any column.
+struct ConstantScope;
+
+impl Scope for ConstantScope {
+    fn column(&self, _table: Option<&str>, name: &str) -> Result<Value> {
+        bail!("no such column: {name}")
+    }
+}
+
+/// The scope of the expression of a generated column, which may refer to the
+/// other columns of the row, including other generated ones.
+struct GeneratedScope<'a> {
+    table: &'a Table,
+    values: &'a [Value],
+    rowid: Option<i64>,
+}
+
+impl GeneratedScope<'_> {
+    fn column_index(&self, name: &str) -> Option<usize> {
+        self.table
+            .columns
+            .iter()
+            .position(|c| c.name.eq_ignore_ascii_case(name))
+    }
+}
+
+impl Scope for GeneratedScope<'_> {
+    fn column(&self, _table: Option<&str>, name: &str) -> Result<Value> {
+        match self.column_index(name) {
+            // Generated columns the expression refers to may come later in
+            // the row, so they are computed as needed.
+            Some(column) if self.table.columns[column].virtual_expr().is_some() => {
+                self.table.generate(column, self.values, self.rowid)
+            }
+            Some(column) => Ok(self.values[column].clone()),
+            None if is_rowid_name(name) => Ok(self.rowid.map_or(Value::Null, Value::Integer)),
+            None => bail!("no such column: {name}"),
+        }
+    }
+
+    fn affinity(&self, _table: Option<&str>, name: &str) -> Option<Affinity> {
+        let column = self.column_index(name)?;
+        Some(self.table.columns[column].affinity)
+    }
+
+    fn collation(&self, _table: Option<&str>, name: &str) -> Option<Collation> {
+        let column = self.column_index(name)?;
+        let collation = self.table.columns[column].collation();
+        Some(
+            collation
+                .and_then(|c| Collation::from_name(c).ok())
+                .unwrap_or_default(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::Database;
+    use crate::table::*;
+
+    #[test]
+    fn rows_are_in_declared_column_order() {
+        let db = Database::open("without_rowid.db").unwrap();
+        let table = Table::load(&db, "pears").unwrap();
+
+        let rows = table
+            .rows(&db)
+            .map(|row| {
+                let [Value::Text(color), Value::Text(name), Value::Integer(weight)] =
+                    &row.unwrap().values[..]
+                else {
+                    panic!("Unexpected row");
+                };
+                (color.clone(), name.clone(), *weight)
+            })
+            .collect::<Vec<_>>();
+
+        let columns = table.columns.iter().map(|c| c.name.as_str());
+        assert_eq!(columns.collect::<Vec<_>>(), vec!["color", "name", "weight"]);
+        assert_eq!(
+            rows,
+            vec![
+                ("Green".to_string(), "Anjou".to_string(), 200),
+                ("Green".to_string(), "Bartlett".to_string(), 180),
+                ("Red".to_string(), "Bartlett".to_string(), 175),
+                ("Brown".to_string(), "Bosc".to_string(), 160),
+                ("Yellow".to_string(), "Comice".to_string(), 230),
+            ]
+        );
+    }
+
+    #[test]
+    fn rows_walk_index_btree_in_key_order() {
+        let db = Database::open("without_rowid.db").unwrap();
+        let table = Table::load(&db, "grapes").unwrap();
+
+        let rows = table.rows(&db).collect::<Result<Vec<_>>>().unwrap();
+
+        assert_eq!(rows.len(), 1500);
+        for (i, row) in rows.iter().enumerate() {
+            assert_eq!(row.values[0], Value::Integer(i as i64 * 3 + 1));
+            assert_eq!(row.rowid, None);
+        }
+    }
+
+    #[test]
+    fn rows_substitute_the_rowid_for_integer_primary_keys() {
+        let db = Database::open("without_rowid.db").unwrap();
+        let table = Table::load(&db, "plums").unwrap();
+
+        let rows = table.rows(&db).collect::<Result<Vec<_>>>().unwrap();
+
+        for (i, row) in rows.iter().enumerate() {
+            assert_eq!(row.rowid, Some(i as i64 + 1));
+            assert_eq!(row.values[0], Value::Integer(i as i64 + 1));
+        }
+    }
+
+    #[test]
+    fn looks_up_rows_by_rowid_index_and_primary_key() {
+        let db = Database::open("joins.db").unwrap();
+        let ids = |table: &Table, lookup, key| {
+            table
+                .lookup(&db, lookup, key)
+                .unwrap()
+                .map(|row| row.unwrap().values[0].clone())
+                .collect::<Vec<_>>()
+        };
+
+        let binary = |columns: &[usize]| {
+            columns
+                .iter()
+                .map(|&i| (ColumnRef::Column(i), Collation::Binary))
+                .collect::<Vec<_>>()
+        };
+
+        let orders = Table::load(&db, "orders").unwrap();
+        let (lookup, key) = orders.lookup_for(&binary(&[0])).unwrap();
+        assert!(matches!(lookup, Lookup::Rowid) && key == [0]);
+        assert_eq!(
+            ids(&orders, lookup, vec![Value::Real(7.0)]),
+            [Value::Integer(7)]
+        );
+        assert!(ids(&orders, lookup, vec![Value::Integer(5000)]).is_empty());
+
+        // The longest prefix of an index's columns wins.
+        let (lookup, key) = orders.lookup_for(&binary(&[2, 3])).unwrap();
+        assert!(matches!(lookup, Lookup::Index(_)));
+        assert_eq!(key, [1, 0]);
+        let key = vec![Value::Text("open".to_string()), Value::Integer(6)];
+        assert_eq!(ids(&orders, lookup, key), [1002].map(Value::Integer));
+        assert!(orders.lookup_for(&binary(&[2])).is_none());
+        let (lookup, _) = orders.lookup_for(&binary(&[3])).unwrap();
+        let key = vec![Value::Text("paid".to_string())];
+        assert_eq!(ids(&orders, lookup, key).len(), 667);
+
+        let (lookup, _) = orders.lookup_for(&binary(&[1])).unwrap();
+        assert_eq!(ids(&orders, lookup, vec![Value::Integer(4)]).len(), 428);
+        assert!(ids(&orders, lookup, vec![Value::Null]).is_empty());
+
+        let cities = Table::load(&db, "cities").unwrap();
+        let (lookup, _) = cities.lookup_for(&binary(&[0])).unwrap();
+        assert!(matches!(lookup, Lookup::PrimaryKey));
+        let key = vec![Value::Text("Rome".to_string())];
+        assert_eq!(ids(&cities, lookup, key), [Value::Text("Rome".to_string())]);
+    }
+
+    #[test]
+    fn only_looks_up_rows_by_keys_with_the_same_collation() {
+        let db = Database::open("collations.db").unwrap();
+        let text = |t: &str| vec![Value::Text(t.to_string())];
+        let count = |table: &Table, lookup, key| table.lookup(&db, lookup, key).unwrap().count();
+
+        // `word` is NOCASE, and so is its index. `plain` is BINARY, but
+        // indexed with NOCASE.
+        let words = Table::load(&db, "words").unwrap();
+        for column in [1, 3] {
+            let binary = [(ColumnRef::Column(column), Collation::Binary)];
+            let nocase = [(ColumnRef::Column(column), Collation::NoCase)];
+            assert!(words.lookup_for(&binary).is_none());
+            let (lookup, _) = words.lookup_for(&nocase).unwrap();
+            assert_eq!(count(&words, lookup, text("APPLE")), 3);
+        }
+
+        let tags = Table::load(&db, "tags").unwrap();
+        assert!(tags
+            .lookup_for(&[(ColumnRef::Column(0), Collation::Binary)])
+            .is_none());
+        let (lookup, _) = tags
+            .lookup_for(&[(ColumnRef::Column(0), Collation::NoCase)])
+            .unwrap();
+        assert_eq!(count(&tags, lookup, text("RED")), 1);
+    }
+
+    #[test]
+    fn computes_virtual_generated_columns() {
+        let db = Database::open("generated.db").unwrap();
+        let rows = |name| {
+            let table = Table::load(&db, name).unwrap();
+            let rows = table.rows(&db).map(|row| {
+                let values = row.unwrap().values.into_iter().map(|v| v.to_string());
+                values.collect::<Vec<_>>().join("|")
+            });
+            rows.collect::<Vec<_>>()
+        };
+
+        // `area` and `half` are VIRTUAL, so they aren't in the records, and
+        // `half` is computed from `area`. `label` is STORED.
+        assert_eq!(
+            rows("shapes"),
+            [
+                "1|3|9|small|small:9|4.0|a",
+                "2|10|100|large|large:100|50.0|b",
+                "3|||none|||c",
+            ]
+        );
+        assert_eq!(rows("codes"), ["ab|AB|1", "cd|CD|0"]);
+    }
+
+    #[test]
+    fn fills_in_the_defaults_of_added_columns() {
+        let db = Database::open("altered.db").unwrap();
+        let table = Table::load(&db, "h").unwrap();
+        let rows = table.rows(&db).map(|row| {
+            let values = row.unwrap().values.into_iter().map(|v| v.to_string());
+            values.collect::<Vec<_>>().join("|")
+        });
+
+        // The first row was written before `d`, `e`, `f` and `g` were added,
+        // and its record only holds `a`, `b` and `c`.
+        assert_eq!(
+            rows.collect::<Vec<_>>(),
+            ["1|2|3|42||2.0|-7", "4|5|6|42||2.0|-7", "7|8|9||e|1.5|g"]
+        );
+    }
+}
@@ -0,0 +1,671 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::btree_page::RecordValue;
+use crate::collation::Collation;
+
+/// A value as SQLite sees it, independent of how it is stored on disk.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl From<RecordValue> for Value {
+    fn from(value: RecordValue) -> Self {
+        match value {
+            RecordValue::Null => Self::Null,
+            RecordValue::Zero => Self::Integer(0),
+            RecordValue::One => Self::Integer(1),
+            RecordValue::I8(v) => Self::Integer(v.into()),
+            RecordValue::I16(v) => Self::Integer(v.into()),
+            RecordValue::I24(v) | RecordValue::I32(v) => Self::Integer(v.into()),
+            RecordValue::I48(v) | RecordValue::I64(v) => Self::Integer(v),
+            RecordValue::F64(v) => Self::Real(v),
+            RecordValue::N12AndEven(v) => Self::Blob(v),
+            RecordValue::N13AndOdd(v) => Self::Text(v),
+        }
+    }
+}
+
+/// The type affinity of a column, i.e. the type SQLite prefers to store
+/// values of that column as.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Affinity {
+    Text,
+    Numeric,
+    Integer,
+    Real,
+    Blob,
+}
+
+impl Affinity {
+    /// Determines the affinity of a column from its declared type, following
+    /// the rules from section 3.1 of <https://www.sqlite.org/datatype3.html>.
+    /// The rules are checked in order, so `CHARINT` has integer affinity.
+    pub fn from_declared_type(declared_type: &str) -> Self {
+        let declared_type = declared_type.to_ascii_uppercase();
+        if declared_type.contains("INT") {
+            Self::Integer
+        } else if ["CHAR", "CLOB", "TEXT"]
+            .iter()
+            .any(|t| declared_type.contains(t))
+        {
+            Self::Text
+        } else if declared_type.contains("BLOB") || declared_type.is_empty() {
+            Self::Blob
+        } else if ["REAL", "FLOA", "DOUB"]
+            .iter()
+            .any(|t| declared_type.contains(t))
+        {
+            Self::Real
+        } else {
+            Self::Numeric
+        }
+    }
+
+    /// Determines the affinity of the type of a `CAST`, which is numeric
+    /// rather than blob if it is left out.
+    pub fn from_cast_type(type_name: Option<&str>) -> Self {
+        match type_name {
+            Some(type_name) if !type_name.is_empty() => Self::from_declared_type(type_name),
+            _ => Self::Numeric,
+        }
+    }
+
+    pub fn is_numeric(self) -> bool {
+        matches!(self, Self::Integer | Self::Real | Self::Numeric)
+    }
+
+    /// The affinity that applies to both operands of a comparison, given the
+    /// affinities of the expressions they come from, if any. Following
+    /// section 4.2 of <https://www.sqlite.org/datatype3.html>, numeric
+    /// affinity wins over the others, and an operand without affinity takes
+    /// that of the other one.
+    pub fn for_comparison(left: Option<Self>, right: Option<Self>) -> Option<Self> {
+        match (left, right) {
+            (Some(left), Some(right)) => {
+                (left.is_numeric() || right.is_numeric()).then_some(Self::Numeric)
+            }
+            (affinity, None) | (None, affinity) => affinity,
+        }
+    }
+}
+
+impl Value {
+    /// The name of the value's storage class, as returned by `typeof()`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Integer(_) => "integer",
+            Value::Real(_) => "real",
+            Value::Text(_) => "text",
+            Value::Blob(_) => "blob",
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// Interprets the value as a condition, as in a `WHERE` clause. NULL is
+    /// neither true nor false, text and blobs are true if they start with a
+    /// non-zero number.
+    pub fn truth(&self) -> Option<bool> {
+        match self.to_numeric() {
+            Value::Null => None,
+            Value::Integer(i) => Some(i != 0),
+            Value::Real(r) => Some(r != 0.0),
+            _ => unreachable!("Numeric values are integers or reals"),
+        }
+    }
+
+    /// Converts the value to a number the way SQLite does for arithmetic.
+    /// Text and blobs are converted by their longest numeric prefix, which is
+    /// 0 if there is none. NULL stays NULL.
+    pub fn to_numeric(&self) -> Value {
+        match self {
+            Value::Text(t) => numeric_prefix(t),
+            Value::Blob(b) => numeric_prefix(&String::from_utf8_lossy(b)),
+            value => value.clone(),
+        }
+    }
+
+    /// Converts the value to an integer, truncating reals. NULL becomes 0.
+    pub fn to_integer(&self) -> i64 {
+        match self.to_numeric() {
+            Value::Integer(i) => i,
+            // Casts from float to integer saturate.
+            Value::Real(r) => r as i64,
+            _ => 0,
+        }
+    }
+
+    /// Converts the value to a real. NULL becomes 0.0.
+    pub fn to_real(&self) -> f64 {
+        match self.to_numeric() {
+            Value::Integer(i) => i as f64,
+            Value::Real(r) => r,
+            _ => 0.0,
+        }
+    }
+
+    /// Converts the value the way SQLite does when storing it in a column
+    /// with the given affinity.
+    pub fn apply_affinity(self, affinity: Affinity) -> Self {
+        match (affinity, self) {
+            (Affinity::Blob, value) => value,
+            (Affinity::Text, Value::Integer(i)) => Value::Text(i.to_string()),
+            (Affinity::Text, Value::Real(r)) => Value::Text(format_real(r)),
+            (Affinity::Text, value) => value,
+            (Affinity::Numeric | Affinity::Integer, Value::Real(r)) => real_to_integer(r),
+            (Affinity::Numeric | Affinity::Integer, Value::Text(t)) => match parse_number(&t) {
+                Some(Value::Real(r)) => real_to_integer(r),
+                Some(number) => number,
+                None => Value::Text(t),
+            },
+            (Affinity::Real, Value::Integer(i)) => Value::Real(i as f64),
+            (Affinity::Real, Value::Text(t)) => match parse_number(&t) {
+                Some(Value::Integer(i)) => Value::Real(i as f64),
+                Some(number) => number,
+                None => Value::Text(t),
+            },
+            (_, value) => value,
+        }
+    }
+
+    /// Converts the value for a comparison with the given affinity: text that
+    /// looks like a number becomes one for numeric affinities, and numbers
+    /// become text for text affinity.
+    pub fn for_comparison(self, affinity: Option<Affinity>) -> Self {
+        match (affinity, self) {
+            (Some(affinity), Value::Text(t)) if affinity.is_numeric() => {
+                parse_number(&t).unwrap_or(Value::Text(t))
+            }
+            (Some(Affinity::Text), value @ (Value::Integer(_) | Value::Real(_))) => {
+                Value::Text(value.to_string())
+            }
+            (_, value) => value,
+        }
+    }
+
+    /// Converts the value like `CAST(value AS type)`, for a type with the
+    /// given affinity. Unlike [`Value::apply_affinity`], this always results
+    /// in the type of the affinity, using the longest prefix of text that is
+    /// a number. NULL stays NULL.
+    pub fn cast(self, affinity: Affinity) -> Self {
+        match (affinity, self) {
+            (_, Value::Null) => Value::Null,
+            (Affinity::Integer, Value::Text(t)) => Value::Integer(integer_prefix(&t)),
+            (Affinity::Integer, Value::Blob(b)) => {
+                Value::Integer(integer_prefix(&String::from_utf8_lossy(&b)))
+            }
+            (Affinity::Integer, value) => Value::Integer(value.to_integer()),
+            (Affinity::Real, value) => Value::Real(value.to_real()),
+            (Affinity::Numeric, value @ (Value::Text(_) | Value::Blob(_))) => {
+                match value.to_numeric() {
+                    // Text of a real with an integer value, like '1e3', is
+                    // an integer, unless it is too large to be exact.
+                    Value::Real(r) if r.fract() == 0.0 && r.abs() < 2251799813685248.0 => {
+                        Value::Integer(r as i64)
+                    }
+                    number => number,
+                }
+            }
+            (Affinity::Numeric, value) => value,
+            (Affinity::Text, value) => Value::Text(value.to_string()),
+            (Affinity::Blob, Value::Blob(b)) => Value::Blob(b),
+            (Affinity::Blob, value) => Value::Blob(value.to_string().into_bytes()),
+        }
+    }
+
+    /// Compares two values using SQLite's sort order: NULLs come first,
+    /// followed by numbers, text, and finally blobs. Integers and reals are
+    /// compared by their numeric value, text and blobs byte by byte.
+    pub fn compare(&self, other: &Value) -> Ordering {
+        self.compare_with(other, Collation::Binary)
+    }
+
+    /// Compares two values like [`Value::compare`], but compares text using
+    /// the given collating sequence.
+    pub fn compare_with(&self, other: &Value, collation: Collation) -> Ordering {
+        match (self, other) {
+            (Value::Null, Value::Null) => Ordering::Equal,
+            (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+            (Value::Real(a), Value::Real(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+            (Value::Integer(a), Value::Real(b)) => compare_integer_real(*a, *b),
+            (Value::Real(a), Value::Integer(b)) => compare_integer_real(*b, *a).reverse(),
+            (Value::Text(a), Value::Text(b)) => collation.compare(a, b),
+            (Value::Blob(a), Value::Blob(b)) => a.cmp(b),
+            (a, b) => a.type_rank().cmp(&b.type_rank()),
+        }
+    }
+
+    /// The position of the value's storage class in SQLite's sort order.
+    fn type_rank(&self) -> u8 {
+        match self {
+            Value::Null => 0,
+            Value::Integer(_) | Value::Real(_) => 1,
+            Value::Text(_) => 2,
+            Value::Blob(_) => 3,
+        }
+    }
+}
+
+/// A list of values compared the way SQLite sorts them, for use as the key of
+/// sorted collections, like when grouping rows. Integers and reals that are
+/// numerically equal are equal keys.
+#[derive(Clone, Debug)]
+pub struct SortKey(pub Vec<Value>);
+
+impl PartialEq for SortKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SortKey {}
+
+impl PartialOrd for SortKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SortKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .iter()
+            .zip(&other.0)
+            .map(|(a, b)| a.compare(b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| self.0.len().cmp(&other.0.len()))
+    }
+}
+
+/// Compares an integer to a real without losing precision for integers
+/// that cannot be represented exactly as a real.
+fn compare_integer_real(i: i64, r: f64) -> Ordering {
+    if r.is_nan() {
+        return Ordering::Greater;
+    }
+    // i64::MAX is not exactly representable, the bound is 2^63.
+    if r >= 9223372036854775808.0 {
+        return Ordering::Less;
+    }
+    if r < -9223372036854775808.0 {
+        return Ordering::Greater;
+    }
+    match i.cmp(&(r as i64)) {
+        Ordering::Equal => 0.0.partial_cmp(&r.fract()).unwrap_or(Ordering::Equal),
+        ordering => ordering,
+    }
+}
+
+/// Converts a real to an integer if that can be done without loss.
+fn real_to_integer(r: f64) -> Value {
+    if r.fract() == 0.0 && (-9223372036854775808.0..9223372036854775808.0).contains(&r) {
+        Value::Integer(r as i64)
+    } else {
+        Value::Real(r)
+    }
+}
+
+/// Parses text that is entirely a well-formed integer or real literal,
+/// ignoring leading and trailing whitespace. Integers that are too large to
+/// fit in 64 bits are parsed as reals.
+pub fn parse_number(text: &str) -> Option<Value> {
+    let text = text.trim_matches(|c: char| c.is_ascii_whitespace());
+    let digits = text.strip_prefix(['+', '-']).unwrap_or(text);
+
+    let mantissa_end = digits
+        .find(|c: char| c != '.' && !c.is_ascii_digit())
+        .unwrap_or(digits.len());
+    let (mantissa, exponent) = digits.split_at(mantissa_end);
+    let well_formed_mantissa = mantissa.chars().filter(|&c| c == '.').count() <= 1
+        && mantissa.chars().any(|c| c.is_ascii_digit());
+    let well_formed_exponent = match exponent.strip_prefix(['e', 'E']) {
+        Some(e) => {
+            let e = e.strip_prefix(['+', '-']).unwrap_or(e);
+            !e.is_empty() && e.chars().all(|c| c.is_ascii_digit())
+        }
+        None => exponent.is_empty(),
+    };
+    if !well_formed_mantissa || !well_formed_exponent {
+        return None;
+    }
+
+    if exponent.is_empty() && !mantissa.contains('.') {
+        if let Ok(i) = text.parse::<i64>() {
+            return Some(Value::Integer(i));
+        }
+    }
+    text.parse::<f64>().ok().map(Value::Real)
+}
+
+/// Parses the longest prefix of the text that is an integer, after leading
+/// whitespace, saturating if it doesn't fit in 64 bits. Unlike
+/// [`numeric_prefix`], this stops at a decimal point or exponent.
+fn integer_prefix(text: &str) -> i64 {
+    let text = text.trim_start_matches(|c: char| c.is_ascii_whitespace());
+    let (negative, digits) = match text.as_bytes().first() {
+        Some(b'-') => (true, &text[1..]),
+        Some(b'+') => (false, &text[1..]),
+        _ => (false, text),
+    };
+    let mut value: i64 = 0;
+    for digit in digits.bytes().take_while(u8::is_ascii_digit) {
+        let digit = i64::from(digit - b'0');
+        value = match value.checked_mul(10).and_then(|v| {
+            if negative {
+                v.checked_sub(digit)
+            } else {
+                v.checked_add(digit)
+            }
+        }) {
+            Some(v) => v,
+            None if negative => return i64::MIN,
+            None => return i64::MAX,
+        };
+    }
+    value
+}
+
+/// Parses the longest prefix of the text that is a number, after leading
+/// whitespace. Like in SQLite, `'12abc'` is 12 and `'abc'` is 0.
+fn numeric_prefix(text: &str) -> Value {
+    let text = text.trim_start_matches(|c: char| c.is_ascii_whitespace());
+    let bytes = text.as_bytes();
+    let digits_from = |mut i: usize| {
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+        }
+        i
+    };
+
+    let sign = usize::from(matches!(bytes.first(), Some(b'+' | b'-')));
+    let mut end = digits_from(sign);
+    let mut has_digits = end > sign;
+    let mut is_integer = true;
+    if bytes.get(end) == Some(&b'.') {
+        let fraction_end = digits_from(end + 1);
+        has_digits |= fraction_end > end + 1;
+        end = fraction_end;
+        is_integer = false;
+    }
+    if !has_digits {
+        return Value::Integer(0);
+    }
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+        let exponent = end + 1;
+        let exponent_sign = usize::from(matches!(bytes.get(exponent), Some(b'+' | b'-')));
+        let exponent_end = digits_from(exponent + exponent_sign);
+        if exponent_end > exponent + exponent_sign {
+            end = exponent_end;
+            is_integer = false;
+        }
+    }
+
+    let number = &text[..end];
+    if is_integer {
+        if let Ok(i) = number.parse::<i64>() {
+            return Value::Integer(i);
+        }
+    }
+    // A lone "." was ruled out above, and "1." parses as a float.
+    Value::Real(number.parse::<f64>().unwrap_or(0.0))
+}
+
+/// Formats a real the way SQLite does, i.e. like `printf("%!.15g")`:
+/// with up to 15 significant digits, always including a decimal point.
+pub fn format_real(r: f64) -> String {
+    if r.is_infinite() {
+        return if r > 0.0 { "Inf" } else { "-Inf" }.to_string();
+    }
+    if r.is_nan() {
+        return String::new();
+    }
+    if r == 0.0 {
+        // Negative zero is printed without its sign.
+        return "0.0".to_string();
+    }
+
+    // Let the standard library do the rounding to 15 significant digits.
+    let scientific = format!("{:.14e}", r);
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("Scientific notation has an exponent");
+    let exponent: i32 = exponent.parse().expect("Exponent is an integer");
+
+    if !(-4..15).contains(&exponent) {
+        let mantissa = trim_fraction(mantissa);
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{mantissa}e{sign}{:02}", exponent.abs())
+    } else {
+        let decimals = (14 - exponent) as usize;
+        trim_fraction(&format!("{:.*}", decimals, r))
+    }
+}
+
+/// Removes trailing zeroes after the decimal point, keeping at least one digit.
+fn trim_fraction(number: &str) -> String {
+    match number.split_once('.') {
+        Some((integer, fraction)) => {
+            let fraction = fraction.trim_end_matches('0');
+            let fraction = if fraction.is_empty() { "0" } else { fraction };
+            format!("{integer}.{fraction}")
+        }
+        None => format!("{number}.0"),
+    }
+}
+
+impl fmt::Display for Value {
+    /// Formats the value the way the sqlite3 shell outputs it.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => Ok(()),
+            Value::Integer(i) => write!(f, "{i}"),
+            Value::Real(r) => write!(f, "{}", format_real(*r)),
+            Value::Text(t) => write!(f, "{t}"),
+            Value::Blob(b) => write!(f, "{}", String::from_utf8_lossy(b)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::value::*;
+
+    #[test]
+    fn displays_reals_like_sqlite() {
+        let reals = vec![
+            (1e20, "1.0e+20"),
+            (0.1, "0.1"),
+            (0.1 + 0.2, "0.3"),
+            (1.0 / 3.0, "0.333333333333333"),
+            (123456789012345678.0, "1.23456789012346e+17"),
+            (123456789012345.0, "123456789012345.0"),
+            (1e15, "1.0e+15"),
+            (1.0, "1.0"),
+            (-0.0, "0.0"),
+            (1e-7, "1.0e-07"),
+            (0.000123, "0.000123"),
+            (2.5e-5, "2.5e-05"),
+            (12345.678, "12345.678"),
+            (f64::INFINITY, "Inf"),
+        ];
+
+        for (real, expected) in reals {
+            assert_eq!(Value::Real(real).to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn determines_affinity_from_declared_type() {
+        let types = vec![
+            ("INT", Affinity::Integer),
+            ("unsigned big int", Affinity::Integer),
+            ("CHARINT", Affinity::Integer),
+            ("VARCHAR(255)", Affinity::Text),
+            ("clob", Affinity::Text),
+            ("BLOB", Affinity::Blob),
+            ("", Affinity::Blob),
+            ("DOUBLE PRECISION", Affinity::Real),
+            ("FLOAT", Affinity::Real),
+            ("FLOATING POINT", Affinity::Integer),
+            ("DECIMAL(10,5)", Affinity::Numeric),
+            ("STRING", Affinity::Numeric),
+        ];
+
+        for (declared_type, expected) in types {
+            assert_eq!(
+                Affinity::from_declared_type(declared_type),
+                expected,
+                "{declared_type}"
+            );
+        }
+    }
+
+    #[test]
+    fn applies_affinity_like_sqlite() {
+        let text = |t: &str| Value::Text(t.to_string());
+        let cases = vec![
+            (text(" 3.0 "), Affinity::Numeric, Value::Integer(3)),
+            (text("1e3"), Affinity::Integer, Value::Integer(1000)),
+            (text("1.5"), Affinity::Numeric, Value::Real(1.5)),
+            (text("0x10"), Affinity::Numeric, text("0x10")),
+            (text("12abc"), Affinity::Integer, text("12abc")),
+            (
+                text("9223372036854775808"),
+                Affinity::Numeric,
+                Value::Real(9223372036854775808.0),
+            ),
+            (Value::Real(5.0), Affinity::Numeric, Value::Integer(5)),
+            (Value::Integer(5), Affinity::Real, Value::Real(5.0)),
+            (text("1."), Affinity::Real, Value::Real(1.0)),
+            (Value::Real(1e20), Affinity::Text, text("1.0e+20")),
+            (Value::Integer(8), Affinity::Blob, Value::Integer(8)),
+            (text("  42"), Affinity::Blob, text("  42")),
+        ];
+
+        for (value, affinity, expected) in cases {
+            assert_eq!(
+                value.clone().apply_affinity(affinity),
+                expected,
+                "{value:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn casts_like_sqlite() {
+        let text = |t: &str| Value::Text(t.to_string());
+        let cases = vec![
+            (text("12.9abc"), Affinity::Integer, Value::Integer(12)),
+            (text("1e3"), Affinity::Integer, Value::Integer(1)),
+            (
+                text("-99999999999999999999"),
+                Affinity::Integer,
+                Value::Integer(i64::MIN),
+            ),
+            (Value::Real(-3.9), Affinity::Integer, Value::Integer(-3)),
+            (text("1e3"), Affinity::Numeric, Value::Integer(1000)),
+            (text("12abc"), Affinity::Numeric, Value::Integer(12)),
+            (text("1e17"), Affinity::Numeric, Value::Real(1e17)),
+            (Value::Real(3.0), Affinity::Numeric, Value::Real(3.0)),
+            (text("x"), Affinity::Real, Value::Real(0.0)),
+            (Value::Real(2.5), Affinity::Text, text("2.5")),
+            (
+                Value::Integer(5),
+                Affinity::Blob,
+                Value::Blob(b"5".to_vec()),
+            ),
+            (Value::Null, Affinity::Text, Value::Null),
+        ];
+
+        for (value, affinity, expected) in cases {
+            assert_eq!(value.clone().cast(affinity), expected, "{value:?}");
+        }
+        assert_eq!(Affinity::from_cast_type(None), Affinity::Numeric);
+    }
+
+    #[test]
+    fn converts_operands_of_comparisons() {
+        let text = |t: &str| Value::Text(t.to_string());
+        let integer = Some(Affinity::Integer);
+        let numeric = Affinity::for_comparison(integer, Some(Affinity::Text));
+
+        assert_eq!(numeric, Some(Affinity::Numeric));
+        assert_eq!(
+            Affinity::for_comparison(Some(Affinity::Text), None),
+            Some(Affinity::Text)
+        );
+        assert_eq!(
+            Affinity::for_comparison(Some(Affinity::Text), Some(Affinity::Blob)),
+            None
+        );
+        assert_eq!(text(" 5 ").for_comparison(numeric), Value::Integer(5));
+        assert_eq!(text("5x").for_comparison(numeric), text("5x"));
+        assert_eq!(
+            Value::Real(2.0).for_comparison(Some(Affinity::Text)),
+            text("2.0")
+        );
+        assert_eq!(text("5").for_comparison(None), text("5"));
+    }
+
+    #[test]
+    fn converts_to_numbers_by_prefix() {
+        let text = |t: &str| Value::Text(t.to_string());
+        let cases = vec![
+            (text("12abc"), Value::Integer(12)),
+            (text(" -1.5e2x"), Value::Real(-150.0)),
+            (text("3.0"), Value::Real(3.0)),
+            (text("1e"), Value::Integer(1)),
+            (text(".5"), Value::Real(0.5)),
+            (text("abc"), Value::Integer(0)),
+            (text("-"), Value::Integer(0)),
+            (
+                text("9223372036854775808"),
+                Value::Real(9223372036854775808.0),
+            ),
+            (Value::Blob(b"7".to_vec()), Value::Integer(7)),
+            (Value::Null, Value::Null),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(value.to_numeric(), expected, "{value:?}");
+        }
+        assert_eq!(text("0.0").truth(), Some(false));
+        assert_eq!(text("0.1").truth(), Some(true));
+        assert_eq!(Value::Null.truth(), None);
+    }
+
+    #[test]
+    fn compares_across_types() {
+        let ordered = vec![
+            Value::Null,
+            Value::Integer(-5),
+            Value::Real(-4.5),
+            Value::Integer(1),
+            Value::Real(1.5),
+            Value::Integer(i64::MAX),
+            Value::Real(1e19),
+            Value::Text("A".to_string()),
+            Value::Text("a".to_string()),
+            Value::Blob(vec![0]),
+        ];
+
+        for (i, a) in ordered.iter().enumerate() {
+            for (j, b) in ordered.iter().enumerate() {
+                assert_eq!(a.compare(b), i.cmp(&j), "{a:?} vs {b:?}");
+            }
+        }
+        assert_eq!(
+            Value::Integer(1).compare(&Value::Real(1.0)),
+            Ordering::Equal
+        );
+    }
+}
@@ -25,4 +25,85 @@ impl Varint {
 
         (Self(varint), bytes_read)
     }
+
+    /// Encodes the Varint into its on-disk representation of 1 to 9 bytes.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut value = self.0 as u64;
+
+        if value > 0x00FF_FFFF_FFFF_FFFF {
+            // The value needs more than 56 bits, so all 9 bytes are used:
+            // the last one holds the lowest 8 bits, the others 7 bits each.
+            let mut bytes = vec![0; 9];
+            bytes[8] = value as u8;
+            value >>= 8;
+            for byte in bytes[..8].iter_mut().rev() {
+                *byte = (value & 0b0111_1111) as u8 | 0b1000_0000;
+                value >>= 7;
+            }
+            return bytes;
+        }
+
+        let mut bytes = Vec::with_capacity(8);
+        loop {
+            bytes.push((value & 0b0111_1111) as u8);
+            value >>= 7;
+            if value == 0 {
+                break;
+            }
+        }
+        bytes.reverse();
+        // Every byte except the last one has the most significant bit set.
+        let last = bytes.len() - 1;
+        for byte in bytes[..last].iter_mut() {
+            *byte |= 0b1000_0000;
+        }
+
+        bytes
+    }
+
+    /// The number of bytes needed to encode the Varint.
+    pub fn encoded_len(&self) -> usize {
+        match self.0 as u64 {
+            v if v > 0x00FF_FFFF_FFFF_FFFF => 9,
+            v => (64 - v.leading_zeros() as usize).max(1).div_ceil(7),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::varint::*;
+
+    #[test]
+    fn round_trips_all_lengths() {
+        let values = vec![
+            (0, 1),
+            (1, 1),
+            (127, 1),
+            (128, 2),
+            (16383, 2),
+            (16384, 3),
+            (0x00FF_FFFF_FFFF_FFFF, 8),
+            (0x0100_0000_0000_0000, 9),
+            (i64::MAX, 9),
+            (-1, 9),
+            (i64::MIN, 9),
+        ];
+
+        for (value, expected_len) in values {
+            let bytes = Varint(value).encode();
+            assert_eq!(bytes.len(), expected_len, "{value}");
+            assert_eq!(Varint(value).encoded_len(), expected_len, "{value}");
+
+            let (parsed, read) = Varint::parse(&bytes);
+            assert_eq!(parsed.0, value);
+            assert_eq!(read, expected_len);
+        }
+    }
+
+    #[test]
+    fn encodes_like_sqlite() {
+        assert_eq!(Varint(0x81).encode(), vec![0x81, 0x01]);
+        assert_eq!(Varint(-1).encode(), vec![0xFF; 9]);
+    }
 }
@@ -0,0 +1,105 @@
+use anyhow::{bail, ensure, Result};
+use std::rc::Rc;
+
+use crate::ast::*;
+use crate::parser::parse;
+use crate::query::{run, Context, ResultSet};
+use crate::schema::SchemaEntry;
+
+/// Runs the view a table name refers to, if there is one by that name.
+pub fn lookup(name: &str, context: Context) -> Result<Option<ResultSet>> {
+    let Some(entry) = context.db.schema.view(name) else {
+        return Ok(None);
+    };
+    let view = parsed(entry, context)?;
+
+    let key = Rc::as_ptr(&view);
+    if context.state.running_views.borrow().contains(&key) {
+        bail!("view {} is circularly defined", view.name);
+    }
+    context.state.running_views.borrow_mut().push(key);
+    // A view only sees the tables and views of the database, not the CTEs or
+    // the outer queries of the statement it is referred to in.
+    let result = run(&view.select, false, Context::new(context.db, context.state));
+    context.state.running_views.borrow_mut().pop();
+
+    let mut result = result?;
+    if !view.columns.is_empty() {
+        ensure!(
+            view.columns.len() == result.columns.len(),
+            "expected {} columns for '{}' but got {}",
+            view.columns.len(),
+            view.name,
+            result.columns.len()
+        );
+        result.columns = view.columns.clone();
+    }
+    Ok(Some(result))
+}
+
+/// The definition of a view, which is parsed the first time the statement
+/// refers to it.
+fn parsed(entry: &SchemaEntry, context: Context) -> Result<Rc<CreateView>> {
+    let key = entry.name.to_ascii_lowercase();
+    if let Some(view) = context.state.views.borrow().get(&key) {
+        return Ok(view.clone());
+    }
+    let Some(sql) = &entry.sql else {
+        bail!("Missing schema SQL for view {}", entry.name);
+    };
+    let view = match parse(sql)?.pop() {
+        Some(Statement::CreateView(view)) => Rc::new(*view),
+        _ => bail!("Invalid schema SQL for view {}", entry.name),
+    };
+    context.state.views.borrow_mut().insert(key, view.clone());
+    Ok(view)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::query::tests::{rows, run};
+
+    #[test]
+    fn runs_views_of_views() {
+        let sql = "SELECT * FROM adult_names WHERE years > 40 ORDER BY who DESC";
+
+        assert_eq!(rows("views.db", sql), ["Eve|41", "Cid|52"]);
+        assert_eq!(run("views.db", sql).unwrap().columns, ["who", "years"]);
+        assert_eq!(rows("views.db", "SELECT count(*) FROM adults"), ["3"]);
+        assert_eq!(
+            rows("views.db", "SELECT n FROM by_city WHERE city = 'ROME'"),
+            ["2"]
+        );
+        // Columns keep the affinity and collation of the table columns.
+        assert_eq!(
+            rows(
+                "views.db",
+                "SELECT name FROM adults WHERE age = '34' OR city = 'ROME'"
+            ),
+            ["Ann", "Cid", "Eve"]
+        );
+        // CTEs hide views with the same name.
+        assert_eq!(
+            rows("views.db", "WITH adults AS (SELECT 1) SELECT * FROM adults"),
+            ["1"]
+        );
+    }
+
+    #[test]
+    fn reports_broken_views() {
+        let error = |sql| run("views.db", sql).err().map(|e| e.to_string());
+
+        assert_eq!(
+            error("SELECT * FROM mismatched"),
+            Some("expected 2 columns for 'mismatched' but got 3".to_string())
+        );
+        assert_eq!(
+            error("SELECT * FROM second_loop"),
+            Some("view second_loop is circularly defined".to_string())
+        );
+        assert_eq!(
+            error("SELECT rowid FROM adults"),
+            Some("no such column: rowid".to_string())
+        );
+    }
+}
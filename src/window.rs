@@ -0,0 +1,711 @@
+use anyhow::{bail, ensure, Result};
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use crate::aggregate::{is_aggregate_function, Aggregate};
+use crate::ast::*;
+use crate::collation::Collation;
+use crate::eval::{collation, evaluate, Scope};
+use crate::function::{argument_collation, is_scalar_function};
+use crate::query::ResultSet;
+use crate::sort::{compare_keys, SortOrder};
+use crate::value::{Affinity, Value};
+
+/// The functions that may only be called with `OVER`, as opposed to
+/// aggregate functions, which may be called either way.
+const WINDOW_FUNCTIONS: &[&str] = &[
+    "row_number",
+    "rank",
+    "dense_rank",
+    "percent_rank",
+    "cume_dist",
+    "ntile",
+    "lag",
+    "lead",
+    "first_value",
+    "last_value",
+    "nth_value",
+];
+
+/// Whether the function with the given name is one that can only be called
+/// with a window.
+pub fn is_window_function(name: &str) -> bool {
+    WINDOW_FUNCTIONS
+        .iter()
+        .any(|function| name.eq_ignore_ascii_case(function))
+}
+
+/// Finds the calls to window functions in an expression, checking that
+/// functions are called with `OVER` exactly when they have to be.
+pub fn windows_in(expr: &Expr) -> Result<Vec<&FunctionCall>> {
+    let mut calls = Vec::new();
+    collect_windows(expr, false, &mut calls)?;
+    Ok(calls)
+}
+
+/// Adds the calls to window functions in an expression to `calls`. Window
+/// functions are evaluated over the rows of the query, which the arguments
+/// and windows of other window functions are evaluated for, so they can't be
+/// `nested` in those.
+fn collect_windows<'a>(
+    expr: &'a Expr,
+    nested: bool,
+    calls: &mut Vec<&'a FunctionCall>,
+) -> Result<()> {
+    let mut nested = nested;
+    if let Expr::Function(call) = expr {
+        let window_function = is_window_function(&call.name);
+        if nested && call.over.is_some() || window_function && call.over.is_none() {
+            bail!("misuse of window function {}()", call.name);
+        }
+        if call.over.is_some() {
+            calls.push(call);
+            nested = true;
+        }
+    }
+    for child in expr.children() {
+        collect_windows(child, nested, calls)?;
+    }
+    Ok(())
+}
+
+/// Checks that a function can be called as a window function, with the
+/// number of arguments it takes.
+fn check_call(call: &FunctionCall) -> Result<()> {
+    let arity_ok = match call.name.to_ascii_lowercase().as_str() {
+        "row_number" | "rank" | "dense_rank" | "percent_rank" | "cume_dist" => call.args.is_empty(),
+        "ntile" | "first_value" | "last_value" => call.args.len() == 1,
+        "lag" | "lead" => (1..=3).contains(&call.args.len()),
+        "nth_value" => call.args.len() == 2,
+        _ if is_scalar_function(&call.name) && !is_aggregate_function(call) => {
+            bail!("{}() may not be used as a window function", call.name)
+        }
+        _ if !is_aggregate_function(call) => bail!("no such function: {}", call.name),
+        _ => {
+            ensure!(
+                !call.distinct,
+                "DISTINCT is not supported for window functions"
+            );
+            // The aggregate checks its own arguments.
+            Aggregate::new(call, Collation::Binary)?;
+            return Ok(());
+        }
+    };
+    ensure!(
+        arity_ok && !call.star && !call.distinct,
+        "wrong number of arguments to function {}()",
+        call.name
+    );
+    Ok(())
+}
+
+/// A window combined with the windows of the `WINDOW` clause it is based on.
+/// The expressions are those of the windows, as the scopes they are
+/// evaluated in may tell them apart by their address.
+#[derive(Copy, Clone)]
+struct ResolvedWindow<'a> {
+    partition_by: &'a [Expr],
+    order_by: &'a [OrderingTerm],
+    frame: Option<&'a Frame>,
+}
+
+/// Combines the window of a function call with the window of the `WINDOW`
+/// clause it is based on, if any.
+fn resolve_window<'a>(
+    window: &'a Window,
+    named: &'a [(String, Window)],
+) -> Result<ResolvedWindow<'a>> {
+    let own = ResolvedWindow {
+        partition_by: &window.partition_by,
+        order_by: &window.order_by,
+        frame: window.frame.as_ref(),
+    };
+    let Some(base) = &window.base else {
+        return Ok(own);
+    };
+    // Of windows with the same name, the last one counts.
+    let Some((_, base_window)) = named
+        .iter()
+        .rev()
+        .find(|(name, _)| name.eq_ignore_ascii_case(base))
+    else {
+        bail!("no such window: {base}");
+    };
+    let mut resolved = resolve_window(base_window, named)?;
+    ensure!(
+        own.partition_by.is_empty(),
+        "cannot override PARTITION clause of window: {base}"
+    );
+    if !own.order_by.is_empty() {
+        ensure!(
+            resolved.order_by.is_empty(),
+            "cannot override ORDER BY clause of window: {base}"
+        );
+        resolved.order_by = own.order_by;
+    }
+    if own.frame.is_some() {
+        ensure!(
+            resolved.frame.is_none(),
+            "cannot override frame specification of window: {base}"
+        );
+        resolved.frame = own.frame;
+    }
+    Ok(resolved)
+}
+
+/// Evaluates the window functions of a query for its rows, given by the
+/// scopes to evaluate their expressions in. Returns the order the rows come
+/// out in, which is that of the window of the first call, and the results of
+/// the calls for each row.
+///
+/// The calls are evaluated from last to first, each sorting the rows by its
+/// partition and order. The sort is stable, so rows that the window of one
+/// call doesn't order stay in the order of the windows after it.
+pub fn evaluate_windows<'a>(
+    calls: &[&'a FunctionCall],
+    named: &'a [(String, Window)],
+    scopes: &[&dyn Scope],
+) -> Result<(Vec<usize>, Vec<Vec<Value>>)> {
+    let mut windows = Vec::new();
+    for call in calls {
+        check_call(call)?;
+        let window = call.over.as_ref().expect("Window functions have a window");
+        let window = resolve_window(window, named)?;
+        if let Some(frame) = window.frame {
+            let offset = frame.start.offset().or(frame.end.offset()).is_some();
+            if frame.units == FrameUnits::Range && offset && window.order_by.len() != 1 {
+                bail!("RANGE with offset PRECEDING/FOLLOWING requires one ORDER BY expression");
+            }
+        }
+        windows.push(window);
+    }
+
+    let mut order = (0..scopes.len()).collect::<Vec<_>>();
+    let mut results = vec![vec![Value::Null; calls.len()]; scopes.len()];
+    if scopes.is_empty() {
+        return Ok((order, results));
+    }
+    for (i, (call, window)) in calls.iter().zip(&windows).enumerate().rev() {
+        // The scopes only differ in their rows, so any of them tells the
+        // collations of the expressions.
+        let mut partition_order = Vec::new();
+        for expr in window.partition_by {
+            let collation = collation(expr, scopes[0])?.unwrap_or_default();
+            partition_order.push(SortOrder::new(false, None, collation));
+        }
+        let mut key_order = Vec::new();
+        for term in window.order_by {
+            let collation = collation(&term.expr, scopes[0])?.unwrap_or_default();
+            key_order.push(SortOrder::new(term.descending, term.nulls_first, collation));
+        }
+        let arg_collation = argument_collation(call, scopes[0])?;
+
+        let mut rows = Vec::with_capacity(scopes.len());
+        for scope in scopes {
+            let values = |exprs: &mut dyn Iterator<Item = &Expr>| {
+                exprs
+                    .map(|expr| evaluate(expr, *scope))
+                    .collect::<Result<Vec<_>>>()
+            };
+            rows.push(WindowRow {
+                partition: values(&mut window.partition_by.iter())?,
+                keys: values(&mut window.order_by.iter().map(|term| &term.expr))?,
+                args: values(&mut call.args.iter())?,
+            });
+        }
+        let frame = match window.frame {
+            Some(frame) => Some(FrameSpec::new(frame, scopes[0])?),
+            None => None,
+        };
+
+        order.sort_by(|&a, &b| {
+            compare_keys(&partition_order, &rows[a].partition, &rows[b].partition)
+                .then_with(|| compare_keys(&key_order, &rows[a].keys, &rows[b].keys))
+        });
+        let mut start = 0;
+        while start < order.len() {
+            let partition = &rows[order[start]].partition;
+            let len = order[start..]
+                .iter()
+                .take_while(|&&row| {
+                    compare_keys(&partition_order, &rows[row].partition, partition).is_eq()
+                })
+                .count();
+            let members = &order[start..start + len];
+            let partition = Partition::new(members, &rows, &key_order);
+            let values = partition.evaluate(call, frame.as_ref(), arg_collation)?;
+            for (&row, value) in members.iter().zip(values) {
+                results[row][i] = value;
+            }
+            start += len;
+        }
+    }
+
+    Ok((order, results))
+}
+
+/// The values of the expressions of a window function call for a row.
+struct WindowRow {
+    partition: Vec<Value>,
+    keys: Vec<Value>,
+    args: Vec<Value>,
+}
+
+/// A frame specification with the values of its offsets.
+struct FrameSpec {
+    units: FrameUnits,
+    start: Bound,
+    end: Bound,
+    exclude: FrameExclude,
+}
+
+#[derive(Clone)]
+enum Bound {
+    UnboundedPreceding,
+    Preceding(Value),
+    CurrentRow,
+    Following(Value),
+    UnboundedFollowing,
+}
+
+impl FrameSpec {
+    /// Evaluates the offsets of a frame, which must be non-negative numbers,
+    /// and integers unless the frame is a `RANGE`.
+    fn new(frame: &Frame, scope: &dyn Scope) -> Result<Self> {
+        let bound = |bound: &FrameBound, which: &str| -> Result<Bound> {
+            let offset = |expr: &Expr| -> Result<Value> {
+                let value = evaluate(expr, scope)?;
+                let number = match value.to_numeric() {
+                    Value::Real(r) if r.fract() == 0.0 && frame.units != FrameUnits::Range => {
+                        Some(Value::Integer(r as i64))
+                    }
+                    number @ Value::Integer(_) => Some(number),
+                    number @ Value::Real(_) if frame.units == FrameUnits::Range => Some(number),
+                    _ => None,
+                };
+                match number {
+                    Some(number) if number.to_real() >= 0.0 => Ok(number),
+                    _ if frame.units == FrameUnits::Range => {
+                        bail!("frame {which} offset must be a non-negative number")
+                    }
+                    _ => bail!("frame {which} offset must be a non-negative integer"),
+                }
+            };
+            Ok(match bound {
+                FrameBound::UnboundedPreceding => Bound::UnboundedPreceding,
+                FrameBound::Preceding(expr) => Bound::Preceding(offset(expr)?),
+                FrameBound::CurrentRow => Bound::CurrentRow,
+                FrameBound::Following(expr) => Bound::Following(offset(expr)?),
+                FrameBound::UnboundedFollowing => Bound::UnboundedFollowing,
+            })
+        };
+        Ok(Self {
+            units: frame.units,
+            start: bound(&frame.start, "starting")?,
+            end: bound(&frame.end, "ending")?,
+            exclude: frame.exclude,
+        })
+    }
+}
+
+/// The rows of a partition, in the order of the window.
+struct Partition<'a> {
+    rows: Vec<&'a WindowRow>,
+    key_order: &'a [SortOrder],
+    /// The index of the group of peers of each row.
+    group: Vec<usize>,
+    /// The rows each group of peers starts at, followed by the number of rows.
+    group_starts: Vec<usize>,
+}
+
+impl<'a> Partition<'a> {
+    fn new(members: &[usize], rows: &'a [WindowRow], key_order: &'a [SortOrder]) -> Self {
+        let rows = members.iter().map(|&row| &rows[row]).collect::<Vec<_>>();
+        let mut group = Vec::with_capacity(rows.len());
+        let mut group_starts = Vec::new();
+        for (i, row) in rows.iter().enumerate() {
+            let peer = i > 0 && compare_keys(key_order, &row.keys, &rows[i - 1].keys).is_eq();
+            if !peer {
+                group_starts.push(i);
+            }
+            group.push(group_starts.len() - 1);
+        }
+        group_starts.push(rows.len());
+        Self {
+            rows,
+            key_order,
+            group,
+            group_starts,
+        }
+    }
+
+    /// Evaluates a window function call for each row of the partition.
+    /// Aggregates compare the values of the argument with `collation`.
+    fn evaluate(
+        &self,
+        call: &FunctionCall,
+        frame: Option<&FrameSpec>,
+        collation: Collation,
+    ) -> Result<Vec<Value>> {
+        let n = self.rows.len();
+        let arg = |i: usize, index: usize| self.rows[i].args.get(index).cloned();
+        let mut values = Vec::with_capacity(n);
+        match call.name.to_ascii_lowercase().as_str() {
+            "row_number" => values.extend((1..=n as i64).map(Value::Integer)),
+            "rank" => {
+                for i in 0..n {
+                    let rank = self.group_starts[self.group[i]] + 1;
+                    values.push(Value::Integer(rank as i64));
+                }
+            }
+            "dense_rank" => {
+                values.extend(self.group.iter().map(|&g| Value::Integer(g as i64 + 1)));
+            }
+            "percent_rank" => {
+                for i in 0..n {
+                    let rank = self.group_starts[self.group[i]];
+                    let percent = if n > 1 {
+                        rank as f64 / (n - 1) as f64
+                    } else {
+                        0.0
+                    };
+                    values.push(Value::Real(percent));
+                }
+            }
+            "cume_dist" => {
+                for i in 0..n {
+                    let end = self.group_starts[self.group[i] + 1];
+                    values.push(Value::Real(end as f64 / n as f64));
+                }
+            }
+            "ntile" => {
+                let buckets = match arg(0, 0).map(|value| value.to_numeric()) {
+                    Some(number @ (Value::Integer(_) | Value::Real(_))) => number.to_integer(),
+                    _ => 0,
+                };
+                ensure!(buckets > 0, "argument of ntile must be a positive integer");
+                // The first n % buckets buckets have one row more than the rest.
+                let buckets = buckets as usize;
+                let size = n / buckets;
+                let larger = n % buckets;
+                for i in 0..n {
+                    let bucket = if size == 0 {
+                        i
+                    } else if i < larger * (size + 1) {
+                        i / (size + 1)
+                    } else {
+                        larger + (i - larger * (size + 1)) / size
+                    };
+                    values.push(Value::Integer(bucket as i64 + 1));
+                }
+            }
+            name @ ("lag" | "lead") => {
+                for i in 0..n {
+                    let offset = arg(i, 1).map_or(1, |offset| offset.to_integer());
+                    let offset = if name == "lag" { -offset } else { offset };
+                    let other = (i as i64)
+                        .checked_add(offset)
+                        .filter(|&j| 0 <= j && j < n as i64);
+                    values.push(match other {
+                        Some(j) => arg(j as usize, 0).unwrap_or(Value::Null),
+                        None => arg(i, 2).unwrap_or(Value::Null),
+                    });
+                }
+            }
+            "first_value" | "last_value" | "nth_value" => {
+                let last = call.name.eq_ignore_ascii_case("last_value");
+                for i in 0..n {
+                    let position = match arg(i, 1) {
+                        Some(position) => {
+                            let number = position.to_numeric();
+                            let integer = match number {
+                                Value::Integer(n) => Some(n),
+                                Value::Real(r) if r.fract() == 0.0 => Some(r as i64),
+                                _ => None,
+                            };
+                            match integer {
+                                Some(n) if n > 0 => n as usize - 1,
+                                _ => {
+                                    bail!("second argument to nth_value must be a positive integer")
+                                }
+                            }
+                        }
+                        None => 0,
+                    };
+                    let rows = self.frame_rows(i, frame);
+                    let row = if last {
+                        rows.last()
+                    } else {
+                        rows.get(position)
+                    };
+                    values.push(row.map_or(Value::Null, |&row| arg(row, 0).unwrap_or(Value::Null)));
+                }
+            }
+            _ => {
+                // The frames of consecutive rows often start at the same row
+                // and end at the same row or later, as for running totals,
+                // which lets an aggregate carry on from the previous row.
+                let mut aggregate = Aggregate::new(call, collation)?;
+                let mut previous: Option<(usize, usize)> = None;
+                for i in 0..n {
+                    let (start, end) = self.frame_range(i, frame);
+                    let rows = match previous {
+                        Some((previous_start, previous_end))
+                            if previous_start == start
+                                && previous_end <= end
+                                && frame.map_or(true, |f| f.exclude == FrameExclude::NoOthers) =>
+                        {
+                            (previous_end..end).collect()
+                        }
+                        _ => {
+                            aggregate = Aggregate::new(call, collation)?;
+                            self.frame_rows(i, frame)
+                        }
+                    };
+                    for row in rows {
+                        aggregate.step(&self.rows[row].args)?;
+                    }
+                    values.push(aggregate.finish());
+                    previous = Some((start, end));
+                }
+            }
+        }
+        Ok(values)
+    }
+
+    /// The rows in the frame of a row, leaving out those that are excluded.
+    fn frame_rows(&self, i: usize, frame: Option<&FrameSpec>) -> Vec<usize> {
+        let (start, end) = self.frame_range(i, frame);
+        let group = self.group_starts[self.group[i]]..self.group_starts[self.group[i] + 1];
+        let exclude = frame.map_or(FrameExclude::NoOthers, |frame| frame.exclude);
+        (start..end)
+            .filter(|&row| match exclude {
+                FrameExclude::NoOthers => true,
+                FrameExclude::CurrentRow => row != i,
+                FrameExclude::Group => !group.contains(&row),
+                FrameExclude::Ties => row == i || !group.contains(&row),
+            })
+            .collect()
+    }
+
+    /// The range of rows the frame of a row spans. Without a frame
+    /// specification, the frame goes from the start of the partition to the
+    /// last peer of the row.
+    fn frame_range(&self, i: usize, frame: Option<&FrameSpec>) -> (usize, usize) {
+        let Some(frame) = frame else {
+            return (0, self.group_starts[self.group[i] + 1]);
+        };
+        let start = self.bound(i, frame, &frame.start, true);
+        let end = self.bound(i, frame, &frame.end, false);
+        (start, end.max(start))
+    }
+
+    /// Where a bound of the frame of a row falls: the first row of the frame
+    /// for the start, and the row after the last one for the end.
+    fn bound(&self, i: usize, frame: &FrameSpec, bound: &Bound, start: bool) -> usize {
+        let n = self.rows.len();
+        let group = self.group[i];
+        let groups = self.group_starts.len() - 1;
+        // The first row of a group for the start, and the end of a group
+        // for the end.
+        let group_edge = |group: usize| {
+            if start {
+                self.group_starts[group]
+            } else {
+                self.group_starts[group + 1]
+            }
+        };
+        let (offset, preceding) = match bound {
+            Bound::UnboundedPreceding => return 0,
+            Bound::UnboundedFollowing => return n,
+            Bound::CurrentRow if frame.units == FrameUnits::Rows => {
+                return if start { i } else { i + 1 };
+            }
+            Bound::CurrentRow => return group_edge(group),
+            Bound::Preceding(offset) => (offset, true),
+            Bound::Following(offset) => (offset, false),
+        };
+
+        match frame.units {
+            FrameUnits::Rows => {
+                let k = offset.to_integer().max(0) as usize;
+                let row = if preceding {
+                    i.checked_sub(k)
+                } else {
+                    Some(i.saturating_add(k))
+                };
+                match row {
+                    Some(row) if start => row.min(n),
+                    Some(row) => row.saturating_add(1).min(n),
+                    None => 0,
+                }
+            }
+            FrameUnits::Groups => {
+                let k = offset.to_integer().max(0) as usize;
+                let other = if preceding {
+                    group.checked_sub(k)
+                } else {
+                    Some(group.saturating_add(k))
+                };
+                match other {
+                    Some(other) if other < groups => group_edge(other),
+                    Some(_) => n,
+                    None => 0,
+                }
+            }
+            FrameUnits::Range => {
+                let key = &self.rows[i].keys[0];
+                let order = &self.key_order[..1];
+                // Rows whose value can't be offset are only in range of
+                // their peers.
+                if !matches!(key, Value::Integer(_) | Value::Real(_)) {
+                    return group_edge(group);
+                }
+                // Preceding rows have smaller values in ascending order, and
+                // larger ones in descending order.
+                let op = if preceding != order[0].descending {
+                    BinaryOp::Subtract
+                } else {
+                    BinaryOp::Add
+                };
+                let target = [crate::eval::binary(op, key.clone(), offset.clone())];
+                let compare = |row: &&WindowRow| compare_keys(order, &row.keys[..1], &target);
+                if start {
+                    self.rows
+                        .partition_point(|row| compare(row) == Ordering::Less)
+                } else {
+                    self.rows
+                        .partition_point(|row| compare(row) != Ordering::Greater)
+                }
+            }
+        }
+    }
+}
+
+/// The scope of a row of a query with window functions, in which calls to
+/// them have the results evaluated for the row.
+pub struct WindowScope<'a> {
+    pub inner: &'a dyn Scope,
+    pub calls: &'a [&'a FunctionCall],
+    pub results: &'a [Value],
+}
+
+impl Scope for WindowScope<'_> {
+    fn column(&self, table: Option<&str>, name: &str) -> Result<Value> {
+        self.inner.column(table, name)
+    }
+
+    fn affinity(&self, table: Option<&str>, name: &str) -> Option<Affinity> {
+        self.inner.affinity(table, name)
+    }
+
+    fn collation(&self, table: Option<&str>, name: &str) -> Option<Collation> {
+        self.inner.collation(table, name)
+    }
+
+    fn parameter(&self, index: usize) -> Value {
+        self.inner.parameter(index)
+    }
+
+    fn aggregate(&self, call: &FunctionCall) -> Option<Value> {
+        match self
+            .calls
+            .iter()
+            .position(|other| std::ptr::eq(*other, call))
+        {
+            Some(index) => Some(self.results[index].clone()),
+            None => self.inner.aggregate(call),
+        }
+    }
+
+    fn subquery(&self, select: &Select, first_only: bool) -> Result<Rc<ResultSet>> {
+        self.inner.subquery(select, first_only)
+    }
+
+    fn case_sensitive_like(&self) -> bool {
+        self.inner.case_sensitive_like()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::query::tests::{rows, run};
+
+    #[test]
+    fn ranks_rows_of_windows() {
+        // Without a frame, the sum runs up to the last peer of the row.
+        assert_eq!(
+            rows(
+                "joins.db",
+                "SELECT name, rank() OVER w, dense_rank() OVER w, row_number() OVER w, \
+                 lag(name) OVER w, sum(id) OVER w FROM customers WINDOW w AS (ORDER BY city)"
+            ),
+            [
+                "Dave|1|1|1||4",
+                "Bob|2|2|2|Dave|6",
+                "Carol|3|3|3|Bob|9",
+                "Alice|4|4|4|Carol|15",
+                "Eve|4|4|5|Alice|15",
+            ]
+        );
+        assert_eq!(
+            rows(
+                "joins.db",
+                "SELECT city, count(*), rank() OVER (ORDER BY count(*) DESC), \
+                 sum(count(*)) OVER () FROM customers GROUP BY city"
+            ),
+            ["Paris|2|1|5", "|1|2|5", "Berlin|1|2|5", "Oslo|1|2|5"]
+        );
+    }
+
+    #[test]
+    fn evaluates_aggregates_over_frames() {
+        assert_eq!(
+            rows(
+                "joins.db",
+                "SELECT id, \
+                 sum(id) OVER (ORDER BY id ROWS BETWEEN 1 PRECEDING AND 1 FOLLOWING), \
+                 sum(id) OVER (ORDER BY city GROUPS BETWEEN 1 PRECEDING AND CURRENT ROW \
+                   EXCLUDE CURRENT ROW), \
+                 sum(id) OVER (ORDER BY id RANGE BETWEEN 1 PRECEDING AND 1 FOLLOWING \
+                   EXCLUDE GROUP) \
+                 FROM customers ORDER BY id"
+            ),
+            ["1|3|8|2", "2|6|4|4", "3|9|2|6", "4|12||8", "5|9|4|4"]
+        );
+    }
+
+    #[test]
+    fn reports_invalid_windows() {
+        let error = |sql| run("joins.db", sql).err().unwrap().to_string();
+
+        assert_eq!(
+            error("SELECT rank() FROM customers"),
+            "misuse of window function rank()"
+        );
+        assert_eq!(
+            error("SELECT id FROM customers WHERE row_number() OVER () > 1"),
+            "misuse of window function row_number()"
+        );
+        assert_eq!(
+            error("SELECT count(DISTINCT id) OVER () FROM customers"),
+            "DISTINCT is not supported for window functions"
+        );
+        assert_eq!(
+            error("SELECT sum(id) OVER w FROM customers"),
+            "no such window: w"
+        );
+        assert_eq!(
+            error("SELECT sum(id) OVER (ORDER BY id, name RANGE 1 PRECEDING) FROM customers"),
+            "RANGE with offset PRECEDING/FOLLOWING requires one ORDER BY expression"
+        );
+        assert_eq!(
+            error("SELECT ntile(0) OVER () FROM customers"),
+            "argument of ntile must be a positive integer"
+        );
+        assert_eq!(
+            error("SELECT sum(id) OVER (ROWS -1 PRECEDING) FROM customers"),
+            "frame starting offset must be a non-negative integer"
+        );
+    }
+}